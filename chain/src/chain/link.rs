@@ -53,6 +53,9 @@ pub struct Link<R: Resource> {
     /// Combination of all stages.
     stages: rendy_core::hal::pso::PipelineStage,
 
+    /// Range of the resource.
+    range: R::Range,
+
     /// Number of queues involved.
     queue_count: usize,
 
@@ -89,6 +92,7 @@ where
             usage: node.state.usage,
             layout: node.state.layout,
             stages: node.state.stages,
+            range: node.state.range,
             queue_count: 1,
             queues: Vec::new(),
             family: node.sid.family(),
@@ -123,6 +127,7 @@ where
             layout: self.layout,
             stages: lqs.stages,
             usage: self.usage,
+            range: self.range,
         }
     }
 
@@ -133,6 +138,7 @@ where
             layout: self.layout,
             stages: self.stages,
             usage: self.usage,
+            range: self.range,
         }
     }
 
@@ -164,8 +170,19 @@ where
     /// Check if the given state and submission are compatible with link.
     /// If compatible then the submission can be associated with the link.
     pub fn compatible(&self, node: &LinkNode<R>) -> bool {
+        self.compatible_state(node.sid.family(), &node.state)
+    }
+
+    /// Check if the given state on the given family is compatible with link.
+    pub(crate) fn compatible_state(
+        &self,
+        family: rendy_core::hal::queue::QueueFamilyId,
+        state: &State<R>,
+    ) -> bool {
         // If queue the same and states are compatible.
-        self.family == node.sid.family() && !(self.access | node.state.access).exclusive()
+        self.family == family
+            && self.range == state.range
+            && !(self.access | state.access).exclusive()
     }
 
     /// Insert submission with specified state to the link.
@@ -181,6 +198,7 @@ where
     ///
     pub fn add_node(&mut self, node: LinkNode<R>) {
         assert_eq!(self.family, node.sid.family());
+        assert_eq!(self.range, node.state.range);
         self.ensure_queue(node.sid.queue().index());
 
         self.access |= node.state.access;
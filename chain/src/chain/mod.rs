@@ -8,9 +8,10 @@ use std::collections::HashMap;
 
 mod link;
 
-use std::ops::BitOr;
+use std::ops::{BitOr, Range};
 
 use crate::{
+    node::State,
     resource::{Buffer, Image, Resource},
     Id,
 };
@@ -19,9 +20,12 @@ pub use self::link::{Link, LinkNode};
 
 /// This type corresponds to resource category.
 /// All resources from the same category must be accessed as permitted by links of the chain.
+/// Each chain tracks one range of the resource. Disjoint ranges are tracked by separate chains.
 #[derive(Clone, Debug)]
 pub struct Chain<R: Resource> {
+    range: R::Range,
     links: Vec<Link<R>>,
+    external: Option<Range<State<R>>>,
 }
 
 impl<R> Chain<R>
@@ -33,9 +37,32 @@ where
         &self.links
     }
 
-    /// Create new empty `Chain`
-    pub fn new() -> Self {
-        Chain { links: Vec::new() }
+    /// Create new empty `Chain` for the range of the resource.
+    pub fn new(range: R::Range) -> Self {
+        Chain {
+            range,
+            links: Vec::new(),
+            external: None,
+        }
+    }
+
+    /// Get range of the resource tracked by the chain.
+    pub fn range(&self) -> R::Range {
+        self.range
+    }
+
+    /// Get states of the resource before first and after last link.
+    /// `None` if resource is used only by the chain
+    /// and last link is followed by first link of the next frame.
+    pub fn external(&self) -> Option<&Range<State<R>>> {
+        self.external.as_ref()
+    }
+
+    /// Set states of the resource before first and after last link.
+    /// Resource in `states.start` state is acquired by first link
+    /// and released by last link into `states.end` state.
+    pub fn set_external(&mut self, states: Range<State<R>>) {
+        self.external = Some(states);
     }
 
     /// Get links slice
@@ -93,7 +120,9 @@ where
 }
 
 /// Type alias for map of chains by id for buffers.
-pub(crate) type BufferChains = HashMap<Id, Chain<Buffer>>;
+/// Chains of one buffer track its disjoint ranges.
+pub(crate) type BufferChains = HashMap<Id, Vec<Chain<Buffer>>>;
 
 /// Type alias for map of chains by id for images.
-pub(crate) type ImageChains = HashMap<Id, Chain<Image>>;
+/// Chains of one image track its disjoint subresource ranges.
+pub(crate) type ImageChains = HashMap<Id, Vec<Chain<Image>>>;
@@ -2,16 +2,72 @@ use std::cmp::max;
 use std::collections::hash_map::RandomState;
 use std::collections::HashMap;
 use std::hash::Hash;
-use std::ops::Range;
 
 use crate::{
     chain::{BufferChains, Chain, ImageChains, Link, LinkNode},
     node::{Node, State},
-    resource::{Buffer, Image, Resource},
+    resource::{AccessFlags, Buffer, Image, Resource, ResourceRange},
     schedule::{Queue, QueueId, Schedule, Submission, SubmissionId},
+    scheduler::{Candidate, FitnessScheduler, Scheduler},
     Id,
 };
 
+/// Nodes that can't be ordered because they depend on each other.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DependencyCycle {
+    /// Ids of the nodes forming the cycle.
+    /// Each node must be executed after the next one and the last one after the first.
+    pub nodes: Vec<usize>,
+
+    /// Buffers that make nodes of the cycle implicitly depend on each other.
+    pub buffers: Vec<Id>,
+
+    /// Images that make nodes of the cycle implicitly depend on each other.
+    pub images: Vec<Id>,
+}
+
+impl std::fmt::Display for DependencyCycle {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(fmt, "Nodes {:?} depend on each other", self.nodes)?;
+        if !self.buffers.is_empty() || !self.images.is_empty() {
+            write!(
+                fmt,
+                " through buffers {:?} and images {:?}",
+                self.buffers, self.images
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Error calculating `Chains` for nodes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CollectError {
+    /// Nodes can't be ordered because of dependency cycles.
+    /// Besides explicit dependencies, node that reads a resource
+    /// depends on the last node declared before it that writes the resource,
+    /// and node that writes a resource depends on nodes declared before it
+    /// that access the resource since it was last written,
+    /// unless explicit dependencies order them the other way.
+    DependencyCycles(Vec<DependencyCycle>),
+}
+
+impl std::fmt::Display for CollectError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CollectError::DependencyCycles(cycles) => {
+                write!(fmt, "Dependency cycles found:")?;
+                for cycle in cycles {
+                    write!(fmt, "\n  {}", cycle)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for CollectError {}
+
 /// Placeholder for synchronization type.
 #[derive(Clone, Copy, Debug)]
 pub struct Unsynchronized;
@@ -29,16 +85,12 @@ pub struct Chains {
     pub images: ImageChains,
 }
 
-#[derive(PartialEq, PartialOrd, Eq, Ord)]
-struct Fitness {
-    transfers: usize,
-    wait_factor: usize,
-}
-
 struct ResolvedNode {
     id: usize,
-    family: rendy_core::hal::queue::QueueFamilyId,
-    queues: Range<usize>,
+    queues: Vec<usize>,
+    cost: usize,
+    critical_path: usize,
+    dependencies: Vec<(usize, Order)>,
     rev_deps: Vec<usize>,
     buffers: Vec<(usize, State<Buffer>)>,
     images: Vec<(usize, State<Image>)>,
@@ -48,8 +100,10 @@ impl Default for ResolvedNode {
     fn default() -> Self {
         ResolvedNode {
             id: 0,
-            family: rendy_core::hal::queue::QueueFamilyId(0),
-            queues: 0..0,
+            queues: Vec::new(),
+            cost: 0,
+            critical_path: 0,
+            dependencies: Vec::new(),
             rev_deps: Vec::new(),
             buffers: Vec::new(),
             images: Vec::new(),
@@ -57,11 +111,15 @@ impl Default for ResolvedNode {
     }
 }
 
+/// Resource id, index of the segment and range of the resource tracked by the chain.
+type ResolvedChain<R> = (Id, usize, <R as Resource>::Range);
+
 struct ResolvedNodeSet {
     nodes: Vec<ResolvedNode>,
+    ids: Vec<usize>,
     queues: Vec<QueueId>,
-    buffers: Vec<Id>,
-    images: Vec<Id>,
+    buffers: Vec<ResolvedChain<Buffer>>,
+    images: Vec<ResolvedChain<Image>>,
 }
 
 struct ChainData<R: Resource> {
@@ -70,10 +128,10 @@ struct ChainData<R: Resource> {
     current_link_wait_factor: usize,
     current_family: Option<rendy_core::hal::queue::QueueFamilyId>,
 }
-impl<R: Resource> Default for ChainData<R> {
-    fn default() -> Self {
+impl<R: Resource> ChainData<R> {
+    fn new(range: R::Range) -> Self {
         ChainData {
-            chain: Chain::new(),
+            chain: Chain::new(range),
             last_link_wait_factor: 0,
             current_link_wait_factor: 0,
             current_family: None,
@@ -88,17 +146,53 @@ struct QueueData {
 
 /// Calculate automatic `Chains` for nodes.
 /// This function tries to find the most appropriate schedule for nodes execution.
-pub fn collect<Q>(nodes: Vec<Node>, max_queues: Q) -> Chains
+///
+/// Besides explicit dependencies, node that reads a resource segment
+/// (including nodes that read and write it) is executed after the last node
+/// declared before it that writes the segment.
+/// Node that writes a segment is executed after the last node declared before it
+/// that writes the segment and after nodes declared since then that read it.
+/// Implicit dependencies are not added when explicit dependencies order nodes the other way.
+/// Nodes are declared in order they appear in `nodes`.
+///
+/// Returns `CollectError::DependencyCycles` if nodes can't be ordered.
+pub fn collect<Q>(nodes: Vec<Node>, max_queues: Q) -> Result<Chains, CollectError>
+where
+    Q: Fn(rendy_core::hal::queue::QueueFamilyId) -> usize,
+{
+    collect_with_scheduler(nodes, max_queues, &FitnessScheduler)
+}
+
+/// Calculate automatic `Chains` for nodes.
+/// Order of submissions and queues they are submitted to are picked by `scheduler`.
+pub fn collect_with_scheduler<Q>(
+    nodes: Vec<Node>,
+    max_queues: Q,
+    scheduler: &dyn Scheduler,
+) -> Result<Chains, CollectError>
 where
     Q: Fn(rendy_core::hal::queue::QueueFamilyId) -> usize,
 {
     // Resolve nodes into a form faster to work with.
     let (nodes, mut unscheduled_nodes) = resolve_nodes(nodes, max_queues);
+
+    let cycles = dependency_cycles(&nodes);
+    if !cycles.is_empty() {
+        return Err(CollectError::DependencyCycles(cycles));
+    }
     let mut ready_nodes = Vec::new();
 
     // Chains.
-    let mut images: Vec<ChainData<Image>> = fill(nodes.images.len());
-    let mut buffers: Vec<ChainData<Buffer>> = fill(nodes.buffers.len());
+    let mut images: Vec<ChainData<Image>> = nodes
+        .images
+        .iter()
+        .map(|&(_, _, range)| ChainData::new(range))
+        .collect();
+    let mut buffers: Vec<ChainData<Buffer>> = nodes
+        .buffers
+        .iter()
+        .map(|&(_, _, range)| ChainData::new(range))
+        .collect();
 
     // Schedule
     let mut schedule = Vec::with_capacity(nodes.queues.len());
@@ -116,59 +210,200 @@ where
     }
 
     let mut scheduled = 0;
-    if nodes.queues.len() == 1 {
-        // With a single queue, wait_factor is always the number of scheduled nodes, and
-        // transfers is always zero. Thus, we only need dependency resolution.
-        while let Some(node) = ready_nodes.pop() {
-            schedule_node(
-                &mut ready_nodes,
-                &mut unscheduled_nodes,
-                &nodes,
-                node,
-                0,
-                scheduled,
-                scheduled,
-                &mut schedule,
-                &mut images,
-                &mut buffers,
-            );
-            scheduled += 1;
-        }
-    } else {
-        while !ready_nodes.is_empty() {
-            // Among ready nodes find best fit.
-            let (fitness, qid, index) = ready_nodes
-                .iter()
-                .enumerate()
-                .map(|(index, &node)| {
-                    let (fitness, qid) = fitness(node, &mut images, &mut buffers, &mut schedule);
-                    (fitness, qid, index)
-                })
-                .min()
-                .unwrap();
-
-            let node = ready_nodes.swap_remove(index);
-            schedule_node(
-                &mut ready_nodes,
-                &mut unscheduled_nodes,
-                &nodes,
+    let mut candidates = Vec::new();
+    while !ready_nodes.is_empty() {
+        // Let scheduler pick among all placements of ready nodes.
+        candidates.clear();
+        for &node in &ready_nodes {
+            push_candidates(
+                &mut candidates,
                 node,
-                qid,
-                fitness.wait_factor,
-                scheduled,
-                &mut schedule,
-                &mut images,
-                &mut buffers,
+                &nodes.queues,
+                &images,
+                &buffers,
+                &schedule,
             );
-            scheduled += 1;
         }
+        let picked = candidates[scheduler.pick(&nodes.queues, &candidates)];
+
+        let index = ready_nodes
+            .iter()
+            .position(|node| node.id == picked.node)
+            .unwrap();
+        let node = ready_nodes.swap_remove(index);
+        schedule_node(
+            &mut ready_nodes,
+            &mut unscheduled_nodes,
+            &nodes,
+            node,
+            picked.queue,
+            picked.wait_factor,
+            scheduled,
+            &mut schedule,
+            &mut images,
+            &mut buffers,
+        );
+        scheduled += 1;
     }
-    assert_eq!(scheduled, nodes.nodes.len(), "Dependency loop found!");
+    debug_assert_eq!(scheduled, nodes.nodes.len());
 
-    Chains {
+    Ok(Chains {
         schedule: reify_schedule(schedule),
         buffers: reify_chain(&nodes.buffers, buffers),
         images: reify_chain(&nodes.images, images),
+    })
+}
+
+/// Reason node must be executed after another one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Order {
+    Explicit,
+    Buffer(Id),
+    Image(Id),
+}
+
+/// Find cycles formed by explicit and implicit dependencies.
+/// Each cycle is reported once.
+fn dependency_cycles(nodes: &ResolvedNodeSet) -> Vec<DependencyCycle> {
+    let count = nodes.nodes.len();
+    let edges: Vec<&[(usize, Order)]> = nodes
+        .nodes
+        .iter()
+        .map(|node| &node.dependencies[..])
+        .collect();
+
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    enum Mark {
+        Unvisited,
+        InPath,
+        Done,
+    }
+
+    fn visit(
+        edges: &[&[(usize, Order)]],
+        node: usize,
+        marks: &mut [Mark],
+        path: &mut Vec<(usize, Order)>,
+        cycles: &mut Vec<Vec<(usize, Order)>>,
+    ) {
+        marks[node] = Mark::InPath;
+        for &(before, order) in edges[node] {
+            path.push((node, order));
+            match marks[before] {
+                Mark::Unvisited => visit(edges, before, marks, path, cycles),
+                Mark::InPath => {
+                    let start = path.iter().rposition(|&(n, _)| n == before).unwrap();
+                    cycles.push(path[start..].to_vec());
+                }
+                Mark::Done => {}
+            }
+            path.pop();
+        }
+        marks[node] = Mark::Done;
+    }
+
+    let mut marks = vec![Mark::Unvisited; count];
+    let mut path = Vec::new();
+    let mut cycles = Vec::new();
+    for node in 0..count {
+        if marks[node] == Mark::Unvisited {
+            visit(&edges, node, &mut marks, &mut path, &mut cycles);
+        }
+    }
+
+    cycles
+        .into_iter()
+        .map(|cycle| {
+            let mut buffers = Vec::new();
+            let mut images = Vec::new();
+            for &(_, order) in &cycle {
+                match order {
+                    Order::Explicit => {}
+                    Order::Buffer(id) if !buffers.contains(&id) => buffers.push(id),
+                    Order::Image(id) if !images.contains(&id) => images.push(id),
+                    Order::Buffer(_) | Order::Image(_) => {}
+                }
+            }
+            DependencyCycle {
+                nodes: cycle.iter().map(|&(node, _)| nodes.ids[node]).collect(),
+                buffers,
+                images,
+            }
+        })
+        .collect()
+}
+
+/// Nodes each node explicitly depends on directly or indirectly.
+fn explicit_ancestors(nodes: &[ResolvedNode]) -> Vec<Vec<bool>> {
+    (0..nodes.len())
+        .map(|id| {
+            let mut visited = vec![false; nodes.len()];
+            let mut stack: Vec<usize> = nodes[id].dependencies.iter().map(|&(d, _)| d).collect();
+            while let Some(dependency) = stack.pop() {
+                if !visited[dependency] {
+                    visited[dependency] = true;
+                    stack.extend(nodes[dependency].dependencies.iter().map(|&(d, _)| d));
+                }
+            }
+            visited
+        })
+        .collect()
+}
+
+/// Make nodes that access a resource segment depend on nodes declared before
+/// that access it in a conflicting way, unless explicit dependencies order them the other way.
+/// Node that reads the segment (including node that reads and writes it)
+/// depends on the last node declared before that writes it.
+/// Node that writes the segment depends on the last node declared before that writes it
+/// and on nodes declared after that one which read it,
+/// so that it doesn't overwrite content they still need.
+/// Nodes that read a segment before it is written by a node declared later
+/// (like feedback passes reading previous frame content) are executed before that writer.
+fn implicit_dependencies<R: Resource>(
+    nodes: &mut [ResolvedNode],
+    unscheduled_nodes: &mut [usize],
+    declared: &[usize],
+    states: impl Fn(&ResolvedNode) -> &[(usize, State<R>)],
+    chains: &[ResolvedChain<R>],
+    ancestors: &[Vec<bool>],
+    order: fn(Id) -> Order,
+) {
+    let mut last_writer: Vec<Option<usize>> = vec![None; chains.len()];
+    let mut readers: Vec<Vec<usize>> = vec![Vec::new(); chains.len()];
+    for &node in declared {
+        let mut implicit = Vec::new();
+        for &(chain, ref state) in states(&nodes[node]) {
+            let writes = state.access.exclusive();
+            if !writes && !state.access.reads() {
+                continue;
+            }
+            let earlier_readers: &[usize] = if writes { &readers[chain] } else { &[] };
+            for &before in last_writer[chain].iter().chain(earlier_readers) {
+                if !ancestors[before][node]
+                    && nodes[node]
+                        .dependencies
+                        .iter()
+                        .chain(&implicit)
+                        .all(|&(dependency, _)| dependency != before)
+                {
+                    implicit.push((before, order(chains[chain].0)));
+                }
+            }
+        }
+        for &(chain, ref state) in states(&nodes[node]) {
+            if state.access.exclusive() {
+                last_writer[chain] = Some(node);
+                readers[chain].clear();
+            } else if state.access.reads() {
+                readers[chain].push(node);
+            }
+        }
+
+        unscheduled_nodes[node] += implicit.len();
+        for &(before, _) in &implicit {
+            nodes[before].rev_deps.push(node);
+        }
+        nodes[node].dependencies.extend(implicit);
     }
 }
 
@@ -214,24 +449,30 @@ where
     let mut reified_nodes: Vec<ResolvedNode> = fill(nodes.len());
     let mut node_ids = LookupBuilder::new();
     let mut queues = LookupBuilder::new();
-    let mut buffers = LookupBuilder::new();
-    let mut images = LookupBuilder::new();
+    let mut node_buffers: Vec<Vec<(Id, State<Buffer>)>> = fill(nodes.len());
+    let mut node_images: Vec<Vec<(Id, State<Image>)>> = fill(nodes.len());
+    let mut declared = Vec::with_capacity(nodes.len());
 
     let s = RandomState::new();
     let mut family_full = HashMap::with_hasher(s);
 
     for node in nodes {
-        let family = node.family;
-        if !family_full.contains_key(&family) {
-            let count = max_queues(family);
-            assert!(count > 0, "Cannot create a family with 0 max queues.");
-            for i in 0..count {
-                queues.forward(QueueId::new(family, i));
+        assert!(
+            !node.families.is_empty(),
+            "Node must have at least one family."
+        );
+        for &family in &node.families {
+            if !family_full.contains_key(&family) {
+                let count = max_queues(family);
+                assert!(count > 0, "Cannot create a family with 0 max queues.");
+                for i in 0..count {
+                    queues.forward(QueueId::new(family, i));
+                }
+
+                let full_range = queues.forward(QueueId::new(family, 0))
+                    ..queues.forward(QueueId::new(family, count - 1)) + 1;
+                family_full.insert(family, full_range);
             }
-
-            let full_range = queues.forward(QueueId::new(family, 0))
-                ..queues.forward(QueueId::new(family, count - 1)) + 1;
-            family_full.insert(family, full_range);
         }
 
         let id = node_ids.forward(node.id);
@@ -241,41 +482,167 @@ where
         for dep in node.dependencies {
             // Duplicated dependencies work fine, since they push two rev_deps entries and add two
             // to unscheduled_nodes.
-            reified_nodes[node_ids.forward(dep)].rev_deps.push(id);
+            let dep = node_ids.forward(dep);
+            reified_nodes[dep].rev_deps.push(id);
+            reified_nodes[id].dependencies.push((dep, Order::Explicit));
         }
         unscheduled_nodes[id] = unscheduled_count;
 
         // We set these manually, and notably, do *not* touch rev_deps.
         reified_nodes[id].id = id;
-        reified_nodes[id].family = node.family;
-        reified_nodes[id].queues = family_full[&family].clone();
-        reified_nodes[id].buffers = node
-            .buffers
-            .into_iter()
-            .map(|(k, v)| (buffers.forward(k), v))
-            .collect();
-        reified_nodes[id].images = node
-            .images
-            .into_iter()
-            .map(|(k, v)| (images.forward(k), v))
+        reified_nodes[id].cost = node.cost;
+        // Node can be scheduled onto any queue of any of its families.
+        reified_nodes[id].queues = node
+            .families
+            .iter()
+            .flat_map(|family| family_full[family].clone())
             .collect();
+        node_buffers[id] = node.buffers;
+        node_images[id] = node.images;
+        declared.push(id);
     }
 
+    let (node_buffers, buffers) = resolve_resources(node_buffers);
+    let (node_images, images) = resolve_resources(node_images);
+    for ((node, buffers), images) in reified_nodes.iter_mut().zip(node_buffers).zip(node_images) {
+        node.buffers = buffers;
+        node.images = images;
+    }
+
+    let ancestors = explicit_ancestors(&reified_nodes);
+    implicit_dependencies(
+        &mut reified_nodes,
+        &mut unscheduled_nodes,
+        &declared,
+        |node| &node.buffers[..],
+        &buffers,
+        &ancestors,
+        Order::Buffer,
+    );
+    implicit_dependencies(
+        &mut reified_nodes,
+        &mut unscheduled_nodes,
+        &declared,
+        |node| &node.images[..],
+        &images,
+        &ancestors,
+        Order::Image,
+    );
+    critical_paths(&mut reified_nodes, &unscheduled_nodes);
+
     (
         ResolvedNodeSet {
             nodes: reified_nodes,
+            ids: node_ids.backward,
             queues: queues.backward,
-            buffers: buffers.backward,
-            images: images.backward,
+            buffers,
+            images,
         },
         unscheduled_nodes,
     )
 }
 
-fn reify_chain<R: Resource>(ids: &[Id], vec: Vec<ChainData<R>>) -> HashMap<Id, Chain<R>> {
-    let mut map = HashMap::with_capacity_and_hasher(vec.len(), Default::default());
-    for (chain, &i) in vec.into_iter().zip(ids) {
-        map.insert(i, chain.chain);
+/// Calculate longest path of node costs from each node to the end of the graph.
+fn critical_paths(nodes: &mut [ResolvedNode], unscheduled_nodes: &[usize]) {
+    // Order nodes so that each one comes after its dependencies.
+    let mut unscheduled = unscheduled_nodes.to_vec();
+    let mut order: Vec<usize> = (0..nodes.len())
+        .filter(|&id| unscheduled[id] == 0)
+        .collect();
+    let mut next = 0;
+    while next < order.len() {
+        for &rev_dep in &nodes[order[next]].rev_deps {
+            unscheduled[rev_dep] -= 1;
+            if unscheduled[rev_dep] == 0 {
+                order.push(rev_dep);
+            }
+        }
+        next += 1;
+    }
+
+    for node in nodes.iter_mut() {
+        node.critical_path = node.cost;
+    }
+    for &id in order.iter().rev() {
+        let longest = nodes[id]
+            .rev_deps
+            .iter()
+            .map(|&rev_dep| nodes[rev_dep].critical_path)
+            .max()
+            .unwrap_or(0);
+        nodes[id].critical_path = nodes[id].cost + longest;
+    }
+}
+
+/// Split ranges of each resource used by nodes into disjoint segments
+/// and map states of the nodes to chains of the segments.
+fn resolve_resources<R: Resource>(
+    nodes: Vec<Vec<(Id, State<R>)>>,
+) -> (Vec<Vec<(usize, State<R>)>>, Vec<ResolvedChain<R>>) {
+    let mut ids = LookupBuilder::new();
+    let mut ranges: Vec<Vec<R::Range>> = Vec::new();
+    for &(id, state) in nodes.iter().flatten() {
+        let index = ids.forward(id);
+        if index == ranges.len() {
+            ranges.push(Vec::new());
+        }
+        ranges[index].push(state.range);
+    }
+
+    let mut chains = Vec::new();
+    let mut segments = Vec::with_capacity(ranges.len());
+    for (&id, ranges) in ids.backward.iter().zip(&ranges) {
+        let first = chains.len();
+        let resource_segments = R::Range::segments(ranges);
+        chains.extend(
+            resource_segments
+                .iter()
+                .enumerate()
+                .map(|(segment, &range)| (id, segment, range)),
+        );
+        segments.push((first, resource_segments));
+    }
+
+    let nodes = nodes
+        .into_iter()
+        .map(|states| {
+            let mut resolved: Vec<(usize, State<R>)> = Vec::new();
+            for (id, state) in states {
+                let (first, ref resource_segments) = segments[ids.forward(id)];
+                for (segment, &range) in resource_segments.iter().enumerate() {
+                    if state.range.contains(&range) {
+                        let index = first + segment;
+                        match resolved.iter_mut().find(|(other, _)| *other == index) {
+                            // Overlapping ranges are accessed in combined state.
+                            Some((_, merged)) => {
+                                merged.access |= state.access;
+                                merged.stages |= state.stages;
+                                merged.usage |= state.usage;
+                                if merged.layout != state.layout {
+                                    merged.layout = R::layout_for(merged.access);
+                                }
+                            }
+                            None => resolved.push((index, State { range, ..state })),
+                        }
+                    }
+                }
+            }
+            resolved
+        })
+        .collect();
+
+    (nodes, chains)
+}
+
+fn reify_chain<R: Resource>(
+    chains: &[ResolvedChain<R>],
+    vec: Vec<ChainData<R>>,
+) -> HashMap<Id, Vec<Chain<R>>> {
+    let mut map: HashMap<Id, Vec<Chain<R>>> = HashMap::default();
+    for (chain, &(id, segment, _)) in vec.into_iter().zip(chains) {
+        let segments = map.entry(id).or_insert_with(Vec::new);
+        debug_assert_eq!(segments.len(), segment);
+        segments.push(chain.chain);
     }
     map
 }
@@ -288,51 +655,65 @@ fn reify_schedule(vec: Vec<QueueData>) -> Schedule<Unsynchronized> {
     schedule
 }
 
-fn fitness(
+fn push_candidates(
+    candidates: &mut Vec<Candidate>,
     node: &ResolvedNode,
-    images: &mut Vec<ChainData<Image>>,
-    buffers: &mut Vec<ChainData<Buffer>>,
-    schedule: &mut Vec<QueueData>,
-) -> (Fitness, usize) {
-    let mut transfers = 0;
-    let mut wait_factor_from_chains = 0;
-
-    // Collect minimal waits required and resource transfers count.
-    for &(id, _) in &node.buffers {
-        let chain = &buffers[id];
-        if chain
-            .current_family
-            .map_or(false, |family| family != node.family)
-        {
-            transfers += 1;
-        }
-        wait_factor_from_chains = max(wait_factor_from_chains, chain.last_link_wait_factor);
-    }
-    for &(id, _) in &node.images {
-        let chain = &images[id];
-        if chain
-            .current_family
-            .map_or(false, |family| family != node.family)
-        {
-            transfers += 1;
-        }
-        wait_factor_from_chains = max(wait_factor_from_chains, chain.last_link_wait_factor);
+    queues: &[QueueId],
+    images: &[ChainData<Image>],
+    buffers: &[ChainData<Buffer>],
+    schedule: &[QueueData],
+) {
+    // Collect minimal waits required by chains.
+    let wait_factor_from_chains = node
+        .buffers
+        .iter()
+        .map(|&(id, _)| buffers[id].last_link_wait_factor)
+        .chain(
+            node.images
+                .iter()
+                .map(|&(id, _)| images[id].last_link_wait_factor),
+        )
+        .max()
+        .unwrap_or(0);
+
+    // Node can be placed onto any queue of all compatible families.
+    // Each resource used on another family requires ownership transfer.
+    for (preference, &index) in node.queues.iter().enumerate() {
+        let family = queues[index].family();
+        let (buffer_transfers, buffer_barriers) = count_syncs(family, &node.buffers, buffers);
+        let (image_transfers, image_barriers) = count_syncs(family, &node.images, images);
+        candidates.push(Candidate {
+            node: node.id,
+            queue: index,
+            preference,
+            transfers: buffer_transfers + image_transfers,
+            barriers: buffer_barriers + image_barriers,
+            wait_factor: max(wait_factor_from_chains, schedule[index].wait_factor),
+            critical_path: node.critical_path,
+        });
     }
+}
 
-    // Find best queue for node.
-    let (wait_factor_from_queue, queue) = node
-        .queues
-        .clone()
-        .map(|index| (schedule[index].wait_factor, index))
-        .min()
-        .unwrap();
-    (
-        Fitness {
-            transfers,
-            wait_factor: max(wait_factor_from_chains, wait_factor_from_queue),
-        },
-        queue,
-    )
+/// Count ownership transfers and new links states would require on the family.
+fn count_syncs<R: Resource>(
+    family: rendy_core::hal::queue::QueueFamilyId,
+    states: &[(usize, State<R>)],
+    chains: &[ChainData<R>],
+) -> (usize, usize) {
+    states
+        .iter()
+        .fold((0, 0), |(transfers, barriers), (id, state)| {
+            let chain_data = &chains[*id];
+            let transfer = chain_data
+                .current_family
+                .map_or(false, |current| current != family);
+            let barrier = chain_data
+                .chain
+                .links()
+                .last()
+                .map_or(true, |link| !link.compatible_state(family, state));
+            (transfers + transfer as usize, barriers + barrier as usize)
+        })
 }
 
 fn schedule_node<'a>(
@@ -347,6 +728,7 @@ fn schedule_node<'a>(
     images: &mut Vec<ChainData<Image>>,
     buffers: &mut Vec<ChainData<Buffer>>,
 ) {
+    let family = nodes.queues[queue].family();
     let ref mut queue_data = schedule[queue];
     queue_data.wait_factor = max(queue_data.wait_factor, wait_factor + 1);
     let sid = queue_data
@@ -355,26 +737,16 @@ fn schedule_node<'a>(
     let submission = queue_data.queue.submission_mut(sid).unwrap();
 
     for &(id, state) in &node.buffers {
-        add_to_chain(
-            nodes.buffers[id],
-            node.family,
-            &mut buffers[id],
-            sid,
-            submission,
-            state,
-            |s, i, l| s.set_buffer_link(i, l),
-        );
+        let (buffer, segment, _) = nodes.buffers[id];
+        add_to_chain(family, &mut buffers[id], sid, submission, state, |s, l| {
+            s.set_buffer_link(buffer, segment, l)
+        });
     }
     for &(id, state) in &node.images {
-        add_to_chain(
-            nodes.images[id],
-            node.family,
-            &mut images[id],
-            sid,
-            submission,
-            state,
-            |s, i, l| s.set_image_link(i, l),
-        );
+        let (image, segment, _) = nodes.images[id];
+        add_to_chain(family, &mut images[id], sid, submission, state, |s, l| {
+            s.set_image_link(image, segment, l)
+        });
     }
 
     for &rev_dep in &node.rev_deps {
@@ -386,13 +758,12 @@ fn schedule_node<'a>(
 }
 
 fn add_to_chain<R, S>(
-    id: Id,
     family: rendy_core::hal::queue::QueueFamilyId,
     chain_data: &mut ChainData<R>,
     sid: SubmissionId,
     submission: &mut Submission<S>,
     state: State<R>,
-    set_link: impl FnOnce(&mut Submission<S>, Id, usize),
+    set_link: impl FnOnce(&mut Submission<S>, usize),
 ) where
     R: Resource,
 {
@@ -408,12 +779,12 @@ fn add_to_chain<R, S>(
     let chain_len = chain.links().len();
     let append = match chain.last_link_mut() {
         Some(ref mut link) if link.compatible(&node) => {
-            set_link(submission, id, chain_len - 1);
+            set_link(submission, chain_len - 1);
             link.add_node(node);
             None
         }
         Some(_) | None => {
-            set_link(submission, id, chain_len);
+            set_link(submission, chain_len);
             chain_data.last_link_wait_factor = chain_data.current_link_wait_factor;
             Some(Link::new(node))
         }
@@ -423,3 +794,167 @@ fn add_to_chain<R, S>(
         chain.add_link(link);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rendy_core::hal::{buffer::Access, pso::PipelineStage, queue::QueueFamilyId};
+
+    fn node(id: usize, dependencies: Vec<usize>, buffers: Vec<(usize, Access)>) -> Node {
+        Node {
+            id,
+            families: vec![QueueFamilyId(0)],
+            cost: 1,
+            dependencies,
+            buffers: buffers
+                .into_iter()
+                .map(|(buffer, access)| {
+                    (
+                        Id(buffer),
+                        State {
+                            access,
+                            layout: (),
+                            stages: PipelineStage::COMPUTE_SHADER,
+                            usage: rendy_core::hal::buffer::Usage::STORAGE,
+                            range: ResourceRange::whole(),
+                        },
+                    )
+                })
+                .collect(),
+            images: Vec::new(),
+        }
+    }
+
+    fn cycles(nodes: Vec<Node>) -> Vec<DependencyCycle> {
+        match collect(nodes, |_| 1) {
+            Ok(_) => Vec::new(),
+            Err(CollectError::DependencyCycles(cycles)) => cycles,
+        }
+    }
+
+    #[test]
+    fn explicit_cycle() {
+        let nodes = vec![
+            node(0, vec![], vec![]),
+            node(1, vec![0, 3], vec![]),
+            node(2, vec![1], vec![]),
+            node(3, vec![2], vec![]),
+        ];
+        assert_eq!(
+            cycles(nodes),
+            vec![DependencyCycle {
+                nodes: vec![1, 3, 2],
+                buffers: vec![],
+                images: vec![],
+            }],
+        );
+    }
+
+    #[test]
+    fn implicit_cycle() {
+        // Nodes 1 and 3 read buffers written by nodes declared before them,
+        // while node 0 explicitly depends on node 3.
+        let nodes = vec![
+            node(0, vec![3], vec![(0, Access::SHADER_WRITE)]),
+            node(1, vec![], vec![(0, Access::SHADER_READ)]),
+            node(2, vec![1], vec![(1, Access::SHADER_WRITE)]),
+            node(3, vec![], vec![(1, Access::SHADER_READ)]),
+        ];
+        assert_eq!(
+            cycles(nodes),
+            vec![DependencyCycle {
+                nodes: vec![0, 3, 2, 1],
+                buffers: vec![Id(1), Id(0)],
+                images: vec![],
+            }],
+        );
+    }
+
+    #[test]
+    fn read_write_after_earlier_writer() {
+        // Node 1 updates content written by node 0 in place.
+        let nodes = vec![
+            node(0, vec![], vec![(0, Access::SHADER_WRITE)]),
+            node(
+                1,
+                vec![],
+                vec![(0, Access::SHADER_READ | Access::SHADER_WRITE)],
+            ),
+            node(
+                2,
+                vec![],
+                vec![(0, Access::SHADER_READ | Access::SHADER_WRITE)],
+            ),
+        ];
+        let chains = collect(nodes, |_| 1).unwrap();
+        let order: Vec<usize> = chains.buffers[&Id(0)][0]
+            .links()
+            .iter()
+            .flat_map(|link| link.queues().map(|(qid, state)| (qid, state.first)))
+            .map(|(qid, first)| chains.schedule[SubmissionId::new(qid, first)].node())
+            .collect();
+        assert_eq!(order, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn write_after_earlier_readers_and_writer() {
+        // Node 3 overwrites buffer 0 nodes 1 and 2 read, node 4 overwrites buffer 1.
+        let nodes = vec![
+            node(0, vec![], vec![(0, Access::SHADER_WRITE)]),
+            node(1, vec![], vec![(0, Access::SHADER_READ)]),
+            node(2, vec![], vec![(0, Access::SHADER_READ)]),
+            node(
+                3,
+                vec![],
+                vec![(0, Access::SHADER_WRITE), (1, Access::SHADER_WRITE)],
+            ),
+            node(4, vec![], vec![(1, Access::SHADER_WRITE)]),
+        ];
+        let (resolved, _) = resolve_nodes(nodes, |_| 1);
+        let dependencies: Vec<Vec<usize>> = resolved
+            .nodes
+            .iter()
+            .map(|node| node.dependencies.iter().map(|&(d, _)| d).collect())
+            .collect();
+        assert_eq!(
+            dependencies,
+            vec![vec![], vec![0], vec![0], vec![0, 1, 2], vec![3]]
+        );
+    }
+
+    #[test]
+    fn read_before_later_write_is_not_cycle() {
+        // Node 0 reads buffer 1 before node 1 overwrites it, like feedback passes do.
+        let nodes = vec![
+            node(
+                0,
+                vec![],
+                vec![(0, Access::SHADER_WRITE), (1, Access::SHADER_READ)],
+            ),
+            node(
+                1,
+                vec![],
+                vec![(0, Access::SHADER_READ), (1, Access::SHADER_WRITE)],
+            ),
+        ];
+        assert!(cycles(nodes).is_empty());
+    }
+
+    #[test]
+    fn explicit_dependency_breaks_implicit_cycle() {
+        // Node 1 reads buffer 0 before node 0 overwrites it.
+        let nodes = vec![
+            node(
+                0,
+                vec![1],
+                vec![(0, Access::SHADER_WRITE), (1, Access::SHADER_READ)],
+            ),
+            node(
+                1,
+                vec![],
+                vec![(0, Access::SHADER_READ), (1, Access::SHADER_WRITE)],
+            ),
+        ];
+        assert!(cycles(nodes).is_empty());
+    }
+}
@@ -19,15 +19,29 @@ pub struct Id(pub usize);
 mod chain;
 mod collect;
 mod node;
+mod optimize;
 mod resource;
 mod schedule;
+mod scheduler;
 mod sync;
 
 pub use crate::{
     chain::{Chain, Link, LinkNode},
-    collect::{collect, Chains, Unsynchronized},
+    collect::{
+        collect, collect_with_scheduler, Chains, CollectError, DependencyCycle, Unsynchronized,
+    },
     node::{BufferState, ImageState, Node, State},
-    resource::{AccessFlags, Buffer, Image, Resource, UsageFlags},
+    optimize::{optimize_barriers, optimize_barriers_with_discards, BarrierStats},
+    resource::{
+        AccessFlags, Buffer, BufferRange, Image, ImageRange, Resource, ResourceRange, UsageFlags,
+    },
     schedule::{Family, Queue, QueueId, Schedule, Submission, SubmissionId},
-    sync::{sync, Barrier, Barriers, BufferBarriers, Guard, ImageBarriers, Signal, SyncData, Wait},
+    scheduler::{
+        Candidate, CriticalPathScheduler, FitnessScheduler, InsertionOrderScheduler,
+        MinimalBarrierScheduler, Scheduler,
+    },
+    sync::{
+        sync, sync_with_options, Barrier, Barriers, BufferBarriers, Guard, ImageBarriers, SetEvent,
+        Signal, SyncData, SyncOptions, Wait,
+    },
 };
@@ -1,5 +1,3 @@
-use std::collections::hash_map::{HashMap, Iter as HashMapIter};
-
 use crate::{
     resource::{Buffer, Image, Resource},
     Id,
@@ -19,6 +17,9 @@ pub struct State<R: Resource> {
 
     /// Usage flags required for resource.
     pub usage: R::Usage,
+
+    /// Range of the resource accessed.
+    pub range: R::Range,
 }
 
 /// Type alias for `State<Buffer>`
@@ -33,24 +34,34 @@ pub struct Node {
     /// Id of the node.
     pub id: usize,
 
-    /// Family required to execute the node.
-    pub family: rendy_core::hal::queue::QueueFamilyId,
+    /// Families that can execute the node.
+    /// Node is scheduled onto one of them.
+    /// Families listed first are preferred when others bring no benefit.
+    pub families: Vec<rendy_core::hal::queue::QueueFamilyId>,
+
+    /// Estimated cost of executing the node relative to other nodes.
+    /// Used by schedulers to find longest paths in the graph.
+    pub cost: usize,
 
     /// Dependencies of the node.
     /// Those are indices of other nodes in array.
     pub dependencies: Vec<usize>,
 
     /// Buffer category ids and required state.
-    pub buffers: HashMap<Id, State<Buffer>>,
+    /// Node may access disjoint ranges of the same buffer in different states.
+    /// Overlapping ranges are accessed in combined state.
+    pub buffers: Vec<(Id, State<Buffer>)>,
 
     /// Image category ids and required state.
-    pub images: HashMap<Id, State<Image>>,
+    /// Node may access disjoint ranges of the same image in different states.
+    /// Overlapping ranges are accessed in combined state.
+    pub images: Vec<(Id, State<Image>)>,
 }
 
 impl Node {
-    /// Get family on which this node will be executed.
-    pub fn family(&self) -> rendy_core::hal::queue::QueueFamilyId {
-        self.family
+    /// Get families on which this node can be executed.
+    pub fn families(&self) -> &[rendy_core::hal::queue::QueueFamilyId] {
+        &self.families
     }
 
     /// Get indices of nodes this node depends on.
@@ -59,12 +70,12 @@ impl Node {
     }
 
     /// Get iterator to buffer states this node accesses.
-    pub fn buffers(&self) -> HashMapIter<'_, Id, State<Buffer>> {
-        self.buffers.iter()
+    pub fn buffers(&self) -> impl Iterator<Item = (&Id, &State<Buffer>)> {
+        self.buffers.iter().map(|(id, state)| (id, state))
     }
 
     /// Get iterator to image states this node accesses.
-    pub fn images(&self) -> HashMapIter<'_, Id, State<Image>> {
-        self.images.iter()
+    pub fn images(&self) -> impl Iterator<Item = (&Id, &State<Image>)> {
+        self.images.iter().map(|(id, state)| (id, state))
     }
 }
@@ -0,0 +1,500 @@
+//! This module provides a pass that removes redundant barriers found by `sync`.
+//!
+
+use {
+    crate::{
+        chain::Chain,
+        collect::Chains,
+        resource::{AccessFlags, BufferRange, ImageRange, Resource, ResourceRange},
+        schedule::{Schedule, Submission},
+        sync::{Barrier, Barriers, Guard, SyncData},
+        Id,
+    },
+    std::collections::{HashMap, HashSet},
+};
+
+/// Statistics collected by `optimize_barriers`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BarrierStats {
+    /// Number of barriers before optimization.
+    pub total: usize,
+
+    /// Number of barriers merged into adjacent ones.
+    pub merged: usize,
+
+    /// Number of barriers dropped because they synchronize nothing.
+    pub dropped: usize,
+}
+
+impl BarrierStats {
+    /// Number of barriers left after optimization.
+    pub fn remaining(&self) -> usize {
+        self.total - self.merged - self.dropped
+    }
+}
+
+impl std::ops::AddAssign for BarrierStats {
+    fn add_assign(&mut self, other: Self) {
+        self.total += other.total;
+        self.merged += other.merged;
+        self.dropped += other.dropped;
+    }
+}
+
+/// Remove redundant barriers from the schedule.
+///
+/// Barriers that neither transition layout nor order any write
+/// (read-after-read on the same queue) are dropped.
+/// Barriers of the same resource at the same side of a submission
+/// with identical transitions are merged when their ranges are adjacent.
+/// Ownership transfers and halves of split barriers are never dropped.
+pub fn optimize_barriers<S, W>(schedule: &mut Schedule<SyncData<S, W>>) -> BarrierStats {
+    optimize(schedule, |_| (HashSet::new(), HashSet::new()))
+}
+
+/// Remove redundant barriers from the schedule like `optimize_barriers`,
+/// except barriers between the last and the first use in the frame of resources
+/// for which `discard_buffer` or `discard_image` returns `true`.
+///
+/// Content of such resources is not carried from the last use to the first one
+/// because they share memory with other resources or are discarded before first use.
+/// First use still has to wait for previous users of the memory.
+pub fn optimize_barriers_with_discards<S, W>(
+    schedule: &mut Schedule<SyncData<S, W>>,
+    chains: &Chains,
+    discard_buffer: impl Fn(Id) -> bool,
+    discard_image: impl Fn(Id) -> bool,
+) -> BarrierStats {
+    optimize(schedule, |submission| {
+        (
+            first_use_barriers(
+                submission,
+                &submission.sync().acquire.buffers,
+                &chains.buffers,
+                &discard_buffer,
+                Submission::buffer_link_index,
+            ),
+            first_use_barriers(
+                submission,
+                &submission.sync().acquire.images,
+                &chains.images,
+                &discard_image,
+                Submission::image_link_index,
+            ),
+        )
+    })
+}
+
+/// Acquire barriers to keep for buffers and images.
+type Keep = (HashSet<(Id, BufferRange)>, HashSet<(Id, ImageRange)>);
+
+fn optimize<S, W>(
+    schedule: &mut Schedule<SyncData<S, W>>,
+    keep: impl Fn(&Submission<SyncData<S, W>>) -> Keep,
+) -> BarrierStats {
+    let mut stats = BarrierStats::default();
+    for family in schedule.iter_mut() {
+        for queue in family.iter_mut() {
+            for submission in queue.iter_mut() {
+                let (buffers, images) = keep(submission);
+                let sync = submission.sync_mut();
+                stats += optimize_guard(&mut sync.acquire, &buffers, &images);
+                stats += optimize_guard(&mut sync.release, &HashSet::new(), &HashSet::new());
+            }
+        }
+    }
+    stats
+}
+
+/// Find acquire barriers of the submission from the last use to the first one
+/// of resources which content is discarded.
+/// Barriers found are not merged yet, so each one covers single segment of the resource.
+fn first_use_barriers<R: Resource, S>(
+    submission: &Submission<S>,
+    acquire: &Barriers<R>,
+    chains: &HashMap<Id, Vec<Chain<R>>>,
+    discard: impl Fn(Id) -> bool,
+    link_index: fn(&Submission<S>, Id, usize) -> usize,
+) -> HashSet<(Id, R::Range)> {
+    acquire
+        .iter()
+        .filter(|&(&id, _)| discard(id))
+        .flat_map(|(&id, barriers)| {
+            barriers.iter().filter_map(move |barrier| {
+                let segment = chains[&id]
+                    .iter()
+                    .position(|chain| chain.range() == barrier.range)?;
+                if link_index(submission, id, segment) == 0 {
+                    Some((id, barrier.range))
+                } else {
+                    None
+                }
+            })
+        })
+        .collect()
+}
+
+fn optimize_guard(
+    guard: &mut Guard,
+    keep_buffers: &HashSet<(Id, BufferRange)>,
+    keep_images: &HashSet<(Id, ImageRange)>,
+) -> BarrierStats {
+    let mut stats = optimize_barriers_map(&mut guard.buffers, keep_buffers);
+    stats += optimize_barriers_map(&mut guard.images, keep_images);
+    stats
+}
+
+fn optimize_barriers_map<R: Resource>(
+    barriers: &mut Barriers<R>,
+    keep: &HashSet<(Id, R::Range)>,
+) -> BarrierStats {
+    let mut stats = BarrierStats::default();
+    for (&id, barriers) in barriers.iter_mut() {
+        stats.total += barriers.len();
+
+        let before = barriers.len();
+        barriers.retain(|barrier| keep.contains(&(id, barrier.range)) || !redundant(barrier));
+        stats.dropped += before - barriers.len();
+
+        let before = barriers.len();
+        merge(barriers);
+        stats.merged += before - barriers.len();
+    }
+    barriers.retain(|_, barriers| !barriers.is_empty());
+    stats
+}
+
+/// Check if barrier synchronizes nothing.
+fn redundant<R: Resource>(barrier: &Barrier<R>) -> bool {
+    barrier.families.is_none()
+        && barrier.event.is_none()
+        && barrier.states.start.1 == barrier.states.end.1
+        && !barrier.states.start.0.exclusive()
+        && !barrier.states.end.0.exclusive()
+}
+
+/// Check if barriers differ only in ranges.
+fn same_transition<R: Resource>(left: &Barrier<R>, right: &Barrier<R>) -> bool {
+    left.families == right.families && left.states == right.states && left.event == right.event
+}
+
+/// Merge barriers until no pair can be merged.
+fn merge<R: Resource>(barriers: &mut Vec<Barrier<R>>) {
+    let mut index = 0;
+    while index < barriers.len() {
+        let merged = (index + 1..barriers.len()).find_map(|other| {
+            if same_transition(&barriers[index], &barriers[other]) {
+                barriers[index]
+                    .range
+                    .union(&barriers[other].range)
+                    .map(|range| (other, range))
+            } else {
+                None
+            }
+        });
+
+        match merged {
+            Some((other, range)) => {
+                barriers.remove(other);
+                barriers[index].range = range;
+                // Merged barrier may now be adjacent to barriers skipped before.
+                index = 0;
+            }
+            None => index += 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        collect::collect,
+        node::{Node, State},
+        resource::{Buffer, BufferRange, Image, ImageRange},
+        sync::sync,
+        Id,
+    };
+    use rendy_core::hal::{buffer, image, pso::PipelineStage, queue::QueueFamilyId};
+    use std::fmt::Write;
+
+    fn node(id: usize, dependencies: Vec<usize>) -> Node {
+        Node {
+            id,
+            families: vec![QueueFamilyId(0)],
+            cost: 1,
+            dependencies,
+            buffers: Vec::new(),
+            images: Vec::new(),
+        }
+    }
+
+    fn with_buffer(mut node: Node, range: std::ops::Range<u64>, access: buffer::Access) -> Node {
+        node.buffers.push((
+            Id(0),
+            State {
+                access,
+                layout: (),
+                stages: PipelineStage::COMPUTE_SHADER,
+                usage: buffer::Usage::STORAGE,
+                range: BufferRange::new(range),
+            },
+        ));
+        node
+    }
+
+    fn with_image(
+        mut node: Node,
+        range: ImageRange,
+        access: image::Access,
+        stages: PipelineStage,
+    ) -> Node {
+        node.images.push((
+            Id(0),
+            State {
+                access,
+                layout: Image::layout_for(access),
+                stages,
+                usage: image::Usage::empty(),
+                range,
+            },
+        ));
+        node
+    }
+
+    fn access<A: AccessFlags>(access: A) -> &'static str {
+        if access.exclusive() {
+            "write"
+        } else if access == A::empty() {
+            "none"
+        } else {
+            "read"
+        }
+    }
+
+    fn render_barriers<R: Resource>(
+        lines: &mut Vec<String>,
+        prefix: &str,
+        barriers: &Barriers<R>,
+        range: impl Fn(&R::Range) -> String,
+    ) {
+        for (id, barriers) in barriers {
+            for barrier in barriers {
+                let mut line = format!(
+                    "{} {} {}: {} {:?} -> {} {:?}",
+                    prefix,
+                    id.0,
+                    range(&barrier.range),
+                    access(barrier.states.start.0),
+                    barrier.states.start.1,
+                    access(barrier.states.end.0),
+                    barrier.states.end.1,
+                );
+                if let Some(families) = &barrier.families {
+                    write!(line, " families {}..{}", families.start.0, families.end.0).unwrap();
+                }
+                lines.push(line);
+            }
+        }
+    }
+
+    /// Render barriers of the whole schedule one per line in stable order.
+    fn render(schedule: &Schedule<SyncData<(), ()>>) -> String {
+        let mut lines = Vec::new();
+        for family in schedule.iter() {
+            for queue in family.iter() {
+                for submission in queue.iter() {
+                    let sync = submission.sync();
+                    for (side, guard) in &[("acquire", &sync.acquire), ("release", &sync.release)] {
+                        let prefix = format!("{} {}", submission.node(), side);
+                        render_barriers::<Buffer>(
+                            &mut lines,
+                            &format!("{} buffer", prefix),
+                            &guard.buffers,
+                            |range| format!("{:?}", range.range()),
+                        );
+                        render_barriers::<Image>(
+                            &mut lines,
+                            &format!("{} image", prefix),
+                            &guard.images,
+                            |range| {
+                                format!(
+                                    "levels {:?} layers {:?}",
+                                    range.level_range(),
+                                    range.layer_range()
+                                )
+                            },
+                        );
+                    }
+                }
+            }
+        }
+        lines.sort();
+        lines.join("\n")
+    }
+
+    /// Run `sync` and the optimization pass, checking rendered barriers before and after.
+    fn golden(nodes: Vec<Node>, before: &str, after: &str, stats: BarrierStats) {
+        let chains = collect(nodes, |_| 1).unwrap();
+        let mut schedule = sync(&chains, || ((), ()));
+        assert_eq!(render(&schedule), before.trim());
+        assert_eq!(optimize_barriers(&mut schedule), stats);
+        assert_eq!(render(&schedule), after.trim());
+    }
+
+    #[test]
+    fn read_only_image() {
+        golden(
+            vec![with_image(
+                node(0, vec![]),
+                ImageRange::whole(),
+                image::Access::SHADER_READ,
+                PipelineStage::FRAGMENT_SHADER,
+            )],
+            "
+0 acquire image 0 levels 0..255 layers 0..65535: read General -> read General
+",
+            "",
+            BarrierStats {
+                total: 1,
+                merged: 0,
+                dropped: 1,
+            },
+        );
+    }
+
+    #[test]
+    fn read_only_discarded_image() {
+        let nodes = vec![with_image(
+            node(0, vec![]),
+            ImageRange::whole(),
+            image::Access::SHADER_READ,
+            PipelineStage::FRAGMENT_SHADER,
+        )];
+        let chains = collect(nodes, |_| 1).unwrap();
+
+        // Discarding buffers doesn't keep image barriers.
+        let mut schedule = sync(&chains, || ((), ()));
+        optimize_barriers_with_discards(&mut schedule, &chains, |_| true, |_| false);
+        assert_eq!(render(&schedule), "");
+
+        let mut schedule = sync(&chains, || ((), ()));
+        assert_eq!(
+            optimize_barriers_with_discards(&mut schedule, &chains, |_| false, |id| id == Id(0)),
+            BarrierStats {
+                total: 1,
+                merged: 0,
+                dropped: 0,
+            },
+        );
+        assert_eq!(
+            render(&schedule),
+            "0 acquire image 0 levels 0..255 layers 0..65535: read General -> read General",
+        );
+    }
+
+    #[test]
+    fn scattered_reads_then_write() {
+        golden(
+            vec![
+                with_buffer(node(0, vec![]), 0..128, buffer::Access::SHADER_READ),
+                with_buffer(node(1, vec![]), 128..256, buffer::Access::SHADER_READ),
+                with_buffer(node(2, vec![0, 1]), 0..256, buffer::Access::SHADER_WRITE),
+            ],
+            "
+2 acquire buffer 0 0..128: read () -> write ()
+2 acquire buffer 0 128..256: read () -> write ()
+2 release buffer 0 0..128: write () -> read ()
+2 release buffer 0 128..256: write () -> read ()
+",
+            "
+2 acquire buffer 0 0..256: read () -> write ()
+2 release buffer 0 0..256: write () -> read ()
+",
+            BarrierStats {
+                total: 4,
+                merged: 2,
+                dropped: 0,
+            },
+        );
+    }
+
+    #[test]
+    fn whole_image_then_mip_chain() {
+        let mut nodes = vec![with_image(
+            node(0, vec![]),
+            ImageRange::whole(),
+            image::Access::TRANSFER_WRITE,
+            PipelineStage::TRANSFER,
+        )];
+        for level in 1..4u8 {
+            let downsample = node(level as usize, vec![level as usize - 1]);
+            let downsample = with_image(
+                downsample,
+                ImageRange::new(level - 1..level, 0..!0),
+                image::Access::SHADER_READ,
+                PipelineStage::COMPUTE_SHADER,
+            );
+            nodes.push(with_image(
+                downsample,
+                ImageRange::new(level..level + 1, 0..!0),
+                image::Access::SHADER_WRITE,
+                PipelineStage::COMPUTE_SHADER,
+            ));
+        }
+
+        golden(
+            nodes,
+            "
+0 acquire image 0 levels 0..1 layers 0..65535: read General -> write TransferDstOptimal
+0 acquire image 0 levels 1..2 layers 0..65535: read General -> write TransferDstOptimal
+0 acquire image 0 levels 2..3 layers 0..65535: read General -> write TransferDstOptimal
+0 acquire image 0 levels 3..4 layers 0..65535: write General -> write TransferDstOptimal
+0 acquire image 0 levels 4..255 layers 0..65535: write TransferDstOptimal -> write TransferDstOptimal
+0 release image 0 levels 0..1 layers 0..65535: write TransferDstOptimal -> read General
+1 acquire image 0 levels 1..2 layers 0..65535: write TransferDstOptimal -> write General
+1 release image 0 levels 1..2 layers 0..65535: write General -> read General
+2 acquire image 0 levels 2..3 layers 0..65535: write TransferDstOptimal -> write General
+2 release image 0 levels 2..3 layers 0..65535: write General -> read General
+3 acquire image 0 levels 3..4 layers 0..65535: write TransferDstOptimal -> write General
+",
+            "
+0 acquire image 0 levels 0..3 layers 0..65535: read General -> write TransferDstOptimal
+0 acquire image 0 levels 3..4 layers 0..65535: write General -> write TransferDstOptimal
+0 acquire image 0 levels 4..255 layers 0..65535: write TransferDstOptimal -> write TransferDstOptimal
+0 release image 0 levels 0..1 layers 0..65535: write TransferDstOptimal -> read General
+1 acquire image 0 levels 1..2 layers 0..65535: write TransferDstOptimal -> write General
+1 release image 0 levels 1..2 layers 0..65535: write General -> read General
+2 acquire image 0 levels 2..3 layers 0..65535: write TransferDstOptimal -> write General
+2 release image 0 levels 2..3 layers 0..65535: write General -> read General
+3 acquire image 0 levels 3..4 layers 0..65535: write TransferDstOptimal -> write General
+",
+            BarrierStats {
+                total: 11,
+                merged: 2,
+                dropped: 0,
+            },
+        );
+    }
+
+    #[test]
+    fn unions() {
+        let left = BufferRange::new(0..64);
+        assert_eq!(
+            left.union(&BufferRange::new(64..128)),
+            Some(BufferRange::new(0..128))
+        );
+        assert_eq!(left.union(&BufferRange::new(65..128)), None);
+
+        let levels = ImageRange::new(0..2, 0..1);
+        assert_eq!(
+            levels.union(&ImageRange::new(2..4, 0..1)),
+            Some(ImageRange::new(0..4, 0..1))
+        );
+        assert_eq!(levels.union(&ImageRange::new(2..4, 1..2)), None);
+        assert_eq!(
+            levels.union(&ImageRange::new(0..2, 1..6)),
+            Some(ImageRange::new(0..2, 0..6))
+        );
+    }
+}
@@ -1,10 +1,11 @@
 use std::{
     fmt::Debug,
-    ops::{BitOr, BitOrAssign},
+    hash::Hash,
+    ops::{BitOr, BitOrAssign, Range},
 };
 
 /// Trait to abstract of specific access flags.
-pub trait AccessFlags: Copy + Debug + BitOr<Output = Self> + BitOrAssign + 'static {
+pub trait AccessFlags: Copy + Debug + Eq + BitOr<Output = Self> + BitOrAssign + 'static {
     /// Get flags value with no flags set.
     fn empty() -> Self;
 
@@ -12,6 +13,12 @@ pub trait AccessFlags: Copy + Debug + BitOr<Output = Self> + BitOrAssign + 'stat
     ///
     /// Basically this checks if all flags are known read flags.
     fn exclusive(&self) -> bool;
+
+    /// Check if this access reads content of the resource.
+    ///
+    /// Basically this checks if any flag is not a known write flag.
+    /// Access without flags is treated as read.
+    fn reads(&self) -> bool;
 }
 
 impl AccessFlags for rendy_core::hal::buffer::Access {
@@ -26,6 +33,17 @@ impl AccessFlags for rendy_core::hal::buffer::Access {
             Self::SHADER_WRITE | Self::TRANSFER_WRITE | Self::HOST_WRITE | Self::MEMORY_WRITE,
         )
     }
+
+    #[inline]
+    fn reads(&self) -> bool {
+        self.is_empty()
+            || !(*self
+                - (Self::SHADER_WRITE
+                    | Self::TRANSFER_WRITE
+                    | Self::HOST_WRITE
+                    | Self::MEMORY_WRITE))
+                .is_empty()
+    }
 }
 
 impl AccessFlags for rendy_core::hal::image::Access {
@@ -45,6 +63,19 @@ impl AccessFlags for rendy_core::hal::image::Access {
                 | Self::MEMORY_WRITE,
         )
     }
+
+    #[inline]
+    fn reads(&self) -> bool {
+        self.is_empty()
+            || !(*self
+                - (Self::SHADER_WRITE
+                    | Self::COLOR_ATTACHMENT_WRITE
+                    | Self::DEPTH_STENCIL_ATTACHMENT_WRITE
+                    | Self::TRANSFER_WRITE
+                    | Self::HOST_WRITE
+                    | Self::MEMORY_WRITE))
+                .is_empty()
+    }
 }
 
 /// Trait to abstract of specific usage flags.
@@ -53,6 +84,208 @@ pub trait UsageFlags: Copy + Debug + BitOr<Output = Self> + BitOrAssign + 'stati
 impl UsageFlags for rendy_core::hal::buffer::Usage {}
 impl UsageFlags for rendy_core::hal::image::Usage {}
 
+/// Trait to abstract of ranges of the resource that are tracked independently.
+pub trait ResourceRange: Copy + Debug + Eq + Hash + 'static {
+    /// Range that covers whole resource.
+    fn whole() -> Self;
+
+    /// Check if this range contains all of the `other` range.
+    fn contains(&self, other: &Self) -> bool;
+
+    /// Get range that covers both ranges and nothing else.
+    /// Returns `None` if there is no such range.
+    fn union(&self, other: &Self) -> Option<Self>;
+
+    /// Split ranges into disjoint segments.
+    /// Each of the `ranges` is covered exactly by some of the segments.
+    /// Segments that are not covered by any of the `ranges` are omitted.
+    fn segments(ranges: &[Self]) -> Vec<Self>;
+}
+
+/// Range of bytes of the buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BufferRange {
+    start: u64,
+    end: u64,
+}
+
+impl BufferRange {
+    /// Create range of specified bytes.
+    pub fn new(range: Range<u64>) -> Self {
+        assert!(range.start <= range.end);
+        BufferRange {
+            start: range.start,
+            end: range.end,
+        }
+    }
+
+    /// Get bytes of the range.
+    pub fn range(&self) -> Range<u64> {
+        self.start..self.end
+    }
+
+    /// Check if range has no bytes.
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+impl ResourceRange for BufferRange {
+    /// Range of all bytes buffer can have.
+    /// Exceeds real size of any buffer, so nodes should use ranges of actual buffers.
+    fn whole() -> Self {
+        Self::new(0..!0)
+    }
+
+    fn contains(&self, other: &Self) -> bool {
+        other.is_empty() || (self.start <= other.start && other.end <= self.end)
+    }
+
+    fn union(&self, other: &Self) -> Option<Self> {
+        if self.start <= other.end && other.start <= self.end {
+            Some(BufferRange {
+                start: self.start.min(other.start),
+                end: self.end.max(other.end),
+            })
+        } else {
+            None
+        }
+    }
+
+    fn segments(ranges: &[Self]) -> Vec<Self> {
+        let mut bounds: Vec<u64> = ranges
+            .iter()
+            .filter(|range| !range.is_empty())
+            .flat_map(|range| vec![range.start, range.end])
+            .collect();
+        bounds.sort();
+        bounds.dedup();
+
+        bounds
+            .windows(2)
+            .map(|bounds| BufferRange {
+                start: bounds[0],
+                end: bounds[1],
+            })
+            .filter(|segment| ranges.iter().any(|range| range.contains(segment)))
+            .collect()
+    }
+}
+
+/// Range of mip levels and array layers of the image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ImageRange {
+    levels: (rendy_core::hal::image::Level, rendy_core::hal::image::Level),
+    layers: (rendy_core::hal::image::Layer, rendy_core::hal::image::Layer),
+}
+
+impl ImageRange {
+    /// Create range of specified mip levels and array layers.
+    pub fn new(
+        levels: Range<rendy_core::hal::image::Level>,
+        layers: Range<rendy_core::hal::image::Layer>,
+    ) -> Self {
+        assert!(levels.start <= levels.end && layers.start <= layers.end);
+        ImageRange {
+            levels: (levels.start, levels.end),
+            layers: (layers.start, layers.end),
+        }
+    }
+
+    /// Create range of specified mip levels of all array layers.
+    pub fn levels(levels: Range<rendy_core::hal::image::Level>) -> Self {
+        Self::new(levels, 0..!0)
+    }
+
+    /// Get mip levels of the range.
+    pub fn level_range(&self) -> Range<rendy_core::hal::image::Level> {
+        self.levels.0..self.levels.1
+    }
+
+    /// Get array layers of the range.
+    pub fn layer_range(&self) -> Range<rendy_core::hal::image::Layer> {
+        self.layers.0..self.layers.1
+    }
+
+    /// Check if range has no subresources.
+    pub fn is_empty(&self) -> bool {
+        self.levels.0 == self.levels.1 || self.layers.0 == self.layers.1
+    }
+}
+
+impl ResourceRange for ImageRange {
+    /// Range of all mip levels and array layers image can have.
+    /// Exceeds real subresources of any image, so nodes should use ranges of actual images.
+    fn whole() -> Self {
+        Self::new(0..!0, 0..!0)
+    }
+
+    fn contains(&self, other: &Self) -> bool {
+        other.is_empty()
+            || (self.levels.0 <= other.levels.0
+                && other.levels.1 <= self.levels.1
+                && self.layers.0 <= other.layers.0
+                && other.layers.1 <= self.layers.1)
+    }
+
+    fn union(&self, other: &Self) -> Option<Self> {
+        fn join<T: Ord + Copy>(left: (T, T), right: (T, T)) -> Option<(T, T)> {
+            if left.0 <= right.1 && right.0 <= left.1 {
+                Some((left.0.min(right.0), left.1.max(right.1)))
+            } else {
+                None
+            }
+        }
+
+        if self.layers == other.layers {
+            join(self.levels, other.levels).map(|levels| ImageRange {
+                levels,
+                layers: self.layers,
+            })
+        } else if self.levels == other.levels {
+            join(self.layers, other.layers).map(|layers| ImageRange {
+                levels: self.levels,
+                layers,
+            })
+        } else {
+            None
+        }
+    }
+
+    fn segments(ranges: &[Self]) -> Vec<Self> {
+        let bounds = |f: fn(&Self) -> (u16, u16)| {
+            let mut bounds: Vec<u16> = ranges
+                .iter()
+                .filter(|range| !range.is_empty())
+                .flat_map(|range| {
+                    let (start, end) = f(range);
+                    vec![start, end]
+                })
+                .collect();
+            bounds.sort();
+            bounds.dedup();
+            bounds
+        };
+
+        let levels = bounds(|range| (range.levels.0 as u16, range.levels.1 as u16));
+        let layers = bounds(|range| range.layers);
+
+        let mut segments = Vec::new();
+        for levels in levels.windows(2) {
+            for layers in layers.windows(2) {
+                let segment = ImageRange {
+                    levels: (levels[0] as _, levels[1] as _),
+                    layers: (layers[0], layers[1]),
+                };
+                if ranges.iter().any(|range| range.contains(&segment)) {
+                    segments.push(segment);
+                }
+            }
+        }
+        segments
+    }
+}
+
 /// Abstracts resource types that uses different usage flags and layouts types.
 pub trait Resource: 'static {
     /// Access flags for resource type.
@@ -62,7 +295,10 @@ pub trait Resource: 'static {
     type Usage: UsageFlags;
 
     /// Layout type for the resource.
-    type Layout: Copy + Debug + 'static;
+    type Layout: Copy + Debug + Eq + 'static;
+
+    /// Type of the resource ranges tracked independently.
+    type Range: ResourceRange;
 
     /// Empty usage.
     fn no_usage() -> Self::Usage;
@@ -79,6 +315,7 @@ impl Resource for Buffer {
     type Access = rendy_core::hal::buffer::Access;
     type Usage = rendy_core::hal::buffer::Usage;
     type Layout = ();
+    type Range = BufferRange;
 
     fn no_usage() -> Self::Usage {
         rendy_core::hal::buffer::Usage::empty()
@@ -98,6 +335,8 @@ impl Resource for Image {
 
     type Layout = rendy_core::hal::image::Layout;
 
+    type Range = ImageRange;
+
     fn no_usage() -> Self::Usage {
         rendy_core::hal::image::Usage::empty()
     }
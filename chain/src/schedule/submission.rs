@@ -39,8 +39,8 @@ impl SubmissionId {
 pub struct Submission<S> {
     node: usize,
     id: SubmissionId,
-    buffer_links: HashMap<Id, usize>,
-    image_links: HashMap<Id, usize>,
+    buffer_links: HashMap<(Id, usize), usize>,
+    image_links: HashMap<(Id, usize), usize>,
     wait_factor: usize,
     submit_order: usize,
     sync: S,
@@ -62,6 +62,11 @@ impl<S> Submission<S> {
         &self.sync
     }
 
+    /// Get mutable synchronization for `Submission`.
+    pub(crate) fn sync_mut(&mut self) -> &mut S {
+        &mut self.sync
+    }
+
     /// Get wait factor for `Submission`
     pub fn wait_factor(&self) -> usize {
         self.wait_factor
@@ -72,24 +77,24 @@ impl<S> Submission<S> {
         self.submit_order
     }
 
-    /// Get link index for resource by id.
-    pub fn buffer_link_index(&self, id: Id) -> usize {
-        self.buffer_links[&id]
+    /// Get link index for resource by id and index of the resource segment.
+    pub fn buffer_link_index(&self, id: Id, segment: usize) -> usize {
+        self.buffer_links[&(id, segment)]
     }
 
     /// Set link index for given chain.
-    pub fn set_buffer_link(&mut self, id: Id, link: usize) {
-        assert!(self.buffer_links.insert(id, link).is_none());
+    pub fn set_buffer_link(&mut self, id: Id, segment: usize, link: usize) {
+        assert!(self.buffer_links.insert((id, segment), link).is_none());
     }
 
-    /// Get link index for resource by id.
-    pub fn image_link_index(&self, id: Id) -> usize {
-        self.image_links[&id]
+    /// Get link index for resource by id and index of the resource segment.
+    pub fn image_link_index(&self, id: Id, segment: usize) -> usize {
+        self.image_links[&(id, segment)]
     }
 
     /// Set link index for given chain.
-    pub fn set_image_link(&mut self, id: Id, link: usize) {
-        assert!(self.image_links.insert(id, link).is_none());
+    pub fn set_image_link(&mut self, id: Id, segment: usize, link: usize) {
+        assert!(self.image_links.insert((id, segment), link).is_none());
     }
 
     /// Create new submission with specified pass.
@@ -0,0 +1,413 @@
+use crate::schedule::QueueId;
+
+/// Possible placement of a ready node onto a queue.
+/// `collect` lists candidates for every queue of every node
+/// which dependencies are already scheduled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Candidate {
+    /// Index of the node as reported by `Submission::node`.
+    pub node: usize,
+
+    /// Index of the queue in the list of queues passed to the scheduler.
+    pub queue: usize,
+
+    /// Position of the queue among all queues the node can be executed on.
+    /// Queues of the families listed first by the node come first.
+    pub preference: usize,
+
+    /// Number of resources that would be transferred from another family.
+    pub transfers: usize,
+
+    /// Number of resources that would require barrier before the node.
+    pub barriers: usize,
+
+    /// Wait factor of the submission if the node is scheduled onto the queue.
+    /// Submission can't start before submissions with lower wait factor.
+    pub wait_factor: usize,
+
+    /// Sum of costs of the node and nodes along the longest path of its dependents.
+    pub critical_path: usize,
+}
+
+impl Candidate {
+    /// Key ordering candidates by cost of placing the node onto the queue.
+    fn placement(&self) -> (usize, usize, usize, usize) {
+        (
+            self.transfers,
+            self.wait_factor,
+            self.preference,
+            self.queue,
+        )
+    }
+}
+
+/// Strategy that decides in which order nodes are submitted and onto which queues.
+pub trait Scheduler: std::fmt::Debug {
+    /// Pick next candidate to schedule.
+    /// `queues` contains all queues nodes can be scheduled onto.
+    /// Candidates of the same node are adjacent.
+    /// Returns index of the picked candidate.
+    fn pick(&self, queues: &[QueueId], candidates: &[Candidate]) -> usize;
+}
+
+/// Scheduler that picks placements with fewest transfers and lowest wait factor.
+/// With a single queue the node that became ready last is picked.
+///
+/// This is the default scheduler.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FitnessScheduler;
+
+impl Scheduler for FitnessScheduler {
+    fn pick(&self, queues: &[QueueId], candidates: &[Candidate]) -> usize {
+        if queues.len() == 1 {
+            return candidates.len() - 1;
+        }
+
+        // Find best queue for every node, then best node among them.
+        let mut best: Vec<(usize, &Candidate)> = Vec::new();
+        for (index, candidate) in candidates.iter().enumerate() {
+            match best.last_mut() {
+                Some(last) if last.1.node == candidate.node => {
+                    let key = |c: &Candidate| (c.transfers, c.wait_factor, c.preference);
+                    if key(candidate) < key(last.1) {
+                        *last = (index, candidate);
+                    }
+                }
+                _ => best.push((index, candidate)),
+            }
+        }
+
+        best.iter()
+            .enumerate()
+            .min_by_key(|&(position, &(_, c))| (c.transfers, c.wait_factor, c.queue, position))
+            .map(|(_, &(index, _))| index)
+            .unwrap()
+    }
+}
+
+/// Scheduler that submits nodes in order they were added.
+/// Queue is chosen with fewest transfers and lowest wait factor.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct InsertionOrderScheduler;
+
+impl Scheduler for InsertionOrderScheduler {
+    fn pick(&self, _queues: &[QueueId], candidates: &[Candidate]) -> usize {
+        min_index_by_key(candidates, |c| (c.node, c.placement()))
+    }
+}
+
+/// Scheduler that prefers nodes on the longest path to the end of the graph
+/// according to the node cost estimates.
+/// Ties are resolved in favor of nodes added first.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CriticalPathScheduler;
+
+impl Scheduler for CriticalPathScheduler {
+    fn pick(&self, _queues: &[QueueId], candidates: &[Candidate]) -> usize {
+        min_index_by_key(candidates, |c| {
+            (std::cmp::Reverse(c.critical_path), c.node, c.placement())
+        })
+    }
+}
+
+/// Scheduler that prefers nodes that can share barriers with nodes submitted before them.
+/// Ties are resolved in favor of nodes added first.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MinimalBarrierScheduler;
+
+impl Scheduler for MinimalBarrierScheduler {
+    fn pick(&self, _queues: &[QueueId], candidates: &[Candidate]) -> usize {
+        min_index_by_key(candidates, |c| (c.barriers, c.node, c.placement()))
+    }
+}
+
+fn min_index_by_key<K: Ord>(candidates: &[Candidate], key: impl Fn(&Candidate) -> K) -> usize {
+    candidates
+        .iter()
+        .enumerate()
+        .min_by_key(|&(_, candidate)| key(candidate))
+        .map(|(index, _)| index)
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chain::Chain,
+        collect::{collect_with_scheduler, Chains, CollectError},
+        node::{Node, State},
+        resource::{Buffer, BufferRange, Image, ImageRange, Resource, ResourceRange},
+        schedule::SubmissionId,
+        sync::sync,
+        Id,
+    };
+    use rendy_core::hal::{buffer, image, pso::PipelineStage, queue::QueueFamilyId};
+
+    const SCHEDULERS: &[&dyn Scheduler] = &[
+        &FitnessScheduler,
+        &InsertionOrderScheduler,
+        &CriticalPathScheduler,
+        &MinimalBarrierScheduler,
+    ];
+
+    /// Xorshift generator to get reproducible random graphs without extra dependencies.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self, bound: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % bound as u64) as usize
+        }
+
+        fn chance(&mut self) -> bool {
+            self.next(2) == 0
+        }
+    }
+
+    fn max_queues(family: QueueFamilyId) -> usize {
+        family.0 + 1
+    }
+
+    /// Generate nodes that rely on implicit ordering of resource accesses.
+    /// With `forward` some nodes explicitly depend on nodes declared after them,
+    /// which may contradict implicit ordering and cause dependency cycles.
+    fn random_nodes(rng: &mut Rng, forward: bool) -> Vec<Node> {
+        let count = 1 + rng.next(12);
+        let mut nodes: Vec<Node> = Vec::with_capacity(count);
+        for id in 0..count {
+            let families = match rng.next(3) {
+                0 => vec![QueueFamilyId(0)],
+                1 => vec![QueueFamilyId(1)],
+                _ => vec![QueueFamilyId(0), QueueFamilyId(1)],
+            };
+            let dependencies: Vec<usize> = (0..id).filter(|_| rng.next(4) == 0).collect();
+
+            let mut buffers = Vec::new();
+            for index in 0..3 {
+                if rng.chance() {
+                    let range = [0..64, 64..128, 0..128][rng.next(3)].clone();
+                    let access = if rng.chance() {
+                        buffer::Access::SHADER_READ
+                    } else {
+                        buffer::Access::SHADER_WRITE
+                    };
+                    buffers.push((
+                        Id(index),
+                        State {
+                            access,
+                            layout: (),
+                            stages: PipelineStage::COMPUTE_SHADER,
+                            usage: buffer::Usage::STORAGE,
+                            range: BufferRange::new(range),
+                        },
+                    ));
+                }
+            }
+
+            let mut images = Vec::new();
+            for index in 3..5 {
+                if rng.chance() {
+                    let access = if rng.chance() {
+                        image::Access::SHADER_READ
+                    } else {
+                        image::Access::SHADER_WRITE
+                    };
+                    images.push((
+                        Id(index),
+                        State {
+                            access,
+                            layout: Image::layout_for(access),
+                            stages: PipelineStage::COMPUTE_SHADER,
+                            usage: image::Usage::STORAGE,
+                            range: ImageRange::whole(),
+                        },
+                    ));
+                }
+            }
+
+            nodes.push(Node {
+                id,
+                families,
+                cost: 1 + rng.next(4),
+                dependencies,
+                buffers,
+                images,
+            });
+        }
+
+        if forward {
+            for id in 0..count - 1 {
+                if rng.next(4) == 0 {
+                    let later = id + 1 + rng.next(count - id - 1);
+                    nodes[id].dependencies.push(later);
+                }
+            }
+        }
+        nodes
+    }
+
+    /// Collect nodes, treating dependency cycles as a valid outcome.
+    fn try_collect(nodes: &[Node], scheduler: &dyn Scheduler) -> Option<Chains> {
+        match collect_with_scheduler(nodes.to_vec(), max_queues, scheduler) {
+            Ok(chains) => Some(chains),
+            Err(CollectError::DependencyCycles(cycles)) => {
+                assert!(!cycles.is_empty());
+                None
+            }
+        }
+    }
+
+    fn submission_order(chains: &Chains) -> Vec<(usize, SubmissionId)> {
+        let mut order: Vec<_> = chains
+            .schedule
+            .iter()
+            .flat_map(|family| family.iter())
+            .flat_map(|queue| queue.iter())
+            .map(|submission| {
+                (
+                    submission.submit_order(),
+                    submission.node(),
+                    submission.id(),
+                )
+            })
+            .collect();
+        order.sort_by_key(|&(submit_order, _, _)| submit_order);
+        order
+            .into_iter()
+            .map(|(_, node, sid)| (node, sid))
+            .collect()
+    }
+
+    fn check_links<R: Resource>(chains: &Chains, chain: &Chain<R>) {
+        let submit_order = |sid| chains.schedule[sid].submit_order();
+        for pair in chain.links().windows(2) {
+            let last = pair[0]
+                .queues()
+                .map(|(qid, queue)| submit_order(SubmissionId::new(qid, queue.last)))
+                .max()
+                .unwrap();
+            let first = pair[1]
+                .queues()
+                .map(|(qid, queue)| submit_order(SubmissionId::new(qid, queue.first)))
+                .min()
+                .unwrap();
+            assert!(last < first, "Links of the chain overlap");
+        }
+    }
+
+    fn check_schedule(nodes: &[Node], chains: &Chains) {
+        let order = submission_order(chains);
+
+        // Every node is submitted exactly once onto one of its families.
+        let mut submitted: Vec<_> = order.iter().map(|&(node, _)| node).collect();
+        submitted.sort();
+        assert_eq!(submitted, (0..nodes.len()).collect::<Vec<_>>());
+        for &(node, sid) in &order {
+            assert!(nodes[node].families.contains(&sid.family()));
+        }
+
+        // Dependencies are submitted first.
+        let position = |node| order.iter().position(|&(n, _)| n == node).unwrap();
+        for node in nodes {
+            for &dependency in &node.dependencies {
+                assert!(position(dependency) < position(node.id));
+            }
+        }
+
+        // Submissions on each queue follow submission order.
+        for queue in chains.schedule.iter().flat_map(|family| family.iter()) {
+            let orders: Vec<_> = queue.iter().map(|s| s.submit_order()).collect();
+            assert!(orders.windows(2).all(|pair| pair[0] < pair[1]));
+        }
+
+        for chain in chains.buffers.values().flatten() {
+            check_links::<Buffer>(chains, chain);
+        }
+        for chain in chains.images.values().flatten() {
+            check_links::<Image>(chains, chain);
+        }
+
+        sync(chains, || ((), ()));
+    }
+
+    #[test]
+    fn schedules_are_valid() {
+        let (mut scheduled, mut cycles) = (0, 0);
+        for seed in 1..200 {
+            for &forward in &[false, true] {
+                let nodes = random_nodes(&mut Rng(seed), forward);
+                for &scheduler in SCHEDULERS {
+                    match try_collect(&nodes, scheduler) {
+                        Some(chains) => {
+                            check_schedule(&nodes, &chains);
+                            scheduled += 1;
+                        }
+                        None => {
+                            // Only explicit dependencies on later nodes can cause cycles.
+                            assert!(forward);
+                            cycles += 1;
+                        }
+                    }
+                }
+            }
+        }
+        assert!(scheduled > 0 && cycles > 0);
+    }
+
+    #[test]
+    fn schedules_are_deterministic() {
+        for seed in 1..50 {
+            let nodes = random_nodes(&mut Rng(seed), true);
+            for &scheduler in SCHEDULERS {
+                let first = try_collect(&nodes, scheduler);
+                let second = try_collect(&nodes, scheduler);
+                assert_eq!(
+                    first.as_ref().map(submission_order),
+                    second.as_ref().map(submission_order)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn insertion_order() {
+        for seed in 1..50 {
+            let mut nodes = random_nodes(&mut Rng(seed), false);
+            for node in &mut nodes {
+                node.families = vec![QueueFamilyId(0)];
+            }
+            let chains =
+                collect_with_scheduler(nodes.clone(), max_queues, &InsertionOrderScheduler)
+                    .unwrap();
+            let order: Vec<_> = submission_order(&chains)
+                .into_iter()
+                .map(|(node, _)| node)
+                .collect();
+            assert_eq!(order, (0..nodes.len()).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn critical_path_first() {
+        let node = |id, cost, dependencies| Node {
+            id,
+            families: vec![QueueFamilyId(0)],
+            cost,
+            dependencies,
+            buffers: Vec::new(),
+            images: Vec::new(),
+        };
+
+        // Node 1 starts the longest path and must be submitted before node 0.
+        let nodes = vec![node(0, 2, vec![]), node(1, 1, vec![]), node(2, 2, vec![1])];
+        let chains = collect_with_scheduler(nodes, max_queues, &CriticalPathScheduler).unwrap();
+        let order: Vec<_> = submission_order(&chains)
+            .into_iter()
+            .map(|(node, _)| node)
+            .collect();
+        assert_eq!(order, vec![1, 0, 2]);
+    }
+}
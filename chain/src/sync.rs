@@ -19,12 +19,17 @@ use crate::{
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 struct Semaphore {
     id: Id,
+    segment: usize,
     points: Range<SubmissionId>,
 }
 
 impl Semaphore {
-    fn new(id: Id, points: Range<SubmissionId>) -> Self {
-        Semaphore { id, points }
+    fn new(id: Id, segment: usize, points: Range<SubmissionId>) -> Self {
+        Semaphore {
+            id,
+            segment,
+            points,
+        }
     }
 }
 
@@ -71,6 +76,24 @@ impl<S> Wait<S> {
     }
 }
 
+/// Event set info.
+/// There must be paired acquire barrier that waits for the event.
+/// Release side barriers of the submission that carry the event describe what it guards.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SetEvent(usize, rendy_core::hal::pso::PipelineStage);
+
+impl SetEvent {
+    /// Index of the event.
+    pub fn event(&self) -> usize {
+        self.0
+    }
+
+    /// Stages after which event is set.
+    pub fn stage(&self) -> rendy_core::hal::pso::PipelineStage {
+        self.1
+    }
+}
+
 /// Pipeline barrier info.
 #[derive(Clone, Debug)]
 pub struct Barrier<R: Resource> {
@@ -79,13 +102,21 @@ pub struct Barrier<R: Resource> {
 
     /// State transition.
     pub states: Range<(R::Access, R::Layout, rendy_core::hal::pso::PipelineStage)>,
+
+    /// `Some` event if this is a half of split barrier. Or `None`.
+    /// Release side half sets the event after `states.start` stages
+    /// and acquire side half waits for it and performs the transition.
+    pub event: Option<usize>,
+
+    /// Range of the resource affected by the barrier.
+    pub range: R::Range,
 }
 
 impl<R> Barrier<R>
 where
     R: Resource,
 {
-    fn new(states: Range<State<R>>) -> Self {
+    fn new(range: R::Range, states: Range<State<R>>) -> Self {
         Barrier {
             families: None,
             states: (
@@ -93,10 +124,20 @@ where
                 states.start.layout,
                 states.start.stages,
             )..(states.end.access, states.end.layout, states.end.stages),
+            event: None,
+            range,
+        }
+    }
+
+    fn split(range: R::Range, states: Range<State<R>>, event: usize) -> Self {
+        Barrier {
+            event: Some(event),
+            ..Self::new(range, states)
         }
     }
 
     fn transfer(
+        range: R::Range,
         families: Range<rendy_core::hal::queue::QueueFamilyId>,
         states: Range<(R::Access, R::Layout)>,
     ) -> Self {
@@ -112,26 +153,32 @@ where
                     states.end.1,
                     rendy_core::hal::pso::PipelineStage::BOTTOM_OF_PIPE,
                 ),
+            event: None,
+            range,
         }
     }
 
     fn acquire(
+        range: R::Range,
         families: Range<rendy_core::hal::queue::QueueFamilyId>,
         left: RangeFrom<R::Layout>,
         right: RangeTo<(R::Access, R::Layout)>,
     ) -> Self {
         Self::transfer(
+            range,
             families,
             (R::Access::empty(), left.start)..(right.end.0, right.end.1),
         )
     }
 
     fn release(
+        range: R::Range,
         families: Range<rendy_core::hal::queue::QueueFamilyId>,
         left: RangeFrom<(R::Access, R::Layout)>,
         right: RangeTo<R::Layout>,
     ) -> Self {
         Self::transfer(
+            range,
             families,
             (left.start.0, left.start.1)..(R::Access::empty(), right.end),
         )
@@ -139,7 +186,8 @@ where
 }
 
 /// Map of barriers by resource id.
-pub type Barriers<R> = HashMap<Id, Barrier<R>>;
+/// Each barrier of the resource affects separate range of it.
+pub type Barriers<R> = HashMap<Id, Vec<Barrier<R>>>;
 
 /// Map of barriers by buffer id.
 pub type BufferBarriers = Barriers<Buffer>;
@@ -175,6 +223,13 @@ impl Guard {
             .or_else(move || Any::downcast_mut(images))
             .expect("`R` should be `Buffer` or `Image`")
     }
+
+    fn push<R: Resource>(&mut self, id: Id, barrier: Barrier<R>) {
+        self.pick::<R>()
+            .entry(id)
+            .or_insert_with(Vec::new)
+            .push(barrier);
+    }
 }
 
 /// Both sides of synchronization for submission.
@@ -191,6 +246,10 @@ pub struct SyncData<S, W> {
     /// Synchronization commands from this side must be recorded after main commands of submission.
     pub release: Guard,
 
+    /// Events to set after release side barriers.
+    /// Acquire side barriers of later submissions on the same queue wait for them.
+    pub set_events: Vec<SetEvent>,
+
     /// Points at other queues that can run after barriers above.
     pub signal: Vec<Signal<S>>,
 }
@@ -201,6 +260,7 @@ impl<S, W> SyncData<S, W> {
             wait: Vec::new(),
             acquire: Guard::new(),
             release: Guard::new(),
+            set_events: Vec::new(),
             signal: Vec::new(),
         }
     }
@@ -219,6 +279,7 @@ impl<S, W> SyncData<S, W> {
                 buffers: self.release.buffers,
                 images: self.release.images,
             },
+            set_events: self.set_events,
             signal: self
                 .signal
                 .into_iter()
@@ -245,20 +306,53 @@ impl<S, W> SyncData<S, W> {
                 buffers: self.release.buffers,
                 images: self.release.images,
             },
+            set_events: self.set_events,
             signal: self.signal,
         }
     }
 }
 
-struct SyncTemp(HashMap<SubmissionId, SyncData<Semaphore, Semaphore>>);
+/// Options for synchronization generation.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SyncOptions {
+    /// Generate split barriers (event set after producer and waited before consumer)
+    /// instead of pipeline barriers when both sides are on the same queue
+    /// and other submissions are executed between them.
+    /// Events are indexed sequentially starting from zero.
+    /// Submission that waits for the event is expected to reset it afterwards.
+    pub split_barriers: bool,
+}
+
+struct SyncTemp {
+    map: HashMap<SubmissionId, SyncData<Semaphore, Semaphore>>,
+    options: SyncOptions,
+    events: usize,
+}
 impl SyncTemp {
     fn get_sync(&mut self, sid: SubmissionId) -> &mut SyncData<Semaphore, Semaphore> {
-        self.0.entry(sid).or_insert_with(|| SyncData::new())
+        self.map.entry(sid).or_insert_with(|| SyncData::new())
+    }
+
+    fn new_event(&mut self) -> usize {
+        self.events += 1;
+        self.events - 1
     }
 }
 
 /// Find required synchronization for all submissions in `Chains`.
-pub fn sync<F, S, W>(chains: &Chains, mut new_semaphore: F) -> Schedule<SyncData<S, W>>
+pub fn sync<F, S, W>(chains: &Chains, new_semaphore: F) -> Schedule<SyncData<S, W>>
+where
+    F: FnMut() -> (S, W),
+{
+    sync_with_options(chains, SyncOptions::default(), new_semaphore)
+}
+
+/// Find required synchronization for all submissions in `Chains` using specified options.
+pub fn sync_with_options<F, S, W>(
+    chains: &Chains,
+    options: SyncOptions,
+    mut new_semaphore: F,
+) -> Schedule<SyncData<S, W>>
 where
     F: FnMut() -> (S, W),
 {
@@ -266,12 +360,20 @@ where
     let ref buffers = chains.buffers;
     let ref images = chains.images;
 
-    let mut sync = SyncTemp(HashMap::default());
-    for (&id, chain) in buffers {
-        sync_chain(id, chain, schedule, &mut sync);
+    let mut sync = SyncTemp {
+        map: HashMap::default(),
+        options,
+        events: 0,
+    };
+    for (&id, chains) in buffers {
+        for (segment, chain) in chains.iter().enumerate() {
+            sync_chain(id, segment, chain, schedule, &mut sync);
+        }
     }
-    for (&id, chain) in images {
-        sync_chain(id, chain, schedule, &mut sync);
+    for (&id, chains) in images {
+        for (segment, chain) in chains.iter().enumerate() {
+            sync_chain(id, segment, chain, schedule, &mut sync);
+        }
     }
     if schedule.queue_count() > 1 {
         optimize(schedule, &mut sync);
@@ -284,7 +386,7 @@ where
     for queue in schedule.iter().flat_map(|family| family.iter()) {
         let mut new_queue = Queue::new(queue.id());
         for submission in queue.iter() {
-            let sync = if let Some(sync) = sync.0.remove(&submission.id()) {
+            let sync = if let Some(sync) = sync.map.remove(&submission.id()) {
                 let sync = sync.convert_signal(|semaphore| match signals.get_mut(&semaphore) {
                     None => {
                         let (signal, wait) = new_semaphore();
@@ -312,7 +414,7 @@ where
         result.set_queue(new_queue);
     }
 
-    debug_assert!(sync.0.is_empty());
+    debug_assert!(sync.map.is_empty());
     debug_assert!(signals.values().all(|x| x.is_none()));
     debug_assert!(waits.values().all(|x| x.is_none()));
 
@@ -354,11 +456,12 @@ where
 fn generate_semaphore_pair<R: Resource>(
     sync: &mut SyncTemp,
     id: Id,
+    segment: usize,
     link: &Link<R>,
     range: Range<SubmissionId>,
 ) {
     if range.start.queue() != range.end.queue() {
-        let semaphore = Semaphore::new(id, range.clone());
+        let semaphore = Semaphore::new(id, segment, range.clone());
         sync.get_sync(range.start)
             .signal
             .push(Signal::new(semaphore.clone()));
@@ -368,11 +471,52 @@ fn generate_semaphore_pair<R: Resource>(
     }
 }
 
-fn sync_chain<R, S>(id: Id, chain: &Chain<R>, schedule: &Schedule<S>, sync: &mut SyncTemp)
+// Split barrier is useful only if producer and consumer are on the same queue
+// and there are other submissions between them.
+fn split_barrier<R, S>(
+    prev_link: &Link<R>,
+    link: &Link<R>,
+    schedule: &Schedule<S>,
+) -> Option<Range<SubmissionId>>
 where
     R: Resource,
 {
-    let uid = id.into();
+    if prev_link.family() != link.family() || !prev_link.single_queue() || !link.single_queue() {
+        return None;
+    }
+
+    let signal_sid = latest(prev_link, schedule);
+    let wait_sid = earliest(link, schedule);
+    if signal_sid.queue() == wait_sid.queue() && signal_sid.index() + 1 < wait_sid.index() {
+        Some(signal_sid..wait_sid)
+    } else {
+        None
+    }
+}
+
+fn sync_chain<R, S>(
+    id: Id,
+    segment: usize,
+    chain: &Chain<R>,
+    schedule: &Schedule<S>,
+    sync: &mut SyncTemp,
+) where
+    R: Resource,
+{
+    let range = chain.range();
+
+    // External resource is acquired from and released to the external states
+    // instead of being passed from the last link to the first one.
+    if let Some(external) = chain.external() {
+        if let (Some(first), Some(last)) = (chain.links().first(), chain.links().last()) {
+            sync.get_sync(earliest(first, schedule))
+                .acquire
+                .push(id, Barrier::new(range, external.start..first.state()));
+            sync.get_sync(latest(last, schedule))
+                .release
+                .push(id, Barrier::new(range, last.state()..external.end));
+        }
+    }
 
     let pairs = chain
         .links()
@@ -382,12 +526,38 @@ where
             chain
                 .links()
                 .first()
+                .filter(|_| chain.external().is_none())
                 .and_then(|first| chain.links().last().map(move |last| (last, first))),
         );
 
     for (prev_link, link) in pairs {
         log::trace!("Sync {:#?}:{:#?}", prev_link.access(), link.access());
-        if prev_link.family() == link.family() {
+        let split = if sync.options.split_barriers {
+            split_barrier(prev_link, link, schedule)
+        } else {
+            None
+        };
+
+        if let Some(points) = split {
+            let event = sync.new_event();
+
+            // Set event in prev link's last submission.
+            let signal = sync.get_sync(points.start);
+            signal.set_events.push(SetEvent(
+                event,
+                prev_link.queue(points.start.queue()).stages,
+            ));
+            signal.release.push(
+                id,
+                Barrier::split(range, prev_link.state()..link.state(), event),
+            );
+
+            // Wait for the event in next link's first submission.
+            sync.get_sync(points.end).acquire.push(
+                id,
+                Barrier::split(range, prev_link.state()..link.state(), event),
+            );
+        } else if prev_link.family() == link.family() {
             // Prefer to generate barriers on the acquire side, if possible.
             if prev_link.access().exclusive() && !link.access().exclusive() {
                 let signal_sid = latest(prev_link, schedule);
@@ -395,13 +565,12 @@ where
                 // Generate barrier in prev link's last submission.
                 sync.get_sync(signal_sid)
                     .release
-                    .pick::<R>()
-                    .insert(id, Barrier::new(prev_link.state()..link.state()));
+                    .push(id, Barrier::new(range, prev_link.state()..link.state()));
 
                 // Generate semaphores between queues in the previous link and the current one.
                 for (queue_id, queue) in link.queues() {
                     let head = SubmissionId::new(queue_id, queue.first);
-                    generate_semaphore_pair(sync, uid, link, signal_sid..head);
+                    generate_semaphore_pair(sync, id, segment, link, signal_sid..head);
                 }
             } else {
                 let wait_sid = earliest(link, schedule);
@@ -409,50 +578,57 @@ where
                 // Generate semaphores between queues in the previous link and the current one.
                 for (queue_id, queue) in prev_link.queues() {
                     let tail = SubmissionId::new(queue_id, queue.last);
-                    generate_semaphore_pair(sync, uid, link, tail..wait_sid);
+                    generate_semaphore_pair(sync, id, segment, link, tail..wait_sid);
                 }
 
                 // Generate barrier in next link's first submission.
                 sync.get_sync(wait_sid)
                     .acquire
-                    .pick()
-                    .insert(id, Barrier::new(prev_link.state()..link.state()));
+                    .push(id, Barrier::new(range, prev_link.state()..link.state()));
 
-                if !link.access().exclusive() {
-                    unimplemented!("This case is unimplemented");
+                // Other queues in the next link must wait for the barrier.
+                for (queue_id, queue) in link.queues() {
+                    let head = SubmissionId::new(queue_id, queue.first);
+                    generate_semaphore_pair(sync, id, segment, link, wait_sid..head);
                 }
             }
         } else {
             let signal_sid = latest(prev_link, schedule);
             let wait_sid = earliest(link, schedule);
 
-            if !prev_link.access().exclusive() {
-                unimplemented!("This case is unimplemented");
+            // Release must happen after all queues in the previous link are done.
+            for (queue_id, queue) in prev_link.queues() {
+                let tail = SubmissionId::new(queue_id, queue.last);
+                generate_semaphore_pair(sync, id, segment, prev_link, tail..signal_sid);
             }
 
             // Generate a semaphore between the signal and wait sides of the transfer.
-            generate_semaphore_pair(sync, uid, link, signal_sid..wait_sid);
+            generate_semaphore_pair(sync, id, segment, link, signal_sid..wait_sid);
 
             // Generate barriers to transfer the resource to another family.
-            sync.get_sync(signal_sid).release.pick::<R>().insert(
+            sync.get_sync(signal_sid).release.push(
                 id,
                 Barrier::release(
+                    range,
                     signal_sid.family()..wait_sid.family(),
                     (prev_link.access(), prev_link.layout())..,
                     ..link.layout(),
                 ),
             );
-            sync.get_sync(wait_sid).acquire.pick::<R>().insert(
+            sync.get_sync(wait_sid).acquire.push(
                 id,
                 Barrier::acquire(
+                    range,
                     signal_sid.family()..wait_sid.family(),
                     prev_link.layout()..,
                     ..(link.access(), link.layout()),
                 ),
             );
 
-            if !link.access().exclusive() {
-                unimplemented!("This case is unimplemented");
+            // Other queues in the next link must wait for the acquire.
+            for (queue_id, queue) in link.queues() {
+                let head = SubmissionId::new(queue_id, queue.first);
+                generate_semaphore_pair(sync, id, segment, link, wait_sid..head);
             }
         }
     }
@@ -464,7 +640,7 @@ fn optimize_submission(
     sync: &mut SyncTemp,
 ) {
     let mut to_remove = Vec::new();
-    if let Some(sync_data) = sync.0.get_mut(&sid) {
+    if let Some(sync_data) = sync.map.get_mut(&sid) {
         sync_data
             .wait
             .sort_unstable_by_key(|wait| (wait.stage(), wait.semaphore().points.end.index()));
@@ -488,7 +664,7 @@ fn optimize_submission(
 
     for semaphore in to_remove.drain(..) {
         // Delete signal as well.
-        let ref mut signal = sync.0.get_mut(&semaphore.points.start).unwrap().signal;
+        let ref mut signal = sync.map.get_mut(&semaphore.points.start).unwrap().signal;
         let index = signal
             .iter()
             .position(|signal| signal.0 == semaphore)
@@ -505,3 +681,490 @@ fn optimize<S>(schedule: &Schedule<S>, sync: &mut SyncTemp) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        collect::collect,
+        node::Node,
+        resource::{BufferRange, ImageRange, ResourceRange},
+        schedule::Submission,
+    };
+    use rendy_core::hal::{image::Access, pso::PipelineStage, queue::QueueFamilyId};
+
+    const IMAGE: Id = Id(0);
+    const BUFFER: Id = Id(1);
+
+    fn state(range: ImageRange, access: Access, stages: PipelineStage) -> State<Image> {
+        State {
+            access,
+            layout: Image::layout_for(access),
+            stages,
+            usage: rendy_core::hal::image::Usage::empty(),
+            range,
+        }
+    }
+
+    fn node(id: usize, dependencies: Vec<usize>, images: Vec<State<Image>>) -> Node {
+        Node {
+            id,
+            families: vec![QueueFamilyId(0)],
+            cost: 1,
+            dependencies,
+            buffers: Vec::new(),
+            images: images.into_iter().map(|state| (IMAGE, state)).collect(),
+        }
+    }
+
+    fn buffer_node(id: usize, dependencies: Vec<usize>, range: Range<u64>) -> Node {
+        Node {
+            id,
+            families: vec![QueueFamilyId(0)],
+            cost: 1,
+            dependencies,
+            buffers: vec![(
+                BUFFER,
+                State {
+                    access: rendy_core::hal::buffer::Access::SHADER_WRITE,
+                    layout: (),
+                    stages: PipelineStage::COMPUTE_SHADER,
+                    usage: rendy_core::hal::buffer::Usage::STORAGE,
+                    range: BufferRange::new(range),
+                },
+            )],
+            images: Vec::new(),
+        }
+    }
+
+    /// Node that reads previous mip level and writes `level`.
+    fn downsample(level: u8, dependency: usize) -> Node {
+        node(
+            level as usize,
+            vec![dependency],
+            vec![
+                state(
+                    ImageRange::levels(level - 1..level),
+                    Access::SHADER_READ,
+                    PipelineStage::COMPUTE_SHADER,
+                ),
+                state(
+                    ImageRange::levels(level..level + 1),
+                    Access::SHADER_WRITE,
+                    PipelineStage::COMPUTE_SHADER,
+                ),
+            ],
+        )
+    }
+
+    /// First node renders into level 0 and others generate the rest of mip chain.
+    fn mip_chain(levels: u8) -> Vec<Node> {
+        let mut nodes = vec![node(
+            0,
+            vec![],
+            vec![state(
+                ImageRange::levels(0..1),
+                Access::COLOR_ATTACHMENT_WRITE,
+                PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+            )],
+        )];
+        nodes.extend((1..levels).map(|level| downsample(level, level as usize - 1)));
+        nodes
+    }
+
+    fn submission<S>(schedule: &Schedule<S>, node: usize) -> &Submission<S> {
+        schedule
+            .iter()
+            .flat_map(|family| family.iter())
+            .flat_map(|queue| queue.iter())
+            .find(|submission| submission.node() == node)
+            .unwrap()
+    }
+
+    fn levels(guard: &Guard) -> Vec<Range<u8>> {
+        let mut levels: Vec<_> = guard
+            .images
+            .get(&IMAGE)
+            .into_iter()
+            .flatten()
+            .map(|barrier| barrier.range.level_range())
+            .collect();
+        levels.sort_by_key(|levels| levels.start);
+        levels
+    }
+
+    #[test]
+    fn image_range_segments() {
+        assert_eq!(
+            ImageRange::segments(&[ImageRange::levels(0..4), ImageRange::levels(1..2)]),
+            vec![
+                ImageRange::levels(0..1),
+                ImageRange::levels(1..2),
+                ImageRange::levels(2..4),
+            ],
+        );
+        assert_eq!(
+            ImageRange::segments(&[ImageRange::new(0..1, 0..2), ImageRange::new(0..1, 1..3)]),
+            vec![
+                ImageRange::new(0..1, 0..1),
+                ImageRange::new(0..1, 1..2),
+                ImageRange::new(0..1, 2..3),
+            ],
+        );
+        assert_eq!(
+            ImageRange::segments(&[ImageRange::new(0..1, 0..1), ImageRange::new(2..3, 0..1)]),
+            vec![ImageRange::new(0..1, 0..1), ImageRange::new(2..3, 0..1)],
+        );
+    }
+
+    #[test]
+    fn mip_chain_links() {
+        let chains = collect(mip_chain(4), |_| 1).unwrap();
+        let image = &chains.images[&IMAGE];
+        assert_eq!(
+            image.iter().map(Chain::range).collect::<Vec<_>>(),
+            (0..4)
+                .map(|level| ImageRange::levels(level..level + 1))
+                .collect::<Vec<_>>(),
+        );
+        assert_eq!(
+            image
+                .iter()
+                .map(|chain| chain.links().len())
+                .collect::<Vec<_>>(),
+            vec![2, 2, 2, 1],
+        );
+
+        // Level 0 is read in second link and level 1 is written in first one.
+        let second = submission(&chains.schedule, 1);
+        assert_eq!(second.image_link_index(IMAGE, 0), 1);
+        assert_eq!(second.image_link_index(IMAGE, 1), 0);
+    }
+
+    #[test]
+    fn mip_chain_barriers() {
+        let chains = collect(mip_chain(4), |_| 1).unwrap();
+        let schedule = sync(&chains, || ((), ()));
+
+        for level in 0..4u8 {
+            let sync = submission(&schedule, level as usize).sync();
+            assert!(sync.wait.is_empty() && sync.signal.is_empty());
+
+            // Each node transitions only the level it writes.
+            // Level it reads was already transitioned by the previous node.
+            assert_eq!(levels(&sync.acquire), vec![level..level + 1]);
+            if level < 3 {
+                assert_eq!(levels(&sync.release), vec![level..level + 1]);
+                let barrier = &sync.release.images[&IMAGE][0];
+                assert_eq!(barrier.states.end.0, Access::SHADER_READ);
+            } else {
+                assert!(levels(&sync.release).is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn whole_image_split_into_levels() {
+        let mut nodes = vec![node(
+            0,
+            vec![],
+            vec![state(
+                ImageRange::whole(),
+                Access::TRANSFER_WRITE,
+                PipelineStage::TRANSFER,
+            )],
+        )];
+        nodes.extend((1..4).map(|level| downsample(level, level as usize - 1)));
+
+        let chains = collect(nodes, |_| 1).unwrap();
+        assert_eq!(chains.images[&IMAGE].len(), 5);
+
+        let schedule = sync(&chains, || ((), ()));
+        let first = submission(&schedule, 0).sync();
+        assert_eq!(levels(&first.acquire), vec![0..1, 1..2, 2..3, 3..4, 4..!0],);
+        assert_eq!(levels(&first.release), vec![0..1]);
+
+        let second = submission(&schedule, 1).sync();
+        assert_eq!(levels(&second.acquire), vec![1..2]);
+        let barrier = &second.acquire.images[&IMAGE][0];
+        assert_eq!(
+            barrier.states.start.0..barrier.states.end.0,
+            Access::TRANSFER_WRITE..Access::SHADER_WRITE,
+        );
+    }
+
+    #[test]
+    fn array_layers_are_independent() {
+        let layer = |id, layer| {
+            node(
+                id,
+                vec![],
+                vec![state(
+                    ImageRange::new(0..1, layer..layer + 1),
+                    Access::SHADER_WRITE,
+                    PipelineStage::COMPUTE_SHADER,
+                )],
+            )
+        };
+
+        let chains = collect(vec![layer(0, 0), layer(1, 1)], |_| 2).unwrap();
+        let schedule = sync(&chains, || ((), ()));
+
+        let first = submission(&schedule, 0);
+        let second = submission(&schedule, 1);
+
+        // Writes to different layers don't wait for each other.
+        assert_ne!(first.id().queue(), second.id().queue());
+        for submission in &[first, second] {
+            assert!(submission.sync().wait.is_empty());
+            assert!(submission.sync().signal.is_empty());
+        }
+
+        let layers = |guard: &Guard| {
+            guard.images[&IMAGE]
+                .iter()
+                .map(|barrier| barrier.range.layer_range())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(layers(&first.sync().acquire), vec![0..1]);
+        assert_eq!(layers(&second.sync().acquire), vec![1..2]);
+    }
+
+    #[test]
+    fn buffer_range_segments() {
+        assert_eq!(
+            BufferRange::segments(&[
+                BufferRange::new(0..128),
+                BufferRange::new(64..192),
+                BufferRange::new(256..512),
+            ]),
+            vec![
+                BufferRange::new(0..64),
+                BufferRange::new(64..128),
+                BufferRange::new(128..192),
+                BufferRange::new(256..512),
+            ],
+        );
+    }
+
+    #[test]
+    fn disjoint_buffer_writes_are_independent() {
+        let chains = collect(
+            vec![
+                buffer_node(0, vec![], 0..128),
+                buffer_node(1, vec![], 128..256),
+            ],
+            |_| 2,
+        )
+        .unwrap();
+        let schedule = sync(&chains, || ((), ()));
+
+        let first = submission(&schedule, 0);
+        let second = submission(&schedule, 1);
+
+        assert_ne!(first.id().queue(), second.id().queue());
+        for submission in &[first, second] {
+            assert!(submission.sync().wait.is_empty());
+            assert!(submission.sync().signal.is_empty());
+        }
+    }
+
+    #[test]
+    fn overlapping_buffer_writes() {
+        let chains = collect(
+            vec![
+                buffer_node(0, vec![], 0..128),
+                buffer_node(1, vec![0], 64..192),
+            ],
+            |_| 1,
+        )
+        .unwrap();
+        assert_eq!(
+            chains.buffers[&BUFFER]
+                .iter()
+                .map(|chain| chain.links().len())
+                .collect::<Vec<_>>(),
+            vec![1, 2, 1],
+        );
+
+        // Barriers of the second write never cover bytes written only by the first one.
+        let schedule = sync(&chains, || ((), ()));
+        let second = submission(&schedule, 1).sync();
+        let mut ranges: Vec<_> = second.acquire.buffers[&BUFFER]
+            .iter()
+            .map(|barrier| barrier.range.range())
+            .collect();
+        ranges.sort_by_key(|range| range.start);
+        assert_eq!(ranges, vec![64..128, 128..192]);
+    }
+
+    #[test]
+    fn async_compute_family() {
+        let families = || vec![QueueFamilyId(0), QueueFamilyId(1)];
+        let mut first = buffer_node(0, vec![], 0..128);
+        first.families = families();
+        let mut second = buffer_node(1, vec![], 128..256);
+        second.families = families();
+        let mut reader = buffer_node(2, vec![0, 1], 0..256);
+        reader.buffers[0].1.access = rendy_core::hal::buffer::Access::SHADER_READ;
+
+        let chains = collect(vec![first, second, reader], |_| 1).unwrap();
+        let schedule = sync(&chains, || ((), ()));
+
+        // Second write doesn't wait for the first one on another family.
+        assert_eq!(submission(&schedule, 0).id().family(), QueueFamilyId(0));
+        assert_eq!(submission(&schedule, 1).id().family(), QueueFamilyId(1));
+
+        // Ownership of the bytes written on another family is transferred to the reader.
+        let reader = submission(&schedule, 2).sync();
+        assert_eq!(reader.wait.len(), 1);
+        let transfer = reader.acquire.buffers[&BUFFER]
+            .iter()
+            .find(|barrier| barrier.families.is_some())
+            .unwrap();
+        assert_eq!(transfer.range.range(), 128..256);
+        assert_eq!(transfer.families, Some(QueueFamilyId(1)..QueueFamilyId(0)),);
+    }
+
+    #[test]
+    fn overlapping_ranges_in_node() {
+        let chains = collect(
+            vec![node(
+                0,
+                vec![],
+                vec![
+                    state(
+                        ImageRange::levels(0..2),
+                        Access::SHADER_READ,
+                        PipelineStage::FRAGMENT_SHADER,
+                    ),
+                    state(
+                        ImageRange::levels(1..2),
+                        Access::SHADER_WRITE,
+                        PipelineStage::COMPUTE_SHADER,
+                    ),
+                ],
+            )],
+            |_| 1,
+        )
+        .unwrap();
+
+        let states: Vec<_> = chains.images[&IMAGE]
+            .iter()
+            .map(|chain| {
+                assert_eq!(chain.links().len(), 1);
+                let state = chain.links()[0].state();
+                (state.range.level_range(), state.access, state.stages)
+            })
+            .collect();
+
+        // Only the overlapped level is accessed in combined state.
+        assert_eq!(
+            states,
+            vec![
+                (0..1, Access::SHADER_READ, PipelineStage::FRAGMENT_SHADER),
+                (
+                    1..2,
+                    Access::SHADER_READ | Access::SHADER_WRITE,
+                    PipelineStage::FRAGMENT_SHADER | PipelineStage::COMPUTE_SHADER,
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn split_barriers_on_single_queue() {
+        // Buffer node runs between the writer and the reader of the image.
+        let nodes = vec![
+            node(
+                0,
+                vec![],
+                vec![state(
+                    ImageRange::levels(0..1),
+                    Access::COLOR_ATTACHMENT_WRITE,
+                    PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+                )],
+            ),
+            buffer_node(1, vec![0], 0..256),
+            node(
+                2,
+                vec![1],
+                vec![state(
+                    ImageRange::levels(0..1),
+                    Access::SHADER_READ,
+                    PipelineStage::FRAGMENT_SHADER,
+                )],
+            ),
+        ];
+        let events = |guard: &Guard| -> Vec<Option<usize>> {
+            guard
+                .images
+                .values()
+                .flatten()
+                .map(|barrier| barrier.event)
+                .chain(
+                    guard
+                        .buffers
+                        .values()
+                        .flatten()
+                        .map(|barrier| barrier.event),
+                )
+                .collect()
+        };
+
+        let chains = collect(nodes, |_| 1).unwrap();
+        let schedule = sync_with_options(
+            &chains,
+            SyncOptions {
+                split_barriers: true,
+            },
+            || ((), ()),
+        );
+
+        // Writer sets the event after writing the image.
+        let writer = submission(&schedule, 0).sync();
+        assert_eq!(
+            writer
+                .set_events
+                .iter()
+                .map(|set| (set.event(), set.stage()))
+                .collect::<Vec<_>>(),
+            vec![(0, PipelineStage::COLOR_ATTACHMENT_OUTPUT)],
+        );
+        assert_eq!(events(&writer.release), vec![Some(0)]);
+        let release = &writer.release.images[&IMAGE][0];
+        assert_eq!(
+            release.states.start.0..release.states.end.0,
+            Access::COLOR_ATTACHMENT_WRITE..Access::SHADER_READ,
+        );
+
+        // Barrier from the last use back to the first one has nothing in between to split.
+        assert_eq!(events(&writer.acquire), vec![None]);
+
+        // Node in between is not affected.
+        let middle = submission(&schedule, 1).sync();
+        assert!(middle.set_events.is_empty());
+        assert!(events(&middle.acquire)
+            .into_iter()
+            .chain(events(&middle.release))
+            .all(|event| event.is_none()));
+
+        // Reader waits for the event instead of pipeline barrier.
+        let reader = submission(&schedule, 2).sync();
+        assert!(reader.set_events.is_empty());
+        assert_eq!(events(&reader.acquire), vec![Some(0)]);
+        let acquire = &reader.acquire.images[&IMAGE][0];
+        assert_eq!(acquire.states, release.states);
+
+        // Same graph without the option uses only pipeline barriers.
+        let schedule = sync(&chains, || ((), ()));
+        for node in 0..3 {
+            let sync = submission(&schedule, node).sync();
+            assert!(sync.set_events.is_empty());
+            assert!(events(&sync.acquire)
+                .into_iter()
+                .chain(events(&sync.release))
+                .all(|event| event.is_none()));
+        }
+    }
+}
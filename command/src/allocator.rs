@@ -0,0 +1,730 @@
+//! Allocator module docs.
+
+use {
+    crate::{
+        buffer::{
+            CommandBuffer, InitialState, InvalidState, Level, PendingState, PrimaryLevel,
+            Resettable,
+        },
+        capability::{Capability, QueueType, Supports},
+        core::{device_owned, Device, DeviceId},
+        family::{Family, FamilyId},
+        pool::CommandPool,
+    },
+    rendy_core::hal::{device::OutOfMemory, Backend},
+    std::{
+        cell::RefCell,
+        collections::{HashMap, VecDeque},
+        sync::{Arc, Weak},
+        thread::ThreadId,
+    },
+};
+
+/// Source of frame indices for command pools recycling.
+pub trait FrameTimeline {
+    /// Index of the frame being recorded.
+    fn next_frame(&self) -> u64;
+
+    /// Upper bound of complete frames.
+    /// All frames with index less than returned value must be complete.
+    fn complete_upper_bound(&self) -> u64;
+}
+
+/// Pool with command buffers allocated for one frame.
+#[derive(Debug)]
+struct FramePool<P, I, R> {
+    pool: P,
+
+    /// Reset buffers ready to be allocated again.
+    initial: Vec<I>,
+
+    /// Released buffers.
+    /// Submitted ones may be pending until the frame is complete.
+    released: Vec<R>,
+}
+
+impl<P, I, R> FramePool<P, I, R> {
+    fn new(pool: P) -> Self {
+        FramePool {
+            pool,
+            initial: Vec::new(),
+            released: Vec::new(),
+        }
+    }
+
+    /// Take reset buffer or allocate new one from the pool.
+    fn allocate(&mut self, allocate: impl FnOnce(&mut P) -> I) -> I {
+        match self.initial.pop() {
+            Some(buffer) => buffer,
+            None => allocate(&mut self.pool),
+        }
+    }
+
+    /// Reset the pool so that released buffers can be allocated again.
+    fn reset(&mut self, reset: impl FnOnce(&mut P), mark_reset: impl FnMut(R) -> I) {
+        reset(&mut self.pool);
+        let reset = self.released.drain(..).map(mark_reset);
+        self.initial.extend(reset);
+    }
+}
+
+/// Frame pool of command buffers.
+type CommandFramePool<B, C, L> = FramePool<
+    CommandPool<B, C>,
+    CommandBuffer<B, C, InitialState, L>,
+    CommandBuffer<B, C, InvalidState, L>,
+>;
+
+/// Frame and number of its buffers that are not released yet.
+#[derive(Debug)]
+struct FrameEntry<P> {
+    frame: u64,
+    outstanding: usize,
+    pool: P,
+}
+
+/// Pools of frames in allocation order.
+/// Pools are recycled when their frame is complete and all buffers are released.
+#[derive(Debug)]
+struct FrameQueue<P> {
+    frames: VecDeque<FrameEntry<P>>,
+    free: Vec<P>,
+}
+
+impl<P> FrameQueue<P> {
+    fn new() -> Self {
+        FrameQueue {
+            frames: VecDeque::new(),
+            free: Vec::new(),
+        }
+    }
+
+    /// Get pool of the `frame` to allocate buffer from.
+    fn allocate<E>(
+        &mut self,
+        frame: u64,
+        create: impl FnOnce() -> Result<P, E>,
+    ) -> Result<&mut P, E> {
+        if self.frames.back().map_or(true, |last| last.frame != frame) {
+            assert!(
+                self.frames.back().map_or(true, |last| last.frame < frame),
+                "Frames must be allocated in order"
+            );
+            let pool = match self.free.pop() {
+                Some(pool) => pool,
+                None => create()?,
+            };
+            self.frames.push_back(FrameEntry {
+                frame,
+                outstanding: 0,
+                pool,
+            });
+        }
+
+        let entry = self.frames.back_mut().unwrap();
+        entry.outstanding += 1;
+        Ok(&mut entry.pool)
+    }
+
+    /// Get pool of the `frame` to release buffer to.
+    fn release(&mut self, frame: u64) -> &mut P {
+        let entry = self
+            .frames
+            .iter_mut()
+            .find(|entry| entry.frame == frame)
+            .expect("No command buffers were allocated for the frame");
+        assert!(
+            entry.outstanding > 0,
+            "All command buffers of the frame are released"
+        );
+
+        entry.outstanding -= 1;
+        &mut entry.pool
+    }
+
+    /// Reset pools of frames that are complete and have all buffers released.
+    fn recycle(&mut self, complete_upper_bound: u64, mut reset: impl FnMut(&mut P)) {
+        while let Some(entry) = self.frames.front() {
+            if entry.frame >= complete_upper_bound || entry.outstanding > 0 {
+                break;
+            }
+
+            let mut entry = self.frames.pop_front().unwrap();
+            reset(&mut entry.pool);
+            self.free.push(entry.pool);
+        }
+    }
+
+    /// Check if all pools are recycled.
+    fn is_idle(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+/// Command pools of one family that are reset in bulk when their frame is complete.
+///
+/// Command pools must not be used from multiple threads simultaneously.
+/// Each thread recording commands should use its own instance
+/// or share [`CommandAllocator`] that keeps instances for each thread.
+///
+/// Pools must be [disposed] explicitly.
+///
+/// [`CommandAllocator`]: struct.CommandAllocator.html
+/// [disposed]: #method.dispose
+#[derive(Debug)]
+pub struct FrameCommandPools<B: Backend, C = QueueType, L = PrimaryLevel> {
+    family: FamilyId,
+    capability: C,
+    frames: FrameQueue<CommandFramePool<B, C, L>>,
+    relevant: relevant::Relevant,
+}
+
+family_owned!(FrameCommandPools<B, C, L>);
+
+impl<B, C, L> FrameCommandPools<B, C, L>
+where
+    B: Backend,
+    C: Capability,
+    L: Level,
+{
+    /// Create new empty pools for the family.
+    pub fn new(family: &Family<B, C>) -> Self {
+        FrameCommandPools {
+            family: family.id(),
+            capability: family.capability(),
+            frames: FrameQueue::new(),
+            relevant: relevant::Relevant,
+        }
+    }
+
+    /// Create new empty pools for the family with capability `C` the family supports.
+    /// Returns `None` if family doesn't support the capability.
+    pub fn with_capability<U>(family: &Family<B, U>) -> Option<Self>
+    where
+        U: Supports<C>,
+    {
+        Some(FrameCommandPools {
+            family: family.id(),
+            capability: Supports::<C>::supports(&family.capability())?,
+            frames: FrameQueue::new(),
+            relevant: relevant::Relevant,
+        })
+    }
+
+    /// Allocate command buffer for next frame of the `frames`.
+    /// Buffers of complete frames are recycled before allocation.
+    ///
+    /// Command buffer must be [released] within the same frame
+    /// after submission or [without submission].
+    ///
+    /// [released]: #method.release
+    /// [without submission]: #method.release_unsubmitted
+    pub fn allocate(
+        &mut self,
+        device: &Device<B>,
+        frames: &impl FrameTimeline,
+    ) -> Result<CommandBuffer<B, C, InitialState, L>, OutOfMemory> {
+        self.assert_device_owner(device);
+        self.recycle(frames.complete_upper_bound());
+
+        let (family, capability) = (self.family, self.capability);
+        let frame_pool =
+            self.frames
+                .allocate(frames.next_frame(), || -> Result<_, OutOfMemory> {
+                    Ok(FramePool::new(unsafe {
+                        // Family id and capability are taken from `Family`.
+                        CommandPool::create(family, capability, device)?
+                    }))
+                })?;
+
+        Ok(frame_pool.allocate(|pool| pool.allocate_buffers(1).remove(0)))
+    }
+
+    /// Return submitted command buffer allocated for the `frame`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if no buffers are allocated for the `frame`.
+    pub fn release<N>(&mut self, frame: u64, buffer: CommandBuffer<B, C, PendingState<N>, L>) {
+        self.release_buffer(frame, unsafe {
+            // Buffer won't be used until frame is complete.
+            buffer.change_state(|_| InvalidState)
+        });
+    }
+
+    /// Return command buffer allocated for the `frame` that was never submitted.
+    /// Buffer can be in initial, recording or executable state.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if no buffers are allocated for the `frame`.
+    pub fn release_unsubmitted<S>(&mut self, frame: u64, buffer: CommandBuffer<B, C, S, L>)
+    where
+        S: Resettable,
+    {
+        self.release_buffer(frame, unsafe {
+            // Buffer is reset along with the pool before reuse.
+            buffer.change_state(|_| InvalidState)
+        });
+    }
+
+    fn release_buffer(&mut self, frame: u64, buffer: CommandBuffer<B, C, InvalidState, L>) {
+        assert_eq!(
+            buffer.family(),
+            self.family,
+            "Resource is not owned by specified family"
+        );
+        self.frames.release(frame).released.push(buffer);
+    }
+
+    /// Reset pools of frames that are complete.
+    /// Pools with buffers that are not released yet are left intact.
+    pub fn recycle(&mut self, complete_upper_bound: u64) {
+        self.frames
+            .recycle(complete_upper_bound, |frame_pool| unsafe {
+                // Frame is complete so all released buffers are complete too.
+                frame_pool.reset(|pool| pool.reset(), |buffer| buffer.mark_reset());
+            });
+    }
+
+    /// Dispose of the pools.
+    ///
+    /// # Safety
+    ///
+    /// All frames for which command buffers were allocated must be complete.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if some command buffers are not released.
+    pub unsafe fn dispose(mut self, device: &Device<B>) {
+        self.assert_device_owner(device);
+        self.recycle(!0);
+        assert!(
+            self.frames.is_idle(),
+            "All command buffers must be released before disposing"
+        );
+
+        for mut frame_pool in self.frames.free.drain(..) {
+            frame_pool.pool.free_buffers(frame_pool.initial.drain(..));
+            frame_pool.pool.dispose(device);
+        }
+        self.relevant.dispose();
+    }
+}
+
+/// Threads that exited after allocating from the allocator.
+#[derive(Debug, Default)]
+struct ExitedThreads(parking_lot::Mutex<Vec<ThreadId>>);
+
+/// Reports exit of the thread to allocators it used.
+struct ThreadExit {
+    thread: ThreadId,
+    allocators: RefCell<Vec<Weak<ExitedThreads>>>,
+}
+
+impl Drop for ThreadExit {
+    fn drop(&mut self) {
+        for exited in self.allocators.get_mut().drain(..) {
+            if let Some(exited) = exited.upgrade() {
+                exited.0.lock().push(self.thread);
+            }
+        }
+    }
+}
+
+thread_local! {
+    static THREAD_EXIT: ThreadExit = ThreadExit {
+        thread: std::thread::current().id(),
+        allocators: RefCell::new(Vec::new()),
+    };
+}
+
+/// Report exit of the current thread to `exited`.
+fn report_exit(exited: &Arc<ExitedThreads>) {
+    // Thread is exiting already if thread local is destroyed.
+    let _ = THREAD_EXIT.try_with(|exit| {
+        let exited = Arc::downgrade(exited);
+        let mut allocators = exit.allocators.borrow_mut();
+        allocators.retain(|allocator| allocator.upgrade().is_some());
+        if !allocators
+            .iter()
+            .any(|allocator| Weak::ptr_eq(allocator, &exited))
+        {
+            allocators.push(exited);
+        }
+    });
+}
+
+/// Frame command pools of one thread for each family.
+#[derive(Debug)]
+struct ThreadPools<B: Backend, L> {
+    /// Thread that uses the pools.
+    /// Pools of exited thread are taken over by the next new thread.
+    thread: ThreadId,
+    pools: HashMap<FamilyId, FrameCommandPools<B, QueueType, L>>,
+}
+
+/// Command allocator that keeps separate frame command pools for each thread and family.
+/// Allocated command buffers must be recorded on the thread that allocated them.
+///
+/// Each thread has its own shard of pools, so allocation and release
+/// don't contend with other threads. Only [`recycle`] visits shards of all threads.
+/// Idle pools of exited threads are disposed by [`recycle`].
+/// Allocator must be [disposed] explicitly.
+///
+/// [`recycle`]: #method.recycle
+/// [disposed]: #method.dispose
+pub struct CommandAllocator<B: Backend, L = PrimaryLevel> {
+    device: DeviceId,
+    threads: thread_local::ThreadLocal<parking_lot::Mutex<ThreadPools<B, L>>>,
+    exited: Arc<ExitedThreads>,
+    relevant: relevant::Relevant,
+}
+
+device_owned!(CommandAllocator<B, L>);
+
+impl<B, L> std::fmt::Debug for CommandAllocator<B, L>
+where
+    B: Backend,
+{
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.debug_struct("CommandAllocator")
+            .field("device", &self.device)
+            .field("exited", &self.exited)
+            .finish()
+    }
+}
+
+impl<B, L> CommandAllocator<B, L>
+where
+    B: Backend,
+    L: Level + Send,
+{
+    /// Create new command allocator.
+    pub fn new(device: &Device<B>) -> Self {
+        CommandAllocator {
+            device: device.id(),
+            threads: thread_local::ThreadLocal::new(),
+            exited: Arc::new(ExitedThreads::default()),
+            relevant: relevant::Relevant,
+        }
+    }
+
+    /// Get shard of the current thread.
+    fn current(&self) -> parking_lot::MutexGuard<'_, ThreadPools<B, L>> {
+        let thread = std::thread::current().id();
+        let mut pools = self
+            .threads
+            .get_or(|| {
+                report_exit(&self.exited);
+                parking_lot::Mutex::new(ThreadPools {
+                    thread,
+                    pools: HashMap::new(),
+                })
+            })
+            .lock();
+
+        if pools.thread != thread {
+            // Pools of exited thread are taken over by the current one.
+            pools.thread = thread;
+            report_exit(&self.exited);
+        }
+        pools
+    }
+
+    /// Allocate command buffer for the next frame of the `frames`
+    /// from the pool of the current thread.
+    ///
+    /// Command buffer must be [released] within the same frame
+    /// after submission or [without submission].
+    ///
+    /// [released]: #method.release
+    /// [without submission]: #method.release_unsubmitted
+    pub fn allocate(
+        &self,
+        device: &Device<B>,
+        family: &Family<B>,
+        frames: &impl FrameTimeline,
+    ) -> Result<CommandBuffer<B, QueueType, InitialState, L>, OutOfMemory> {
+        self.assert_device_owner(device);
+        self.current()
+            .pools
+            .entry(family.id())
+            .or_insert_with(|| FrameCommandPools::new(family))
+            .allocate(device, frames)
+    }
+
+    /// Return submitted command buffer allocated for the `frame`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the current thread allocated no buffers for the `frame`.
+    pub fn release<N>(&self, frame: u64, buffer: CommandBuffer<B, QueueType, PendingState<N>, L>) {
+        self.current()
+            .pools
+            .get_mut(&buffer.family())
+            .expect("Command buffer must be released by the thread that allocated it")
+            .release(frame, buffer);
+    }
+
+    /// Return command buffer allocated for the `frame` that was never submitted.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the current thread allocated no buffers for the `frame`.
+    pub fn release_unsubmitted<S>(&self, frame: u64, buffer: CommandBuffer<B, QueueType, S, L>)
+    where
+        S: Resettable,
+    {
+        self.current()
+            .pools
+            .get_mut(&buffer.family())
+            .expect("Command buffer must be released by the thread that allocated it")
+            .release_unsubmitted(frame, buffer);
+    }
+
+    /// Reset pools of all threads for frames that are complete.
+    /// Pools of exited threads are disposed when all their frames are recycled.
+    pub fn recycle(&self, device: &Device<B>, frames: &impl FrameTimeline) {
+        self.assert_device_owner(device);
+        let complete_upper_bound = frames.complete_upper_bound();
+        let mut exited = self.exited.0.lock();
+        let mut busy = Vec::new();
+        for pools in self.threads.iter() {
+            let mut pools = pools.lock();
+            for pools in pools.pools.values_mut() {
+                pools.recycle(complete_upper_bound);
+            }
+
+            if exited.contains(&pools.thread) {
+                let idle: Vec<_> = pools
+                    .pools
+                    .iter()
+                    .filter(|(_, pools)| pools.frames.is_idle())
+                    .map(|(&family, _)| family)
+                    .collect();
+                for family in idle {
+                    unsafe {
+                        // All frames of the pools are complete.
+                        pools.pools.remove(&family).unwrap().dispose(device);
+                    }
+                }
+                if !pools.pools.is_empty() {
+                    busy.push(pools.thread);
+                }
+            }
+        }
+
+        // Shards of other exited threads are empty or taken over by new threads.
+        *exited = busy;
+    }
+
+    /// Dispose of the allocator.
+    ///
+    /// # Safety
+    ///
+    /// All frames for which command buffers were allocated must be complete.
+    pub unsafe fn dispose(self, device: &Device<B>) {
+        self.assert_device_owner(device);
+        for pools in self.threads {
+            for (_, pools) in pools.into_inner().pools {
+                pools.dispose(device);
+            }
+        }
+        self.relevant.dispose();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pool that counts resets.
+    #[derive(Debug)]
+    struct Pool {
+        id: usize,
+        resets: usize,
+    }
+
+    fn allocate(frames: &mut FrameQueue<Pool>, frame: u64, created: &mut usize) -> usize {
+        frames
+            .allocate(frame, || -> Result<_, ()> {
+                *created += 1;
+                Ok(Pool {
+                    id: *created,
+                    resets: 0,
+                })
+            })
+            .unwrap()
+            .id
+    }
+
+    fn reset(pool: &mut Pool) {
+        pool.resets += 1;
+    }
+
+    #[test]
+    fn frames_share_pool() {
+        let mut frames = FrameQueue::new();
+        let mut created = 0;
+        assert_eq!(allocate(&mut frames, 0, &mut created), 1);
+        assert_eq!(allocate(&mut frames, 0, &mut created), 1);
+        assert_eq!(allocate(&mut frames, 1, &mut created), 2);
+        assert_eq!(created, 2);
+    }
+
+    #[test]
+    fn recycle_complete_released_frames() {
+        let mut frames = FrameQueue::new();
+        let mut created = 0;
+        allocate(&mut frames, 0, &mut created);
+        allocate(&mut frames, 0, &mut created);
+        allocate(&mut frames, 1, &mut created);
+
+        // Frame 0 has buffers that are not released.
+        frames.release(0);
+        frames.release(1);
+        frames.recycle(2, reset);
+        assert_eq!(frames.frames.len(), 2);
+
+        // Frame 0 is not complete.
+        frames.release(0);
+        frames.recycle(0, reset);
+        assert_eq!(frames.frames.len(), 2);
+
+        frames.recycle(2, reset);
+        assert!(frames.is_idle());
+        assert_eq!(frames.free.iter().map(|pool| pool.resets).sum::<usize>(), 2);
+
+        // Recycled pools are reused.
+        allocate(&mut frames, 2, &mut created);
+        allocate(&mut frames, 3, &mut created);
+        assert_eq!(created, 2);
+    }
+
+    #[test]
+    fn recycle_stops_at_outstanding_frame() {
+        let mut frames = FrameQueue::new();
+        let mut created = 0;
+        allocate(&mut frames, 0, &mut created);
+        allocate(&mut frames, 1, &mut created);
+        frames.release(1);
+
+        // Later frames are recycled only after earlier ones.
+        frames.recycle(2, reset);
+        assert_eq!(frames.frames.len(), 2);
+
+        frames.release(0);
+        frames.recycle(2, reset);
+        assert!(frames.is_idle());
+    }
+
+    /// Allocate buffer from the pool of the `frame`.
+    /// New buffers get ids starting from 100.
+    fn allocate_buffer(
+        frames: &mut FrameQueue<FramePool<Pool, usize, usize>>,
+        frame: u64,
+        created: &mut usize,
+        buffers: &mut usize,
+    ) -> (usize, usize) {
+        let frame_pool = frames
+            .allocate(frame, || -> Result<_, ()> {
+                *created += 1;
+                Ok(FramePool::new(Pool {
+                    id: *created,
+                    resets: 0,
+                }))
+            })
+            .unwrap();
+        let buffer = frame_pool.allocate(|_| {
+            *buffers += 1;
+            99 + *buffers
+        });
+        (frame_pool.pool.id, buffer)
+    }
+
+    fn reset_frame_pool(frame_pool: &mut FramePool<Pool, usize, usize>) {
+        frame_pool.reset(reset, |buffer| buffer);
+    }
+
+    #[test]
+    fn recycled_pools_reuse_released_buffers() {
+        let mut frames = FrameQueue::new();
+        let (mut created, mut buffers) = (0, 0);
+
+        assert_eq!(
+            allocate_buffer(&mut frames, 0, &mut created, &mut buffers),
+            (1, 100)
+        );
+        assert_eq!(
+            allocate_buffer(&mut frames, 0, &mut created, &mut buffers),
+            (1, 101)
+        );
+        frames.release(0).released.push(100);
+        frames.release(0).released.push(101);
+
+        assert_eq!(
+            allocate_buffer(&mut frames, 1, &mut created, &mut buffers),
+            (2, 102)
+        );
+        frames.release(1).released.push(102);
+
+        // Frame 0 is complete, frame 1 is not.
+        frames.recycle(1, reset_frame_pool);
+        assert_eq!(frames.free.len(), 1);
+        assert_eq!(frames.free[0].pool.resets, 1);
+        assert_eq!(frames.free[0].initial.len(), 2);
+        assert!(frames.free[0].released.is_empty());
+
+        // Buffers of the recycled pool are allocated again without new allocations.
+        let (pool, first) = allocate_buffer(&mut frames, 2, &mut created, &mut buffers);
+        let (_, second) = allocate_buffer(&mut frames, 2, &mut created, &mut buffers);
+        assert_eq!(pool, 1);
+        let mut reused = vec![first, second];
+        reused.sort();
+        assert_eq!(reused, vec![100, 101]);
+        assert_eq!((created, buffers), (2, 3));
+
+        // Pool runs out of reset buffers.
+        assert_eq!(
+            allocate_buffer(&mut frames, 2, &mut created, &mut buffers),
+            (1, 103)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "All command buffers of the frame are released")]
+    fn release_more_than_allocated() {
+        let mut frames = FrameQueue::new();
+        let mut created = 0;
+        allocate(&mut frames, 0, &mut created);
+        frames.release(0);
+        frames.release(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Frames must be allocated in order")]
+    fn allocate_out_of_order() {
+        let mut frames = FrameQueue::new();
+        let mut created = 0;
+        allocate(&mut frames, 1, &mut created);
+        allocate(&mut frames, 0, &mut created);
+    }
+
+    #[test]
+    fn exited_threads_are_reported() {
+        let exited = Arc::new(ExitedThreads::default());
+        let thread = {
+            let exited = exited.clone();
+            std::thread::spawn(move || {
+                report_exit(&exited);
+                report_exit(&exited);
+                std::thread::current().id()
+            })
+        }
+        .join()
+        .unwrap();
+
+        assert_eq!(*exited.0.lock(), vec![thread]);
+    }
+}
@@ -67,6 +67,22 @@ pub struct DispatchCommand {
     pub z: u32,
 }
 
+/// Command that device can read from indirect buffer.
+///
+/// # Safety
+///
+/// Implementors must have memory layout expected by the device for the command.
+pub unsafe trait IndirectCommand: Copy + std::fmt::Debug + 'static {
+    /// Stride between consecutive commands in tightly packed buffer.
+    fn stride() -> u32 {
+        std::mem::size_of::<Self>() as u32
+    }
+}
+
+unsafe impl IndirectCommand for DrawCommand {}
+unsafe impl IndirectCommand for DrawIndexedCommand {}
+unsafe impl IndirectCommand for DispatchCommand {}
+
 /// Encoder for recording commands inside or outside renderpass.
 #[derive(Debug)]
 pub struct EncoderCommon<'a, B: rendy_core::hal::Backend, C> {
@@ -239,6 +255,25 @@ where
         )
     }
 
+    /// Wait for events to be set and insert memory barriers.
+    /// This is the second half of split barrier which first half is `set_event`.
+    /// `stages.start` must include stages with which events were set.
+    ///
+    /// # Safety
+    ///
+    /// Events must be set on the same queue by previously submitted commands
+    /// or from the host before submission.
+    ///
+    /// See: https://www.khronos.org/registry/vulkan/specs/1.1-extensions/man/html/vkCmdWaitEvents.html
+    pub unsafe fn wait_events<'b>(
+        &mut self,
+        events: impl IntoIterator<Item = &'b B::Event>,
+        stages: std::ops::Range<rendy_core::hal::pso::PipelineStage>,
+        barriers: impl IntoIterator<Item = rendy_core::hal::memory::Barrier<'b, B>>,
+    ) {
+        rendy_core::hal::command::CommandBuffer::wait_events(self.raw, events, stages, barriers)
+    }
+
     /// Push graphics constants.
     ///
     /// # Safety
@@ -527,6 +562,72 @@ where
         )
     }
 
+    /// Draw indirect with draw count read from buffer.
+    /// Similar to [`draw_indirect`] except number of draws is read from `count_buffer` at `count_offset`.
+    /// At most `max_draw_count` draws are performed.
+    ///
+    /// [`draw_indirect`]: #method.draw_indirect
+    ///
+    /// # Safety
+    ///
+    /// Similar to `draw_indirect()`.
+    /// `DRAW_INDIRECT_COUNT` feature must be enabled for the device.
+    /// `count_buffer` must contain `u32` value at `count_offset` that is multiple of 4.
+    ///
+    /// See: https://www.khronos.org/registry/vulkan/specs/1.1-extensions/man/html/vkCmdDrawIndirectCountKHR.html
+    pub unsafe fn draw_indirect_count(
+        &mut self,
+        buffer: &B::Buffer,
+        offset: u64,
+        count_buffer: &B::Buffer,
+        count_offset: u64,
+        max_draw_count: u32,
+        stride: u32,
+    ) {
+        rendy_core::hal::command::CommandBuffer::draw_indirect_count(
+            self.inner.raw,
+            buffer,
+            offset,
+            count_buffer,
+            count_offset,
+            max_draw_count,
+            stride,
+        )
+    }
+
+    /// Draw indirect with indices and draw count read from buffer.
+    /// Similar to [`draw_indexed_indirect`] except number of draws is read from `count_buffer` at `count_offset`.
+    /// At most `max_draw_count` draws are performed.
+    ///
+    /// [`draw_indexed_indirect`]: #method.draw_indexed_indirect
+    ///
+    /// # Safety
+    ///
+    /// Similar to `draw_indexed_indirect()`.
+    /// `DRAW_INDIRECT_COUNT` feature must be enabled for the device.
+    /// `count_buffer` must contain `u32` value at `count_offset` that is multiple of 4.
+    ///
+    /// See: https://www.khronos.org/registry/vulkan/specs/1.1-extensions/man/html/vkCmdDrawIndexedIndirectCountKHR.html
+    pub unsafe fn draw_indexed_indirect_count(
+        &mut self,
+        buffer: &B::Buffer,
+        offset: u64,
+        count_buffer: &B::Buffer,
+        count_offset: u64,
+        max_draw_count: u32,
+        stride: u32,
+    ) {
+        rendy_core::hal::command::CommandBuffer::draw_indexed_indirect_count(
+            self.inner.raw,
+            buffer,
+            offset,
+            count_buffer,
+            count_offset,
+            max_draw_count,
+            stride,
+        )
+    }
+
     /// Reborrow encoder.
     pub fn reborrow(&mut self) -> RenderPassEncoder<'_, B> {
         RenderPassEncoder {
@@ -795,6 +896,36 @@ where
         self.level
     }
 
+    /// Set event after commands recorded so far execute specified `stages`.
+    ///
+    /// # Safety
+    ///
+    /// Event must not be reset by commands executing simultaneously.
+    ///
+    /// See: https://www.khronos.org/registry/vulkan/specs/1.1-extensions/man/html/vkCmdSetEvent.html
+    pub unsafe fn set_event(
+        &mut self,
+        event: &B::Event,
+        stages: rendy_core::hal::pso::PipelineStage,
+    ) {
+        rendy_core::hal::command::CommandBuffer::set_event(self.inner.raw, event, stages)
+    }
+
+    /// Reset event after commands recorded so far execute specified `stages`.
+    ///
+    /// # Safety
+    ///
+    /// Event must not be waited upon by commands executing simultaneously.
+    ///
+    /// See: https://www.khronos.org/registry/vulkan/specs/1.1-extensions/man/html/vkCmdResetEvent.html
+    pub unsafe fn reset_event(
+        &mut self,
+        event: &B::Event,
+        stages: rendy_core::hal::pso::PipelineStage,
+    ) {
+        rendy_core::hal::command::CommandBuffer::reset_event(self.inner.raw, event, stages)
+    }
+
     /// Copy buffer regions.
     /// `src` and `dst` can be the same buffer or alias in memory.
     /// But regions must not overlap.
@@ -0,0 +1,81 @@
+use {
+    crate::core::{device_owned, Device, DeviceId},
+    rendy_core::hal::{
+        device::{Device as _, OomOrDeviceLost, OutOfMemory},
+        Backend,
+    },
+};
+
+/// Event wrapper.
+///
+/// Events allow fine-grained synchronization between commands submitted to the same queue.
+/// Unlike pipeline barrier, event can be set after producer commands
+/// and waited upon before consumer commands,
+/// letting independent commands recorded in between execute without stall.
+#[derive(Debug)]
+pub struct Event<B: Backend> {
+    device: DeviceId,
+    raw: B::Event,
+}
+
+device_owned!(Event<B>);
+
+impl<B> Event<B>
+where
+    B: Backend,
+{
+    /// Create new event in unset state.
+    pub fn new(device: &Device<B>) -> Result<Self, OutOfMemory> {
+        let raw = device.raw().create_event()?;
+        Ok(Event {
+            device: device.id(),
+            raw,
+        })
+    }
+
+    /// Check if event is set.
+    pub fn is_set(&self, device: &Device<B>) -> Result<bool, OomOrDeviceLost> {
+        self.assert_device_owner(device);
+        unsafe { device.get_event_status(&self.raw) }
+    }
+
+    /// Set event from the host.
+    ///
+    /// # Safety
+    ///
+    /// Event must not be in use by pending commands.
+    pub unsafe fn set(&self, device: &Device<B>) -> Result<(), OutOfMemory> {
+        self.assert_device_owner(device);
+        device.set_event(&self.raw)
+    }
+
+    /// Reset event from the host.
+    ///
+    /// # Safety
+    ///
+    /// Event must not be in use by pending commands.
+    pub unsafe fn reset(&self, device: &Device<B>) -> Result<(), OutOfMemory> {
+        self.assert_device_owner(device);
+        device.reset_event(&self.raw)
+    }
+
+    /// Get raw event reference.
+    pub fn raw(&self) -> &B::Event {
+        &self.raw
+    }
+
+    /// Unwrap raw event value.
+    pub fn into_inner(self) -> B::Event {
+        self.raw
+    }
+
+    /// Destroy event.
+    ///
+    /// # Safety
+    ///
+    /// Event must not be in use by pending commands.
+    pub unsafe fn dispose(self, device: &Device<B>) {
+        self.assert_device_owner(device);
+        device.destroy_event(self.raw);
+    }
+}
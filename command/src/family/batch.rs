@@ -0,0 +1,338 @@
+use {
+    super::{submission::Submission, Families, QueueId},
+    crate::{buffer::Submittable, fence::Fence},
+    rendy_core::hal::{pso::PipelineStage, Backend},
+    smallvec::SmallVec,
+    std::borrow::Borrow,
+};
+
+/// Submissions that will be performed with single raw submit.
+#[derive(Debug)]
+struct Batch<Q, W, C, S> {
+    queue: Q,
+    waits: SmallVec<[W; 8]>,
+    command_buffers: SmallVec<[C; 8]>,
+    signals: SmallVec<[S; 8]>,
+}
+
+impl<Q, W, C, S> Batch<Q, W, C, S>
+where
+    Q: Copy + PartialEq,
+{
+    /// Add submission to the `batches`.
+    /// Submission that waits starts new batch,
+    /// otherwise it goes into the last batch of its queue.
+    fn add(
+        batches: &mut Vec<Self>,
+        queue: Q,
+        waits: SmallVec<[W; 8]>,
+        command_buffers: impl IntoIterator<Item = C>,
+        signals: impl IntoIterator<Item = S>,
+    ) {
+        let index = match merge_index(batches, !waits.is_empty(), |b| b.queue == queue) {
+            Some(index) => index,
+            None => {
+                batches.push(Batch {
+                    queue,
+                    waits: SmallVec::new(),
+                    command_buffers: SmallVec::new(),
+                    signals: SmallVec::new(),
+                });
+                batches.len() - 1
+            }
+        };
+
+        let batch = &mut batches[index];
+        batch.waits.extend(waits);
+        batch.command_buffers.extend(command_buffers);
+        batch.signals.extend(signals);
+    }
+
+    /// Check if this is the last batch of its queue in `batches`
+    /// and the fence of the queue should be submitted with it.
+    fn takes_fence(batches: &[Self], index: usize) -> bool {
+        let queue = batches[index].queue;
+        last_in_queue(batches, index, |b| b.queue == queue)
+    }
+}
+
+type RawBatch<'a, B> = Batch<
+    QueueId,
+    (&'a <B as Backend>::Semaphore, PipelineStage),
+    &'a <B as Backend>::CommandBuffer,
+    &'a <B as Backend>::Semaphore,
+>;
+
+/// Collects submissions to the queues during a frame
+/// and performs them with as few raw submits as possible.
+///
+/// Submissions to the same queue are merged into last batch of that queue
+/// unless they wait for semaphores.
+/// Submission that waits starts new batch,
+/// so command buffers added before it never wait for its semaphores.
+/// Batches are flushed in the order they were started
+/// so semaphores are always signaled by earlier raw submits than those waiting for them.
+#[derive(Debug)]
+pub struct SubmissionBatcher<'a, B: Backend> {
+    batches: Vec<RawBatch<'a, B>>,
+}
+
+impl<'a, B> Default for SubmissionBatcher<'a, B>
+where
+    B: Backend,
+{
+    fn default() -> Self {
+        SubmissionBatcher {
+            batches: Vec::new(),
+        }
+    }
+}
+
+impl<'a, B> SubmissionBatcher<'a, B>
+where
+    B: Backend,
+{
+    /// Create new empty batcher.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check if there is no submissions to flush.
+    pub fn is_empty(&self) -> bool {
+        self.batches.is_empty()
+    }
+
+    /// Number of raw submits next flush will perform without fences.
+    pub fn batch_count(&self) -> usize {
+        self.batches.len()
+    }
+
+    /// Queues that have submissions to flush.
+    pub fn queues(&self) -> impl Iterator<Item = QueueId> + '_ {
+        self.batches
+            .iter()
+            .enumerate()
+            .filter(move |&(index, batch)| {
+                last_in_queue(&self.batches, index, |b| b.queue == batch.queue)
+            })
+            .map(|(_, batch)| batch.queue)
+    }
+
+    /// Add submission to the queue.
+    ///
+    /// # Safety
+    ///
+    /// Submission must be valid to submit to the queue at the point of `flush`
+    /// after all submissions added before.
+    /// Command buffers must be valid for `'a`.
+    pub unsafe fn add<W, C, S, E>(&mut self, queue: QueueId, submission: Submission<B, W, C, S>)
+    where
+        W: IntoIterator<Item = (&'a E, PipelineStage)>,
+        C: IntoIterator,
+        C::Item: Submittable<B>,
+        S: IntoIterator<Item = &'a E>,
+        E: Borrow<B::Semaphore> + 'a,
+    {
+        let waits: SmallVec<[_; 8]> = submission
+            .waits
+            .into_iter()
+            .map(|(semaphore, stage)| (semaphore.borrow(), stage))
+            .collect();
+
+        Batch::add(
+            &mut self.batches,
+            queue,
+            waits,
+            submission.submits.into_iter().map(|submit| {
+                assert_eq!(submit.family(), queue.family);
+                submit.raw()
+            }),
+            submission.signals.into_iter().map(Borrow::borrow),
+        );
+    }
+
+    /// Submit all collected submissions.
+    /// Fences are submitted with last batch of their queues.
+    ///
+    /// # Safety
+    ///
+    /// Same as for `Queue::submit` for all collected submissions.
+    pub unsafe fn flush<'f>(
+        &mut self,
+        families: &mut Families<B>,
+        fences: impl IntoIterator<Item = (QueueId, &'f mut Fence<B>)>,
+    ) {
+        let mut fences: SmallVec<[_; 8]> = fences.into_iter().map(Some).collect();
+        let mut take_fence = |queue: QueueId| {
+            fences
+                .iter_mut()
+                .find(|fence| fence.as_ref().map_or(false, |&(id, _)| id == queue))
+                .and_then(Option::take)
+                .map(|(_, fence)| fence)
+        };
+
+        let batches = std::mem::replace(&mut self.batches, Vec::new());
+        for (index, batch) in batches.iter().enumerate() {
+            let fence = if Batch::takes_fence(&batches, index) {
+                take_fence(batch.queue)
+            } else {
+                None
+            };
+
+            families
+                .family_mut(batch.queue.family)
+                .queue_mut(batch.queue.index)
+                .submit_batch(&batch.command_buffers, &batch.waits, &batch.signals, fence);
+        }
+
+        // Fences for queues without submissions.
+        for (queue, fence) in fences.into_iter().flatten() {
+            families
+                .family_mut(queue.family)
+                .queue_mut(queue.index)
+                .submit(std::iter::empty::<Submission<B>>(), Some(fence));
+        }
+    }
+}
+
+/// Find batch new submission can be merged into.
+/// Submission without waits goes into last batch of its queue.
+/// Submission with waits always starts new batch.
+fn merge_index<T>(batches: &[T], waits: bool, same_queue: impl Fn(&T) -> bool) -> Option<usize> {
+    if waits {
+        None
+    } else {
+        batches.iter().rposition(same_queue)
+    }
+}
+
+/// Check that no batch after `index` goes to the same queue.
+fn last_in_queue<T>(batches: &[T], index: usize, same_queue: impl Fn(&T) -> bool) -> bool {
+    !batches[index + 1..].iter().any(same_queue)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_into_last_batch_of_queue() {
+        let batches = [0, 1, 0, 2];
+        assert_eq!(merge_index(&batches, false, |&q| q == 0), Some(2));
+        assert_eq!(merge_index(&batches, false, |&q| q == 1), Some(1));
+    }
+
+    #[test]
+    fn new_queue_starts_batch() {
+        let batches = [0, 1];
+        assert_eq!(merge_index(&batches, false, |&q| q == 2), None);
+    }
+
+    #[test]
+    fn waits_start_batch() {
+        let batches = [0, 1];
+        assert_eq!(merge_index(&batches, true, |&q| q == 0), None);
+        assert_eq!(merge_index(&batches, true, |&q| q == 1), None);
+        assert_eq!(merge_index(&batches, true, |&q| q == 2), None);
+    }
+
+    type TestBatch = Batch<u32, &'static str, &'static str, &'static str>;
+
+    fn add(
+        batches: &mut Vec<TestBatch>,
+        queue: u32,
+        waits: &[&'static str],
+        commands: &[&'static str],
+        signals: &[&'static str],
+    ) {
+        Batch::add(
+            batches,
+            queue,
+            waits.iter().cloned().collect(),
+            commands.iter().cloned(),
+            signals.iter().cloned(),
+        );
+    }
+
+    /// Command buffers of the `queue` in order of raw submits.
+    fn submitted(batches: &[TestBatch], queue: u32) -> Vec<&'static str> {
+        batches
+            .iter()
+            .filter(|b| b.queue == queue)
+            .flat_map(|b| b.command_buffers.iter().cloned())
+            .collect()
+    }
+
+    #[test]
+    fn flush_keeps_submission_order_of_each_queue() {
+        let mut batches = Vec::new();
+        add(&mut batches, 0, &[], &["a"], &[]);
+        add(&mut batches, 1, &[], &["b"], &[]);
+        add(&mut batches, 0, &["s"], &["c"], &[]);
+        add(&mut batches, 0, &[], &["d"], &[]);
+        add(&mut batches, 1, &[], &["e"], &[]);
+
+        assert_eq!(batches.len(), 3);
+        assert_eq!(submitted(&batches, 0), vec!["a", "c", "d"]);
+        assert_eq!(submitted(&batches, 1), vec!["b", "e"]);
+    }
+
+    #[test]
+    fn wait_is_never_merged_ahead_of_signal() {
+        let mut batches = Vec::new();
+        add(&mut batches, 0, &[], &["a"], &[]);
+        add(&mut batches, 1, &[], &["b"], &["s"]);
+        // Waits for `b`, must not go into the batch with `a` flushed before `b`.
+        add(&mut batches, 0, &["s"], &["c"], &["t"]);
+        add(&mut batches, 1, &["t"], &["d"], &[]);
+
+        let position = |f: &dyn Fn(&TestBatch) -> bool| batches.iter().position(f).unwrap();
+        for semaphore in &["s", "t"] {
+            let signal = position(&|b| b.signals.contains(semaphore));
+            let wait = position(&|b| b.waits.contains(semaphore));
+            assert!(signal < wait, "{} is waited before signaled", semaphore);
+        }
+        assert_eq!(
+            batches[position(&|b| b.waits.contains(&"s"))]
+                .command_buffers
+                .as_slice(),
+            &["c"]
+        );
+    }
+
+    #[test]
+    fn submission_without_waits_joins_batch_with_waits() {
+        let mut batches = Vec::new();
+        add(&mut batches, 0, &["s"], &["a"], &[]);
+        add(&mut batches, 0, &[], &["b"], &["t"]);
+
+        // `b` is submitted after `a` anyway so it may wait for the same semaphores.
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].waits.as_slice(), &["s"]);
+        assert_eq!(batches[0].signals.as_slice(), &["t"]);
+    }
+
+    #[test]
+    fn flush_submits_fence_with_last_batch_of_queue() {
+        let mut batches = Vec::new();
+        add(&mut batches, 0, &[], &["a"], &["s"]);
+        add(&mut batches, 1, &["s"], &["b"], &["t"]);
+        add(&mut batches, 0, &["t"], &["c"], &[]);
+
+        let fenced: Vec<_> = (0..batches.len())
+            .filter(|&index| Batch::takes_fence(&batches, index))
+            .map(|index| batches[index].command_buffers[0])
+            .collect();
+        assert_eq!(fenced, vec!["b", "c"]);
+    }
+
+    #[test]
+    fn fence_goes_to_last_batch_of_queue() {
+        let batches = [0, 1, 0, 2];
+        let last = |index: usize| last_in_queue(&batches, index, |&q| q == batches[index]);
+        assert!(!last(0));
+        assert!(last(1));
+        assert!(last(2));
+        assert!(last(3));
+    }
+}
@@ -1,5 +1,6 @@
 //! Family module docs.
 
+mod batch;
 mod queue;
 mod submission;
 
@@ -13,7 +14,7 @@ use {
     rendy_core::hal::Backend,
 };
 
-pub use self::{queue::*, submission::*};
+pub use self::{batch::*, queue::*, submission::*};
 
 /// Family id.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -120,6 +121,19 @@ where
         &mut self.queues
     }
 
+    /// Get submission counters summed over queues of the family.
+    pub fn submit_stats(&self) -> SubmitStats {
+        self.queues
+            .iter()
+            .map(Queue::submit_stats)
+            .fold(SubmitStats::default(), std::ops::Add::add)
+    }
+
+    /// Reset submission counters of all queues of the family.
+    pub fn reset_submit_stats(&mut self) {
+        self.queues.iter_mut().for_each(Queue::reset_submit_stats);
+    }
+
     /// Create command pool associated with the family.
     /// Command buffers created from the pool could be submitted to the queues of the family.
     pub fn create_pool<R>(
@@ -223,6 +237,27 @@ where
         &self.families_indices
     }
 
+    /// Get submission counters of the queue.
+    pub fn queue_submit_stats(&self, id: QueueId) -> SubmitStats {
+        self.family(id.family).queue(id.index).submit_stats()
+    }
+
+    /// Get submission counters summed over all queues.
+    pub fn submit_stats(&self) -> SubmitStats {
+        self.families
+            .iter()
+            .map(Family::submit_stats)
+            .fold(SubmitStats::default(), std::ops::Add::add)
+    }
+
+    /// Reset submission counters of all queues.
+    /// Call it at the beginning of a frame to get per-frame counters.
+    pub fn reset_submit_stats(&mut self) {
+        self.families
+            .iter_mut()
+            .for_each(Family::reset_submit_stats);
+    }
+
     /// Find family id matching predicate
     pub fn find<F>(&self, predicate: F) -> Option<FamilyId>
     where
@@ -235,6 +270,15 @@ where
     pub fn with_capability<C: Capability>(&self) -> Option<FamilyId> {
         self.find(|family| Supports::<C>::supports(&family.capability()).is_some())
     }
+
+    /// Get ids of all families with specified capability
+    pub fn all_with_capability<C: Capability>(&self) -> Vec<FamilyId> {
+        self.families
+            .iter()
+            .filter(|family| Supports::<C>::supports(&family.capability()).is_some())
+            .map(Family::id)
+            .collect()
+    }
 }
 
 /// Query queue families from device.
@@ -4,12 +4,48 @@ use {
     rendy_core::hal::{queue::CommandQueue, Backend},
 };
 
+/// Submission counters.
+/// Useful for profiling how many raw submits were performed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SubmitStats {
+    /// Number of raw submits.
+    pub submits: u64,
+
+    /// Number of submitted command buffers.
+    pub command_buffers: u64,
+
+    /// Number of semaphores waited.
+    pub waits: u64,
+
+    /// Number of semaphores signaled.
+    pub signals: u64,
+}
+
+impl std::ops::AddAssign for SubmitStats {
+    fn add_assign(&mut self, rhs: Self) {
+        self.submits += rhs.submits;
+        self.command_buffers += rhs.command_buffers;
+        self.waits += rhs.waits;
+        self.signals += rhs.signals;
+    }
+}
+
+impl std::ops::Add for SubmitStats {
+    type Output = Self;
+
+    fn add(mut self, rhs: Self) -> Self {
+        self += rhs;
+        self
+    }
+}
+
 /// Command queue wrapper.
 #[derive(Debug)]
 pub struct Queue<B: Backend> {
     raw: B::CommandQueue,
     id: QueueId,
     next_epoch: u64,
+    stats: SubmitStats,
 }
 
 family_owned!(@NOCAP Queue<B> @ |q: &Self| q.id.family);
@@ -23,6 +59,7 @@ where
             id,
             raw,
             next_epoch: 0,
+            stats: SubmitStats::default(),
         }
     }
 
@@ -41,6 +78,16 @@ where
         self.next_epoch
     }
 
+    /// Get submission counters of the queue.
+    pub fn submit_stats(&self) -> SubmitStats {
+        self.stats
+    }
+
+    /// Reset submission counters of the queue.
+    pub fn reset_submit_stats(&mut self) {
+        self.stats = SubmitStats::default();
+    }
+
     /// Submit commands to the queue of the family.
     /// Fence must be submitted.
     pub unsafe fn submit<'a>(
@@ -62,42 +109,8 @@ where
     ) {
         assert!(fence.as_ref().map_or(true, |f| f.is_unsignaled()));
 
-        let mut submissions = submissions.into_iter().peekable();
-        if submissions.peek().is_none() && fence.is_some() {
-            self.raw.submit(
-                rendy_core::hal::queue::Submission {
-                    command_buffers: std::iter::empty::<&'a B::CommandBuffer>(),
-                    wait_semaphores: std::iter::empty::<(&'a B::Semaphore, _)>(),
-                    signal_semaphores: std::iter::empty::<&'a B::Semaphore>(),
-                },
-                fence.as_ref().map(|f| f.raw()),
-            );
-        } else {
-            let family = self.id.family;
-            while let Some(submission) = submissions.next() {
-                self.raw.submit(
-                    rendy_core::hal::queue::Submission {
-                        command_buffers: submission.submits.into_iter().map(|submit| {
-                            assert_eq!(submit.family(), family);
-                            submit.raw()
-                        }),
-                        wait_semaphores: submission.waits.into_iter().map(|w| (w.0.borrow(), w.1)),
-                        signal_semaphores: submission.signals.into_iter().map(|s| s.borrow()),
-                    },
-                    submissions
-                        .peek()
-                        .map_or(fence.as_ref().map(|f| f.raw()), |_| None),
-                );
-            }
-        }
-
-        if let Some(fence) = fence {
-            fence.mark_submitted(FenceEpoch {
-                queue: self.id,
-                epoch: self.next_epoch,
-            });
-            self.next_epoch += 1;
-        }
+        self.submit_raw_fence(submissions, fence.as_ref().map(|f| f.raw()));
+        self.mark_fence_submitted(fence);
     }
 
     /// Submit commands to the queue of the family.
@@ -122,32 +135,85 @@ where
     ) {
         let mut submissions = submissions.into_iter().peekable();
         if submissions.peek().is_none() && fence.is_some() {
-            self.raw.submit(
-                rendy_core::hal::queue::Submission {
-                    command_buffers: std::iter::empty::<&'a B::CommandBuffer>(),
-                    wait_semaphores: std::iter::empty::<(&'a B::Semaphore, _)>(),
-                    signal_semaphores: std::iter::empty::<&'a B::Semaphore>(),
-                },
+            self.submit_counted(
+                std::iter::empty::<&'a B::CommandBuffer>(),
+                std::iter::empty::<(&'a B::Semaphore, _)>(),
+                std::iter::empty::<&'a B::Semaphore>(),
                 fence,
             );
         } else {
             let family = self.id.family;
             while let Some(submission) = submissions.next() {
-                self.raw.submit(
-                    rendy_core::hal::queue::Submission {
-                        command_buffers: submission.submits.into_iter().map(|submit| {
-                            assert_eq!(submit.family(), family);
-                            submit.raw()
-                        }),
-                        wait_semaphores: submission.waits.into_iter().map(|w| (w.0.borrow(), w.1)),
-                        signal_semaphores: submission.signals.into_iter().map(|s| s.borrow()),
-                    },
+                self.submit_counted(
+                    submission.submits.into_iter().map(|submit| {
+                        assert_eq!(submit.family(), family);
+                        submit.raw()
+                    }),
+                    submission.waits.into_iter().map(|w| (w.0.borrow(), w.1)),
+                    submission.signals.into_iter().map(|s| s.borrow()),
                     submissions.peek().map_or(fence, |_| None),
                 );
             }
         }
     }
 
+    /// Submit batch of raw command buffers in single raw submit.
+    /// Fence must be unsignaled.
+    pub(super) unsafe fn submit_batch<'a>(
+        &mut self,
+        command_buffers: &[&'a B::CommandBuffer],
+        waits: &[(&'a B::Semaphore, rendy_core::hal::pso::PipelineStage)],
+        signals: &[&'a B::Semaphore],
+        fence: Option<&mut Fence<B>>,
+    ) {
+        assert!(fence.as_ref().map_or(true, |f| f.is_unsignaled()));
+
+        self.submit_counted(
+            command_buffers.iter().cloned(),
+            waits.iter().cloned(),
+            signals.iter().cloned(),
+            fence.as_ref().map(|f| f.raw()),
+        );
+        self.mark_fence_submitted(fence);
+    }
+
+    unsafe fn submit_counted<'a>(
+        &mut self,
+        command_buffers: impl IntoIterator<Item = &'a B::CommandBuffer>,
+        waits: impl IntoIterator<Item = (&'a B::Semaphore, rendy_core::hal::pso::PipelineStage)>,
+        signals: impl IntoIterator<Item = &'a B::Semaphore>,
+        fence: Option<&B::Fence>,
+    ) {
+        let SubmitStats {
+            submits,
+            command_buffers: command_buffers_count,
+            waits: waits_count,
+            signals: signals_count,
+        } = &mut self.stats;
+
+        *submits += 1;
+        self.raw.submit(
+            rendy_core::hal::queue::Submission {
+                command_buffers: command_buffers
+                    .into_iter()
+                    .inspect(|_| *command_buffers_count += 1),
+                wait_semaphores: waits.into_iter().inspect(|_| *waits_count += 1),
+                signal_semaphores: signals.into_iter().inspect(|_| *signals_count += 1),
+            },
+            fence,
+        );
+    }
+
+    fn mark_fence_submitted(&mut self, fence: Option<&mut Fence<B>>) {
+        if let Some(fence) = fence {
+            fence.mark_submitted(FenceEpoch {
+                queue: self.id,
+                epoch: self.next_epoch,
+            });
+            self.next_epoch += 1;
+        }
+    }
+
     /// Wait for queue to finish all pending commands.
     pub fn wait_idle(&self) -> Result<(), rendy_core::hal::device::OutOfMemory> {
         self.raw.wait_idle()
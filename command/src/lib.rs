@@ -79,10 +79,15 @@ macro_rules! family_owned {
 
 use rendy_core as core;
 
+mod allocator;
 mod buffer;
 mod capability;
+mod event;
 mod family;
 mod fence;
 mod pool;
+mod timeline;
 
-pub use crate::{buffer::*, capability::*, family::*, fence::*, pool::*};
+pub use crate::{
+    allocator::*, buffer::*, capability::*, event::*, family::*, fence::*, pool::*, timeline::*,
+};
@@ -0,0 +1,281 @@
+//! Timeline module docs.
+
+use {
+    crate::{
+        buffer::Submittable,
+        core::{device_owned, Device, DeviceId},
+        family::{Queue, QueueId, Submission},
+        fence::{Fence, FenceEpoch},
+    },
+    rendy_core::hal::{
+        device::{Device as _, OomOrDeviceLost, OutOfMemory},
+        Backend,
+    },
+    std::collections::VecDeque,
+};
+
+/// Progress of a single queue.
+#[derive(Debug)]
+struct QueueProgress<B: Backend> {
+    /// Fences submitted to the queue in epoch order.
+    pending: VecDeque<Fence<B>>,
+
+    /// All epochs less than this value are reached.
+    reached: u64,
+}
+
+impl<B> Default for QueueProgress<B>
+where
+    B: Backend,
+{
+    fn default() -> Self {
+        QueueProgress {
+            pending: VecDeque::new(),
+            reached: 0,
+        }
+    }
+}
+
+/// Timeline of queue epochs.
+/// Allows to wait until queue reaches particular epoch
+/// or check if it already did without blocking.
+///
+/// Epochs are signaled by fences from the pool owned by the timeline.
+/// Fences are returned to the pool as soon as their epochs are known to be reached.
+#[derive(Debug)]
+pub struct Timeline<B: Backend> {
+    device: DeviceId,
+    queues: Vec<Vec<QueueProgress<B>>>,
+    free: Vec<Fence<B>>,
+}
+
+device_owned!(Timeline<B>);
+
+impl<B> Timeline<B>
+where
+    B: Backend,
+{
+    /// Create new empty timeline.
+    pub fn new(device: &Device<B>) -> Self {
+        Timeline {
+            device: device.id(),
+            queues: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+
+    /// Submit commands to the queue signaling new epoch after them.
+    /// Returns signaled epoch.
+    ///
+    /// # Safety
+    ///
+    /// Same as for `Queue::submit`.
+    pub unsafe fn submit<'a>(
+        &mut self,
+        device: &Device<B>,
+        queue: &mut Queue<B>,
+        submissions: impl IntoIterator<
+            Item = Submission<
+                B,
+                impl IntoIterator<
+                    Item = (
+                        &'a (impl std::borrow::Borrow<B::Semaphore> + 'a),
+                        rendy_core::hal::pso::PipelineStage,
+                    ),
+                >,
+                impl IntoIterator<Item = impl Submittable<B>>,
+                impl IntoIterator<Item = &'a (impl std::borrow::Borrow<B::Semaphore> + 'a)>,
+            >,
+        >,
+    ) -> Result<FenceEpoch, OutOfMemory> {
+        self.assert_device_owner(device);
+        queue.assert_device_owner(device);
+
+        let mut fence = self.fence(device)?;
+        queue.submit(submissions, Some(&mut fence));
+        Ok(self.track(fence))
+    }
+
+    /// Get unsignaled fence from the pool.
+    /// It can be submitted to the queue directly and then passed to `track`.
+    pub fn fence(&mut self, device: &Device<B>) -> Result<Fence<B>, OutOfMemory> {
+        self.assert_device_owner(device);
+
+        match self.free.pop() {
+            Some(fence) => Ok(fence),
+            None => Fence::new(device, false),
+        }
+    }
+
+    /// Return fence taken with `fence` that wasn't submitted back to the pool.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if fence is submitted.
+    pub fn recycle(&mut self, fence: Fence<B>) {
+        assert!(!fence.is_submitted(), "Submitted fence can't be recycled");
+        self.free.push(fence);
+    }
+
+    /// Track submitted fence.
+    /// Fence returns to the pool once its epoch is reached.
+    /// Returns signaled epoch.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if fence is not submitted.
+    /// Fences of the same queue must be tracked in submission order.
+    pub fn track(&mut self, fence: Fence<B>) -> FenceEpoch {
+        let epoch = fence.epoch();
+        self.progress_mut(epoch.queue).pending.push_back(fence);
+        epoch
+    }
+
+    /// Signal new epoch on the queue.
+    /// Epoch will be reached after all commands previously submitted to the queue complete.
+    pub fn signal(
+        &mut self,
+        device: &Device<B>,
+        queue: &mut Queue<B>,
+    ) -> Result<FenceEpoch, OutOfMemory> {
+        unsafe {
+            // Empty submission can't violate any requirement.
+            self.submit(device, queue, std::iter::empty::<Submission<B>>())
+        }
+    }
+
+    /// Get upper bound of reached epochs of the queue known to the timeline.
+    /// All epochs less than returned value are reached.
+    /// This function doesn't check fences. Use `is_reached` or `poll` to update timeline.
+    pub fn reached(&self, queue: QueueId) -> u64 {
+        self.queues
+            .get(queue.family.index)
+            .and_then(|family| family.get(queue.index))
+            .map_or(0, |progress| progress.reached)
+    }
+
+    /// Mark epoch as reached.
+    /// This can be used to inform timeline about epochs waited upon with fences it doesn't own.
+    ///
+    /// # Safety
+    ///
+    /// Epoch must be reached by the queue.
+    pub unsafe fn mark_reached(&mut self, epoch: FenceEpoch) {
+        let progress = self.progress_mut(epoch.queue);
+        progress.reached = progress.reached.max(epoch.epoch + 1);
+    }
+
+    /// Check fences of the queue and update its progress.
+    /// Returns upper bound of reached epochs.
+    pub fn poll(&mut self, device: &Device<B>, queue: QueueId) -> Result<u64, OomOrDeviceLost> {
+        self.assert_device_owner(device);
+
+        let Timeline { queues, free, .. } = self;
+        let progress = match queues
+            .get_mut(queue.family.index)
+            .and_then(|family| family.get_mut(queue.index))
+        {
+            Some(progress) => progress,
+            None => return Ok(0),
+        };
+
+        while let Some(fence) = progress.pending.front_mut() {
+            if fence.is_signaled() {
+                // Fence was waited upon in `wait`.
+            } else if fence.epoch().epoch < progress.reached {
+                // Later epoch was reached so this fence is signaled too.
+                unsafe { fence.mark_signaled() };
+            } else {
+                match fence.check_signaled(device)? {
+                    Some(epoch) => progress.reached = epoch.epoch + 1,
+                    None => break,
+                }
+            }
+
+            // Signaled fence stays pending if reset fails and is reset on next poll.
+            fence.reset(device)?;
+            free.push(progress.pending.pop_front().unwrap());
+        }
+
+        Ok(progress.reached)
+    }
+
+    /// Check if epoch is reached without blocking.
+    pub fn is_reached(
+        &mut self,
+        device: &Device<B>,
+        epoch: FenceEpoch,
+    ) -> Result<bool, OomOrDeviceLost> {
+        if self.reached(epoch.queue) > epoch.epoch {
+            return Ok(true);
+        }
+
+        Ok(self.poll(device, epoch.queue)? > epoch.epoch)
+    }
+
+    /// Wait until epoch is reached.
+    /// Returns `false` if timeout expired first.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if epoch is not reached
+    /// and wasn't signaled by this timeline.
+    pub fn wait(
+        &mut self,
+        device: &Device<B>,
+        epoch: FenceEpoch,
+        timeout_ns: u64,
+    ) -> Result<bool, OomOrDeviceLost> {
+        if self.is_reached(device, epoch)? {
+            return Ok(true);
+        }
+
+        let progress = self.progress_mut(epoch.queue);
+        let fence = progress
+            .pending
+            .iter_mut()
+            .find(|fence| fence.epoch().epoch >= epoch.epoch)
+            .expect("Epoch must be signaled by this timeline");
+
+        match fence.wait_signaled(device, timeout_ns)? {
+            Some(signaled) => {
+                progress.reached = progress.reached.max(signaled.epoch + 1);
+                // Recycle waited fence along with all earlier ones.
+                self.poll(device, epoch.queue)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Wait until all epochs signaled by this timeline are reached and destroy all fences.
+    pub fn dispose(mut self, device: &Device<B>) {
+        self.assert_device_owner(device);
+
+        for progress in self.queues.drain(..).flatten() {
+            for mut fence in progress.pending {
+                if !fence.is_signaled() {
+                    fence
+                        .wait_signaled(device, !0)
+                        .expect("Failed to wait for timeline fence");
+                }
+                unsafe { device.destroy_fence(fence.into_inner()) };
+            }
+        }
+
+        for fence in self.free.drain(..) {
+            unsafe { device.destroy_fence(fence.into_inner()) };
+        }
+    }
+
+    fn progress_mut(&mut self, queue: QueueId) -> &mut QueueProgress<B> {
+        while self.queues.len() <= queue.family.index {
+            self.queues.push(Vec::new());
+        }
+        let family = &mut self.queues[queue.family.index];
+        while family.len() <= queue.index {
+            family.push(QueueProgress::default());
+        }
+        &mut family[queue.index]
+    }
+}
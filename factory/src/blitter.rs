@@ -2,15 +2,14 @@ use {
     crate::{
         barriers::Barriers,
         command::{
-            CommandBuffer, CommandPool, Encoder, Families, Family, Graphics, IndividualReset,
-            InitialState, Level, OneShot, PendingOnceState, PrimaryLevel, QueueId, RecordingState,
-            Submission, Supports,
+            CommandBuffer, Encoder, Families, FenceEpoch, FrameCommandPools, Graphics, Level,
+            OneShot, QueueId, RecordingState, Submission, SubmissionBatcher, Supports, Timeline,
         },
         core::Device,
         resource::{Handle, Image},
-        upload::ImageState,
+        upload::{Flushes, ImageState},
     },
-    rendy_core::hal::device::{Device as _, OutOfMemory},
+    rendy_core::hal::device::{OomOrDeviceLost, OutOfMemory},
     smallvec::SmallVec,
     std::{collections::VecDeque, iter::once, ops::DerefMut, ops::Range},
 };
@@ -172,12 +171,12 @@ where
                 family_ops.push(None);
             }
 
+            family.assert_device_owner(device);
             family_ops[family.id().index] = Some(parking_lot::Mutex::new(FamilyGraphicsOps {
-                pool: family
-                    .create_pool(device)
-                    .map(|pool| pool.with_capability().unwrap())?,
-                initial: Vec::new(),
+                pools: FrameCommandPools::with_capability(family).unwrap(),
+                flushes: 0,
                 next: Vec::new(),
+                submitted: Vec::new(),
                 pending: VecDeque::new(),
                 read_barriers: Barriers::new(
                     rendy_core::hal::pso::PipelineStage::TRANSFER,
@@ -258,27 +257,48 @@ where
     /// # Safety
     ///
     /// `device` must be the same that was used to create this `Blitter`.
+    /// `timeline` must be the one that tracks epochs passed to `submitted`.
     ///
-    pub(crate) unsafe fn cleanup(&mut self, device: &Device<B>) {
+    pub(crate) unsafe fn cleanup(
+        &mut self,
+        device: &Device<B>,
+        timeline: &mut Timeline<B>,
+    ) -> Result<(), OutOfMemory> {
         for blitter in self.family_ops.iter_mut() {
             if let Some(blitter) = blitter {
-                blitter.get_mut().cleanup(device);
+                blitter.get_mut().cleanup(device, timeline)?;
             }
         }
+        Ok(())
     }
 
-    /// Flush new updates.
+    /// Flush new updates into the batcher.
+    /// Epochs that signal completion of flushed updates must be reported with `submitted`.
     ///
     /// # Safety
     ///
-    /// `families` must be the same that was used to create this `Blitter`.
+    /// Batcher must be flushed to the queues of the `device` that was used to create this `Blitter`.
     ///
-    pub(crate) unsafe fn flush(&mut self, families: &mut Families<B>) {
-        for family in families.as_slice_mut() {
-            let blitter = self.family_ops[family.id().index]
-                .as_mut()
-                .expect("Blitter must be initialized for all families");
-            blitter.get_mut().flush(family);
+    pub(crate) unsafe fn flush(&mut self, batcher: &mut SubmissionBatcher<'static, B>) {
+        for blitter in self.family_ops.iter_mut() {
+            if let Some(blitter) = blitter {
+                blitter.get_mut().flush(batcher);
+            }
+        }
+    }
+
+    /// Queues that have updates to flush.
+    pub(crate) fn queues(&mut self) -> impl Iterator<Item = QueueId> + '_ {
+        self.family_ops
+            .iter_mut()
+            .flatten()
+            .flat_map(|blitter| blitter.get_mut().queues())
+    }
+
+    /// Report epoch signaled after updates flushed to the queue.
+    pub(crate) fn submitted(&mut self, queue: QueueId, epoch: FenceEpoch) {
+        if let Some(Some(blitter)) = self.family_ops.get_mut(queue.family.index) {
+            blitter.get_mut().submitted(queue.index, epoch);
         }
     }
 
@@ -286,6 +306,7 @@ where
     ///
     /// `device` must be the same that was used to create this `Blitter`.
     /// `device` must be idle.
+    /// Timeline that tracks epochs passed to `submitted` must be disposed first.
     ///
     pub(crate) unsafe fn dispose(&mut self, device: &Device<B>) {
         self.family_ops.drain(..).for_each(|fu| {
@@ -376,25 +397,34 @@ pub unsafe fn blit_image<B, C, L>(
 
 #[derive(Debug)]
 pub(crate) struct FamilyGraphicsOps<B: rendy_core::hal::Backend> {
-    pool: CommandPool<B, Graphics, IndividualReset>,
-    initial: Vec<GraphicsOps<B, InitialState>>,
-    next: Vec<Option<GraphicsOps<B, RecordingState<OneShot>>>>,
-    pending: VecDeque<GraphicsOps<B, PendingOnceState>>,
+    pools: FrameCommandPools<B, Graphics>,
+    /// Index of the next flush.
+    /// Command buffers are allocated from the pool of the flush they will be submitted by.
+    flushes: u64,
+    next: Vec<Option<GraphicsOps<B>>>,
+    submitted: Vec<(usize, u64)>,
+    pending: VecDeque<(FenceEpoch, u64)>,
     read_barriers: Barriers<B>,
     write_barriers: Barriers<B>,
 }
 
 #[derive(Debug)]
-struct GraphicsOps<B: rendy_core::hal::Backend, S> {
-    command_buffer: CommandBuffer<B, Graphics, S, PrimaryLevel, IndividualReset>,
-    fence: B::Fence,
+struct GraphicsOps<B: rendy_core::hal::Backend> {
+    command_buffer: CommandBuffer<B, Graphics, RecordingState<OneShot>>,
 }
 
 impl<B> FamilyGraphicsOps<B>
 where
     B: rendy_core::hal::Backend,
 {
-    unsafe fn flush(&mut self, family: &mut Family<B>) {
+    unsafe fn flush(&mut self, batcher: &mut SubmissionBatcher<'static, B>) {
+        if self.next.is_empty() {
+            return;
+        }
+
+        let flush = self.flushes;
+        self.flushes += 1;
+
         for (queue, next) in self
             .next
             .drain(..)
@@ -404,15 +434,52 @@ where
             log::trace!("Flush blitter");
             let (submit, command_buffer) = next.command_buffer.finish().submit_once();
 
-            family.queue_mut(queue).submit_raw_fence(
-                Some(Submission::new().submits(once(submit))),
-                Some(&next.fence),
+            batcher.add(
+                QueueId {
+                    family: self.pools.family_id(),
+                    index: queue,
+                },
+                Submission::new().submits(once(submit)),
             );
 
-            self.pending.push_back(GraphicsOps {
-                command_buffer,
-                fence: next.fence,
-            });
+            // Pool of the flush is reset only after the flush is complete.
+            self.pools.release(flush, command_buffer);
+            self.submitted.push((queue, flush));
+        }
+    }
+
+    fn queues(&self) -> impl Iterator<Item = QueueId> + '_ {
+        let family = self.pools.family_id();
+        self.next
+            .iter()
+            .enumerate()
+            .filter(|(_, next)| next.is_some())
+            .map(move |(index, _)| QueueId { family, index })
+    }
+
+    fn submitted(&mut self, queue: usize, epoch: FenceEpoch) {
+        let mut index = 0;
+        while index < self.submitted.len() {
+            if self.submitted[index].0 == queue {
+                let (_, flush) = self.submitted.remove(index);
+                self.pending.push_back((epoch, flush));
+            } else {
+                index += 1;
+            }
+        }
+    }
+
+    /// Flushes with pending operations are not complete.
+    fn flushes(&self) -> Flushes {
+        Flushes {
+            next: self.flushes,
+            complete: self
+                .pending
+                .iter()
+                .map(|&(_, flush)| flush)
+                .chain(self.submitted.iter().map(|&(_, flush)| flush))
+                .min()
+                .unwrap_or(self.flushes),
         }
     }
 
@@ -420,35 +487,19 @@ where
         &mut self,
         device: &Device<B>,
         queue: usize,
-    ) -> Result<&mut GraphicsOps<B, RecordingState<OneShot>>, OutOfMemory> {
+    ) -> Result<&mut GraphicsOps<B>, OutOfMemory> {
         while self.next.len() <= queue {
             self.next.push(None);
         }
 
-        let pool = &mut self.pool;
-
-        match &mut self.next[queue] {
-            Some(next) => Ok(next),
-            slot @ None => {
-                let initial: Result<_, OutOfMemory> = self.initial.pop().map_or_else(
-                    || {
-                        Ok(GraphicsOps {
-                            command_buffer: pool.allocate_buffers(1).remove(0),
-                            fence: device.create_fence(false)?,
-                        })
-                    },
-                    Ok,
-                );
-                let initial = initial?;
-
-                *slot = Some(GraphicsOps {
-                    command_buffer: initial.command_buffer.begin(OneShot, ()),
-                    fence: initial.fence,
-                });
-
-                Ok(slot.as_mut().unwrap())
-            }
+        if self.next[queue].is_none() {
+            let command_buffer = self.pools.allocate(device, &self.flushes())?;
+            self.next[queue] = Some(GraphicsOps {
+                command_buffer: command_buffer.begin(OneShot, ()),
+            });
         }
+
+        Ok(self.next[queue].as_mut().unwrap())
     }
 
     /// Cleanup pending updates.
@@ -457,48 +508,45 @@ where
     ///
     /// `device` must be the same that was used with other methods of this instance.
     ///
-    unsafe fn cleanup(&mut self, device: &Device<B>) {
-        while let Some(pending) = self.pending.pop_front() {
-            match device.get_fence_status(&pending.fence) {
+    unsafe fn cleanup(
+        &mut self,
+        device: &Device<B>,
+        timeline: &mut Timeline<B>,
+    ) -> Result<(), OutOfMemory> {
+        while let Some((epoch, flush)) = self.pending.pop_front() {
+            match timeline.is_reached(device, epoch) {
                 Ok(false) => {
-                    self.pending.push_front(pending);
-                    return;
+                    self.pending.push_front((epoch, flush));
+                    break;
                 }
-                Err(rendy_core::hal::device::DeviceLost) => {
+                Err(OomOrDeviceLost::DeviceLost(_)) => {
                     panic!("Device lost error is not handled yet");
                 }
-                Ok(true) => {
-                    device
-                        .reset_fence(&pending.fence)
-                        .expect("Can always reset signalled fence");
-                    self.initial.push(GraphicsOps {
-                        command_buffer: pending.command_buffer.mark_complete().reset(),
-                        fence: pending.fence,
-                    })
+                Err(OomOrDeviceLost::OutOfMemory(err)) => {
+                    // Fence of the reached epoch is recycled on next cleanup.
+                    self.pending.push_front((epoch, flush));
+                    return Err(err);
                 }
+                Ok(true) => {}
             }
         }
+
+        self.pools.recycle(self.flushes().complete);
+        Ok(())
     }
 
     /// # Safety
     ///
     /// Device must be idle.
+    /// Timeline that tracks pending epochs must be disposed first.
     ///
     unsafe fn dispose(mut self, device: &Device<B>) {
-        let pool = &mut self.pool;
-        self.pending.drain(..).for_each(|pending| {
-            device.destroy_fence(pending.fence);
-            pool.free_buffers(once(pending.command_buffer.mark_complete()));
-        });
-        self.initial.drain(..).for_each(|initial| {
-            device.destroy_fence(initial.fence);
-            pool.free_buffers(once(initial.command_buffer));
-        });
-        self.next.drain(..).filter_map(|n| n).for_each(|next| {
-            device.destroy_fence(next.fence);
-            pool.free_buffers(once(next.command_buffer));
-        });
-        drop(pool);
-        self.pool.dispose(device);
+        for next in self.next.drain(..).flatten() {
+            self.pools
+                .release_unsubmitted(self.flushes, next.command_buffer);
+        }
+        self.pending.clear();
+        self.submitted.clear();
+        self.pools.dispose(device);
     }
 }
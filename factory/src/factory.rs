@@ -2,7 +2,8 @@ use {
     crate::{
         blitter::Blitter,
         command::{
-            families_from_device, CommandPool, Families, Family, FamilyId, Fence, QueueType, Reset,
+            families_from_device, CommandPool, Event, Families, Family, FamilyId, Fence,
+            FenceEpoch, QueueId, QueueType, Reset, SubmissionBatcher, Timeline,
         },
         config::{Config, DevicesConfigure, HeapsConfigure, QueuesConfigure},
         core::{rendy_with_slow_safety_checks, Device, DeviceId, Instance, InstanceId},
@@ -165,6 +166,7 @@ pub struct Factory<B: Backend> {
     heaps: ManuallyDrop<parking_lot::Mutex<Heaps<B>>>,
     resources: ManuallyDrop<ResourceHub<B>>,
     epochs: Vec<parking_lot::RwLock<Vec<u64>>>,
+    timeline: ManuallyDrop<Timeline<B>>,
     uploader: Uploader<B>,
     blitter: Blitter<B>,
     families_indices: Vec<usize>,
@@ -189,6 +191,8 @@ where
 
         unsafe {
             // Device is idle.
+            std::ptr::read(&mut *self.timeline).dispose(&self.device);
+            log::trace!("Timeline disposed");
             self.uploader.dispose(&self.device);
             log::trace!("Uploader disposed");
             self.blitter.dispose(&self.device);
@@ -324,6 +328,114 @@ where
         Ok(self.resources.images.escape(image))
     }
 
+    /// Allocate memory block suitable for resources with specified memory requirements.
+    /// This allows to bind multiple resources created with
+    /// [`create_unbound_buffer`] or [`create_unbound_image`] to the same block.
+    ///
+    /// [`create_unbound_buffer`]: #method.create_unbound_buffer
+    /// [`create_unbound_image`]: #method.create_unbound_image
+    pub fn allocate_memory(
+        &self,
+        type_mask: u32,
+        memory_usage: impl MemoryUsage,
+        size: u64,
+        align: u64,
+    ) -> Result<memory::MemoryBlock<B>, memory::HeapsError> {
+        profile_scope!("allocate_memory");
+
+        self.heaps
+            .lock()
+            .allocate(&self.device, type_mask, memory_usage, size, align)
+    }
+
+    /// Free memory block allocated with [`allocate_memory`].
+    ///
+    /// # Safety
+    ///
+    /// Memory block must be allocated by this `Factory`.
+    /// Resources bound to the block must not be used by any pending commands
+    /// or after this call.
+    ///
+    /// [`allocate_memory`]: #method.allocate_memory
+    pub unsafe fn free_memory(&self, block: memory::MemoryBlock<B>) {
+        self.heaps.lock().free(&self.device, block);
+    }
+
+    /// Creates a buffer without memory.
+    /// Memory must be bound with [`bind_buffer_memory`] before buffer can be used.
+    ///
+    /// [`bind_buffer_memory`]: #method.bind_buffer_memory
+    pub fn create_unbound_buffer(
+        &self,
+        info: BufferInfo,
+    ) -> Result<Buffer<B>, BufferCreationError> {
+        profile_scope!("create_unbound_buffer");
+
+        unsafe { Buffer::create_unbound(&self.device, info) }
+    }
+
+    /// Bind range of memory block to the buffer created with [`create_unbound_buffer`].
+    /// Memory block is not owned by the buffer and can be shared.
+    ///
+    /// # Safety
+    ///
+    /// Memory range starting at `offset` must satisfy buffer requirements
+    /// and outlive the buffer.
+    ///
+    /// [`create_unbound_buffer`]: #method.create_unbound_buffer
+    pub unsafe fn bind_buffer_memory(
+        &self,
+        mut buffer: Buffer<B>,
+        block: &memory::MemoryBlock<B>,
+        offset: u64,
+    ) -> Result<Escape<Buffer<B>>, BufferCreationError> {
+        use memory::Block as _;
+
+        match buffer.bind_memory(&self.device, block.memory(), block.range().start + offset) {
+            Ok(()) => Ok(self.resources.buffers.escape(buffer)),
+            Err(err) => {
+                self.destroy_relevant_buffer(buffer);
+                Err(crate::resource::CreationError::Bind(err))
+            }
+        }
+    }
+
+    /// Creates an image without memory.
+    /// Memory must be bound with [`bind_image_memory`] before image can be used.
+    ///
+    /// [`bind_image_memory`]: #method.bind_image_memory
+    pub fn create_unbound_image(&self, info: ImageInfo) -> Result<Image<B>, ImageCreationError> {
+        profile_scope!("create_unbound_image");
+
+        unsafe { Image::create_unbound(&self.device, info) }
+    }
+
+    /// Bind range of memory block to the image created with [`create_unbound_image`].
+    /// Memory block is not owned by the image and can be shared.
+    ///
+    /// # Safety
+    ///
+    /// Memory range starting at `offset` must satisfy image requirements
+    /// and outlive the image.
+    ///
+    /// [`create_unbound_image`]: #method.create_unbound_image
+    pub unsafe fn bind_image_memory(
+        &self,
+        mut image: Image<B>,
+        block: &memory::MemoryBlock<B>,
+        offset: u64,
+    ) -> Result<Escape<Image<B>>, ImageCreationError> {
+        use memory::Block as _;
+
+        match image.bind_memory(&self.device, block.memory(), block.range().start + offset) {
+            Ok(()) => Ok(self.resources.images.escape(image)),
+            Err(err) => {
+                self.destroy_relevant_image(image);
+                Err(crate::resource::CreationError::Bind(err))
+            }
+        }
+    }
+
     /// Fetch image format details for a particular `ImageInfo`.
     pub fn image_format_properties(&self, info: ImageInfo) -> Option<FormatProperties> {
         self.physical().image_format_properties(
@@ -833,6 +945,14 @@ where
         &self.adapter.physical_device
     }
 
+    /// Get features enabled for the device.
+    /// Commands that require optional features,
+    /// e.g. `draw_indirect_count` requires `DRAW_INDIRECT_COUNT`,
+    /// can be used only if the feature is present.
+    pub fn features(&self) -> Features {
+        self.adapter.physical_device.features()
+    }
+
     /// Create new semaphore.
     pub fn create_semaphore(&self) -> Result<B::Semaphore, OutOfMemory> {
         profile_scope!("create_semaphore");
@@ -854,6 +974,21 @@ where
         Fence::new(&self.device, signaled)
     }
 
+    /// Create new event in unset state.
+    pub fn create_event(&self) -> Result<Event<B>, OutOfMemory> {
+        Event::new(&self.device)
+    }
+
+    /// Destroy event.
+    ///
+    /// # Safety
+    ///
+    /// Event must be created by this `Factory`
+    /// and must not be in use by pending commands.
+    pub unsafe fn destroy_event(&self, event: Event<B>) {
+        event.dispose(&self.device);
+    }
+
     /// Wait for the fence become signeled.
     pub fn reset_fence(&self, fence: &mut Fence<B>) -> Result<(), OutOfMemory> {
         fence.reset(&self.device)
@@ -896,17 +1031,28 @@ where
 
         if let Some(fence_epoch) = fence.wait_signaled(&self.device, timeout_ns)? {
             // Now we can update epochs counter.
-            let family_index = self.families_indices[fence_epoch.queue.family.index];
-            let mut lock = self.epochs[family_index].write();
-            let epoch = &mut lock[fence_epoch.queue.index];
-            *epoch = max(*epoch, fence_epoch.epoch);
-
+            unsafe { self.mark_reached(fence_epoch) };
             Ok(true)
         } else {
             Ok(false)
         }
     }
 
+    /// Inform factory that queue reached the epoch.
+    /// Resources dropped before the epoch was signaled can be freed on next `cleanup`.
+    /// This must be called for epochs waited upon without `wait_for_fence(s)`,
+    /// e.g. with fences owned by a `Timeline`.
+    ///
+    /// # Safety
+    ///
+    /// Epoch must be reached by the queue.
+    pub unsafe fn mark_reached(&self, fence_epoch: FenceEpoch) {
+        let family_index = self.families_indices[fence_epoch.queue.family.index];
+        let mut lock = self.epochs[family_index].write();
+        let epoch = &mut lock[fence_epoch.queue.index];
+        *epoch = max(*epoch, fence_epoch.epoch);
+    }
+
     /// Wait for the fences become signeled.
     pub fn wait_for_fences<'a>(
         &self,
@@ -1026,12 +1172,24 @@ where
         }
     }
 
-    fn complete_epochs(&mut self) -> Epochs {
+    fn complete_epochs(&mut self, families: &Families<B>) -> Epochs {
+        let timeline = &self.timeline;
         Epochs {
             values: self
                 .epochs
                 .iter_mut()
-                .map(|l| l.get_mut().iter().cloned().collect())
+                .zip(families.as_slice())
+                .map(|(l, f)| {
+                    // Epochs reached by uploads and blits are known only to the timeline.
+                    l.get_mut()
+                        .iter_mut()
+                        .zip(f.as_slice())
+                        .map(|(epoch, q)| {
+                            *epoch = max(*epoch, timeline.reached(q.id()).saturating_sub(1));
+                            *epoch
+                        })
+                        .collect()
+                })
                 .collect(),
         }
     }
@@ -1041,10 +1199,15 @@ where
         profile_scope!("cleanup");
 
         let next = self.next_epochs(families);
-        let complete = self.complete_epochs();
         unsafe {
-            self.uploader.cleanup(&self.device);
-            self.blitter.cleanup(&self.device);
+            // Pending operations are checked again on next cleanup.
+            if let Err(err) = self.uploader.cleanup(&self.device, &mut self.timeline) {
+                log::warn!("Failed to cleanup uploads: {:?}", err);
+            }
+            if let Err(err) = self.blitter.cleanup(&self.device, &mut self.timeline) {
+                log::warn!("Failed to cleanup blits: {:?}", err);
+            }
+            let complete = self.complete_epochs(families);
             self.resources.cleanup(
                 &self.device,
                 self.heaps.get_mut(),
@@ -1058,20 +1221,76 @@ where
     }
 
     /// Flush uploads
-    pub fn flush_uploads(&mut self, families: &mut Families<B>) {
-        unsafe { self.uploader.flush(families) }
+    pub fn flush_uploads(&mut self, families: &mut Families<B>) -> Result<(), OutOfMemory> {
+        unsafe { self.flush_batched(families, true, false) }
     }
 
     /// Flush blits
-    pub fn flush_blits(&mut self, families: &mut Families<B>) {
-        unsafe { self.blitter.flush(families) }
+    pub fn flush_blits(&mut self, families: &mut Families<B>) -> Result<(), OutOfMemory> {
+        unsafe { self.flush_batched(families, false, true) }
+    }
+
+    /// Flush uploads and blits with single raw submit per queue.
+    /// Fences are allocated before updates are moved into the batcher,
+    /// so updates stay pending until next flush if allocation fails.
+    unsafe fn flush_batched(
+        &mut self,
+        families: &mut Families<B>,
+        uploads: bool,
+        blits: bool,
+    ) -> Result<(), OutOfMemory> {
+        let mut queues = SmallVec::<[QueueId; 8]>::new();
+        if uploads {
+            queues.extend(self.uploader.queues());
+        }
+        if blits {
+            for queue in self.blitter.queues() {
+                if !queues.contains(&queue) {
+                    queues.push(queue);
+                }
+            }
+        }
+
+        let mut fences = SmallVec::<[_; 8]>::new();
+        for queue in queues {
+            match self.timeline.fence(&self.device) {
+                Ok(fence) => fences.push((queue, fence)),
+                Err(err) => {
+                    for (_, fence) in fences {
+                        self.timeline.recycle(fence);
+                    }
+                    return Err(err);
+                }
+            }
+        }
+
+        let mut batcher = SubmissionBatcher::new();
+        // Uploads go first as blits may read uploaded data.
+        if uploads {
+            self.uploader.flush(&mut batcher);
+        }
+        if blits {
+            self.blitter.flush(&mut batcher);
+        }
+        batcher.flush(
+            families,
+            fences.iter_mut().map(|(queue, fence)| (*queue, fence)),
+        );
+
+        for (queue, fence) in fences {
+            let epoch = self.timeline.track(fence);
+            self.uploader.submitted(queue, epoch);
+            self.blitter.submitted(queue, epoch);
+        }
+        Ok(())
     }
 
-    /// Flush uploads and cleanup unused resources.
-    pub fn maintain(&mut self, families: &mut Families<B>) {
-        self.flush_uploads(families);
-        self.flush_blits(families);
+    /// Flush uploads and blits and cleanup unused resources.
+    /// Resources are cleaned up even if flush fails.
+    pub fn maintain(&mut self, families: &mut Families<B>) -> Result<(), OutOfMemory> {
+        let flushed = unsafe { self.flush_batched(families, true, true) };
         self.cleanup(families);
+        flushed
     }
 
     /// Create descriptor set layout with specified bindings.
@@ -1293,6 +1512,7 @@ where
         ),
         heaps: ManuallyDrop::new(parking_lot::Mutex::new(heaps)),
         resources: ManuallyDrop::new(ResourceHub::default()),
+        timeline: ManuallyDrop::new(Timeline::new(&device)),
         uploader: unsafe { Uploader::new(&device, &families) }
             .map_err(rendy_core::hal::device::CreationError::OutOfMemory)?,
         blitter: unsafe { Blitter::new(&device, &families) }
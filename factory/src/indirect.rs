@@ -0,0 +1,369 @@
+use {
+    crate::{
+        command::{
+            Compute, DispatchCommand, DrawCommand, DrawIndexedCommand, Encoder, IndirectCommand,
+            RenderPassEncoder, Supports,
+        },
+        factory::{Factory, UploadError},
+        memory::Dynamic,
+        resource::{Buffer, BufferInfo, Escape},
+    },
+    rendy_core::hal::{buffer::Usage, device::MapError, Backend},
+};
+
+/// Builder for buffers of indirect commands.
+///
+/// Commands can be written by the host upon creation
+/// and later overwritten by either host or device.
+#[derive(Clone, Debug)]
+pub struct IndirectBufferBuilder<T> {
+    commands: Vec<T>,
+    capacity: u32,
+    count: bool,
+}
+
+impl<T> Default for IndirectBufferBuilder<T> {
+    fn default() -> Self {
+        IndirectBufferBuilder {
+            commands: Vec::new(),
+            capacity: 0,
+            count: false,
+        }
+    }
+}
+
+impl<T> IndirectBufferBuilder<T>
+where
+    T: IndirectCommand,
+{
+    /// Create new empty builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add command.
+    pub fn add_command(&mut self, command: T) -> &mut Self {
+        self.commands.push(command);
+        self
+    }
+
+    /// Add command.
+    pub fn with_command(mut self, command: T) -> Self {
+        self.add_command(command);
+        self
+    }
+
+    /// Add commands.
+    pub fn add_commands(&mut self, commands: impl IntoIterator<Item = T>) -> &mut Self {
+        self.commands.extend(commands);
+        self
+    }
+
+    /// Add commands.
+    pub fn with_commands(mut self, commands: impl IntoIterator<Item = T>) -> Self {
+        self.add_commands(commands);
+        self
+    }
+
+    /// Reserve space for at least `capacity` commands.
+    /// This allows device to write more commands than host provided.
+    pub fn set_capacity(&mut self, capacity: u32) -> &mut Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Reserve space for at least `capacity` commands.
+    /// This allows device to write more commands than host provided.
+    pub fn with_capacity(mut self, capacity: u32) -> Self {
+        self.set_capacity(capacity);
+        self
+    }
+
+    /// Store number of commands in the buffer after commands array.
+    /// Stored value can be overwritten by device and used
+    /// with `draw_indirect_count` and `draw_indexed_indirect_count` commands.
+    pub fn set_count(&mut self, count: bool) -> &mut Self {
+        self.count = count;
+        self
+    }
+
+    /// Store number of commands in the buffer after commands array.
+    /// Stored value can be overwritten by device and used
+    /// with `draw_indirect_count` and `draw_indexed_indirect_count` commands.
+    pub fn with_count(mut self) -> Self {
+        self.set_count(true);
+        self
+    }
+
+    /// Capacity, offset of the count and info of the buffer to create.
+    fn layout(&self) -> (u32, Option<u64>, BufferInfo) {
+        let capacity = self.capacity.max(self.commands.len() as u32).max(1);
+        let commands_size = u64::from(capacity) * u64::from(T::stride());
+        let count_offset = if self.count {
+            Some(commands_size)
+        } else {
+            None
+        };
+
+        let info = BufferInfo {
+            size: commands_size + if self.count { 4 } else { 0 },
+            // Device may overwrite commands and count.
+            usage: Usage::INDIRECT | Usage::TRANSFER_DST | Usage::STORAGE,
+        };
+
+        (capacity, count_offset, info)
+    }
+
+    /// Create buffer and write commands into it.
+    /// Buffer always has `STORAGE` usage so that shaders can write commands and count.
+    pub fn build<B>(&self, factory: &Factory<B>) -> Result<IndirectBuffer<B, T>, UploadError>
+    where
+        B: Backend,
+    {
+        let (capacity, count_offset, info) = self.layout();
+        let buffer = factory
+            .create_buffer(info, Dynamic)
+            .map_err(UploadError::Create)?;
+
+        let mut indirect = IndirectBuffer {
+            buffer,
+            capacity,
+            len: 0,
+            count_offset,
+            marker: std::marker::PhantomData,
+        };
+
+        unsafe {
+            // Buffer was just created and not used by device.
+            indirect.write(factory, &self.commands)
+        }
+        .map_err(UploadError::Map)?;
+
+        Ok(indirect)
+    }
+}
+
+/// Buffer with tightly packed array of indirect commands.
+#[derive(Debug)]
+pub struct IndirectBuffer<B: Backend, T> {
+    buffer: Escape<Buffer<B>>,
+    capacity: u32,
+    len: u32,
+    count_offset: Option<u64>,
+    marker: std::marker::PhantomData<fn() -> T>,
+}
+
+impl<B, T> IndirectBuffer<B, T>
+where
+    B: Backend,
+    T: IndirectCommand,
+{
+    /// Get buffer with commands.
+    pub fn buffer(&self) -> &Buffer<B> {
+        &self.buffer
+    }
+
+    /// Number of commands written by the host.
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    /// Check if no commands were written by the host.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Maximum number of commands buffer can hold.
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Stride between commands in the buffer.
+    pub fn stride(&self) -> u32 {
+        T::stride()
+    }
+
+    /// Offset of the commands count in the buffer.
+    /// Returns `None` if buffer was created without count.
+    pub fn count_offset(&self) -> Option<u64> {
+        self.count_offset
+    }
+
+    /// Overwrite commands in the buffer.
+    /// Stored count is updated as well.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `commands` don't fit into buffer.
+    ///
+    /// # Safety
+    ///
+    /// Buffer must not be used by device.
+    pub unsafe fn write(&mut self, factory: &Factory<B>, commands: &[T]) -> Result<(), MapError> {
+        assert!(
+            commands.len() <= self.capacity as usize,
+            "Too many commands for indirect buffer"
+        );
+
+        if !commands.is_empty() {
+            factory.upload_visible_buffer(&mut self.buffer, 0, commands)?;
+        }
+        if let Some(count_offset) = self.count_offset {
+            factory.upload_visible_buffer(
+                &mut self.buffer,
+                count_offset,
+                &[commands.len() as u32],
+            )?;
+        }
+        self.len = commands.len() as u32;
+        Ok(())
+    }
+}
+
+impl<B> IndirectBuffer<B, DrawCommand>
+where
+    B: Backend,
+{
+    /// Draw commands written by the host.
+    ///
+    /// # Safety
+    ///
+    /// See `RenderPassEncoder::draw_indirect`.
+    pub unsafe fn draw(&self, encoder: &mut RenderPassEncoder<'_, B>) {
+        encoder.draw_indirect(self.buffer.raw(), 0, self.len, self.stride());
+    }
+
+    /// Draw number of commands stored in the buffer.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if buffer was created without count.
+    ///
+    /// # Safety
+    ///
+    /// See `RenderPassEncoder::draw_indirect_count`.
+    pub unsafe fn draw_count(&self, encoder: &mut RenderPassEncoder<'_, B>) {
+        let count_offset = self
+            .count_offset
+            .expect("Indirect buffer was created without count");
+        encoder.draw_indirect_count(
+            self.buffer.raw(),
+            0,
+            self.buffer.raw(),
+            count_offset,
+            self.capacity,
+            self.stride(),
+        );
+    }
+}
+
+impl<B> IndirectBuffer<B, DrawIndexedCommand>
+where
+    B: Backend,
+{
+    /// Draw indexed commands written by the host.
+    ///
+    /// # Safety
+    ///
+    /// See `RenderPassEncoder::draw_indexed_indirect`.
+    pub unsafe fn draw_indexed(&self, encoder: &mut RenderPassEncoder<'_, B>) {
+        encoder.draw_indexed_indirect(self.buffer.raw(), 0, self.len, self.stride());
+    }
+
+    /// Draw number of indexed commands stored in the buffer.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if buffer was created without count.
+    ///
+    /// # Safety
+    ///
+    /// See `RenderPassEncoder::draw_indexed_indirect_count`.
+    pub unsafe fn draw_indexed_count(&self, encoder: &mut RenderPassEncoder<'_, B>) {
+        let count_offset = self
+            .count_offset
+            .expect("Indirect buffer was created without count");
+        encoder.draw_indexed_indirect_count(
+            self.buffer.raw(),
+            0,
+            self.buffer.raw(),
+            count_offset,
+            self.capacity,
+            self.stride(),
+        );
+    }
+}
+
+impl<B> IndirectBuffer<B, DispatchCommand>
+where
+    B: Backend,
+{
+    /// Dispatch command with specified index.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `index` is not less than buffer capacity.
+    ///
+    /// # Safety
+    ///
+    /// See `Encoder::dispatch_indirect`.
+    pub unsafe fn dispatch<C, L>(&self, encoder: &mut Encoder<'_, B, C, L>, index: u32)
+    where
+        C: Supports<Compute>,
+    {
+        assert!(
+            index < self.capacity,
+            "Dispatch command index out of bounds"
+        );
+        encoder.dispatch_indirect(
+            self.buffer.raw(),
+            u64::from(index) * u64::from(self.stride()),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_are_tightly_packed() {
+        let (capacity, count_offset, info) = IndirectBufferBuilder::<DrawCommand>::new()
+            .with_commands(vec![
+                DrawCommand {
+                    vertex_count: 3,
+                    instance_count: 1,
+                    first_vertex: 0,
+                    first_instance: 0,
+                };
+                2
+            ])
+            .layout();
+        assert_eq!(capacity, 2);
+        assert_eq!(count_offset, None);
+        assert_eq!(info.size, 2 * 16);
+        assert!(info.usage.contains(Usage::INDIRECT | Usage::STORAGE));
+    }
+
+    #[test]
+    fn count_follows_reserved_commands() {
+        let (capacity, count_offset, info) = IndirectBufferBuilder::<DrawIndexedCommand>::new()
+            .with_capacity(4)
+            .with_count()
+            .layout();
+        assert_eq!(capacity, 4);
+        assert_eq!(count_offset, Some(4 * 20));
+        assert_eq!(info.size, 4 * 20 + 4);
+        assert!(info.usage.contains(Usage::INDIRECT | Usage::STORAGE));
+    }
+
+    #[test]
+    fn empty_builder_reserves_one_command() {
+        let (capacity, count_offset, info) =
+            IndirectBufferBuilder::<DispatchCommand>::new().layout();
+        assert_eq!(capacity, 1);
+        assert_eq!(count_offset, None);
+        assert_eq!(info.size, 12);
+        assert!(info.usage.contains(Usage::STORAGE));
+    }
+}
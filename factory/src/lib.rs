@@ -21,6 +21,7 @@ mod barriers;
 mod blitter;
 mod config;
 mod factory;
+mod indirect;
 mod upload;
 
-pub use crate::{barriers::*, blitter::*, config::*, factory::*, upload::*};
+pub use crate::{barriers::*, blitter::*, config::*, factory::*, indirect::*, upload::*};
@@ -2,13 +2,13 @@ use {
     crate::{
         barriers::Barriers,
         command::{
-            CommandBuffer, CommandPool, Families, Family, IndividualReset, InitialState, OneShot,
-            PendingOnceState, PrimaryLevel, QueueId, RecordingState, Submission, Transfer,
+            CommandBuffer, Families, FenceEpoch, FrameCommandPools, FrameTimeline, OneShot,
+            QueueId, RecordingState, Submission, SubmissionBatcher, Timeline, Transfer,
         },
         core::Device,
         resource::{Buffer, Escape, Handle, Image},
     },
-    rendy_core::hal::device::{Device as _, OutOfMemory},
+    rendy_core::hal::device::{OomOrDeviceLost, OutOfMemory},
     std::{collections::VecDeque, iter::once},
 };
 
@@ -120,6 +120,27 @@ impl From<rendy_core::hal::image::Layout> for ImageStateOrLayout {
     }
 }
 
+/// Flushes of pending updates.
+/// Command pools of updates are recycled per flush as if flushes were frames.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Flushes {
+    /// Index of the next flush.
+    pub(crate) next: u64,
+
+    /// All flushes with index less than this value are complete.
+    pub(crate) complete: u64,
+}
+
+impl FrameTimeline for Flushes {
+    fn next_frame(&self) -> u64 {
+        self.next
+    }
+
+    fn complete_upper_bound(&self) -> u64 {
+        self.complete
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct Uploader<B: rendy_core::hal::Backend> {
     family_uploads: Vec<Option<parking_lot::Mutex<FamilyUploads<B>>>>,
@@ -142,14 +163,13 @@ where
                 family_uploads.push(None);
             }
 
+            family.assert_device_owner(device);
             family_uploads[family.id().index] = Some(parking_lot::Mutex::new(FamilyUploads {
-                fences: Vec::new(),
-                pool: family
-                    .create_pool(device)
-                    .map(|pool| pool.with_capability().unwrap())?,
+                pools: FrameCommandPools::with_capability(family).unwrap(),
+                flushes: 0,
                 next: Vec::new(),
+                submitted: Vec::new(),
                 pending: VecDeque::new(),
-                command_buffers: Vec::new(),
                 barriers: Barriers::new(
                     rendy_core::hal::pso::PipelineStage::TRANSFER,
                     rendy_core::hal::buffer::Access::TRANSFER_WRITE,
@@ -373,27 +393,48 @@ where
     /// # Safety
     ///
     /// `device` must be the same that was used to create this `Uploader`.
+    /// `timeline` must be the one that tracks epochs passed to `submitted`.
     ///
-    pub(crate) unsafe fn cleanup(&mut self, device: &Device<B>) {
+    pub(crate) unsafe fn cleanup(
+        &mut self,
+        device: &Device<B>,
+        timeline: &mut Timeline<B>,
+    ) -> Result<(), OutOfMemory> {
         for uploader in self.family_uploads.iter_mut() {
             if let Some(uploader) = uploader {
-                uploader.get_mut().cleanup(device);
+                uploader.get_mut().cleanup(device, timeline)?;
             }
         }
+        Ok(())
     }
 
-    /// Flush new updates.
+    /// Flush new updates into the batcher.
+    /// Epochs that signal completion of flushed updates must be reported with `submitted`.
     ///
     /// # Safety
     ///
-    /// `families` must be the same that was used to create this `Uploader`.
+    /// Batcher must be flushed to the queues of the `device` that was used to create this `Uploader`.
     ///
-    pub(crate) unsafe fn flush(&mut self, families: &mut Families<B>) {
-        for family in families.as_slice_mut() {
-            let uploader = self.family_uploads[family.id().index]
-                .as_mut()
-                .expect("Uploader must be initialized for all families");
-            uploader.get_mut().flush(family);
+    pub(crate) unsafe fn flush(&mut self, batcher: &mut SubmissionBatcher<'static, B>) {
+        for uploader in self.family_uploads.iter_mut() {
+            if let Some(uploader) = uploader {
+                uploader.get_mut().flush(batcher);
+            }
+        }
+    }
+
+    /// Queues that have updates to flush.
+    pub(crate) fn queues(&mut self) -> impl Iterator<Item = QueueId> + '_ {
+        self.family_uploads
+            .iter_mut()
+            .flatten()
+            .flat_map(|uploader| uploader.get_mut().queues())
+    }
+
+    /// Report epoch signaled after updates flushed to the queue.
+    pub(crate) fn submitted(&mut self, queue: QueueId, epoch: FenceEpoch) {
+        if let Some(Some(uploader)) = self.family_uploads.get_mut(queue.family.index) {
+            uploader.get_mut().submitted(queue.index, epoch);
         }
     }
 
@@ -401,6 +442,7 @@ where
     ///
     /// `device` must be the same that was used to create this `Uploader`.
     /// `device` must be idle.
+    /// Timeline that tracks epochs passed to `submitted` must be disposed first.
     ///
     pub(crate) unsafe fn dispose(&mut self, device: &Device<B>) {
         self.family_uploads.drain(..).for_each(|fu| {
@@ -411,38 +453,41 @@ where
 
 #[derive(Debug)]
 pub(crate) struct FamilyUploads<B: rendy_core::hal::Backend> {
-    pool: CommandPool<B, Transfer, IndividualReset>,
-    command_buffers:
-        Vec<[CommandBuffer<B, Transfer, InitialState, PrimaryLevel, IndividualReset>; 2]>,
+    pools: FrameCommandPools<B, Transfer>,
+    /// Index of the next flush.
+    /// Command buffers are allocated from the pool of the flush they will be submitted by.
+    flushes: u64,
     next: Vec<Option<NextUploads<B>>>,
-    pending: VecDeque<PendingUploads<B>>,
-    fences: Vec<B::Fence>,
+    submitted: Vec<(usize, PendingUploads<B>)>,
+    pending: VecDeque<(FenceEpoch, PendingUploads<B>)>,
     barriers: Barriers<B>,
 }
 
 #[derive(Debug)]
 pub(crate) struct PendingUploads<B: rendy_core::hal::Backend> {
-    barrier_buffer: CommandBuffer<B, Transfer, PendingOnceState, PrimaryLevel, IndividualReset>,
-    command_buffer: CommandBuffer<B, Transfer, PendingOnceState, PrimaryLevel, IndividualReset>,
+    flush: u64,
     staging_buffers: Vec<Escape<Buffer<B>>>,
-    fence: B::Fence,
 }
 
 #[derive(Debug)]
 struct NextUploads<B: rendy_core::hal::Backend> {
-    barrier_buffer:
-        CommandBuffer<B, Transfer, RecordingState<OneShot>, PrimaryLevel, IndividualReset>,
-    command_buffer:
-        CommandBuffer<B, Transfer, RecordingState<OneShot>, PrimaryLevel, IndividualReset>,
+    barrier_buffer: CommandBuffer<B, Transfer, RecordingState<OneShot>>,
+    command_buffer: CommandBuffer<B, Transfer, RecordingState<OneShot>>,
     staging_buffers: Vec<Escape<Buffer<B>>>,
-    fence: B::Fence,
 }
 
 impl<B> FamilyUploads<B>
 where
     B: rendy_core::hal::Backend,
 {
-    unsafe fn flush(&mut self, family: &mut Family<B>) {
+    unsafe fn flush(&mut self, batcher: &mut SubmissionBatcher<'static, B>) {
+        if self.next.is_empty() {
+            return;
+        }
+
+        let flush = self.flushes;
+        self.flushes += 1;
+
         for (queue, mut next) in self
             .next
             .drain(..)
@@ -458,17 +503,60 @@ where
             let (barriers_submit, barrier_buffer) = next.barrier_buffer.finish().submit_once();
             let (submit, command_buffer) = next.command_buffer.finish().submit_once();
 
-            family.queue_mut(queue).submit_raw_fence(
-                Some(Submission::new().submits(once(barriers_submit).chain(once(submit)))),
-                Some(&next.fence),
+            batcher.add(
+                QueueId {
+                    family: self.pools.family_id(),
+                    index: queue,
+                },
+                Submission::new().submits(once(barriers_submit).chain(once(submit))),
             );
 
-            self.pending.push_back(PendingUploads {
-                barrier_buffer,
-                command_buffer,
-                staging_buffers: next.staging_buffers,
-                fence: next.fence,
-            });
+            // Pool of the flush is reset only after the flush is complete.
+            self.pools.release(flush, barrier_buffer);
+            self.pools.release(flush, command_buffer);
+
+            self.submitted.push((
+                queue,
+                PendingUploads {
+                    flush,
+                    staging_buffers: next.staging_buffers,
+                },
+            ));
+        }
+    }
+
+    fn queues(&self) -> impl Iterator<Item = QueueId> + '_ {
+        let family = self.pools.family_id();
+        self.next
+            .iter()
+            .enumerate()
+            .filter(|(_, next)| next.is_some())
+            .map(move |(index, _)| QueueId { family, index })
+    }
+
+    fn submitted(&mut self, queue: usize, epoch: FenceEpoch) {
+        let mut index = 0;
+        while index < self.submitted.len() {
+            if self.submitted[index].0 == queue {
+                let (_, pending) = self.submitted.remove(index);
+                self.pending.push_back((epoch, pending));
+            } else {
+                index += 1;
+            }
+        }
+    }
+
+    /// Flushes with pending updates are not complete.
+    fn flushes(&self) -> Flushes {
+        Flushes {
+            next: self.flushes,
+            complete: self
+                .pending
+                .iter()
+                .map(|(_, pending)| pending.flush)
+                .chain(self.submitted.iter().map(|(_, pending)| pending.flush))
+                .min()
+                .unwrap_or(self.flushes),
         }
     }
 
@@ -481,29 +569,24 @@ where
             self.next.push(None);
         }
 
-        let pool = &mut self.pool;
-
-        match &mut self.next[queue] {
-            Some(next) => Ok(next),
-            slot @ None => {
-                let [buf_a, buf_b] = self.command_buffers.pop().unwrap_or_else(|| {
-                    let mut bufs = pool.allocate_buffers(2);
-                    [bufs.remove(1), bufs.remove(0)]
-                });
-                let fence = self
-                    .fences
-                    .pop()
-                    .map_or_else(|| device.create_fence(false), Ok)?;
-                *slot = Some(NextUploads {
-                    barrier_buffer: buf_a.begin(OneShot, ()),
-                    command_buffer: buf_b.begin(OneShot, ()),
-                    staging_buffers: Vec::new(),
-                    fence,
-                });
-
-                Ok(slot.as_mut().unwrap())
-            }
+        if self.next[queue].is_none() {
+            let flushes = self.flushes();
+            let barrier_buffer = self.pools.allocate(device, &flushes)?;
+            let command_buffer = match self.pools.allocate(device, &flushes) {
+                Ok(command_buffer) => command_buffer,
+                Err(err) => {
+                    self.pools.release_unsubmitted(flushes.next, barrier_buffer);
+                    return Err(err);
+                }
+            };
+            self.next[queue] = Some(NextUploads {
+                barrier_buffer: barrier_buffer.begin(OneShot, ()),
+                command_buffer: command_buffer.begin(OneShot, ()),
+                staging_buffers: Vec::new(),
+            });
         }
+
+        Ok(self.next[queue].as_mut().unwrap())
     }
 
     /// Cleanup pending updates.
@@ -512,56 +595,50 @@ where
     ///
     /// `device` must be the same that was used with other methods of this instance.
     ///
-    unsafe fn cleanup(&mut self, device: &Device<B>) {
-        while let Some(pending) = self.pending.pop_front() {
-            match device.get_fence_status(&pending.fence) {
+    unsafe fn cleanup(
+        &mut self,
+        device: &Device<B>,
+        timeline: &mut Timeline<B>,
+    ) -> Result<(), OutOfMemory> {
+        while let Some((epoch, pending)) = self.pending.pop_front() {
+            match timeline.is_reached(device, epoch) {
                 Ok(false) => {
-                    self.pending.push_front(pending);
-                    return;
+                    self.pending.push_front((epoch, pending));
+                    break;
                 }
-                Err(rendy_core::hal::device::DeviceLost) => {
+                Err(OomOrDeviceLost::DeviceLost(_)) => {
                     panic!("Device lost error is not handled yet");
                 }
+                Err(OomOrDeviceLost::OutOfMemory(err)) => {
+                    // Fence of the reached epoch is recycled on next cleanup.
+                    self.pending.push_front((epoch, pending));
+                    return Err(err);
+                }
                 Ok(true) => {
-                    device
-                        .reset_fence(&pending.fence)
-                        .expect("Can always reset signalled fence");
-                    self.fences.push(pending.fence);
-                    self.command_buffers.push([
-                        pending.command_buffer.mark_complete().reset(),
-                        pending.barrier_buffer.mark_complete().reset(),
-                    ]);
+                    // Staging buffers are released.
+                    drop(pending);
                 }
             }
         }
+
+        self.pools.recycle(self.flushes().complete);
+        Ok(())
     }
 
     /// # Safety
     ///
     /// Device must be idle.
+    /// Timeline that tracks pending epochs must be disposed first.
     ///
     unsafe fn dispose(mut self, device: &Device<B>) {
-        let pool = &mut self.pool;
-        self.pending.drain(..).for_each(|pending| {
-            device.destroy_fence(pending.fence);
-            pool.free_buffers(Some(pending.command_buffer.mark_complete()));
-            pool.free_buffers(Some(pending.barrier_buffer.mark_complete()));
-        });
-
-        self.fences
-            .drain(..)
-            .for_each(|fence| device.destroy_fence(fence));
-        pool.free_buffers(
-            self.command_buffers
-                .drain(..)
-                .flat_map(|[a, b]| once(a).chain(once(b))),
-        );
-
-        pool.free_buffers(self.next.drain(..).filter_map(|n| n).flat_map(|next| {
-            device.destroy_fence(next.fence);
-            once(next.command_buffer).chain(once(next.barrier_buffer))
-        }));
-        drop(pool);
-        self.pool.dispose(device);
+        for next in self.next.drain(..).flatten() {
+            self.pools
+                .release_unsubmitted(self.flushes, next.barrier_buffer);
+            self.pools
+                .release_unsubmitted(self.flushes, next.command_buffer);
+        }
+        self.pending.clear();
+        self.submitted.clear();
+        self.pools.dispose(device);
     }
 }
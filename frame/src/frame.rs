@@ -1,6 +1,9 @@
 //! Frame module docs.
 
-use crate::{command::Fence, factory::Factory};
+use crate::{
+    command::{Fence, FenceEpoch, FrameTimeline, Timeline},
+    factory::Factory,
+};
 
 /// Fences collection.
 pub type Fences<B> = smallvec::SmallVec<[Fence<B>; 8]>;
@@ -67,10 +70,24 @@ impl FramesRange {
     }
 }
 
+impl FrameTimeline for FramesRange {
+    fn next_frame(&self) -> u64 {
+        self.next
+    }
+
+    fn complete_upper_bound(&self) -> u64 {
+        self.complete_upper_bound
+    }
+}
+
+/// Epochs signaled by fences of the frame.
+type FrameEpochs = smallvec::SmallVec<[FenceEpoch; 8]>;
+
 /// Timeline of frames, complete, pending and next.
 #[derive(Debug)]
 pub struct Frames<B: rendy_core::hal::Backend> {
-    pending: std::collections::VecDeque<Fences<B>>,
+    timeline: Timeline<B>,
+    pending: std::collections::VecDeque<FrameEpochs>,
     next: u64,
 }
 
@@ -79,8 +96,9 @@ where
     B: rendy_core::hal::Backend,
 {
     /// Create new `Frames` instance.
-    pub fn new() -> Self {
+    pub fn new(factory: &Factory<B>) -> Self {
         Frames {
+            timeline: Timeline::new(factory.device()),
             pending: Default::default(),
             next: 0,
         }
@@ -91,11 +109,26 @@ where
         Frame { index: self.next }
     }
 
+    /// Get unsignaled fence to submit as part of the next frame.
+    /// Fences are reused once frames that signaled them complete.
+    pub fn fence(
+        &mut self,
+        factory: &Factory<B>,
+    ) -> Result<Fence<B>, rendy_core::hal::device::OutOfMemory> {
+        self.timeline.fence(factory.device())
+    }
+
     /// Advance to the next frame.
     /// All fences of the next frame must be queued.
     pub fn advance(&mut self, fences: Fences<B>) {
         assert!(fences.iter().all(Fence::is_submitted));
-        self.pending.push_back(fences);
+        let timeline = &mut self.timeline;
+        self.pending.push_back(
+            fences
+                .into_iter()
+                .map(|fence| timeline.track(fence))
+                .collect(),
+        );
         self.next += 1;
     }
 
@@ -131,12 +164,7 @@ where
     /// # Panics
     ///
     /// This function will panic if `target` is greater than or equal to next frame.
-    pub fn wait_complete(
-        &mut self,
-        target: Frame,
-        factory: &Factory<B>,
-        free: impl FnMut(Fences<B>),
-    ) -> CompleteFrame {
+    pub fn wait_complete(&mut self, target: Frame, factory: &Factory<B>) -> CompleteFrame {
         assert!(target.index <= self.next);
         if let Some(complete) = self.complete(target) {
             complete
@@ -145,13 +173,12 @@ where
             // p - n + t + 1 >= 1
             // count >= 1
             let count = self.pending.len() - (self.next - target.index - 1) as usize;
-            let ready = factory.wait_for_fences(
-                self.pending.iter_mut().take(count).flatten(),
-                rendy_core::hal::device::WaitFor::All,
-                !0,
-            );
-            assert_eq!(ready, Ok(true));
-            self.pending.drain(..count).for_each(free);
+            for epoch in self.pending.drain(..count).flatten() {
+                let ready = self.timeline.wait(factory.device(), epoch, !0);
+                assert_eq!(ready, Ok(true));
+                // Let factory free resources dropped before the frame.
+                unsafe { factory.mark_reached(epoch) };
+            }
             CompleteFrame {
                 index: target.index,
             }
@@ -159,18 +186,8 @@ where
     }
 
     /// Dispose of the `Frames`
-    pub fn dispose(mut self, factory: &mut Factory<B>) {
-        let ready = factory.wait_for_fences(
-            self.pending.iter_mut().flatten(),
-            rendy_core::hal::device::WaitFor::All,
-            !0,
-        );
-        assert_eq!(ready, Ok(true));
-
-        self.pending
-            .drain(..)
-            .flatten()
-            .for_each(|fence| factory.destroy_fence(fence));
+    pub fn dispose(self, factory: &mut Factory<B>) {
+        self.timeline.dispose(factory.device());
     }
 
     /// Get range of frame indices in this form:
@@ -182,3 +199,16 @@ where
         }
     }
 }
+
+impl<B> FrameTimeline for Frames<B>
+where
+    B: rendy_core::hal::Backend,
+{
+    fn next_frame(&self) -> u64 {
+        self.next
+    }
+
+    fn complete_upper_bound(&self) -> u64 {
+        Frames::complete_upper_bound(self)
+    }
+}
@@ -0,0 +1,673 @@
+//! Export of the scheduled graph for debugging tools.
+//!
+//! `GraphExport` can be rendered as Graphviz DOT with [`GraphExport::to_dot`]
+//! and serialized with serde when `serde-1` feature is enabled.
+//!
+//! [`GraphExport::to_dot`]: struct.GraphExport.html#method.to_dot
+
+use {crate::chain, std::fmt::Write as _};
+
+/// Resource state required by the node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ResourceUseExport {
+    /// Index of the resource.
+    pub id: usize,
+
+    /// Access flags.
+    pub access: String,
+
+    /// Layout. `None` for buffers.
+    pub layout: Option<String>,
+
+    /// Pipeline stages.
+    pub stages: String,
+}
+
+/// Exported node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeExport {
+    /// Index of the node.
+    pub id: usize,
+
+    /// Debug representation of the node.
+    pub debug: String,
+
+    /// Indices of the families the node can be executed on.
+    pub families: Vec<usize>,
+
+    /// Indices of nodes this node depends on.
+    pub dependencies: Vec<usize>,
+
+    /// Buffers used by the node.
+    pub buffers: Vec<ResourceUseExport>,
+
+    /// Images used by the node.
+    pub images: Vec<ResourceUseExport>,
+}
+
+/// Exported transient buffer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BufferExport {
+    /// Index of the buffer.
+    pub id: usize,
+
+    /// Size of the buffer in bytes.
+    pub size: u64,
+}
+
+/// Exported transient image.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ImageExport {
+    /// Index of the image.
+    pub id: usize,
+
+    /// Kind and extent of the image.
+    pub kind: String,
+
+    /// Number of mip levels.
+    pub levels: u8,
+
+    /// Format of the image.
+    pub format: String,
+}
+
+/// Exported pipeline barrier.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BarrierExport {
+    /// Index of the resource.
+    pub id: usize,
+
+    /// Family ownership transfer. `None` if there is no transfer.
+    pub families: Option<(usize, usize)>,
+
+    /// Access, layout and stages before the barrier.
+    pub from: String,
+
+    /// Access, layout and stages after the barrier.
+    pub to: String,
+
+    /// Event barrier waits for if it is second half of split barrier.
+    pub event: Option<usize>,
+}
+
+/// Exported semaphore wait.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WaitExport {
+    /// Index of the semaphore.
+    pub semaphore: usize,
+
+    /// Stage at which semaphore is waited.
+    pub stage: String,
+}
+
+/// Exported event set.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SetEventExport {
+    /// Index of the event.
+    pub event: usize,
+
+    /// Stage after which event is set.
+    pub stage: String,
+}
+
+/// Exported submission of the node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SubmissionExport {
+    /// Index of the submitted node.
+    pub node: usize,
+
+    /// Index of the family.
+    pub family: usize,
+
+    /// Index of the queue in the family.
+    pub queue: usize,
+
+    /// Index of the submission in the queue.
+    pub index: usize,
+
+    /// Global submission order.
+    pub submit_order: usize,
+
+    /// Semaphores waited before submission.
+    pub waits: Vec<WaitExport>,
+
+    /// Buffer barriers recorded before node's commands.
+    pub acquire_buffers: Vec<BarrierExport>,
+
+    /// Image barriers recorded before node's commands.
+    pub acquire_images: Vec<BarrierExport>,
+
+    /// Buffer barriers recorded after node's commands.
+    pub release_buffers: Vec<BarrierExport>,
+
+    /// Image barriers recorded after node's commands.
+    pub release_images: Vec<BarrierExport>,
+
+    /// Events set after release barriers.
+    pub set_events: Vec<SetEventExport>,
+
+    /// Semaphores signaled after submission.
+    pub signals: Vec<usize>,
+}
+
+/// Exported graph.
+/// Contains nodes, transient resources and scheduled submissions with their synchronization.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GraphExport {
+    /// Nodes of the graph.
+    pub nodes: Vec<NodeExport>,
+
+    /// Transient buffers of the graph.
+    pub buffers: Vec<BufferExport>,
+
+    /// Transient images of the graph.
+    pub images: Vec<ImageExport>,
+
+    /// Submissions in submit order.
+    pub submissions: Vec<SubmissionExport>,
+
+    /// Number of semaphores.
+    pub semaphores: usize,
+}
+
+impl NodeExport {
+    pub(crate) fn new(node: &chain::Node, debug: String) -> Self {
+        NodeExport {
+            id: node.id,
+            debug,
+            families: node.families.iter().map(|family| family.0).collect(),
+            dependencies: node.dependencies.clone(),
+            buffers: resource_uses(node.buffers(), |_| None),
+            images: resource_uses(node.images(), |layout| Some(format!("{:?}", layout))),
+        }
+    }
+}
+
+fn resource_uses<'a, R>(
+    states: impl IntoIterator<Item = (&'a chain::Id, &'a chain::State<R>)>,
+    layout: impl Fn(R::Layout) -> Option<String>,
+) -> Vec<ResourceUseExport>
+where
+    R: chain::Resource,
+{
+    let mut uses: Vec<_> = states
+        .into_iter()
+        .map(|(id, state)| ResourceUseExport {
+            id: id.0,
+            access: format!("{:?}", state.access),
+            layout: layout(state.layout),
+            stages: format!("{:?}", state.stages),
+        })
+        .collect();
+    uses.sort_by_key(|resource| resource.id);
+    uses
+}
+
+fn barriers<R>(barriers: &chain::Barriers<R>) -> Vec<BarrierExport>
+where
+    R: chain::Resource,
+{
+    let mut barriers: Vec<_> = barriers
+        .iter()
+        .flat_map(|(id, barriers)| barriers.iter().map(move |barrier| (id, barrier)))
+        .map(|(id, barrier)| BarrierExport {
+            id: id.0,
+            families: barrier
+                .families
+                .as_ref()
+                .map(|families| (families.start.0, families.end.0)),
+            from: format!("{:?}", barrier.states.start),
+            to: format!("{:?}", barrier.states.end),
+            event: barrier.event,
+        })
+        .collect();
+    barriers.sort_by_key(|barrier| barrier.id);
+    barriers
+}
+
+impl GraphExport {
+    pub(crate) fn new(
+        nodes: Vec<NodeExport>,
+        buffers: Vec<BufferExport>,
+        images: Vec<ImageExport>,
+        schedule: &chain::Schedule<chain::SyncData<usize, usize>>,
+    ) -> Self {
+        let submissions: Vec<_> = schedule
+            .ordered()
+            .map(|submission| {
+                let sid = submission.id();
+                let sync = submission.sync();
+                SubmissionExport {
+                    node: submission.node(),
+                    family: sid.family().0,
+                    queue: sid.queue().index(),
+                    index: sid.index(),
+                    submit_order: submission.submit_order(),
+                    waits: sync
+                        .wait
+                        .iter()
+                        .map(|wait| WaitExport {
+                            semaphore: *wait.semaphore(),
+                            stage: format!("{:?}", wait.stage()),
+                        })
+                        .collect(),
+                    acquire_buffers: barriers(&sync.acquire.buffers),
+                    acquire_images: barriers(&sync.acquire.images),
+                    release_buffers: barriers(&sync.release.buffers),
+                    release_images: barriers(&sync.release.images),
+                    set_events: sync
+                        .set_events
+                        .iter()
+                        .map(|set| SetEventExport {
+                            event: set.event(),
+                            stage: format!("{:?}", set.stage()),
+                        })
+                        .collect(),
+                    signals: sync
+                        .signal
+                        .iter()
+                        .map(|signal| *signal.semaphore())
+                        .collect(),
+                }
+            })
+            .collect();
+
+        let semaphores = submissions
+            .iter()
+            .flat_map(|submission| submission.signals.iter())
+            .map(|&semaphore| semaphore + 1)
+            .max()
+            .unwrap_or(0);
+
+        GraphExport {
+            nodes,
+            buffers,
+            images,
+            submissions,
+            semaphores,
+        }
+    }
+
+    /// Render graph in Graphviz DOT format.
+    ///
+    /// Nodes are clustered by queues they are submitted to.
+    /// Solid edges are node dependencies, dashed edges are resource uses
+    /// and red edges are semaphores between submissions.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        self.write_dot(&mut dot)
+            .expect("Writing to `String` never fails");
+        dot
+    }
+
+    fn write_dot(&self, dot: &mut String) -> std::fmt::Result {
+        writeln!(dot, "digraph rendy_graph {{")?;
+        writeln!(dot, "    node [shape=box];")?;
+
+        let mut queues: Vec<(usize, usize)> = self
+            .submissions
+            .iter()
+            .map(|submission| (submission.family, submission.queue))
+            .collect();
+        queues.sort();
+        queues.dedup();
+
+        for (family, queue) in queues {
+            writeln!(dot, "    subgraph cluster_{}_{} {{", family, queue)?;
+            writeln!(dot, "        label=\"family {} queue {}\";", family, queue)?;
+            for submission in self
+                .submissions
+                .iter()
+                .filter(|submission| (submission.family, submission.queue) == (family, queue))
+            {
+                let debug = self
+                    .nodes
+                    .iter()
+                    .find(|node| node.id == submission.node)
+                    .map_or("", |node| &node.debug);
+                let mut label = format!(
+                    "node {}: {}\\lsubmission {} (order {})\\l",
+                    submission.node,
+                    escape(debug),
+                    submission.index,
+                    submission.submit_order
+                );
+                let sides = [
+                    ("acquire buffer", &submission.acquire_buffers),
+                    ("acquire image", &submission.acquire_images),
+                    ("release buffer", &submission.release_buffers),
+                    ("release image", &submission.release_images),
+                ];
+                for &(side, barriers) in sides.iter() {
+                    for barrier in barriers {
+                        write!(
+                            label,
+                            "{} {}: {} -> {}\\l",
+                            side,
+                            barrier.id,
+                            escape(&barrier.from),
+                            escape(&barrier.to)
+                        )?;
+                    }
+                }
+                writeln!(dot, "        n{} [label=\"{}\"];", submission.node, label)?;
+            }
+            writeln!(dot, "    }}")?;
+        }
+
+        for buffer in &self.buffers {
+            writeln!(
+                dot,
+                "    b{} [shape=ellipse, label=\"buffer {}\\n{} bytes\"];",
+                buffer.id, buffer.id, buffer.size
+            )?;
+        }
+
+        for image in &self.images {
+            writeln!(
+                dot,
+                "    i{} [shape=ellipse, label=\"image {}\\n{}\\n{}\"];",
+                image.id,
+                image.id,
+                escape(&image.kind),
+                escape(&image.format)
+            )?;
+        }
+
+        for node in &self.nodes {
+            for dependency in &node.dependencies {
+                writeln!(dot, "    n{} -> n{};", dependency, node.id)?;
+            }
+            for buffer in &node.buffers {
+                writeln!(
+                    dot,
+                    "    n{} -> b{} [style=dashed, dir=none, label=\"{}\"];",
+                    node.id,
+                    buffer.id,
+                    escape(&buffer.access)
+                )?;
+            }
+            for image in &node.images {
+                writeln!(
+                    dot,
+                    "    n{} -> i{} [style=dashed, dir=none, label=\"{}\"];",
+                    node.id,
+                    image.id,
+                    escape(&image.access)
+                )?;
+            }
+        }
+
+        for signal in &self.submissions {
+            for &semaphore in &signal.signals {
+                for wait in &self.submissions {
+                    for wait_semaphore in wait.waits.iter().filter(|w| w.semaphore == semaphore) {
+                        writeln!(
+                            dot,
+                            "    n{} -> n{} [color=red, label=\"semaphore {}\\n{}\"];",
+                            signal.node,
+                            wait.node,
+                            semaphore,
+                            escape(&wait_semaphore.stage)
+                        )?;
+                    }
+                }
+            }
+        }
+
+        writeln!(dot, "}}")
+    }
+
+    /// Serialize graph as JSON document.
+    #[cfg(all(feature = "serde", feature = "serde_json"))]
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+fn escape(string: &str) -> String {
+    string.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resource_use(
+        id: usize,
+        access: &str,
+        layout: Option<&str>,
+        stages: &str,
+    ) -> ResourceUseExport {
+        ResourceUseExport {
+            id,
+            access: access.into(),
+            layout: layout.map(Into::into),
+            stages: stages.into(),
+        }
+    }
+
+    fn submission(node: usize, family: usize) -> SubmissionExport {
+        SubmissionExport {
+            node,
+            family,
+            queue: 0,
+            index: 0,
+            submit_order: node,
+            waits: Vec::new(),
+            acquire_buffers: Vec::new(),
+            acquire_images: Vec::new(),
+            release_buffers: Vec::new(),
+            release_images: Vec::new(),
+            set_events: Vec::new(),
+            signals: Vec::new(),
+        }
+    }
+
+    /// Compute node writes buffer that render node on another family reads.
+    fn export() -> GraphExport {
+        GraphExport {
+            nodes: vec![
+                NodeExport {
+                    id: 0,
+                    debug: "Producer".into(),
+                    families: vec![0],
+                    dependencies: vec![],
+                    buffers: vec![resource_use(0, "SHADER_WRITE", None, "COMPUTE_SHADER")],
+                    images: vec![],
+                },
+                NodeExport {
+                    id: 1,
+                    debug: "Consumer".into(),
+                    families: vec![1],
+                    dependencies: vec![0],
+                    buffers: vec![resource_use(0, "VERTEX_BUFFER_READ", None, "VERTEX_INPUT")],
+                    images: vec![resource_use(
+                        0,
+                        "COLOR_ATTACHMENT_WRITE",
+                        Some("ColorAttachmentOptimal"),
+                        "COLOR_ATTACHMENT_OUTPUT",
+                    )],
+                },
+            ],
+            buffers: vec![BufferExport { id: 0, size: 256 }],
+            images: vec![ImageExport {
+                id: 0,
+                kind: "D2(4, 4, 1, 1)".into(),
+                levels: 1,
+                format: "Rgba8Unorm".into(),
+            }],
+            submissions: vec![
+                SubmissionExport {
+                    signals: vec![0],
+                    ..submission(0, 0)
+                },
+                SubmissionExport {
+                    waits: vec![WaitExport {
+                        semaphore: 0,
+                        stage: "VERTEX_INPUT".into(),
+                    }],
+                    acquire_buffers: vec![BarrierExport {
+                        id: 0,
+                        families: None,
+                        from: "(SHADER_WRITE, COMPUTE_SHADER)".into(),
+                        to: "(VERTEX_BUFFER_READ, VERTEX_INPUT)".into(),
+                        event: None,
+                    }],
+                    ..submission(1, 1)
+                },
+            ],
+            semaphores: 1,
+        }
+    }
+
+    #[test]
+    fn dot() {
+        assert_eq!(
+            export().to_dot(),
+            r#"digraph rendy_graph {
+    node [shape=box];
+    subgraph cluster_0_0 {
+        label="family 0 queue 0";
+        n0 [label="node 0: Producer\lsubmission 0 (order 0)\l"];
+    }
+    subgraph cluster_1_0 {
+        label="family 1 queue 0";
+        n1 [label="node 1: Consumer\lsubmission 0 (order 1)\lacquire buffer 0: (SHADER_WRITE, COMPUTE_SHADER) -> (VERTEX_BUFFER_READ, VERTEX_INPUT)\l"];
+    }
+    b0 [shape=ellipse, label="buffer 0\n256 bytes"];
+    i0 [shape=ellipse, label="image 0\nD2(4, 4, 1, 1)\nRgba8Unorm"];
+    n0 -> b0 [style=dashed, dir=none, label="SHADER_WRITE"];
+    n0 -> n1;
+    n1 -> b0 [style=dashed, dir=none, label="VERTEX_BUFFER_READ"];
+    n1 -> i0 [style=dashed, dir=none, label="COLOR_ATTACHMENT_WRITE"];
+    n0 -> n1 [color=red, label="semaphore 0\nVERTEX_INPUT"];
+}
+"#
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "serde", feature = "serde_json"))]
+    fn json() {
+        assert_eq!(
+            export().to_json().unwrap(),
+            r#"{
+  "nodes": [
+    {
+      "id": 0,
+      "debug": "Producer",
+      "families": [
+        0
+      ],
+      "dependencies": [],
+      "buffers": [
+        {
+          "id": 0,
+          "access": "SHADER_WRITE",
+          "layout": null,
+          "stages": "COMPUTE_SHADER"
+        }
+      ],
+      "images": []
+    },
+    {
+      "id": 1,
+      "debug": "Consumer",
+      "families": [
+        1
+      ],
+      "dependencies": [
+        0
+      ],
+      "buffers": [
+        {
+          "id": 0,
+          "access": "VERTEX_BUFFER_READ",
+          "layout": null,
+          "stages": "VERTEX_INPUT"
+        }
+      ],
+      "images": [
+        {
+          "id": 0,
+          "access": "COLOR_ATTACHMENT_WRITE",
+          "layout": "ColorAttachmentOptimal",
+          "stages": "COLOR_ATTACHMENT_OUTPUT"
+        }
+      ]
+    }
+  ],
+  "buffers": [
+    {
+      "id": 0,
+      "size": 256
+    }
+  ],
+  "images": [
+    {
+      "id": 0,
+      "kind": "D2(4, 4, 1, 1)",
+      "levels": 1,
+      "format": "Rgba8Unorm"
+    }
+  ],
+  "submissions": [
+    {
+      "node": 0,
+      "family": 0,
+      "queue": 0,
+      "index": 0,
+      "submit_order": 0,
+      "waits": [],
+      "acquire_buffers": [],
+      "acquire_images": [],
+      "release_buffers": [],
+      "release_images": [],
+      "set_events": [],
+      "signals": [
+        0
+      ]
+    },
+    {
+      "node": 1,
+      "family": 1,
+      "queue": 0,
+      "index": 0,
+      "submit_order": 1,
+      "waits": [
+        {
+          "semaphore": 0,
+          "stage": "VERTEX_INPUT"
+        }
+      ],
+      "acquire_buffers": [
+        {
+          "id": 0,
+          "families": null,
+          "from": "(SHADER_WRITE, COMPUTE_SHADER)",
+          "to": "(VERTEX_BUFFER_READ, VERTEX_INPUT)",
+          "event": null
+        }
+      ],
+      "acquire_images": [],
+      "release_buffers": [],
+      "release_images": [],
+      "set_events": [],
+      "signals": []
+    }
+  ],
+  "semaphores": 1
+}"#
+        );
+    }
+}
@@ -0,0 +1,367 @@
+//! Placement of transient resources into shared memory.
+//!
+//! Resources used on the same queue whose lifetimes don't overlap
+//! in submission order can occupy the same memory range.
+
+use {super::validate::buffer_reads, crate::chain, std::ops::Range};
+
+/// Kind of the transient resource.
+/// Buffers and images never alias each other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum Kind {
+    Buffer,
+    Image,
+}
+
+/// Memory required by the transient resource.
+#[derive(Clone, Debug)]
+pub(super) struct Request {
+    pub kind: Kind,
+    pub type_mask: u32,
+    pub size: u64,
+    pub align: u64,
+
+    /// Queue and range of submit orders in which resource is used.
+    /// `None` if resource can't share memory.
+    pub lifetime: Option<Lifetime>,
+}
+
+/// Queue and range of submit orders in which resource is used.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) struct Lifetime {
+    pub queue: chain::QueueId,
+    pub orders: Range<usize>,
+}
+
+/// Memory pool that is allocated as single memory block.
+#[derive(Clone, Debug)]
+pub(super) struct Pool {
+    pub kind: Kind,
+    pub type_mask: u32,
+    pub size: u64,
+    pub align: u64,
+    pub members: usize,
+
+    /// Queue on which all members of the pool are used.
+    /// `None` if pool is occupied by single resource that can't share memory.
+    pub queue: Option<chain::QueueId>,
+}
+
+/// Range of the pool assigned to the resource.
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Placement {
+    pub pool: usize,
+    pub offset: u64,
+}
+
+/// Find queue and range of submit orders in which resource is used.
+/// Returns `None` if resource can't share memory.
+///
+/// Only resources used on single queue and written first can share memory.
+/// Such resources share memory only with resources used on the same queue.
+/// Then all previous users of the memory precede in the queue
+/// and acquire barrier of the first use can synchronize with them.
+/// Every range of the resource tracked by separate chain must be written first.
+pub(super) fn lifetime<R, S>(
+    chains: &[chain::Chain<R>],
+    schedule: &chain::Schedule<S>,
+) -> Option<Lifetime>
+where
+    R: chain::Resource,
+{
+    use chain::AccessFlags;
+
+    let mut queue = None;
+    let mut start = !0;
+    let mut end = 0;
+    for chain in chains {
+        let first = chain.links().first()?;
+        if !first.access().exclusive() {
+            return None;
+        }
+
+        for link in chain.links() {
+            for (qid, state) in link.queues() {
+                if *queue.get_or_insert(qid) != qid {
+                    return None;
+                }
+                let first = schedule[chain::SubmissionId::new(qid, state.first)].submit_order();
+                let last = schedule[chain::SubmissionId::new(qid, state.last)].submit_order();
+                start = start.min(first);
+                end = end.max(last + 1);
+            }
+        }
+    }
+
+    Some(Lifetime {
+        queue: queue?,
+        orders: start..end,
+    })
+}
+
+/// Find queue and range of submit orders in which buffer is used.
+/// Returns `None` if buffer can't share memory.
+///
+/// Buffers are never discarded, so buffer read before it is written in the frame
+/// carries content of the previous frame and can't share memory.
+pub(super) fn buffer_lifetime<S>(
+    chains: &[chain::Chain<chain::Buffer>],
+    schedule: &chain::Schedule<S>,
+) -> Option<Lifetime> {
+    for chain in chains {
+        if buffer_reads(chain.links().first()?.access()) {
+            return None;
+        }
+    }
+
+    lifetime(chains, schedule)
+}
+
+/// Place resources into pools.
+/// Resources with compatible requirements used on the same queue
+/// with non-overlapping lifetimes share memory.
+pub(super) fn place(requests: &[Request]) -> (Vec<Pool>, Vec<Placement>) {
+    let mut pools: Vec<Pool> = Vec::new();
+    let mut placements = vec![Placement { pool: 0, offset: 0 }; requests.len()];
+
+    // Placed ranges of aliasable resources by pool.
+    let mut placed: Vec<Vec<(Range<usize>, Range<u64>)>> = Vec::new();
+
+    // Place larger resources first for tighter packing.
+    let mut order: Vec<usize> = (0..requests.len()).collect();
+    order.sort_by_key(|&index| std::cmp::Reverse(requests[index].size));
+
+    for index in order {
+        let request = &requests[index];
+        let lifetime = match &request.lifetime {
+            Some(lifetime) => lifetime,
+            None => {
+                pools.push(Pool {
+                    kind: request.kind,
+                    type_mask: request.type_mask,
+                    size: request.size,
+                    align: request.align,
+                    members: 1,
+                    queue: None,
+                });
+                placed.push(Vec::new());
+                placements[index] = Placement {
+                    pool: pools.len() - 1,
+                    offset: 0,
+                };
+                continue;
+            }
+        };
+
+        let pool = match pools.iter().position(|pool| {
+            pool.kind == request.kind
+                && pool.type_mask == request.type_mask
+                && pool.queue == Some(lifetime.queue)
+        }) {
+            Some(pool) => pool,
+            None => {
+                pools.push(Pool {
+                    kind: request.kind,
+                    type_mask: request.type_mask,
+                    size: 0,
+                    align: 1,
+                    members: 0,
+                    queue: Some(lifetime.queue),
+                });
+                placed.push(Vec::new());
+                pools.len() - 1
+            }
+        };
+
+        // Memory ranges of resources alive at the same time.
+        let orders = &lifetime.orders;
+        let mut conflicts: Vec<Range<u64>> = placed[pool]
+            .iter()
+            .filter(|(other, _)| other.start < orders.end && orders.start < other.end)
+            .map(|(_, range)| range.clone())
+            .collect();
+        conflicts.sort_by_key(|range| range.start);
+
+        let mut offset = 0;
+        for range in conflicts {
+            if align_up(offset, request.align) + request.size <= range.start {
+                break;
+            }
+            offset = offset.max(range.end);
+        }
+        let offset = align_up(offset, request.align);
+
+        placed[pool].push((orders.clone(), offset..offset + request.size));
+        let ref mut pool_data = pools[pool];
+        pool_data.size = pool_data.size.max(offset + request.size);
+        pool_data.align = pool_data.align.max(request.align);
+        pool_data.members += 1;
+        placements[index] = Placement { pool, offset };
+    }
+
+    (pools, placements)
+}
+
+fn align_up(value: u64, align: u64) -> u64 {
+    debug_assert!(align.is_power_of_two());
+    (value + align - 1) & !(align - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rendy_core::hal::{buffer, pso::PipelineStage, queue::QueueFamilyId};
+
+    fn node(
+        id: usize,
+        family: usize,
+        dependencies: Vec<usize>,
+        access: buffer::Access,
+    ) -> chain::Node {
+        chain::Node {
+            id,
+            families: vec![QueueFamilyId(family)],
+            cost: 1,
+            dependencies,
+            buffers: vec![(
+                chain::Id(0),
+                chain::State {
+                    access,
+                    layout: (),
+                    stages: PipelineStage::COMPUTE_SHADER,
+                    usage: buffer::Usage::STORAGE,
+                    range: chain::BufferRange::new(0..256),
+                },
+            )],
+            images: Vec::new(),
+        }
+    }
+
+    fn collect_lifetime(nodes: Vec<chain::Node>) -> Option<Lifetime> {
+        let chains = chain::collect(nodes, |_| 1).unwrap();
+        buffer_lifetime(&chains.buffers[&chain::Id(0)], &chains.schedule)
+    }
+
+    fn queue(family: usize) -> chain::QueueId {
+        chain::QueueId {
+            family: QueueFamilyId(family),
+            index: 0,
+        }
+    }
+
+    fn request(size: u64, lifetime: Option<(usize, Range<usize>)>) -> Request {
+        Request {
+            kind: Kind::Buffer,
+            type_mask: 1,
+            size,
+            align: 16,
+            lifetime: lifetime.map(|(family, orders)| Lifetime {
+                queue: queue(family),
+                orders,
+            }),
+        }
+    }
+
+    #[test]
+    fn lifetime_on_single_queue() {
+        assert_eq!(
+            collect_lifetime(vec![
+                node(0, 0, vec![], buffer::Access::SHADER_WRITE),
+                node(1, 0, vec![0], buffer::Access::SHADER_READ),
+            ]),
+            Some(Lifetime {
+                queue: queue(0),
+                orders: 0..2,
+            })
+        );
+    }
+
+    #[test]
+    fn lifetime_requires_first_write() {
+        assert_eq!(
+            collect_lifetime(vec![
+                node(0, 0, vec![], buffer::Access::SHADER_READ),
+                node(1, 0, vec![0], buffer::Access::SHADER_WRITE),
+            ]),
+            None
+        );
+    }
+
+    #[test]
+    fn lifetime_requires_first_write_without_read() {
+        // Buffer updated in place keeps content across frames.
+        assert_eq!(
+            collect_lifetime(vec![
+                node(
+                    0,
+                    0,
+                    vec![],
+                    buffer::Access::SHADER_READ | buffer::Access::SHADER_WRITE,
+                ),
+                node(1, 0, vec![0], buffer::Access::SHADER_READ),
+            ]),
+            None
+        );
+    }
+
+    #[test]
+    fn lifetime_requires_single_queue() {
+        assert_eq!(
+            collect_lifetime(vec![
+                node(0, 0, vec![], buffer::Access::SHADER_WRITE),
+                node(1, 1, vec![0], buffer::Access::SHADER_READ),
+            ]),
+            None
+        );
+    }
+
+    #[test]
+    fn place_disjoint_lifetimes() {
+        let (pools, placements) =
+            place(&[request(256, Some((0, 0..2))), request(128, Some((0, 2..4)))]);
+
+        assert_eq!(pools.len(), 1);
+        assert_eq!(pools[0].size, 256);
+        assert_eq!(pools[0].members, 2);
+        assert_eq!(placements[0].offset, 0);
+        assert_eq!(placements[1].pool, 0);
+        assert_eq!(placements[1].offset, 0);
+    }
+
+    #[test]
+    fn place_overlapping_lifetimes() {
+        let (pools, placements) = place(&[
+            request(256, Some((0, 0..2))),
+            request(100, Some((0, 1..3))),
+            request(64, Some((0, 2..4))),
+        ]);
+
+        assert_eq!(pools.len(), 1);
+        assert_eq!(pools[0].size, 356);
+        assert_eq!(placements[0].offset, 0);
+        assert_eq!(placements[1].offset, 256);
+        // Alive together with the second resource only.
+        assert_eq!(placements[2].offset, 0);
+    }
+
+    #[test]
+    fn place_never_aliases_across_queues() {
+        let (pools, placements) =
+            place(&[request(256, Some((0, 0..2))), request(256, Some((1, 2..4)))]);
+
+        assert_eq!(pools.len(), 2);
+        assert_ne!(placements[0].pool, placements[1].pool);
+        assert_eq!(pools[placements[0].pool].queue, Some(queue(0)));
+        assert_eq!(pools[placements[1].pool].queue, Some(queue(1)));
+    }
+
+    #[test]
+    fn place_unaliasable() {
+        let (pools, placements) = place(&[request(256, None), request(128, Some((0, 0..1)))]);
+
+        assert_eq!(pools.len(), 2);
+        assert_ne!(placements[0].pool, placements[1].pool);
+        assert_eq!(pools[placements[0].pool].members, 1);
+        assert_eq!(pools[placements[0].pool].queue, None);
+    }
+}
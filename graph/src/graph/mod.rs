@@ -1,21 +1,35 @@
+mod alias;
+mod validate;
+
+pub use self::validate::ValidationError;
+
 use {
     crate::{
-        chain,
-        command::{Families, FamilyId, QueueId},
-        core::{device_owned, DeviceId},
+        chain::{self, ResourceRange},
+        command::{
+            CommandBuffer, CommandPool, Event, ExecutableState, Families, Family, FamilyId, Fence,
+            MultiShot, PendingState, QueueId, QueueType, SimultaneousUse, Submission,
+            SubmissionBatcher, Submit,
+        },
+        core::{device_owned, uses_pipeline_barriers, DeviceId},
+        export::{BufferExport, GraphExport, ImageExport, NodeExport},
         factory::Factory,
         frame::{Fences, Frame, Frames},
-        memory::Data,
+        memory::{Data, MemoryBlock},
         node::{
-            BufferBarrier, DynNode, ImageBarrier, NodeBuffer, NodeBuildError, NodeBuilder,
-            NodeImage,
+            gfx_acquire_barriers, gfx_release_barriers, gfx_set_events, gfx_wait_events,
+            history::HistoryCopyBuilder,
+            render::{common_layout, AttachmentOps},
+            sets_events, waits_events, BufferAccess, BufferBarrier, DynNode, ImageBarrier,
+            NodeBuffer, NodeBuildError, NodeBuilder, NodeImage,
         },
         resource::{
             Buffer, BufferCreationError, BufferInfo, Handle, Image, ImageCreationError, ImageInfo,
         },
         BufferId, ImageId, NodeId,
     },
-    rendy_core::hal::{queue::QueueFamilyId, Backend},
+    rendy_core::hal::{queue::QueueFamilyId, window::Extent2D, Backend},
+    std::ops::Range,
     thread_profiler::profile_scope,
 };
 
@@ -23,6 +37,283 @@ use {
 struct GraphNode<B: Backend, T: ?Sized> {
     node: Box<dyn DynNode<B, T>>,
     queue: (usize, usize),
+    buffers: Vec<NodeBuffer>,
+    images: Vec<NodeImage>,
+    enabled: bool,
+    passthrough: Option<PassThrough<B>>,
+    events: Option<EventCommands<B>>,
+    init: Option<InitCommands<B>>,
+    /// Persistent images owned by graph that node uses first.
+    init_images: Vec<ImageId>,
+}
+
+/// Commands submitted instead of disabled optional node.
+/// Performs node's barriers to keep resource states valid for other nodes.
+#[derive(Debug)]
+struct PassThrough<B: Backend> {
+    pool: CommandPool<B, QueueType>,
+    commands: Option<(
+        Submit<B, SimultaneousUse>,
+        CommandBuffer<B, QueueType, PendingState<ExecutableState<MultiShot<SimultaneousUse>>>>,
+    )>,
+}
+
+impl<B> PassThrough<B>
+where
+    B: Backend,
+{
+    fn new(
+        ctx: &GraphContext<B>,
+        factory: &Factory<B>,
+        family: &Family<B>,
+        buffers: &[NodeBuffer],
+        images: &[NodeImage],
+    ) -> Result<Self, rendy_core::hal::device::OutOfMemory> {
+        let mut pool = factory.create_command_pool(family)?;
+
+        let (acquire_stages, acquire) = gfx_acquire_barriers(ctx, buffers, images);
+        let (release_stages, release) = gfx_release_barriers(ctx, buffers, images);
+
+        let commands = if uses_pipeline_barriers::<B>(factory.device())
+            && !(acquire.is_empty() && release.is_empty())
+        {
+            let initial = pool.allocate_buffers(1).pop().unwrap();
+            let mut recording = initial.begin(MultiShot(SimultaneousUse), ());
+            let mut encoder = recording.encoder();
+            unsafe {
+                if !acquire.is_empty() {
+                    encoder.pipeline_barrier(
+                        acquire_stages,
+                        rendy_core::hal::memory::Dependencies::empty(),
+                        acquire,
+                    );
+                }
+                if !release.is_empty() {
+                    encoder.pipeline_barrier(
+                        release_stages,
+                        rendy_core::hal::memory::Dependencies::empty(),
+                        release,
+                    );
+                }
+            }
+            Some(recording.finish().submit())
+        } else {
+            None
+        };
+
+        Ok(PassThrough { pool, commands })
+    }
+
+    /// Dispose of the pass-through commands.
+    ///
+    /// # Safety
+    ///
+    /// Commands must not be in use by the device.
+    unsafe fn dispose(mut self, factory: &Factory<B>) {
+        if let Some((submit, buffer)) = self.commands.take() {
+            drop(submit);
+            self.pool.free_buffers(Some(buffer.mark_complete()));
+        }
+        factory.destroy_command_pool(self.pool);
+    }
+}
+
+/// Commands that wait for and set events of node's split barriers.
+/// Submitted before and after commands of the node, enabled or not.
+///
+/// Each frame in flight uses its own events,
+/// so that events are never set for the next frame
+/// before the previous one waits for them and resets them.
+#[derive(Debug)]
+struct EventCommands<B: Backend> {
+    pool: CommandPool<B, QueueType>,
+    wait: Vec<(
+        Submit<B, SimultaneousUse>,
+        CommandBuffer<B, QueueType, PendingState<ExecutableState<MultiShot<SimultaneousUse>>>>,
+    )>,
+    set: Vec<(
+        Submit<B, SimultaneousUse>,
+        CommandBuffer<B, QueueType, PendingState<ExecutableState<MultiShot<SimultaneousUse>>>>,
+    )>,
+}
+
+impl<B> EventCommands<B>
+where
+    B: Backend,
+{
+    /// Record commands for each frame in flight.
+    /// Returns `None` if node neither waits for nor sets events.
+    fn new(
+        ctx: &GraphContext<B>,
+        factory: &Factory<B>,
+        family: &Family<B>,
+        buffers: &[NodeBuffer],
+        images: &[NodeImage],
+    ) -> Result<Option<Self>, rendy_core::hal::device::OutOfMemory> {
+        let wait_events = waits_events(buffers, images);
+        let set_events = sets_events(buffers, images);
+        if !uses_pipeline_barriers::<B>(factory.device()) || !(wait_events || set_events) {
+            return Ok(None);
+        }
+
+        let mut pool = factory.create_command_pool(family)?;
+        let mut record = |frame: u64, wait: bool| {
+            let initial = pool.allocate_buffers(1).pop().unwrap();
+            let mut recording = initial.begin(MultiShot(SimultaneousUse), ());
+            let mut encoder = recording.encoder();
+            unsafe {
+                if wait {
+                    gfx_wait_events(ctx, frame, &mut encoder, buffers, images);
+                } else {
+                    gfx_set_events(ctx, frame, &mut encoder, buffers, images);
+                }
+            }
+            recording.finish().submit()
+        };
+
+        let frames = 0..ctx.frames_in_flight as u64;
+        let wait = frames
+            .clone()
+            .filter(|_| wait_events)
+            .map(|frame| record(frame, true))
+            .collect();
+        let set = frames
+            .filter(|_| set_events)
+            .map(|frame| record(frame, false))
+            .collect();
+
+        Ok(Some(EventCommands { pool, wait, set }))
+    }
+
+    /// Commands that wait for events of the `frame`.
+    fn wait(&self, frame: u64) -> Option<&Submit<B, SimultaneousUse>> {
+        frame_commands(&self.wait, frame)
+    }
+
+    /// Commands that set events of the `frame`.
+    fn set(&self, frame: u64) -> Option<&Submit<B, SimultaneousUse>> {
+        frame_commands(&self.set, frame)
+    }
+
+    /// Dispose of the event commands.
+    ///
+    /// # Safety
+    ///
+    /// Commands must not be in use by the device.
+    unsafe fn dispose(mut self, factory: &Factory<B>) {
+        let buffers = self
+            .wait
+            .drain(..)
+            .chain(self.set.drain(..))
+            .map(|(_, buffer)| buffer.mark_complete());
+        self.pool.free_buffers(buffers);
+        factory.destroy_command_pool(self.pool);
+    }
+}
+
+/// Commands that move images out of `Undefined` layout.
+///
+/// Content of persistent images is not discarded before their first use in the frame.
+/// Images owned by graph are `Undefined` when created, so node that uses them first
+/// submits these commands once, in the first frame after the graph is built or resized.
+#[derive(Debug)]
+struct InitCommands<B: Backend> {
+    pool: CommandPool<B, QueueType>,
+    commands: (
+        Submit<B, SimultaneousUse>,
+        CommandBuffer<B, QueueType, PendingState<ExecutableState<MultiShot<SimultaneousUse>>>>,
+    ),
+    pending: bool,
+}
+
+impl<B> InitCommands<B>
+where
+    B: Backend,
+{
+    /// Record transitions of the images into layout of their first use.
+    /// Returns `None` if there are no images to transition.
+    fn new<'a>(
+        ctx: &GraphContext<B>,
+        factory: &Factory<B>,
+        family: &Family<B>,
+        images: impl IntoIterator<Item = &'a NodeImage>,
+    ) -> Result<Option<Self>, rendy_core::hal::device::OutOfMemory> {
+        use rendy_core::hal::{image, memory, pso::PipelineStage};
+
+        if !uses_pipeline_barriers::<B>(factory.device()) {
+            return Ok(None);
+        }
+
+        let barriers: Vec<_> = images
+            .into_iter()
+            .map(|image| memory::Barrier::Image {
+                // Acquire barrier of the first use transitions image from this layout.
+                states: (image::Access::empty(), image::Layout::Undefined)
+                    ..(
+                        image::Access::MEMORY_READ | image::Access::MEMORY_WRITE,
+                        image
+                            .acquire
+                            .as_ref()
+                            .map_or(image.layout, |acquire| acquire.states.start.1),
+                    ),
+                families: None,
+                target: ctx.get_image(image.id).expect("Image does not exist").raw(),
+                range: image.range.clone(),
+            })
+            .collect();
+
+        if barriers.is_empty() {
+            return Ok(None);
+        }
+
+        let mut pool = factory.create_command_pool(family)?;
+        let initial = pool.allocate_buffers(1).pop().unwrap();
+        let mut recording = initial.begin(MultiShot(SimultaneousUse), ());
+        let mut encoder = recording.encoder();
+        unsafe {
+            encoder.pipeline_barrier(
+                PipelineStage::TOP_OF_PIPE..PipelineStage::ALL_COMMANDS,
+                memory::Dependencies::empty(),
+                barriers,
+            );
+        }
+
+        Ok(Some(InitCommands {
+            pool,
+            commands: recording.finish().submit(),
+            pending: true,
+        }))
+    }
+
+    /// Get commands if they weren't submitted yet.
+    fn take_pending(&mut self) -> Option<&Submit<B, SimultaneousUse>> {
+        if std::mem::replace(&mut self.pending, false) {
+            Some(&self.commands.0)
+        } else {
+            None
+        }
+    }
+
+    /// Dispose of the init commands.
+    ///
+    /// # Safety
+    ///
+    /// Commands must not be in use by the device.
+    unsafe fn dispose(mut self, factory: &Factory<B>) {
+        let (submit, buffer) = self.commands;
+        drop(submit);
+        self.pool.free_buffers(Some(buffer.mark_complete()));
+        factory.destroy_command_pool(self.pool);
+    }
+}
+
+/// Get commands recorded for the `frame`.
+fn frame_commands<S, C>(commands: &[(S, C)], frame: u64) -> Option<&S> {
+    if commands.is_empty() {
+        None
+    } else {
+        Some(&commands[(frame % commands.len() as u64) as usize].0)
+    }
 }
 
 /// Graph that renders whole frame.
@@ -33,9 +324,13 @@ pub struct Graph<B: Backend, T: ?Sized> {
     schedule: chain::Schedule<chain::SyncData<usize, usize>>,
     semaphores: Vec<B::Semaphore>,
     frames: Frames<B>,
-    fences: Vec<Fences<B>>,
     inflight: u32,
     ctx: GraphContext<B>,
+    exported_nodes: Vec<NodeExport>,
+    chains: chain::Chains,
+    images: Vec<(ImageInfo, Option<rendy_core::hal::command::ClearValue>)>,
+    image_sizes: Vec<Option<RelativeSize>>,
+    extent: Option<Extent2D>,
 }
 
 device_owned!(Graph<B, T: ?Sized>);
@@ -49,8 +344,53 @@ pub enum GraphBuildError {
     Image(ImageCreationError),
     /// Failed to create a semaphore.
     Semaphore(rendy_core::hal::device::OutOfMemory),
+    /// Failed to create an event.
+    Event(rendy_core::hal::device::OutOfMemory),
     /// Failed to build a node.
     Node(NodeBuildError),
+    /// Graph description is invalid.
+    Validation(Vec<ValidationError>),
+    /// Nodes can't be scheduled because they depend on each other.
+    DependencyCycles(Vec<NodeCycle>),
+}
+
+/// Nodes that can't be scheduled because they depend on each other.
+/// Besides explicit dependencies, node that reads a resource
+/// (including node that reads and writes it)
+/// depends on the last node added before it that writes the resource,
+/// and node that writes a resource depends on that writer
+/// and on nodes added after it that read the resource,
+/// unless explicit dependencies order them the other way.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NodeCycle {
+    /// Ids and names of the nodes forming the cycle.
+    /// Each node must be executed after the next one and the last one after the first.
+    pub nodes: Vec<(NodeId, String)>,
+
+    /// Buffers that make nodes of the cycle implicitly depend on each other.
+    pub buffers: Vec<BufferId>,
+
+    /// Images that make nodes of the cycle implicitly depend on each other.
+    pub images: Vec<ImageId>,
+}
+
+impl std::fmt::Display for NodeCycle {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let nodes: Vec<_> = self
+            .nodes
+            .iter()
+            .map(|(id, name)| format!("{:?} {}", id, name))
+            .collect();
+        write!(fmt, "Nodes [{}] depend on each other", nodes.join(", "))?;
+        if !self.buffers.is_empty() || !self.images.is_empty() {
+            write!(
+                fmt,
+                " through buffers {:?} and images {:?}",
+                self.buffers, self.images
+            )?;
+        }
+        Ok(())
+    }
 }
 
 impl std::fmt::Display for GraphBuildError {
@@ -71,11 +411,30 @@ impl std::fmt::Display for GraphBuildError {
                 "Failed to build graph because of failure to create a semaphore: {:?}",
                 err
             ),
+            GraphBuildError::Event(err) => write!(
+                fmt,
+                "Failed to build graph because of failure to create an event: {:?}",
+                err
+            ),
             GraphBuildError::Node(err) => write!(
                 fmt,
                 "Failed to build graph because of failure to build a node: {:?}",
                 err
             ),
+            GraphBuildError::Validation(errors) => {
+                write!(fmt, "Failed to build graph because it is invalid:")?;
+                for error in errors {
+                    write!(fmt, "\n  {}", error)?;
+                }
+                Ok(())
+            }
+            GraphBuildError::DependencyCycles(cycles) => {
+                write!(fmt, "Failed to build graph because of dependency cycles:")?;
+                for cycle in cycles {
+                    write!(fmt, "\n  {}", cycle)?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -86,9 +445,106 @@ impl std::error::Error for GraphBuildError {
             GraphBuildError::Buffer(err) => Some(err),
             GraphBuildError::Image(err) => Some(err),
             GraphBuildError::Semaphore(err) => Some(err),
+            GraphBuildError::Event(err) => Some(err),
             GraphBuildError::Node(err) => Some(err),
+            GraphBuildError::Validation(_) => None,
+            GraphBuildError::DependencyCycles(_) => None,
+        }
+    }
+}
+
+/// Pair of graph images that keep content across frames.
+/// Nodes write `current` image and read `previous` image
+/// that contains `current` image content from the previous frame.
+/// Content of `previous` image is undefined for the first frame.
+///
+/// Graph copies `current` image into `previous` one each frame
+/// instead of swapping them, because nodes bind images once when they are built
+/// and may reuse descriptor sets and recorded commands across frames.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct HistoryImage {
+    /// Image written in this frame.
+    pub current: ImageId,
+
+    /// Image written in the previous frame.
+    pub previous: ImageId,
+}
+
+/// Size of the transient image relative to the graph extent.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RelativeSize {
+    /// Fraction of the graph extent width.
+    pub width: f32,
+
+    /// Fraction of the graph extent height.
+    pub height: f32,
+}
+
+impl RelativeSize {
+    /// Size equal to the graph extent.
+    pub const FULL: Self = RelativeSize {
+        width: 1.0,
+        height: 1.0,
+    };
+
+    /// Create relative size with same fraction for both dimensions.
+    pub fn scale(scale: f32) -> Self {
+        RelativeSize {
+            width: scale,
+            height: scale,
         }
     }
+
+    /// Get image kind for the graph extent.
+    /// Dimensions are never less than 1.
+    pub fn kind(
+        &self,
+        extent: Extent2D,
+        layers: rendy_core::hal::image::Layer,
+        samples: rendy_core::hal::image::NumSamples,
+    ) -> rendy_core::hal::image::Kind {
+        let scale = |size: u32, fraction: f32| ((size as f32 * fraction).round() as u32).max(1);
+        rendy_core::hal::image::Kind::D2(
+            scale(extent.width, self.width),
+            scale(extent.height, self.height),
+            layers,
+            samples,
+        )
+    }
+}
+
+/// State of the imported buffer outside of the graph.
+#[derive(Clone, Copy, Debug)]
+pub struct BufferImportState {
+    /// Access performed outside of the graph.
+    pub access: rendy_core::hal::buffer::Access,
+
+    /// Stages at which buffer is accessed outside of the graph.
+    pub stages: rendy_core::hal::pso::PipelineStage,
+}
+
+/// State of the imported image outside of the graph.
+#[derive(Clone, Copy, Debug)]
+pub struct ImageImportState {
+    /// Access performed outside of the graph.
+    pub access: rendy_core::hal::image::Access,
+
+    /// Layout of the image outside of the graph.
+    pub layout: rendy_core::hal::image::Layout,
+
+    /// Stages at which image is accessed outside of the graph.
+    pub stages: rendy_core::hal::pso::PipelineStage,
+}
+
+/// Memory occupied by transient resources of the graph.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TransientMemory {
+    /// Total size of transient resources as if each one had it's own memory.
+    pub unaliased: u64,
+
+    /// Size of memory allocated for transient resources.
+    /// This is the peak amount of memory transient resources occupy at any point of the frame.
+    pub peak: u64,
 }
 
 /// Graphics context contains all transient resources managed by graph.
@@ -101,73 +557,293 @@ pub struct GraphContext<B: Backend> {
             Option<rendy_core::hal::command::ClearValue>,
         )>,
     >,
+    aliased_buffers: Vec<bool>,
+    aliased_images: Vec<bool>,
+    persistent_images: Vec<bool>,
+    attachment_ops: Vec<AttachmentOps>,
+    memory: Vec<MemoryBlock<B>>,
+    transient_memory: TransientMemory,
+    resizable_memory: Vec<MemoryBlock<B>>,
+    resizable_transient_memory: TransientMemory,
+    events: Vec<Vec<Event<B>>>,
     /// Number of potential frames in flight
     pub frames_in_flight: u32,
 }
 
 impl<B: Backend> GraphContext<B> {
-    fn alloc<'a>(
+    fn alloc(
         factory: &Factory<B>,
         chains: &chain::Chains,
-        buffers: impl IntoIterator<Item = &'a BufferInfo>,
-        images: impl IntoIterator<Item = &'a (ImageInfo, Option<rendy_core::hal::command::ClearValue>)>,
+        buffers: &[BufferInfo],
+        images: &[(ImageInfo, Option<rendy_core::hal::command::ClearValue>)],
+        resizable: &[bool],
+        imported_buffers: Vec<Option<Handle<Buffer<B>>>>,
+        imported_images: Vec<Option<Handle<Image<B>>>>,
         frames_in_flight: u32,
     ) -> Result<Self, GraphBuildError> {
         profile_scope!("alloc");
 
-        log::trace!("Allocate buffers");
-        let buffers: Vec<Option<Handle<Buffer<B>>>> = buffers
+        let imported_buffer: Vec<bool> = imported_buffers.iter().map(Option::is_some).collect();
+        let imported_image: Vec<bool> = imported_images.iter().map(Option::is_some).collect();
+
+        let mut ctx = GraphContext {
+            buffers: imported_buffers,
+            images: imported_images
+                .into_iter()
+                .map(|image| image.map(|image| (image, None)))
+                .collect(),
+            aliased_buffers: vec![false; buffers.len()],
+            aliased_images: vec![false; images.len()],
+            persistent_images: imported_image.clone(),
+            attachment_ops: vec![AttachmentOps::default(); images.len()],
+            memory: Vec::new(),
+            transient_memory: TransientMemory::default(),
+            resizable_memory: Vec::new(),
+            resizable_transient_memory: TransientMemory::default(),
+            events: Vec::new(),
+            frames_in_flight,
+        };
+
+        let (memory, transient_memory) = ctx.alloc_transients(
+            factory,
+            chains,
+            buffers
+                .iter()
+                .enumerate()
+                .filter(|&(index, _)| !imported_buffer[index]),
+            images
+                .iter()
+                .enumerate()
+                .filter(|&(index, _)| !resizable[index] && !imported_image[index]),
+        )?;
+        ctx.memory = memory;
+        ctx.transient_memory = transient_memory;
+
+        let (memory, transient_memory) = ctx.alloc_transients(
+            factory,
+            chains,
+            std::iter::empty::<(usize, &BufferInfo)>(),
+            images
+                .iter()
+                .enumerate()
+                .filter(|&(index, _)| resizable[index]),
+        )?;
+        ctx.resizable_memory = memory;
+        ctx.resizable_transient_memory = transient_memory;
+
+        Ok(ctx)
+    }
+
+    /// Recreate images which size depends on graph extent.
+    ///
+    /// # Safety
+    ///
+    /// Resizable images must not be in use by the device.
+    unsafe fn realloc_resizable(
+        &mut self,
+        factory: &Factory<B>,
+        chains: &chain::Chains,
+        images: &[(ImageInfo, Option<rendy_core::hal::command::ClearValue>)],
+        resizable: &[bool],
+    ) -> Result<(), GraphBuildError> {
+        profile_scope!("realloc_resizable");
+
+        for (image, &resizable) in self.images.iter_mut().zip(resizable) {
+            if resizable {
+                *image = None;
+            }
+        }
+        for block in self.resizable_memory.drain(..) {
+            factory.free_memory(block);
+        }
+        self.resizable_transient_memory = TransientMemory::default();
+
+        let (memory, transient_memory) = self.alloc_transients(
+            factory,
+            chains,
+            std::iter::empty::<(usize, &BufferInfo)>(),
+            images
+                .iter()
+                .enumerate()
+                .filter(|&(index, _)| resizable[index]),
+        )?;
+        self.resizable_memory = memory;
+        self.resizable_transient_memory = transient_memory;
+        Ok(())
+    }
+
+    /// Create transient resources, place them into shared memory and bind.
+    /// Resources not referenced by any node are not created.
+    fn alloc_transients<'a>(
+        &mut self,
+        factory: &Factory<B>,
+        chains: &chain::Chains,
+        buffers: impl IntoIterator<Item = (usize, &'a BufferInfo)>,
+        images: impl IntoIterator<
+            Item = (
+                usize,
+                &'a (ImageInfo, Option<rendy_core::hal::command::ClearValue>),
+            ),
+        >,
+    ) -> Result<(Vec<MemoryBlock<B>>, TransientMemory), GraphBuildError> {
+        log::trace!("Create buffers");
+        let buffers: Vec<(usize, Buffer<B>)> = buffers
             .into_iter()
-            .enumerate()
-            .map(|(index, info)| {
-                chains
-                    .buffers
-                    .get(&chain::Id(index))
-                    .map(|buffer| {
-                        factory
-                            .create_buffer(
-                                BufferInfo {
-                                    usage: buffer.usage(),
-                                    ..info.clone()
-                                },
-                                Data,
-                            )
-                            .map(|buffer| Some(buffer.into()))
-                    })
-                    .unwrap_or(Ok(None))
+            .filter_map(|(index, info)| {
+                chains.buffers.get(&chain::Id(index)).map(|buffer| {
+                    factory
+                        .create_unbound_buffer(BufferInfo {
+                            usage: buffer
+                                .iter()
+                                .fold(rendy_core::hal::buffer::Usage::empty(), |usage, chain| {
+                                    usage | chain.usage()
+                                }),
+                            ..info.clone()
+                        })
+                        .map(|buffer| (index, buffer))
+                })
             })
             .collect::<Result<_, _>>()
             .map_err(GraphBuildError::Buffer)?;
 
-        log::trace!("Allocate images");
-        let images: Vec<Option<(Handle<Image<B>>, _)>> = images
+        log::trace!("Create images");
+        let images: Vec<(usize, Image<B>, _)> = images
             .into_iter()
-            .enumerate()
-            .map(|(index, (info, clear))| {
-                chains
-                    .images
-                    .get(&chain::Id(index))
-                    .map(|image| {
-                        factory
-                            .create_image(
-                                ImageInfo {
-                                    usage: image.usage(),
-                                    ..info.clone()
-                                },
-                                Data,
-                            )
-                            .map(|image| Some((image.into(), *clear)))
-                    })
-                    .unwrap_or(Ok(None))
+            .filter_map(|(index, (info, clear))| {
+                chains.images.get(&chain::Id(index)).map(|image| {
+                    factory
+                        .create_unbound_image(ImageInfo {
+                            usage: image
+                                .iter()
+                                .fold(rendy_core::hal::image::Usage::empty(), |usage, chain| {
+                                    usage | chain.usage()
+                                }),
+                            ..info.clone()
+                        })
+                        .map(|image| (index, image, *clear))
+                })
             })
             .collect::<Result<_, _>>()
             .map_err(GraphBuildError::Image)?;
 
-        Ok(Self {
-            buffers,
-            images,
-            frames_in_flight,
-        })
+        log::trace!("Place resources");
+        let request =
+            |kind, requirements: rendy_core::hal::memory::Requirements, lifetime| alias::Request {
+                kind,
+                type_mask: requirements.type_mask as u32,
+                size: requirements.size,
+                align: requirements.alignment,
+                lifetime,
+            };
+
+        let requests: Vec<_> = buffers
+            .iter()
+            .map(|(index, buffer)| {
+                request(
+                    alias::Kind::Buffer,
+                    buffer.requirements(factory.device()),
+                    alias::buffer_lifetime(&chains.buffers[&chain::Id(*index)], &chains.schedule),
+                )
+            })
+            .chain(images.iter().map(|(index, image, _)| {
+                request(
+                    alias::Kind::Image,
+                    image.requirements(factory.device()),
+                    alias::lifetime(&chains.images[&chain::Id(*index)], &chains.schedule),
+                )
+            }))
+            .collect();
+
+        let (pools, placements) = alias::place(&requests);
+
+        let transient_memory = TransientMemory {
+            unaliased: requests.iter().map(|request| request.size).sum(),
+            peak: pools.iter().map(|pool| pool.size).sum(),
+        };
+
+        log::trace!("Allocate memory");
+        let mut memory = Vec::with_capacity(pools.len());
+        for pool in &pools {
+            let block = factory
+                .allocate_memory(pool.type_mask, Data, pool.size, pool.align)
+                .map_err(|err| match pool.kind {
+                    alias::Kind::Buffer => {
+                        GraphBuildError::Buffer(BufferCreationError::Allocate(err))
+                    }
+                    alias::Kind::Image => GraphBuildError::Image(ImageCreationError::Allocate(err)),
+                })?;
+            memory.push(block);
+        }
+
+        log::trace!("Bind memory");
+        let mut placements = placements.into_iter();
+        for (index, buffer) in buffers {
+            let placement = placements.next().unwrap();
+            let buffer = unsafe {
+                factory.bind_buffer_memory(buffer, &memory[placement.pool], placement.offset)
+            }
+            .map_err(GraphBuildError::Buffer)?;
+            self.buffers[index] = Some(buffer.into());
+            self.aliased_buffers[index] = pools[placement.pool].members > 1;
+        }
+
+        for (index, image, clear) in images {
+            let placement = placements.next().unwrap();
+            let image = unsafe {
+                factory.bind_image_memory(image, &memory[placement.pool], placement.offset)
+            }
+            .map_err(GraphBuildError::Image)?;
+            self.images[index] = Some((image.into(), clear));
+            self.aliased_images[index] = pools[placement.pool].members > 1;
+        }
+
+        Ok((memory, transient_memory))
+    }
+
+    /// Dispose of the context.
+    ///
+    /// # Safety
+    ///
+    /// Transient resources must not be in use by the device.
+    unsafe fn dispose(self, factory: &Factory<B>) {
+        // Resources bound to freed memory are never used again
+        // and can be destroyed later.
+        drop(self.buffers);
+        drop(self.images);
+        for block in self.memory.into_iter().chain(self.resizable_memory) {
+            factory.free_memory(block);
+        }
+        for event in self.events.into_iter().flatten() {
+            factory.destroy_event(event);
+        }
+    }
+
+    /// Check if transient buffer shares memory with other transient buffers.
+    fn is_buffer_aliased(&self, id: BufferId) -> bool {
+        self.aliased_buffers.get(id.0).cloned().unwrap_or(false)
+    }
+
+    /// Check if image content is preserved between frames.
+    fn is_image_persistent(&self, id: ImageId) -> bool {
+        self.persistent_images.get(id.0).cloned().unwrap_or(false)
+    }
+
+    /// Check if transient image shares memory with other transient images.
+    fn is_image_aliased(&self, id: ImageId) -> bool {
+        self.aliased_images.get(id.0).cloned().unwrap_or(false)
+    }
+
+    /// Get memory occupied by transient resources.
+    pub fn transient_memory(&self) -> TransientMemory {
+        TransientMemory {
+            unaliased: self.transient_memory.unaliased + self.resizable_transient_memory.unaliased,
+            peak: self.transient_memory.peak + self.resizable_transient_memory.peak,
+        }
+    }
+
+    /// Get attachment operations configured for the image.
+    pub fn get_attachment_ops(&self, id: ImageId) -> AttachmentOps {
+        self.attachment_ops.get(id.0).cloned().unwrap_or_default()
     }
 
     /// Get reference to transient image by id.
@@ -193,6 +869,16 @@ impl<B: Backend> GraphContext<B> {
     pub fn get_buffer(&self, id: BufferId) -> Option<&Handle<Buffer<B>>> {
         self.buffers.get(id.0).and_then(|x| x.as_ref()).map(|x| &*x)
     }
+
+    /// Get event of split barrier by index from `BufferBarrier::event` or `ImageBarrier::event`.
+    /// Frames in flight use separate events, `frame` is the index of the frame.
+    pub fn get_event(&self, index: usize, frame: u64) -> Option<&Event<B>> {
+        let frames = self.events.len() as u64;
+        if frames == 0 {
+            return None;
+        }
+        self.events[(frame % frames) as usize].get(index)
+    }
 }
 
 impl<B, T> Graph<B, T>
@@ -209,23 +895,29 @@ where
 
         if self.frames.next().index() >= self.inflight as _ {
             let wait = Frame::with_index(self.frames.next().index() - self.inflight as u64);
-            let ref mut self_fences = self.fences;
-            self.frames.wait_complete(wait, factory, |mut fences| {
-                factory.reset_fences(&mut fences).unwrap();
-                self_fences.push(fences);
-            });
+            self.frames.wait_complete(wait, factory);
         }
 
-        let mut fences = self.fences.pop().unwrap_or_else(Fences::<B>::default);
-        let mut fences_used = 0;
+        let mut fences = Fences::<B>::default();
+        let mut batched_fences = smallvec::SmallVec::<[(QueueId, Fence<B>); 8]>::new();
+        let mut batcher = SubmissionBatcher::new();
         let ref semaphores = self.semaphores;
+        let frame = self.frames.next().index();
 
         for submission in self.schedule.ordered() {
             log::trace!("Run node {}", submission.node());
             let sid = submission.id();
             let qid = sid.queue();
 
-            let GraphNode { node, queue } = self
+            let GraphNode {
+                node,
+                queue,
+                enabled,
+                passthrough,
+                events,
+                init,
+                ..
+            } = self
                 .nodes
                 .get_mut(submission.node())
                 .expect("Submission references node with out of bound index");
@@ -235,56 +927,116 @@ where
                 "Node's queue doesn't match schedule"
             );
 
+            let queue_id = QueueId {
+                family: FamilyId {
+                    device: self.device,
+                    index: queue.0,
+                },
+                index: queue.1,
+            };
+
             let last_in_queue = sid.index() + 1 == self.schedule.queue(qid).unwrap().len();
-            let fence = if last_in_queue {
-                if fences_used >= fences.len() {
-                    fences.push(factory.create_fence(false).unwrap());
-                }
-                fences_used += 1;
-                Some(&mut fences[fences_used - 1])
+            let mut fence = if last_in_queue {
+                Some(self.frames.fence(factory).unwrap())
             } else {
                 None
             };
 
-            unsafe {
-                node.run(
-                    &self.ctx,
-                    factory,
-                    families.family_by_index_mut(queue.0).queue_mut(queue.1),
-                    aux,
-                    &self.frames,
-                    &submission
-                        .sync()
-                        .wait
-                        .iter()
-                        .map(|wait| {
-                            log::trace!(
-                                "Node {} waits for {}",
-                                submission.node(),
-                                *wait.semaphore()
-                            );
-                            (&semaphores[*wait.semaphore()], wait.stage())
-                        })
-                        .collect::<smallvec::SmallVec<[_; 16]>>(),
-                    &submission
-                        .sync()
-                        .signal
-                        .iter()
-                        .map(|signal| {
-                            log::trace!(
-                                "Node {} signals {}",
-                                submission.node(),
-                                *signal.semaphore()
-                            );
-                            &semaphores[*signal.semaphore()]
-                        })
-                        .collect::<smallvec::SmallVec<[_; 16]>>(),
-                    fence,
-                )
+            let waits = submission
+                .sync()
+                .wait
+                .iter()
+                .map(|wait| {
+                    log::trace!("Node {} waits for {}", submission.node(), *wait.semaphore());
+                    (&semaphores[*wait.semaphore()], wait.stage())
+                })
+                .collect::<smallvec::SmallVec<[_; 16]>>();
+
+            let signals = submission
+                .sync()
+                .signal
+                .iter()
+                .map(|signal| {
+                    log::trace!("Node {} signals {}", submission.node(), *signal.semaphore());
+                    &semaphores[*signal.semaphore()]
+                })
+                .collect::<smallvec::SmallVec<[_; 16]>>();
+
+            if let Some(init) = init.as_mut().and_then(InitCommands::take_pending) {
+                unsafe {
+                    batcher.add(queue_id, Submission::new().submits(Some(init)));
+                }
             }
+
+            if let Some(wait) = events.as_ref().and_then(|events| events.wait(frame)) {
+                unsafe {
+                    batcher.add(queue_id, Submission::new().submits(Some(wait)));
+                }
+            }
+
+            let batched = if *enabled {
+                unsafe {
+                    node.run_batched(
+                        &self.ctx,
+                        factory,
+                        queue_id,
+                        aux,
+                        &self.frames,
+                        &waits,
+                        &signals,
+                        &mut batcher,
+                    )
+                }
+            } else {
+                log::trace!("Node {} is disabled", submission.node());
+                let passthrough = passthrough
+                    .as_ref()
+                    .expect("Only optional nodes can be disabled");
+                unsafe {
+                    batcher.add(
+                        queue_id,
+                        Submission::new()
+                            .submits(passthrough.commands.as_ref().map(|(submit, _)| submit))
+                            .wait(waits.iter().cloned())
+                            .signal(signals.iter().cloned()),
+                    );
+                }
+                true
+            };
+
+            if batched {
+                batched_fences.extend(fence.map(|fence| (queue_id, fence)));
+            } else {
+                // Node submits by itself. Everything batched before must go first.
+                unsafe {
+                    flush_batched(&mut batcher, families, &mut batched_fences, &mut fences);
+                    node.run(
+                        &self.ctx,
+                        factory,
+                        families.family_by_index_mut(queue.0).queue_mut(queue.1),
+                        aux,
+                        &self.frames,
+                        &waits,
+                        &signals,
+                        fence.as_mut(),
+                    );
+                }
+                fences.extend(fence);
+            }
+
+            // Node that sets events is never last in its queue,
+            // the node waiting for them is submitted later.
+            if let Some(set) = events.as_ref().and_then(|events| events.set(frame)) {
+                unsafe {
+                    batcher.add(queue_id, Submission::new().submits(Some(set)));
+                }
+            }
+        }
+
+        unsafe {
+            flush_batched(&mut batcher, families, &mut batched_fences, &mut fences);
         }
 
-        fences.truncate(fences_used);
         self.frames.advance(fences);
     }
 
@@ -300,6 +1052,181 @@ where
         }
     }
 
+    /// Enable or disable optional node.
+    ///
+    /// Disabled node doesn't run. Instead only its barriers are submitted
+    /// so resources stay in valid states for other nodes.
+    /// Content of resources disabled node writes is undefined.
+    ///
+    /// # Panics
+    ///
+    /// If node wasn't marked optional when graph was built.
+    pub fn set_node_enabled(&mut self, node: NodeId, enabled: bool) {
+        let node = &mut self.nodes[node.0];
+        assert!(
+            enabled || node.passthrough.is_some(),
+            "Only optional nodes can be disabled"
+        );
+        node.enabled = enabled;
+    }
+
+    /// Check if node is enabled.
+    pub fn is_node_enabled(&self, node: NodeId) -> bool {
+        self.nodes[node.0].enabled
+    }
+
+    /// Export nodes, transient resources and scheduled submissions of the graph.
+    pub fn export(&self) -> GraphExport {
+        GraphExport::new(
+            self.exported_nodes.clone(),
+            self.ctx
+                .buffers
+                .iter()
+                .enumerate()
+                .filter_map(|(id, buffer)| {
+                    buffer.as_ref().map(|buffer| BufferExport {
+                        id,
+                        size: buffer.size(),
+                    })
+                })
+                .collect(),
+            self.ctx
+                .images
+                .iter()
+                .enumerate()
+                .filter_map(|(id, image)| {
+                    image.as_ref().map(|(image, _)| ImageExport {
+                        id,
+                        kind: format!("{:?}", image.kind()),
+                        levels: image.levels(),
+                        format: format!("{:?}", image.format()),
+                    })
+                })
+                .collect(),
+            &self.schedule,
+        )
+    }
+
+    /// Get memory occupied by transient resources of the graph.
+    pub fn transient_memory(&self) -> TransientMemory {
+        self.ctx.transient_memory()
+    }
+
+    /// Get current extent of the graph.
+    pub fn extent(&self) -> Option<Extent2D> {
+        self.extent
+    }
+
+    /// Resize the graph without rebuilding it.
+    ///
+    /// Images with size relative to the graph extent are recreated
+    /// and nodes rebuild objects that depend on them or on the surface size.
+    /// Pipelines and descriptor set layouts are kept.
+    ///
+    /// Fails with `NodeBuildError::ResizeUnsupported` if resized image is used by
+    /// a node that doesn't support resizing.
+    /// Graph must be disposed after an error.
+    pub fn resize(
+        &mut self,
+        factory: &mut Factory<B>,
+        families: &mut Families<B>,
+        extent: Extent2D,
+        aux: &T,
+    ) -> Result<(), GraphBuildError> {
+        profile_scope!("resize");
+
+        self.assert_device_owner(factory.device());
+
+        if self.extent == Some(extent) {
+            return Ok(());
+        }
+        self.extent = Some(extent);
+
+        assert!(factory.wait_idle().is_ok());
+
+        let resizable: Vec<bool> = self.image_sizes.iter().map(Option::is_some).collect();
+        if resizable.contains(&true) {
+            let images = resolve_images(&self.images, &self.image_sizes, self.extent);
+            unsafe {
+                // Device is idle.
+                self.ctx
+                    .realloc_resizable(factory, &self.chains, &images, &resizable)?;
+            }
+        }
+
+        for node in &mut self.nodes {
+            let resized = node.images.iter().any(|image| resizable[image.id.0]);
+            unsafe {
+                // Device is idle.
+                node.node.resize(
+                    &self.ctx,
+                    factory,
+                    aux,
+                    extent,
+                    &node.buffers,
+                    &node.images,
+                    resized,
+                )
+            }
+            .map_err(GraphBuildError::Node)?;
+
+            if resized {
+                if let Some(passthrough) = node.passthrough.take() {
+                    unsafe {
+                        // Device is idle.
+                        passthrough.dispose(factory);
+                    }
+                    node.passthrough = Some(
+                        PassThrough::new(
+                            &self.ctx,
+                            factory,
+                            families.family_by_index(node.queue.0),
+                            &node.buffers,
+                            &node.images,
+                        )
+                        .map_err(|err| GraphBuildError::Node(NodeBuildError::OutOfMemory(err)))?,
+                    );
+                }
+                if let Some(events) = node.events.take() {
+                    unsafe {
+                        // Device is idle.
+                        events.dispose(factory);
+                    }
+                    node.events = EventCommands::new(
+                        &self.ctx,
+                        factory,
+                        families.family_by_index(node.queue.0),
+                        &node.buffers,
+                        &node.images,
+                    )
+                    .map_err(|err| GraphBuildError::Node(NodeBuildError::OutOfMemory(err)))?;
+                }
+
+                // Recreated images are `Undefined` again.
+                let pending = node.init.as_ref().map_or(false, |init| init.pending);
+                if let Some(init) = node.init.take() {
+                    unsafe {
+                        // Device is idle.
+                        init.dispose(factory);
+                    }
+                }
+                let init_images = &node.init_images;
+                node.init = InitCommands::new(
+                    &self.ctx,
+                    factory,
+                    families.family_by_index(node.queue.0),
+                    node.images.iter().filter(|image| {
+                        init_images.contains(&image.id) && (pending || resizable[image.id.0])
+                    }),
+                )
+                .map_err(|err| GraphBuildError::Node(NodeBuildError::OutOfMemory(err)))?;
+            }
+        }
+
+        factory.cleanup(families);
+        Ok(())
+    }
+
     /// Dispose of the `Graph`.
     pub fn dispose(self, factory: &mut Factory<B>, data: &T) {
         profile_scope!("dispose");
@@ -313,6 +1240,15 @@ where
             // Device is idle.
             for node in self.nodes {
                 node.node.dispose(factory, data);
+                if let Some(passthrough) = node.passthrough {
+                    passthrough.dispose(factory);
+                }
+                if let Some(events) = node.events {
+                    events.dispose(factory);
+                }
+                if let Some(init) = node.init {
+                    init.dispose(factory);
+                }
             }
 
             for semaphore in self.semaphores {
@@ -321,18 +1257,42 @@ where
         }
         drop(self.device);
         drop(self.schedule);
-        drop(self.fences);
         drop(self.inflight);
-        drop(self.ctx);
+        drop(self.exported_nodes);
+        drop(self.chains);
+        drop(self.images);
+        drop(self.image_sizes);
+        drop(self.extent);
+        unsafe {
+            // Device is idle.
+            self.ctx.dispose(factory);
+        }
     }
 }
 
 /// Build graph from nodes and resource.
+///
+/// Nodes are executed after nodes they explicitly depend on.
+/// Besides that, node that reads a resource (including node that reads and writes it)
+/// is executed after the last node added before it that writes the resource.
+/// Node that writes a resource is executed after the last node added before it
+/// that writes the resource and after nodes added since then that read it,
+/// so it never overwrites content they still need.
+/// Implicit ordering is skipped when explicit dependencies order nodes the other way.
+/// `build` fails with `GraphBuildError::DependencyCycles` if nodes can't be ordered.
 pub struct GraphBuilder<B: Backend, T: ?Sized> {
     nodes: Vec<Box<dyn NodeBuilder<B, T>>>,
     buffers: Vec<BufferInfo>,
     images: Vec<(ImageInfo, Option<rendy_core::hal::command::ClearValue>)>,
+    image_sizes: Vec<Option<RelativeSize>>,
+    attachment_ops: Vec<AttachmentOps>,
+    imported_buffers: Vec<Option<(Handle<Buffer<B>>, Range<BufferImportState>)>>,
+    imported_images: Vec<Option<(Handle<Image<B>>, Range<ImageImportState>)>>,
+    histories: Vec<HistoryImage>,
+    extent: Option<Extent2D>,
     frames_in_flight: u32,
+    scheduler: Box<dyn chain::Scheduler>,
+    split_barriers: bool,
 }
 
 impl<B, T> Default for GraphBuilder<B, T>
@@ -345,7 +1305,15 @@ where
             nodes: Vec::default(),
             buffers: Vec::default(),
             images: Vec::default(),
+            image_sizes: Vec::default(),
+            attachment_ops: Vec::default(),
+            imported_buffers: Vec::default(),
+            imported_images: Vec::default(),
+            histories: Vec::default(),
+            extent: None,
             frames_in_flight: u32::default(),
+            scheduler: Box::new(chain::FitnessScheduler),
+            split_barriers: false,
         }
     }
 }
@@ -360,7 +1328,15 @@ where
             .field("nodes", &self.nodes)
             .field("buffers", &self.buffers)
             .field("images", &self.images)
+            .field("image_sizes", &self.image_sizes)
+            .field("attachment_ops", &self.attachment_ops)
+            .field("imported_buffers", &self.imported_buffers)
+            .field("imported_images", &self.imported_images)
+            .field("histories", &self.histories)
+            .field("extent", &self.extent)
             .field("frames_in_flight", &self.frames_in_flight)
+            .field("scheduler", &self.scheduler)
+            .field("split_barriers", &self.split_barriers)
             .finish()
     }
 }
@@ -376,7 +1352,15 @@ where
             nodes: Vec::new(),
             buffers: Vec::new(),
             images: Vec::new(),
+            image_sizes: Vec::new(),
+            attachment_ops: Vec::new(),
+            imported_buffers: Vec::new(),
+            imported_images: Vec::new(),
+            histories: Vec::new(),
+            extent: None,
             frames_in_flight: 3,
+            scheduler: Box::new(chain::FitnessScheduler),
+            split_barriers: false,
         }
     }
 
@@ -388,6 +1372,23 @@ where
             size,
             usage: rendy_core::hal::buffer::Usage::empty(),
         });
+        self.imported_buffers.push(None);
+        BufferId(self.buffers.len() - 1)
+    }
+
+    /// Import buffer created outside of the graph.
+    /// Graph expects buffer to be in `states.start` state when it runs
+    /// and leaves it in `states.end` state.
+    /// Buffer must be created with all usage flags nodes require.
+    pub fn import_buffer(
+        &mut self,
+        buffer: Handle<Buffer<B>>,
+        states: Range<BufferImportState>,
+    ) -> BufferId {
+        profile_scope!("import_buffer");
+
+        self.buffers.push(buffer.info().clone());
+        self.imported_buffers.push(Some((buffer, states)));
         BufferId(self.buffers.len() - 1)
     }
 
@@ -412,10 +1413,106 @@ where
             },
             clear,
         ));
+        self.image_sizes.push(None);
+        self.attachment_ops.push(AttachmentOps::default());
+        self.imported_images.push(None);
+        ImageId(self.images.len() - 1)
+    }
+
+    /// Import image created outside of the graph.
+    /// Graph expects image to be in `states.start` state when it runs
+    /// and leaves it in `states.end` state.
+    /// Image must be created with all usage flags nodes require.
+    pub fn import_image(
+        &mut self,
+        image: Handle<Image<B>>,
+        states: Range<ImageImportState>,
+    ) -> ImageId {
+        profile_scope!("import_image");
+
+        self.images.push((image.info().clone(), None));
+        self.image_sizes.push(None);
+        self.attachment_ops.push(AttachmentOps::default());
+        self.imported_images.push(Some((image, states)));
         ImageId(self.images.len() - 1)
     }
 
+    /// Create pair of images owned by graph
+    /// which content is carried over to the next frame.
+    /// After all nodes that use the pair are executed
+    /// graph copies `current` image into `previous` image.
+    /// `clear` value is applied to `current` image only.
+    pub fn create_history_image(
+        &mut self,
+        kind: rendy_core::hal::image::Kind,
+        levels: rendy_core::hal::image::Level,
+        format: rendy_core::hal::format::Format,
+        clear: Option<rendy_core::hal::command::ClearValue>,
+    ) -> HistoryImage {
+        profile_scope!("create_history_image");
+
+        let history = HistoryImage {
+            current: self.create_image(kind, levels, format, clear),
+            previous: self.create_image(kind, levels, format, None),
+        };
+        self.histories.push(history);
+        history
+    }
+
+    /// Create new 2D image owned by graph
+    /// which size is relative to the graph extent.
+    /// Such images are recreated when graph is resized.
+    pub fn create_relative_image(
+        &mut self,
+        size: RelativeSize,
+        layers: rendy_core::hal::image::Layer,
+        samples: rendy_core::hal::image::NumSamples,
+        levels: rendy_core::hal::image::Level,
+        format: rendy_core::hal::format::Format,
+        clear: Option<rendy_core::hal::command::ClearValue>,
+    ) -> ImageId {
+        let id = self.create_image(
+            rendy_core::hal::image::Kind::D2(1, 1, layers, samples),
+            levels,
+            format,
+            clear,
+        );
+        self.image_sizes[id.0] = Some(size);
+        id
+    }
+
+    /// Set extent of the graph.
+    /// Sizes of the relative images are resolved against it.
+    pub fn set_extent(&mut self, extent: Extent2D) -> &mut Self {
+        self.extent = Some(extent);
+        self
+    }
+
+    /// Set extent of the graph.
+    /// Sizes of the relative images are resolved against it.
+    pub fn with_extent(mut self, extent: Extent2D) -> Self {
+        self.set_extent(extent);
+        self
+    }
+
+    /// Set load and store operations for the image used as render pass attachment.
+    /// Operations left unspecified are inferred by render passes.
+    /// Render pass can override them with `SubpassBuilder::set_attachment_ops`.
+    pub fn set_attachment_ops(&mut self, image: ImageId, ops: AttachmentOps) -> &mut Self {
+        self.attachment_ops[image.0] = ops;
+        self
+    }
+
+    /// Set load and store operations for the image used as render pass attachment.
+    /// Operations left unspecified are inferred by render passes.
+    /// Render pass can override them with `SubpassBuilder::with_attachment_ops`.
+    pub fn with_attachment_ops(mut self, image: ImageId, ops: AttachmentOps) -> Self {
+        self.set_attachment_ops(image, ops);
+        self
+    }
+
     /// Add node to the graph.
+    /// Order in which nodes are added affects implicit dependencies, see `GraphBuilder`.
     pub fn add_node<N: NodeBuilder<B, T> + 'static>(&mut self, builder: N) -> NodeId {
         self.add_dyn_node(Box::new(builder))
     }
@@ -432,6 +1529,93 @@ where
         self
     }
 
+    /// Set scheduler that picks order of node submissions and queues.
+    /// `FitnessScheduler` is used by default.
+    pub fn set_scheduler(&mut self, scheduler: impl chain::Scheduler + 'static) -> &mut Self {
+        self.scheduler = Box::new(scheduler);
+        self
+    }
+
+    /// Set scheduler that picks order of node submissions and queues.
+    /// `FitnessScheduler` is used by default.
+    pub fn with_scheduler(mut self, scheduler: impl chain::Scheduler + 'static) -> Self {
+        self.set_scheduler(scheduler);
+        self
+    }
+
+    /// Synchronize nodes on the same queue with events
+    /// when other nodes are executed between them.
+    /// Pipeline barriers are used otherwise.
+    /// Disabled by default.
+    pub fn set_split_barriers(&mut self, split_barriers: bool) -> &mut Self {
+        self.split_barriers = split_barriers;
+        self
+    }
+
+    /// Synchronize nodes on the same queue with events
+    /// when other nodes are executed between them.
+    /// Pipeline barriers are used otherwise.
+    /// Disabled by default.
+    pub fn with_split_barriers(mut self, split_barriers: bool) -> Self {
+        self.set_split_barriers(split_barriers);
+        self
+    }
+
+    /// Check graph description for problems
+    /// that would make graph fail to build or run incorrectly.
+    /// Every problem found is reported in `GraphBuildError::Validation`.
+    pub fn validate(&self) -> Result<(), GraphBuildError> {
+        profile_scope!("validate");
+
+        let history_nodes = history_nodes(&self.histories, &self.nodes);
+        let nodes: Vec<_> = self
+            .nodes
+            .iter()
+            .chain(&history_nodes)
+            .map(|builder| validate::NodeInfo {
+                buffers: builder.buffers(),
+                images: builder.images(),
+                dependencies: builder.dependencies(),
+            })
+            .collect();
+
+        let buffers: Vec<_> = self
+            .imported_buffers
+            .iter()
+            .map(|import| validate::BufferInfo {
+                imported: import.as_ref().map(|(buffer, _)| buffer.info().usage),
+            })
+            .collect();
+
+        let images: Vec<_> = self
+            .images
+            .iter()
+            .zip(&self.imported_images)
+            .zip(&self.attachment_ops)
+            .enumerate()
+            .map(
+                |(index, (((info, clear), import), ops))| validate::ImageInfo {
+                    format: info.format,
+                    cleared: clear.is_some(),
+                    persistent: import.is_some()
+                        || self
+                            .histories
+                            .iter()
+                            .any(|history| history.previous.0 == index),
+                    load: ops.load,
+                    imported: import.as_ref().map(|(image, _)| image.info().usage),
+                },
+            )
+            .collect();
+
+        let errors = validate::validate(&nodes, &buffers, &images);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(GraphBuildError::Validation(errors))
+        }
+    }
+
     /// Build `Graph`.
     ///
     /// # Parameters
@@ -451,42 +1635,83 @@ where
     ) -> Result<Graph<B, T>, GraphBuildError> {
         profile_scope!("build");
 
+        self.validate()?;
+
+        let mut nodes = self.nodes;
+        let history_nodes = history_nodes(&self.histories, &nodes);
+        nodes.extend(history_nodes);
+
         log::trace!("Schedule nodes execution");
         let chain_nodes: Vec<chain::Node> = {
             profile_scope!("schedule_nodes");
-            self.nodes
+            nodes
                 .iter()
                 .enumerate()
-                .map(|(i, b)| make_chain_node(&**b, i, factory, families))
+                .map(|(i, b)| {
+                    make_chain_node(&**b, i, &self.buffers, &self.images, factory, families)
+                })
                 .collect()
         };
 
-        let chains = chain::collect(chain_nodes, |id| {
-            families.family_by_index(id.0).as_slice().len()
-        });
+        let exported_nodes = export_nodes(&chain_nodes, nodes.iter().map(|b| &**b));
+
+        let mut chains = chain::collect_with_scheduler(
+            chain_nodes,
+            |id| families.family_by_index(id.0).as_slice().len(),
+            &*self.scheduler,
+        )
+        .map_err(|err| dependency_cycles(err, &exported_nodes))?;
+        set_import_states(&mut chains, &self.imported_buffers, &self.imported_images);
         log::trace!("Scheduled nodes execution {:#?}", chains);
 
+        let images = resolve_images(&self.images, &self.image_sizes, self.extent);
+        let resizable: Vec<bool> = self.image_sizes.iter().map(Option::is_some).collect();
         let mut ctx = GraphContext::alloc(
             factory,
             &chains,
             &self.buffers,
-            &self.images,
+            &images,
+            &resizable,
+            self.imported_buffers
+                .iter()
+                .map(|import| import.as_ref().map(|(buffer, _)| buffer.clone()))
+                .collect(),
+            self.imported_images
+                .iter()
+                .map(|import| import.as_ref().map(|(image, _)| image.clone()))
+                .collect(),
             self.frames_in_flight,
         )?;
 
-        log::trace!("Synchronize");
+        mark_persistent_images(&mut ctx, &chains, &self.histories);
+        ctx.attachment_ops = self.attachment_ops.clone();
 
-        let mut semaphores = 0..;
-        let mut schedule = chain::sync(&chains, || {
-            let id = semaphores.next().unwrap();
-            (id, id)
-        });
-        schedule.build_order();
+        log::trace!("Synchronize");
+        let (schedule, semaphores) = synchronize(&ctx, &chains, self.split_barriers);
         log::trace!("Schedule: {:#?}", schedule);
 
+        let events = schedule
+            .iter()
+            .flat_map(|family| family.iter())
+            .flat_map(|queue| queue.iter())
+            .flat_map(|submission| submission.sync().set_events.iter())
+            .map(|set| set.event() + 1)
+            .max()
+            .unwrap_or(0);
+        log::debug!("Create {} events for each frame in flight", events);
+        ctx.events = (0..self.frames_in_flight)
+            .map(|_| {
+                (0..events)
+                    .map(|_| factory.create_event())
+                    .collect::<Result<_, _>>()
+            })
+            .collect::<Result<_, _>>()
+            .map_err(GraphBuildError::Event)?;
+
         log::trace!("Build nodes");
-        let mut built_nodes: Vec<_> = (0..self.nodes.len()).map(|_| None).collect();
-        let mut node_descs: Vec<_> = self.nodes.into_iter().map(Some).collect();
+        let mut built_nodes: Vec<_> = (0..nodes.len()).map(|_| None).collect();
+        let imported_images = &self.imported_images;
+        let mut node_descs: Vec<_> = nodes.into_iter().map(Some).collect();
 
         {
             profile_scope!("build_nodes");
@@ -499,7 +1724,8 @@ where
                         log::trace!("For submission {:#?}", submission.id());
                         let builder = node_descs[submission.node()].take().unwrap();
                         log::trace!("Build node {:#?}", builder);
-                        let node = build_node(
+                        let optional = builder.optional();
+                        let (node, buffers, images) = build_node(
                             &mut ctx,
                             builder,
                             factory,
@@ -511,36 +1737,332 @@ where
                         )
                         .map_err(GraphBuildError::Node)?;
                         log::debug!("Node built: {:#?}", node);
-                        built_nodes[submission.node()] = Some((node, submission.id().queue()));
+                        let passthrough = if optional {
+                            Some(
+                                PassThrough::new(
+                                    &ctx,
+                                    factory,
+                                    families.family_by_index(family.id().0),
+                                    &buffers,
+                                    &images,
+                                )
+                                .map_err(|err| {
+                                    GraphBuildError::Node(NodeBuildError::OutOfMemory(err))
+                                })?,
+                            )
+                        } else {
+                            None
+                        };
+                        let events = EventCommands::new(
+                            &ctx,
+                            factory,
+                            families.family_by_index(family.id().0),
+                            &buffers,
+                            &images,
+                        )
+                        .map_err(|err| GraphBuildError::Node(NodeBuildError::OutOfMemory(err)))?;
+                        let init_images: Vec<_> = images
+                            .iter()
+                            .map(|image| image.id)
+                            .filter(|&id| {
+                                ctx.is_image_persistent(id)
+                                    && imported_images[id.0].is_none()
+                                    && submission.image_link_index(chain::Id(id.0), 0) == 0
+                            })
+                            .collect();
+                        let init = InitCommands::new(
+                            &ctx,
+                            factory,
+                            families.family_by_index(family.id().0),
+                            images
+                                .iter()
+                                .filter(|image| init_images.contains(&image.id)),
+                        )
+                        .map_err(|err| GraphBuildError::Node(NodeBuildError::OutOfMemory(err)))?;
+                        built_nodes[submission.node()] = Some((
+                            node,
+                            submission.id().queue(),
+                            buffers,
+                            images,
+                            passthrough,
+                            events,
+                            init,
+                            init_images,
+                        ));
                     }
                 }
             }
         }
 
-        log::debug!("Create {} semaphores", semaphores.start);
-        let semaphores = (0..semaphores.start)
+        log::debug!("Create {} semaphores", semaphores);
+        let semaphores = (0..semaphores)
             .map(|_| factory.create_semaphore())
             .collect::<Result<_, _>>()
             .map_err(GraphBuildError::Semaphore)?;
 
+        log::debug!(
+            "Transient resources occupy {} bytes instead of {} bytes",
+            ctx.transient_memory().peak,
+            ctx.transient_memory().unaliased,
+        );
+
         Ok(Graph {
             device: factory.device().id(),
             ctx,
             nodes: built_nodes
                 .into_iter()
                 .map(Option::unwrap)
-                .map(|(node, qid)| GraphNode {
-                    node,
-                    queue: (qid.family().0, qid.index()),
-                })
+                .map(
+                    |(node, qid, buffers, images, passthrough, events, init, init_images)| {
+                        GraphNode {
+                            node,
+                            queue: (qid.family().0, qid.index()),
+                            buffers,
+                            images,
+                            enabled: true,
+                            passthrough,
+                            events,
+                            init,
+                            init_images,
+                        }
+                    },
+                )
                 .collect(),
             schedule,
             semaphores,
             inflight: self.frames_in_flight,
-            frames: Frames::new(),
-            fences: Vec::new(),
+            frames: Frames::new(factory),
+            exported_nodes,
+            chains,
+            images: self.images,
+            image_sizes: self.image_sizes,
+            extent: self.extent,
         })
     }
+
+    /// Export nodes, transient resources and submissions
+    /// the graph would be scheduled to without building it.
+    /// Transient memory is allocated for the duration of the call,
+    /// so that barriers match the ones of the built graph.
+    pub fn export(
+        &self,
+        factory: &mut Factory<B>,
+        families: &Families<B>,
+    ) -> Result<GraphExport, GraphBuildError> {
+        let history_nodes = history_nodes(&self.histories, &self.nodes);
+        let nodes: Vec<&dyn NodeBuilder<B, T>> = self
+            .nodes
+            .iter()
+            .chain(&history_nodes)
+            .map(|b| &**b)
+            .collect();
+
+        let chain_nodes: Vec<chain::Node> = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, b)| make_chain_node(*b, i, &self.buffers, &self.images, factory, families))
+            .collect();
+
+        let exported_nodes = export_nodes(&chain_nodes, nodes);
+
+        let mut chains = chain::collect_with_scheduler(
+            chain_nodes,
+            |id| families.family_by_index(id.0).as_slice().len(),
+            &*self.scheduler,
+        )
+        .map_err(|err| dependency_cycles(err, &exported_nodes))?;
+        set_import_states(&mut chains, &self.imported_buffers, &self.imported_images);
+
+        // Resources are allocated to find out which of them share memory,
+        // as that affects barriers.
+        let images = resolve_images(&self.images, &self.image_sizes, self.extent);
+        let resizable: Vec<bool> = self.image_sizes.iter().map(Option::is_some).collect();
+        let mut ctx = GraphContext::alloc(
+            factory,
+            &chains,
+            &self.buffers,
+            &images,
+            &resizable,
+            self.imported_buffers
+                .iter()
+                .map(|import| import.as_ref().map(|(buffer, _)| buffer.clone()))
+                .collect(),
+            self.imported_images
+                .iter()
+                .map(|import| import.as_ref().map(|(image, _)| image.clone()))
+                .collect(),
+            self.frames_in_flight,
+        )?;
+        mark_persistent_images(&mut ctx, &chains, &self.histories);
+
+        let (schedule, _) = synchronize(&ctx, &chains, self.split_barriers);
+
+        unsafe {
+            // Resources were never used by the device.
+            ctx.dispose(factory);
+        }
+
+        Ok(GraphExport::new(
+            exported_nodes,
+            self.buffers
+                .iter()
+                .enumerate()
+                .map(|(id, info)| BufferExport {
+                    id,
+                    size: info.size,
+                })
+                .collect(),
+            images
+                .iter()
+                .enumerate()
+                .map(|(id, (info, _))| ImageExport {
+                    id,
+                    kind: format!("{:?}", info.kind),
+                    levels: info.levels,
+                    format: format!("{:?}", info.format),
+                })
+                .collect(),
+            &schedule,
+        ))
+    }
+}
+
+/// Mark images which content is used in the next frame.
+fn mark_persistent_images<B: Backend>(
+    ctx: &mut GraphContext<B>,
+    chains: &chain::Chains,
+    histories: &[HistoryImage],
+) {
+    for history in histories {
+        ctx.persistent_images[history.previous.0] = true;
+    }
+    for (id, image_chains) in &chains.images {
+        // Image read before it is written in the frame needs content of the previous frame.
+        if image_chains.iter().any(read_first) {
+            ctx.persistent_images[id.0] = true;
+        }
+    }
+}
+
+/// Synchronize scheduled nodes and optimize barriers.
+/// Returns schedule with built submission order and number of semaphores it uses.
+fn synchronize<B: Backend>(
+    ctx: &GraphContext<B>,
+    chains: &chain::Chains,
+    split_barriers: bool,
+) -> (chain::Schedule<chain::SyncData<usize, usize>>, usize) {
+    let mut semaphores = 0..;
+    let options = chain::SyncOptions { split_barriers };
+    let mut schedule = chain::sync_with_options(chains, options, || {
+        let id = semaphores.next().unwrap();
+        (id, id)
+    });
+    // Content of aliased and transient resources is not carried from the last use to the first.
+    let stats = chain::optimize_barriers_with_discards(
+        &mut schedule,
+        chains,
+        |id| ctx.is_buffer_aliased(BufferId(id.0)),
+        |id| {
+            let id = ImageId(id.0);
+            ctx.is_image_aliased(id) || !ctx.is_image_persistent(id)
+        },
+    );
+    log::debug!(
+        "Barriers: {} total, {} merged, {} dropped",
+        stats.total,
+        stats.merged,
+        stats.dropped
+    );
+    schedule.build_order();
+    (schedule, semaphores.start)
+}
+
+/// Submit batched nodes with fences for queues they were last in.
+///
+/// # Safety
+///
+/// Same as for `SubmissionBatcher::flush`.
+unsafe fn flush_batched<'a, B: Backend>(
+    batcher: &mut SubmissionBatcher<'a, B>,
+    families: &mut Families<B>,
+    batched_fences: &mut smallvec::SmallVec<[(QueueId, Fence<B>); 8]>,
+    fences: &mut Fences<B>,
+) {
+    batcher.flush(
+        families,
+        batched_fences
+            .iter_mut()
+            .map(|(queue, fence)| (*queue, fence)),
+    );
+    fences.extend(batched_fences.drain(..).map(|(_, fence)| fence));
+}
+
+/// Convert cycles found by scheduler into error with node names.
+fn dependency_cycles(error: chain::CollectError, nodes: &[NodeExport]) -> GraphBuildError {
+    match error {
+        chain::CollectError::DependencyCycles(cycles) => GraphBuildError::DependencyCycles(
+            cycles
+                .into_iter()
+                .map(|cycle| NodeCycle {
+                    nodes: cycle
+                        .nodes
+                        .into_iter()
+                        .map(|id| (NodeId(id), nodes[id].debug.clone()))
+                        .collect(),
+                    buffers: cycle.buffers.into_iter().map(|id| BufferId(id.0)).collect(),
+                    images: cycle.images.into_iter().map(|id| ImageId(id.0)).collect(),
+                })
+                .collect(),
+        ),
+    }
+}
+
+fn export_nodes<'a, B, T>(
+    chain_nodes: &[chain::Node],
+    builders: impl IntoIterator<Item = &'a dyn NodeBuilder<B, T>>,
+) -> Vec<NodeExport>
+where
+    B: Backend,
+    T: ?Sized,
+{
+    chain_nodes
+        .iter()
+        .zip(builders)
+        .map(|(node, builder)| NodeExport::new(node, format!("{:?}", builder)))
+        .collect()
+}
+
+/// Create nodes that copy history images.
+/// Each copy depends on all nodes that use either image of the pair.
+fn history_nodes<B, T>(
+    histories: &[HistoryImage],
+    builders: &[Box<dyn NodeBuilder<B, T>>],
+) -> Vec<Box<dyn NodeBuilder<B, T>>>
+where
+    B: Backend,
+    T: ?Sized,
+{
+    histories
+        .iter()
+        .map(|history| {
+            let dependencies = builders
+                .iter()
+                .enumerate()
+                .filter(|(_, builder)| {
+                    builder
+                        .images()
+                        .iter()
+                        .any(|&(id, _)| id == history.current || id == history.previous)
+                })
+                .map(|(index, _)| NodeId(index))
+                .collect();
+            Box::new(HistoryCopyBuilder::new(
+                history.current,
+                history.previous,
+                dependencies,
+            )) as Box<dyn NodeBuilder<B, T>>
+        })
+        .collect()
 }
 
 fn build_node<'a, B: Backend, T: ?Sized>(
@@ -552,39 +2074,86 @@ fn build_node<'a, B: Backend, T: ?Sized>(
     aux: &T,
     chains: &chain::Chains,
     submission: &chain::Submission<chain::SyncData<usize, usize>>,
-) -> Result<Box<dyn DynNode<B, T>>, NodeBuildError> {
-    let mut buffer_ids: Vec<_> = builder.buffers().into_iter().map(|(id, _)| id).collect();
+) -> Result<(Box<dyn DynNode<B, T>>, Vec<NodeBuffer>, Vec<NodeImage>), NodeBuildError> {
+    let accesses = builder.buffers();
+    let mut buffer_ids: Vec<_> = accesses.iter().map(|&(id, _)| id).collect();
     buffer_ids.sort();
     buffer_ids.dedup();
 
-    let buffers: Vec<_> = buffer_ids
-        .into_iter()
-        .map(|id| {
-            let chain_id = chain::Id(id.0);
-            let sync = submission.sync();
-            let buffer = ctx
-                .get_buffer(id)
-                .expect("Buffer referenced from at least one node must be instantiated");
-            NodeBuffer {
+    let sync = submission.sync();
+    let mut buffers = Vec::new();
+    for id in buffer_ids {
+        let chain_id = chain::Id(id.0);
+        let buffer = ctx
+            .get_buffer(id)
+            .expect("Buffer referenced from at least one node must be instantiated");
+        let ranges: Vec<_> = accesses
+            .iter()
+            .filter(|&&(other, _)| other == id)
+            .map(|(_, access)| buffer_range(access, buffer.size()))
+            .collect();
+
+        // Node gets separate `NodeBuffer` for each synchronized region it accesses.
+        for (segment, segment_chain) in chains.buffers[&chain_id].iter().enumerate() {
+            let range = segment_chain.range();
+            if !ranges.iter().any(|accessed| accessed.contains(&range)) {
+                continue;
+            }
+
+            let bytes = range.range();
+            let bytes = bytes.start.min(buffer.size())..bytes.end.min(buffer.size());
+            if bytes.start == bytes.end {
+                continue;
+            }
+
+            let link = submission.buffer_link_index(chain_id, segment);
+            let aliased = link == 0 && ctx.is_buffer_aliased(id);
+            let acquire = range_barrier(&sync.acquire.buffers, chain_id, range).cloned();
+            buffers.push(NodeBuffer {
                 id,
-                range: 0..buffer.size(),
-                acquire: sync.acquire.buffers.get(&chain_id).map(
-                    |chain::Barrier { states, families }| BufferBarrier {
-                        states: states.start.0..states.end.0,
-                        stages: states.start.2..states.end.2,
-                        families: families.clone(),
+                range: bytes,
+                acquire: acquire.map(
+                    |chain::Barrier {
+                         states,
+                         families,
+                         event,
+                         ..
+                     }| {
+                        if aliased {
+                            BufferBarrier {
+                                states: states.start.0
+                                    | rendy_core::hal::buffer::Access::MEMORY_WRITE
+                                    ..states.end.0,
+                                stages: aliasing_stages()..states.end.2,
+                                families,
+                                event,
+                            }
+                        } else {
+                            BufferBarrier {
+                                states: states.start.0..states.end.0,
+                                stages: states.start.2..states.end.2,
+                                families,
+                                event,
+                            }
+                        }
                     },
                 ),
-                release: sync.release.buffers.get(&chain_id).map(
-                    |chain::Barrier { states, families }| BufferBarrier {
+                release: range_barrier(&sync.release.buffers, chain_id, range).map(
+                    |chain::Barrier {
+                         states,
+                         families,
+                         event,
+                         ..
+                     }| BufferBarrier {
                         states: states.start.0..states.end.0,
                         stages: states.start.2..states.end.2,
                         families: families.clone(),
+                        event: *event,
                     },
                 ),
-            }
-        })
-        .collect();
+            });
+        }
+    }
 
     let mut image_ids: Vec<_> = builder.images().into_iter().map(|(id, _)| id).collect();
     image_ids.sort();
@@ -594,11 +2163,17 @@ fn build_node<'a, B: Backend, T: ?Sized>(
         .into_iter()
         .map(|id| {
             let chain_id = chain::Id(id.0);
-            let sync = submission.sync();
-            let link = submission.image_link_index(chain_id);
+            // Nodes of the graph access whole images, so each image is tracked by single chain.
+            let image_chain = &chains.images[&chain_id][0];
+            let link = submission.image_link_index(chain_id, 0);
+            let aliased = link == 0 && ctx.is_image_aliased(id);
+            // Content of transient image is discarded before first use.
+            let discard = link == 0 && !ctx.is_image_persistent(id);
             let (image, clear) = ctx
                 .get_image_with_clear(id)
                 .expect("Image referenced from at least one node must be instantiated");
+            let acquire =
+                range_barrier(&sync.acquire.images, chain_id, image_chain.range()).cloned();
             NodeImage {
                 id,
                 range: rendy_core::hal::image::SubresourceRange {
@@ -606,40 +2181,195 @@ fn build_node<'a, B: Backend, T: ?Sized>(
                     levels: 0..image.levels(),
                     layers: 0..image.layers(),
                 },
-                layout: chains.images[&chain_id].links()[link]
+                layout: image_chain.links()[link]
                     .submission_state(submission.id())
                     .layout,
-                clear: if link == 0 { clear } else { None },
-                acquire: sync.acquire.images.get(&chain_id).map(
-                    |chain::Barrier { states, families }| ImageBarrier {
+                clear: if discard { clear } else { None },
+                preserve: ctx.is_image_persistent(id)
+                    || !last_use(image_chain, link, submission.id()),
+                acquire: acquire.map(
+                    |chain::Barrier {
+                         states,
+                         families,
+                         event,
+                         ..
+                     }| ImageBarrier {
                         states: (
-                            states.start.0,
-                            if link == 0 {
+                            if aliased {
+                                states.start.0 | rendy_core::hal::image::Access::MEMORY_WRITE
+                            } else {
+                                states.start.0
+                            },
+                            if discard {
                                 rendy_core::hal::image::Layout::Undefined
                             } else {
                                 states.start.1
                             },
                         )..(states.end.0, states.end.1),
-                        stages: states.start.2..states.end.2,
-                        families: families.clone(),
+                        stages: if aliased {
+                            aliasing_stages()
+                        } else {
+                            states.start.2
+                        }..states.end.2,
+                        families,
+                        event,
                     },
                 ),
-                release: sync.release.images.get(&chain_id).map(
-                    |chain::Barrier { states, families }| ImageBarrier {
+                release: range_barrier(&sync.release.images, chain_id, image_chain.range()).map(
+                    |chain::Barrier {
+                         states,
+                         families,
+                         event,
+                         ..
+                     }| ImageBarrier {
                         states: (states.start.0, states.start.1)..(states.end.0, states.end.1),
                         stages: states.start.2..states.end.2,
                         families: families.clone(),
+                        event: *event,
                     },
                 ),
             }
         })
         .collect();
-    builder.build(ctx, factory, family, queue, aux, buffers, images)
+    builder
+        .build(
+            ctx,
+            factory,
+            family,
+            queue,
+            aux,
+            buffers.clone(),
+            images.clone(),
+        )
+        .map(|node| (node, buffers, images))
+}
+
+/// Get barrier of the resource range tracked by single chain.
+/// Barriers of adjacent ranges may be merged, so the barrier can cover more than the range.
+fn range_barrier<R: chain::Resource>(
+    barriers: &chain::Barriers<R>,
+    id: chain::Id,
+    range: R::Range,
+) -> Option<&chain::Barrier<R>> {
+    barriers
+        .get(&id)?
+        .iter()
+        .find(|barrier| barrier.range.contains(&range))
+}
+
+/// Check if the first use of the resource range in the frame only reads it.
+fn read_first<R: chain::Resource>(segment_chain: &chain::Chain<R>) -> bool {
+    use chain::AccessFlags;
+
+    segment_chain
+        .links()
+        .first()
+        .map_or(false, |link| !link.access().exclusive())
+}
+
+/// Check if the submission is the last one that uses the resource range in the frame.
+fn last_use<R: chain::Resource>(
+    segment_chain: &chain::Chain<R>,
+    link: usize,
+    sid: chain::SubmissionId,
+) -> bool {
+    let links = segment_chain.links();
+    if link + 1 != links.len() || !links[link].single_queue() {
+        return false;
+    }
+    let queue = links[link].queue(sid.queue());
+    queue.first == queue.last
+}
+
+/// Range of the buffer of `size` bytes tracked by chains for the access.
+fn buffer_range(access: &BufferAccess, size: u64) -> chain::BufferRange {
+    chain::BufferRange::new(access.range.clone().unwrap_or(0..size))
+}
+
+/// Range of all mip levels and array layers of the image.
+fn image_range(info: &ImageInfo) -> chain::ImageRange {
+    chain::ImageRange::new(0..info.levels, 0..info.kind.num_layers())
+}
+
+/// Set states imported resources have outside of the graph.
+fn set_import_states<B: Backend>(
+    chains: &mut chain::Chains,
+    buffers: &[Option<(Handle<Buffer<B>>, Range<BufferImportState>)>],
+    images: &[Option<(Handle<Image<B>>, Range<ImageImportState>)>],
+) {
+    for (index, import) in buffers.iter().enumerate() {
+        if let (Some((buffer, states)), Some(segments)) =
+            (import, chains.buffers.get_mut(&chain::Id(index)))
+        {
+            let state = |state: &BufferImportState| chain::BufferState {
+                access: state.access,
+                stages: state.stages,
+                layout: (),
+                usage: rendy_core::hal::buffer::Usage::empty(),
+                range: chain::BufferRange::new(0..buffer.size()),
+            };
+            for chain in segments {
+                chain.set_external(state(&states.start)..state(&states.end));
+            }
+        }
+    }
+
+    for (index, import) in images.iter().enumerate() {
+        if let (Some((image, states)), Some(segments)) =
+            (import, chains.images.get_mut(&chain::Id(index)))
+        {
+            let state = |state: &ImageImportState| chain::ImageState {
+                access: state.access,
+                stages: state.stages,
+                layout: state.layout,
+                usage: rendy_core::hal::image::Usage::empty(),
+                range: image_range(image.info()),
+            };
+            for chain in segments {
+                chain.set_external(state(&states.start)..state(&states.end));
+            }
+        }
+    }
+}
+
+/// Resolve sizes of the relative images against graph extent.
+fn resolve_images(
+    images: &[(ImageInfo, Option<rendy_core::hal::command::ClearValue>)],
+    sizes: &[Option<RelativeSize>],
+    extent: Option<Extent2D>,
+) -> Vec<(ImageInfo, Option<rendy_core::hal::command::ClearValue>)> {
+    images
+        .iter()
+        .zip(sizes)
+        .map(|(&(info, clear), size)| match (size, info.kind) {
+            (Some(size), rendy_core::hal::image::Kind::D2(_, _, layers, samples)) => {
+                let extent = extent.expect(
+                    "Graph extent must be set with `GraphBuilder::with_extent` to use relative images",
+                );
+                (
+                    ImageInfo {
+                        kind: size.kind(extent, layers, samples),
+                        ..info
+                    },
+                    clear,
+                )
+            }
+            _ => (info, clear),
+        })
+        .collect()
+}
+
+/// Source stages of the first use barrier of aliased resource.
+/// Resources that previously occupied the memory could be used at any stage.
+fn aliasing_stages() -> rendy_core::hal::pso::PipelineStage {
+    rendy_core::hal::pso::PipelineStage::all() - rendy_core::hal::pso::PipelineStage::HOST
 }
 
 fn make_chain_node<B, T>(
     builder: &dyn NodeBuilder<B, T>,
     id: usize,
+    buffers_info: &[BufferInfo],
+    images_info: &[(ImageInfo, Option<rendy_core::hal::command::ClearValue>)],
     factory: &mut Factory<B>,
     families: &Families<B>,
 ) -> chain::Node
@@ -647,39 +2377,114 @@ where
     B: Backend,
     T: ?Sized,
 {
-    let buffers = builder.buffers();
-    let images = builder.images();
+    // Accesses to the same region of the resource are merged.
+    // States keep order in which node lists resources.
+    let mut buffers: Vec<(chain::Id, chain::BufferState)> = Vec::new();
+    for (id, access) in builder.buffers() {
+        let range = buffer_range(&access, buffers_info[id.0].size);
+        let id = chain::Id(id.0);
+        match buffers
+            .iter_mut()
+            .find(|(other, state)| *other == id && state.range == range)
+        {
+            Some((_, state)) => {
+                state.access |= access.access;
+                state.stages |= access.stages;
+                state.usage |= access.usage;
+            }
+            None => buffers.push((
+                id,
+                chain::BufferState {
+                    access: access.access,
+                    stages: access.stages,
+                    layout: (),
+                    usage: access.usage,
+                    range,
+                },
+            )),
+        }
+    }
+
+    // Nodes access all subresources of the image.
+    let mut images: Vec<(chain::Id, chain::ImageState)> = Vec::new();
+    for (id, access) in builder.images() {
+        let range = image_range(&images_info[id.0].0);
+        let id = chain::Id(id.0);
+        match images
+            .iter_mut()
+            .find(|(other, state)| *other == id && state.range == range)
+        {
+            Some((_, state)) => {
+                state.access |= access.access;
+                state.stages |= access.stages;
+                state.usage |= access.usage;
+                state.layout = common_layout(state.layout, access.layout);
+            }
+            None => images.push((
+                id,
+                chain::ImageState {
+                    access: access.access,
+                    stages: access.stages,
+                    layout: access.layout,
+                    usage: access.usage,
+                    range,
+                },
+            )),
+        }
+    }
+
     chain::Node {
         id,
-        family: QueueFamilyId(builder.family(factory, families).unwrap().index),
-        dependencies: builder.dependencies().into_iter().map(|id| id.0).collect(),
-        buffers: buffers
-            .into_iter()
-            .map(|(id, access)| {
-                (
-                    chain::Id(id.0),
-                    chain::BufferState {
-                        access: access.access,
-                        stages: access.stages,
-                        layout: (),
-                        usage: access.usage,
-                    },
-                )
-            })
-            .collect(),
-        images: images
+        families: builder
+            .families(factory, families)
             .into_iter()
-            .map(|(id, access)| {
-                (
-                    chain::Id(id.0),
-                    chain::ImageState {
-                        access: access.access,
-                        stages: access.stages,
-                        layout: access.layout,
-                        usage: access.usage,
-                    },
-                )
-            })
+            .map(|family| QueueFamilyId(family.index))
             .collect(),
+        cost: builder.cost(),
+        dependencies: builder.dependencies().into_iter().map(|id| id.0).collect(),
+        buffers,
+        images,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffer_range_defaults_to_buffer_size() {
+        let access = |range| BufferAccess {
+            access: rendy_core::hal::buffer::Access::SHADER_READ,
+            usage: rendy_core::hal::buffer::Usage::STORAGE,
+            stages: rendy_core::hal::pso::PipelineStage::COMPUTE_SHADER,
+            range,
+        };
+        assert_eq!(buffer_range(&access(None), 256).range(), 0..256);
+        assert_eq!(buffer_range(&access(Some(64..128)), 256).range(), 64..128);
+    }
+
+    #[test]
+    fn image_range_covers_all_subresources() {
+        let info = ImageInfo {
+            kind: rendy_core::hal::image::Kind::D2(16, 16, 6, 1),
+            levels: 5,
+            format: rendy_core::hal::format::Format::Rgba8Unorm,
+            tiling: rendy_core::hal::image::Tiling::Optimal,
+            view_caps: rendy_core::hal::image::ViewCapabilities::empty(),
+            usage: rendy_core::hal::image::Usage::SAMPLED,
+        };
+        let range = image_range(&info);
+        assert_eq!(range.level_range(), 0..5);
+        assert_eq!(range.layer_range(), 0..6);
+    }
+
+    #[test]
+    fn frames_in_flight_use_own_event_commands() {
+        let commands = [(0, ()), (1, ()), (2, ())];
+        let slots: Vec<_> = (0..7)
+            .map(|frame| *frame_commands(&commands, frame).unwrap())
+            .collect();
+        assert_eq!(slots, vec![0, 1, 2, 0, 1, 2, 0]);
+        assert_eq!(frame_commands::<u32, ()>(&[], 5), None);
     }
 }
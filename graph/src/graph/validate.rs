@@ -0,0 +1,812 @@
+//! Validation of the graph description.
+//!
+//! Rules operate on plain description of nodes and resources
+//! and don't require a device.
+
+use {
+    crate::{
+        node::{BufferAccess, ImageAccess},
+        BufferId, ImageId, NodeId,
+    },
+    rendy_core::hal::{buffer, format, image, pass},
+    std::ops::Range,
+};
+
+/// Problem found in the graph description.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ValidationError {
+    /// Node references buffer that doesn't exist.
+    UnknownBuffer {
+        /// Node that references the buffer.
+        node: NodeId,
+        /// Unknown buffer.
+        buffer: BufferId,
+    },
+
+    /// Node references image that doesn't exist.
+    UnknownImage {
+        /// Node that references the image.
+        node: NodeId,
+        /// Unknown image.
+        image: ImageId,
+    },
+
+    /// Node depends on node that doesn't exist.
+    UnknownDependency {
+        /// Dependent node.
+        node: NodeId,
+        /// Unknown dependency.
+        dependency: NodeId,
+    },
+
+    /// Nodes depend on each other.
+    DependencyCycle {
+        /// Nodes that form the cycle.
+        /// Each node depends on the next one and the last one depends on the first.
+        nodes: Vec<NodeId>,
+    },
+
+    /// Node only reads buffer that no node ordered before it writes.
+    /// Node is ordered after nodes it depends on
+    /// and after nodes added before it that write the buffer.
+    UninitializedBuffer {
+        /// Node that reads the buffer.
+        node: NodeId,
+        /// Uninitialized buffer.
+        buffer: BufferId,
+    },
+
+    /// Node reads image that is neither cleared
+    /// nor written by any node ordered before it.
+    UninitializedImage {
+        /// Node that reads the image.
+        node: NodeId,
+        /// Uninitialized image.
+        image: ImageId,
+    },
+
+    /// Node requires usage imported buffer wasn't created with.
+    UnsupportedBufferUsage {
+        /// Node that uses the buffer.
+        node: NodeId,
+        /// Imported buffer.
+        buffer: BufferId,
+        /// Missing usage flags.
+        usage: buffer::Usage,
+    },
+
+    /// Node requires usage imported image wasn't created with.
+    UnsupportedImageUsage {
+        /// Node that uses the image.
+        node: NodeId,
+        /// Imported image.
+        image: ImageId,
+        /// Missing usage flags.
+        usage: image::Usage,
+    },
+
+    /// Node uses image as attachment of kind its format doesn't support.
+    FormatMismatch {
+        /// Node that uses the image.
+        node: NodeId,
+        /// Image used as attachment.
+        image: ImageId,
+        /// Format of the image.
+        format: format::Format,
+        /// Attachment usage.
+        usage: image::Usage,
+    },
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationError::UnknownBuffer { node, buffer } => {
+                write!(fmt, "{:?} references unknown {:?}", node, buffer)
+            }
+            ValidationError::UnknownImage { node, image } => {
+                write!(fmt, "{:?} references unknown {:?}", node, image)
+            }
+            ValidationError::UnknownDependency { node, dependency } => {
+                write!(fmt, "{:?} depends on unknown {:?}", node, dependency)
+            }
+            ValidationError::DependencyCycle { nodes } => {
+                write!(fmt, "Nodes {:?} form dependency cycle", nodes)
+            }
+            ValidationError::UninitializedBuffer { node, buffer } => write!(
+                fmt,
+                "{:?} reads {:?} but no node ordered before it writes it",
+                node, buffer
+            ),
+            ValidationError::UninitializedImage { node, image } => write!(
+                fmt,
+                "{:?} reads {:?} but it is not cleared and no node ordered before it writes it",
+                node, image
+            ),
+            ValidationError::UnsupportedBufferUsage {
+                node,
+                buffer,
+                usage,
+            } => write!(
+                fmt,
+                "{:?} requires usage {:?} imported {:?} wasn't created with",
+                node, usage, buffer
+            ),
+            ValidationError::UnsupportedImageUsage { node, image, usage } => write!(
+                fmt,
+                "{:?} requires usage {:?} imported {:?} wasn't created with",
+                node, usage, image
+            ),
+            ValidationError::FormatMismatch {
+                node,
+                image,
+                format,
+                usage,
+            } => write!(
+                fmt,
+                "{:?} uses {:?} with format {:?} as {:?}",
+                node, image, format, usage
+            ),
+        }
+    }
+}
+
+/// Node description checked by validation.
+#[derive(Clone, Debug, Default)]
+pub(super) struct NodeInfo {
+    pub buffers: Vec<(BufferId, BufferAccess)>,
+    pub images: Vec<(ImageId, ImageAccess)>,
+    pub dependencies: Vec<NodeId>,
+}
+
+/// Buffer description checked by validation.
+#[derive(Clone, Debug, Default)]
+pub(super) struct BufferInfo {
+    /// Usage of the imported buffer.
+    /// `None` for buffers owned by graph.
+    pub imported: Option<buffer::Usage>,
+}
+
+/// Image description checked by validation.
+#[derive(Clone, Debug)]
+pub(super) struct ImageInfo {
+    pub format: format::Format,
+
+    /// Image is cleared before first use.
+    pub cleared: bool,
+
+    /// Image content is preserved between frames.
+    pub persistent: bool,
+
+    /// Load operation configured for the image used as attachment.
+    /// `None` if it is inferred by render passes.
+    pub load: Option<pass::AttachmentLoadOp>,
+
+    /// Usage of the imported image.
+    /// `None` for images owned by graph.
+    pub imported: Option<image::Usage>,
+}
+
+/// Check nodes and resources and collect all problems found.
+pub(super) fn validate(
+    nodes: &[NodeInfo],
+    buffers: &[BufferInfo],
+    images: &[ImageInfo],
+) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+
+    for (index, node) in nodes.iter().enumerate() {
+        for &dependency in &node.dependencies {
+            if dependency.0 >= nodes.len() {
+                errors.push(ValidationError::UnknownDependency {
+                    node: NodeId(index),
+                    dependency,
+                });
+            }
+        }
+    }
+
+    errors.extend(
+        cycles(nodes)
+            .into_iter()
+            .map(|nodes| ValidationError::DependencyCycle { nodes }),
+    );
+
+    let all_ancestors: Vec<Vec<usize>> = (0..nodes.len())
+        .map(|index| ancestors(nodes, index))
+        .collect();
+
+    for (index, node) in nodes.iter().enumerate() {
+        let id = NodeId(index);
+        let initializers = initializers(&all_ancestors, index);
+
+        for &(buffer, ref access) in &node.buffers {
+            let info = match buffers.get(buffer.0) {
+                Some(info) => info,
+                None => {
+                    errors.push(ValidationError::UnknownBuffer { node: id, buffer });
+                    continue;
+                }
+            };
+
+            if let Some(supported) = info.imported {
+                if !supported.contains(access.usage) {
+                    errors.push(ValidationError::UnsupportedBufferUsage {
+                        node: id,
+                        buffer,
+                        usage: access.usage - supported,
+                    });
+                }
+            }
+
+            let initialized = info.imported.is_some()
+                || initializers.iter().any(|&initializer| {
+                    nodes[initializer]
+                        .buffers
+                        .iter()
+                        .any(|&(other, ref other_access)| {
+                            other == buffer
+                                && buffer_writes(other_access.access)
+                                && regions_overlap(&access.range, &other_access.range)
+                        })
+                });
+
+            // Buffers are never discarded, so node that reads and writes the buffer
+            // may carry its content from previous frames.
+            if buffer_reads(access.access) && !buffer_writes(access.access) && !initialized {
+                errors.push(ValidationError::UninitializedBuffer { node: id, buffer });
+            }
+        }
+
+        for &(image, access) in &node.images {
+            let info = match images.get(image.0) {
+                Some(info) => info,
+                None => {
+                    errors.push(ValidationError::UnknownImage { node: id, image });
+                    continue;
+                }
+            };
+
+            if let Some(supported) = info.imported {
+                if !supported.contains(access.usage) {
+                    errors.push(ValidationError::UnsupportedImageUsage {
+                        node: id,
+                        image,
+                        usage: access.usage - supported,
+                    });
+                }
+            }
+
+            let aspects = info.format.surface_desc().aspects;
+            let mismatch = (access.usage.contains(image::Usage::COLOR_ATTACHMENT)
+                && !aspects.contains(format::Aspects::COLOR))
+                || (access
+                    .usage
+                    .contains(image::Usage::DEPTH_STENCIL_ATTACHMENT)
+                    && !aspects.intersects(format::Aspects::DEPTH | format::Aspects::STENCIL));
+            if mismatch {
+                errors.push(ValidationError::FormatMismatch {
+                    node: id,
+                    image,
+                    format: info.format,
+                    usage: access.usage
+                        & (image::Usage::COLOR_ATTACHMENT | image::Usage::DEPTH_STENCIL_ATTACHMENT),
+                });
+            }
+
+            let initialized = info.cleared
+                || info.persistent
+                || initializers.iter().any(|&initializer| {
+                    nodes[initializer]
+                        .images
+                        .iter()
+                        .any(|&(other, access)| other == image && image_writes(access.access))
+                });
+
+            let mut reads = access.access;
+            if info
+                .load
+                .map_or(false, |load| load != pass::AttachmentLoadOp::Load)
+            {
+                // Attachment content is not loaded.
+                reads -= image::Access::COLOR_ATTACHMENT_READ
+                    | image::Access::DEPTH_STENCIL_ATTACHMENT_READ;
+            }
+
+            if image_reads(reads) && !initialized {
+                errors.push(ValidationError::UninitializedImage { node: id, image });
+            }
+        }
+    }
+
+    errors
+}
+
+fn buffer_writes(access: buffer::Access) -> bool {
+    access.intersects(
+        buffer::Access::SHADER_WRITE
+            | buffer::Access::TRANSFER_WRITE
+            | buffer::Access::HOST_WRITE
+            | buffer::Access::MEMORY_WRITE,
+    )
+}
+
+/// Check if buffer regions have common bytes. `None` is the whole buffer.
+fn regions_overlap(left: &Option<Range<u64>>, right: &Option<Range<u64>>) -> bool {
+    match (left, right) {
+        (Some(left), Some(right)) => left.start < right.end && right.start < left.end,
+        _ => true,
+    }
+}
+
+pub(super) fn buffer_reads(access: buffer::Access) -> bool {
+    access.intersects(
+        buffer::Access::INDIRECT_COMMAND_READ
+            | buffer::Access::INDEX_BUFFER_READ
+            | buffer::Access::VERTEX_BUFFER_READ
+            | buffer::Access::UNIFORM_READ
+            | buffer::Access::SHADER_READ
+            | buffer::Access::TRANSFER_READ
+            | buffer::Access::HOST_READ
+            | buffer::Access::MEMORY_READ,
+    )
+}
+
+fn image_writes(access: image::Access) -> bool {
+    access.intersects(
+        image::Access::SHADER_WRITE
+            | image::Access::COLOR_ATTACHMENT_WRITE
+            | image::Access::DEPTH_STENCIL_ATTACHMENT_WRITE
+            | image::Access::TRANSFER_WRITE
+            | image::Access::HOST_WRITE
+            | image::Access::MEMORY_WRITE,
+    )
+}
+
+fn image_reads(access: image::Access) -> bool {
+    access.intersects(
+        image::Access::INPUT_ATTACHMENT_READ
+            | image::Access::SHADER_READ
+            | image::Access::COLOR_ATTACHMENT_READ
+            | image::Access::DEPTH_STENCIL_ATTACHMENT_READ
+            | image::Access::TRANSFER_READ
+            | image::Access::HOST_READ
+            | image::Access::MEMORY_READ,
+    )
+}
+
+/// Collect all nodes the node depends on directly or indirectly.
+/// Unknown dependencies are ignored.
+fn ancestors(nodes: &[NodeInfo], node: usize) -> Vec<usize> {
+    let mut visited = vec![false; nodes.len()];
+    let mut stack = vec![node];
+    let mut ancestors = Vec::new();
+
+    while let Some(node) = stack.pop() {
+        for dependency in &nodes[node].dependencies {
+            if dependency.0 < nodes.len() && !visited[dependency.0] {
+                visited[dependency.0] = true;
+                ancestors.push(dependency.0);
+                stack.push(dependency.0);
+            }
+        }
+    }
+
+    ancestors
+}
+
+/// Collect nodes executed before the node that may initialize resources it reads.
+/// Those are nodes the node depends on and nodes added before it,
+/// as the latter are implicitly ordered before the node when they write resources it reads,
+/// unless they depend on the node.
+fn initializers(all_ancestors: &[Vec<usize>], node: usize) -> Vec<usize> {
+    let ancestors = &all_ancestors[node];
+    let mut initializers = ancestors.clone();
+    initializers.extend((0..node).filter(|&earlier| {
+        !ancestors.contains(&earlier) && !all_ancestors[earlier].contains(&node)
+    }));
+    initializers
+}
+
+/// Find dependency cycles.
+/// Each cycle is reported once.
+fn cycles(nodes: &[NodeInfo]) -> Vec<Vec<NodeId>> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Mark {
+        Unvisited,
+        InProgress,
+        Done,
+    }
+
+    fn visit(
+        nodes: &[NodeInfo],
+        node: usize,
+        marks: &mut [Mark],
+        path: &mut Vec<usize>,
+        cycles: &mut Vec<Vec<NodeId>>,
+    ) {
+        marks[node] = Mark::InProgress;
+        path.push(node);
+
+        for dependency in &nodes[node].dependencies {
+            match marks.get(dependency.0).cloned() {
+                Some(Mark::Unvisited) => visit(nodes, dependency.0, marks, path, cycles),
+                Some(Mark::InProgress) => {
+                    let start = path.iter().position(|&n| n == dependency.0).unwrap();
+                    cycles.push(path[start..].iter().map(|&n| NodeId(n)).collect());
+                }
+                Some(Mark::Done) | None => {}
+            }
+        }
+
+        path.pop();
+        marks[node] = Mark::Done;
+    }
+
+    let mut marks = vec![Mark::Unvisited; nodes.len()];
+    let mut path = Vec::new();
+    let mut cycles = Vec::new();
+
+    for node in 0..nodes.len() {
+        if marks[node] == Mark::Unvisited {
+            visit(nodes, node, &mut marks, &mut path, &mut cycles);
+        }
+    }
+
+    cycles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rendy_core::hal::pso::PipelineStage;
+
+    fn color_write() -> ImageAccess {
+        ImageAccess {
+            access: image::Access::COLOR_ATTACHMENT_WRITE,
+            usage: image::Usage::COLOR_ATTACHMENT,
+            layout: image::Layout::ColorAttachmentOptimal,
+            stages: PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+        }
+    }
+
+    fn color_read_write() -> ImageAccess {
+        ImageAccess {
+            access: image::Access::COLOR_ATTACHMENT_READ | image::Access::COLOR_ATTACHMENT_WRITE,
+            ..color_write()
+        }
+    }
+
+    fn sampled() -> ImageAccess {
+        ImageAccess {
+            access: image::Access::SHADER_READ,
+            usage: image::Usage::SAMPLED,
+            layout: image::Layout::ShaderReadOnlyOptimal,
+            stages: PipelineStage::FRAGMENT_SHADER,
+        }
+    }
+
+    fn storage_write() -> BufferAccess {
+        BufferAccess {
+            access: buffer::Access::SHADER_WRITE,
+            usage: buffer::Usage::STORAGE,
+            stages: PipelineStage::COMPUTE_SHADER,
+            range: None,
+        }
+    }
+
+    fn vertex_read() -> BufferAccess {
+        BufferAccess {
+            access: buffer::Access::VERTEX_BUFFER_READ,
+            usage: buffer::Usage::VERTEX,
+            stages: PipelineStage::VERTEX_INPUT,
+            range: None,
+        }
+    }
+
+    fn color_image() -> ImageInfo {
+        ImageInfo {
+            format: format::Format::Rgba8Unorm,
+            cleared: false,
+            persistent: false,
+            load: None,
+            imported: None,
+        }
+    }
+
+    fn node(
+        buffers: Vec<(BufferId, BufferAccess)>,
+        images: Vec<(ImageId, ImageAccess)>,
+        dependencies: Vec<usize>,
+    ) -> NodeInfo {
+        NodeInfo {
+            buffers,
+            images,
+            dependencies: dependencies.into_iter().map(NodeId).collect(),
+        }
+    }
+
+    #[test]
+    fn valid_graph() {
+        let nodes = [
+            node(vec![(BufferId(0), storage_write())], vec![], vec![]),
+            node(
+                vec![(BufferId(0), vertex_read())],
+                vec![(ImageId(0), color_write())],
+                vec![0],
+            ),
+            node(vec![], vec![(ImageId(0), sampled())], vec![1]),
+        ];
+
+        assert_eq!(
+            validate(&nodes, &[BufferInfo::default()], &[color_image()]),
+            Vec::new()
+        );
+    }
+
+    #[test]
+    fn unknown_resources_and_dependencies() {
+        let nodes = [node(
+            vec![(BufferId(1), vertex_read())],
+            vec![(ImageId(2), sampled())],
+            vec![3],
+        )];
+
+        assert_eq!(
+            validate(&nodes, &[BufferInfo::default()], &[color_image()]),
+            vec![
+                ValidationError::UnknownDependency {
+                    node: NodeId(0),
+                    dependency: NodeId(3),
+                },
+                ValidationError::UnknownBuffer {
+                    node: NodeId(0),
+                    buffer: BufferId(1),
+                },
+                ValidationError::UnknownImage {
+                    node: NodeId(0),
+                    image: ImageId(2),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn dependency_cycle() {
+        let nodes = [
+            node(vec![], vec![], vec![2]),
+            node(vec![], vec![], vec![0]),
+            node(vec![], vec![], vec![1]),
+            node(vec![], vec![], vec![3]),
+        ];
+
+        assert_eq!(
+            validate(&nodes, &[], &[]),
+            vec![
+                ValidationError::DependencyCycle {
+                    nodes: vec![NodeId(0), NodeId(2), NodeId(1)],
+                },
+                ValidationError::DependencyCycle {
+                    nodes: vec![NodeId(3)],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn read_without_writer() {
+        // Writer is added after the reader and reader doesn't depend on it.
+        let nodes = [
+            node(
+                vec![(BufferId(0), vertex_read())],
+                vec![(ImageId(0), sampled())],
+                vec![],
+            ),
+            node(
+                vec![(BufferId(0), storage_write())],
+                vec![(ImageId(0), color_write())],
+                vec![],
+            ),
+        ];
+
+        assert_eq!(
+            validate(&nodes, &[BufferInfo::default()], &[color_image()]),
+            vec![
+                ValidationError::UninitializedBuffer {
+                    node: NodeId(0),
+                    buffer: BufferId(0),
+                },
+                ValidationError::UninitializedImage {
+                    node: NodeId(0),
+                    image: ImageId(0),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn read_after_earlier_writer() {
+        // Reader is implicitly ordered after the writer added before it.
+        let nodes = [
+            node(
+                vec![(BufferId(0), storage_write())],
+                vec![(ImageId(0), color_write())],
+                vec![],
+            ),
+            node(
+                vec![(BufferId(0), vertex_read())],
+                vec![(ImageId(0), sampled())],
+                vec![],
+            ),
+        ];
+
+        assert_eq!(
+            validate(&nodes, &[BufferInfo::default()], &[color_image()]),
+            Vec::new()
+        );
+
+        // Unless the writer is explicitly ordered after the reader.
+        let nodes = [
+            node(vec![(BufferId(0), storage_write())], vec![], vec![1]),
+            node(vec![(BufferId(0), vertex_read())], vec![], vec![]),
+        ];
+
+        assert_eq!(
+            validate(&nodes, &[BufferInfo::default()], &[]),
+            vec![ValidationError::UninitializedBuffer {
+                node: NodeId(1),
+                buffer: BufferId(0),
+            }]
+        );
+    }
+
+    #[test]
+    fn read_region_not_written() {
+        let write = |range| BufferAccess {
+            range: Some(range),
+            ..storage_write()
+        };
+        let read = |range| BufferAccess {
+            range: Some(range),
+            ..vertex_read()
+        };
+        let nodes = [
+            node(vec![(BufferId(0), write(0..64))], vec![], vec![]),
+            node(vec![(BufferId(0), read(32..96))], vec![], vec![0]),
+            node(vec![(BufferId(0), read(64..128))], vec![], vec![0]),
+        ];
+
+        assert_eq!(
+            validate(&nodes, &[BufferInfo::default()], &[]),
+            vec![ValidationError::UninitializedBuffer {
+                node: NodeId(2),
+                buffer: BufferId(0),
+            }]
+        );
+    }
+
+    #[test]
+    fn buffer_read_modify_write() {
+        // Buffer carries its content between frames, e.g. particles state.
+        let read_write = BufferAccess {
+            access: buffer::Access::SHADER_READ | buffer::Access::SHADER_WRITE,
+            ..storage_write()
+        };
+        let nodes = [
+            node(vec![(BufferId(0), read_write)], vec![], vec![]),
+            node(vec![(BufferId(0), vertex_read())], vec![], vec![0]),
+        ];
+
+        assert_eq!(validate(&nodes, &[BufferInfo::default()], &[]), Vec::new());
+    }
+
+    #[test]
+    fn attachment_without_clear() {
+        let nodes = [node(vec![], vec![(ImageId(0), color_read_write())], vec![])];
+
+        assert_eq!(
+            validate(&nodes, &[], &[color_image()]),
+            vec![ValidationError::UninitializedImage {
+                node: NodeId(0),
+                image: ImageId(0),
+            }]
+        );
+
+        let cleared = ImageInfo {
+            cleared: true,
+            ..color_image()
+        };
+        assert_eq!(validate(&nodes, &[], &[cleared]), Vec::new());
+
+        let persistent = ImageInfo {
+            persistent: true,
+            ..color_image()
+        };
+        assert_eq!(validate(&nodes, &[], &[persistent]), Vec::new());
+
+        let dont_care = ImageInfo {
+            load: Some(pass::AttachmentLoadOp::DontCare),
+            ..color_image()
+        };
+        assert_eq!(validate(&nodes, &[], &[dont_care]), Vec::new());
+
+        let load = ImageInfo {
+            load: Some(pass::AttachmentLoadOp::Load),
+            ..color_image()
+        };
+        assert_eq!(
+            validate(&nodes, &[], &[load]),
+            vec![ValidationError::UninitializedImage {
+                node: NodeId(0),
+                image: ImageId(0),
+            }]
+        );
+    }
+
+    #[test]
+    fn imported_usage() {
+        let nodes = [node(
+            vec![(BufferId(0), vertex_read())],
+            vec![(ImageId(0), sampled())],
+            vec![],
+        )];
+
+        let buffers = [BufferInfo {
+            imported: Some(buffer::Usage::INDEX),
+        }];
+        let images = [ImageInfo {
+            persistent: true,
+            imported: Some(image::Usage::SAMPLED | image::Usage::TRANSFER_DST),
+            ..color_image()
+        }];
+
+        assert_eq!(
+            validate(&nodes, &buffers, &images),
+            vec![ValidationError::UnsupportedBufferUsage {
+                node: NodeId(0),
+                buffer: BufferId(0),
+                usage: buffer::Usage::VERTEX,
+            }]
+        );
+    }
+
+    #[test]
+    fn format_mismatch() {
+        let depth = ImageInfo {
+            format: format::Format::D32Sfloat,
+            cleared: true,
+            ..color_image()
+        };
+        let depth_access = ImageAccess {
+            access: image::Access::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            usage: image::Usage::DEPTH_STENCIL_ATTACHMENT,
+            layout: image::Layout::DepthStencilAttachmentOptimal,
+            stages: PipelineStage::LATE_FRAGMENT_TESTS,
+        };
+        let nodes = [node(
+            vec![],
+            vec![(ImageId(0), color_write()), (ImageId(1), depth_access)],
+            vec![],
+        )];
+
+        assert_eq!(
+            validate(&nodes, &[], &[depth.clone(), color_image()]),
+            vec![
+                ValidationError::FormatMismatch {
+                    node: NodeId(0),
+                    image: ImageId(0),
+                    format: format::Format::D32Sfloat,
+                    usage: image::Usage::COLOR_ATTACHMENT,
+                },
+                ValidationError::FormatMismatch {
+                    node: NodeId(0),
+                    image: ImageId(1),
+                    format: format::Format::Rgba8Unorm,
+                    usage: image::Usage::DEPTH_STENCIL_ATTACHMENT,
+                },
+            ]
+        );
+
+        assert_eq!(validate(&nodes, &[], &[color_image(), depth]), Vec::new());
+    }
+}
@@ -31,7 +31,12 @@ pub struct ImageId(usize);
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct NodeId(usize);
 
+mod export;
 mod graph;
 mod node;
 
-pub use self::{graph::*, node::*};
+pub use self::{export::*, graph::*, node::*};
+pub use rendy_chain::{
+    Candidate, CriticalPathScheduler, FitnessScheduler, InsertionOrderScheduler,
+    MinimalBarrierScheduler, Scheduler,
+};
@@ -0,0 +1,492 @@
+//! Defines capture node.
+
+use crate::{
+    command::{
+        CommandBuffer, CommandPool, ExecutableState, Families, Family, FamilyId, Fence, MultiShot,
+        PendingState, Queue, QueueId, SimultaneousUse, Submission, SubmissionBatcher, Submit,
+        Transfer,
+    },
+    factory::Factory,
+    frame::{Frame, Frames},
+    graph::GraphContext,
+    memory::Download,
+    node::{
+        gfx_acquire_barriers, gfx_release_barriers, BufferAccess, DynNode, ImageAccess, NodeBuffer,
+        NodeBuildError, NodeBuilder, NodeImage,
+    },
+    resource::{Buffer, BufferInfo, Escape},
+    BufferId, ImageId, NodeId,
+};
+
+/// Image content read back to the host by `CaptureNode`.
+#[derive(Clone, Debug)]
+pub struct CapturedImage {
+    /// Index of the frame in which image was captured.
+    pub frame: u64,
+
+    /// Pixel data of the first level and layer of the image.
+    /// Rows are tightly packed.
+    pub data: Vec<u8>,
+
+    /// Format of the pixel data.
+    pub format: rendy_core::hal::format::Format,
+
+    /// Extent of the captured image.
+    pub extent: rendy_core::hal::image::Extent,
+}
+
+#[derive(Debug)]
+struct ForFrame<B: rendy_core::hal::Backend> {
+    buffer: Escape<Buffer<B>>,
+    submit: Submit<B, SimultaneousUse>,
+    command_buffer: CommandBuffer<
+        B,
+        rendy_core::hal::queue::QueueType,
+        PendingState<ExecutableState<MultiShot<SimultaneousUse>>>,
+    >,
+    /// Frame which result is not yet delivered.
+    pending: Option<Frame>,
+}
+
+impl<B: rendy_core::hal::Backend> ForFrame<B> {
+    unsafe fn dispose(self, pool: &mut CommandPool<B, rendy_core::hal::queue::QueueType>) {
+        drop(self.submit);
+        pool.free_buffers(Some(self.command_buffer.mark_complete()));
+        drop(self.buffer);
+    }
+}
+
+/// Node that copies graph image into host visible memory each frame
+/// and delivers its content to the callback once the frame is complete.
+///
+/// This is an offscreen counterpart of `PresentNode`
+/// for graphs that don't render to a surface.
+pub struct CaptureNode<B: rendy_core::hal::Backend, F> {
+    per_frame: Vec<ForFrame<B>>,
+    pool: CommandPool<B, rendy_core::hal::queue::QueueType>,
+    input_image: NodeImage,
+    format: rendy_core::hal::format::Format,
+    extent: rendy_core::hal::image::Extent,
+    callback: F,
+}
+
+impl<B, F> std::fmt::Debug for CaptureNode<B, F>
+where
+    B: rendy_core::hal::Backend,
+{
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.debug_struct("CaptureNode")
+            .field("per_frame", &self.per_frame)
+            .field("pool", &self.pool)
+            .field("input_image", &self.input_image)
+            .field("format", &self.format)
+            .field("extent", &self.extent)
+            .finish()
+    }
+}
+
+// Raw pointer destroys Send/Sync autoimpl, but it's always from the same graph.
+// Callback is only called through unique reference so it doesn't have to be `Sync`.
+unsafe impl<B: rendy_core::hal::Backend, F: Send> Sync for CaptureNode<B, F> {}
+unsafe impl<B: rendy_core::hal::Backend, F: Send> Send for CaptureNode<B, F> {}
+
+/// Capture node description.
+pub struct CaptureBuilder<F> {
+    image: ImageId,
+    dependencies: Vec<NodeId>,
+    callback: F,
+}
+
+impl<F> std::fmt::Debug for CaptureBuilder<F> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.debug_struct("CaptureBuilder")
+            .field("image", &self.image)
+            .field("dependencies", &self.dependencies)
+            .finish()
+    }
+}
+
+impl<F> CaptureBuilder<F>
+where
+    F: FnMut(CapturedImage) + Send + 'static,
+{
+    /// Create builder for node that captures `image`
+    /// and passes its content to the `callback`.
+    /// Only first level and layer of the image are captured.
+    /// Block-compressed formats are not supported.
+    pub fn new(image: ImageId, callback: F) -> Self {
+        CaptureBuilder {
+            image,
+            dependencies: Vec::new(),
+            callback,
+        }
+    }
+
+    /// Add dependency.
+    /// Node will be placed after its dependencies.
+    pub fn add_dependency(&mut self, dependency: NodeId) -> &mut Self {
+        self.dependencies.push(dependency);
+        self
+    }
+
+    /// Add dependency.
+    /// Node will be placed after its dependencies.
+    pub fn with_dependency(mut self, dependency: NodeId) -> Self {
+        self.add_dependency(dependency);
+        self
+    }
+}
+
+impl<B, T, F> NodeBuilder<B, T> for CaptureBuilder<F>
+where
+    B: rendy_core::hal::Backend,
+    T: ?Sized,
+    F: FnMut(CapturedImage) + Send + 'static,
+{
+    fn family(&self, _factory: &mut Factory<B>, families: &Families<B>) -> Option<FamilyId> {
+        families.with_capability::<Transfer>()
+    }
+
+    fn buffers(&self) -> Vec<(BufferId, BufferAccess)> {
+        Vec::new()
+    }
+
+    fn images(&self) -> Vec<(ImageId, ImageAccess)> {
+        vec![(
+            self.image,
+            ImageAccess {
+                access: rendy_core::hal::image::Access::TRANSFER_READ,
+                layout: rendy_core::hal::image::Layout::TransferSrcOptimal,
+                usage: rendy_core::hal::image::Usage::TRANSFER_SRC,
+                stages: rendy_core::hal::pso::PipelineStage::TRANSFER,
+            },
+        )]
+    }
+
+    fn dependencies(&self) -> Vec<NodeId> {
+        self.dependencies.clone()
+    }
+
+    fn build<'a>(
+        self: Box<Self>,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        family: &mut Family<B>,
+        _queue: usize,
+        _aux: &T,
+        buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
+    ) -> Result<Box<dyn DynNode<B, T>>, NodeBuildError> {
+        assert_eq!(buffers.len(), 0);
+        assert_eq!(images.len(), 1);
+
+        let input_image = images.into_iter().next().unwrap();
+        let image = ctx
+            .get_image(input_image.id)
+            .expect("Context must contain node's image");
+
+        assert_eq!(
+            image.format().surface_desc().dim,
+            (1, 1),
+            "Capturing block-compressed images is not supported"
+        );
+
+        let pool = factory
+            .create_command_pool(family)
+            .map_err(NodeBuildError::OutOfMemory)?;
+
+        let mut node = CaptureNode {
+            per_frame: Vec::new(),
+            pool,
+            format: image.format(),
+            extent: image.kind().extent(),
+            input_image,
+            callback: self.callback,
+        };
+
+        node.per_frame = node.create_per_frame_data(ctx, factory)?;
+        Ok(Box::new(node))
+    }
+}
+
+impl<B, F> CaptureNode<B, F>
+where
+    B: rendy_core::hal::Backend,
+    F: FnMut(CapturedImage),
+{
+    /// Size of the captured data in bytes.
+    fn size(&self) -> u64 {
+        self.extent.width as u64
+            * self.extent.height as u64
+            * (self.format.surface_desc().bits / 8) as u64
+    }
+
+    /// Create readback buffer and record copy commands for each frame in flight.
+    fn create_per_frame_data(
+        &mut self,
+        ctx: &GraphContext<B>,
+        factory: &Factory<B>,
+    ) -> Result<Vec<ForFrame<B>>, NodeBuildError> {
+        let image = ctx
+            .get_image(self.input_image.id)
+            .expect("Context must contain node's image");
+
+        let aspects = self.input_image.range.aspects;
+        let aspects = if aspects.contains(rendy_core::hal::format::Aspects::COLOR) {
+            rendy_core::hal::format::Aspects::COLOR
+        } else if aspects.contains(rendy_core::hal::format::Aspects::DEPTH) {
+            rendy_core::hal::format::Aspects::DEPTH
+        } else {
+            rendy_core::hal::format::Aspects::STENCIL
+        };
+        let layer = self.input_image.range.layers.start;
+        let size = self.size();
+
+        (0..ctx.frames_in_flight)
+            .map(|_| {
+                let buffer = factory
+                    .create_buffer(
+                        BufferInfo {
+                            size,
+                            usage: rendy_core::hal::buffer::Usage::TRANSFER_DST,
+                        },
+                        Download,
+                    )
+                    .map_err(NodeBuildError::Buffer)?;
+
+                let initial = self.pool.allocate_buffers(1).pop().unwrap();
+                let mut recording = initial.begin(MultiShot(SimultaneousUse), ());
+                let mut encoder = recording.encoder();
+
+                let (mut stages, barriers) =
+                    gfx_acquire_barriers(ctx, None, Some(&self.input_image));
+                stages.start |= rendy_core::hal::pso::PipelineStage::TRANSFER;
+                stages.end |= rendy_core::hal::pso::PipelineStage::TRANSFER;
+                log::trace!("Acquire {:?} : {:#?}", stages, barriers);
+                unsafe {
+                    encoder.pipeline_barrier(
+                        stages,
+                        rendy_core::hal::memory::Dependencies::empty(),
+                        barriers,
+                    );
+
+                    encoder.copy_image_to_buffer(
+                        image.raw(),
+                        self.input_image.layout,
+                        buffer.raw(),
+                        Some(rendy_core::hal::command::BufferImageCopy {
+                            buffer_offset: 0,
+                            buffer_width: 0,
+                            buffer_height: 0,
+                            image_layers: rendy_core::hal::image::SubresourceLayers {
+                                aspects,
+                                level: 0,
+                                layers: layer..layer + 1,
+                            },
+                            image_offset: rendy_core::hal::image::Offset::ZERO,
+                            image_extent: self.extent,
+                        }),
+                    );
+                }
+
+                let (mut stages, mut barriers) =
+                    gfx_release_barriers(ctx, None, Some(&self.input_image));
+                stages.start |= rendy_core::hal::pso::PipelineStage::TRANSFER;
+                stages.end |= rendy_core::hal::pso::PipelineStage::HOST;
+                barriers.push(rendy_core::hal::memory::Barrier::Buffer {
+                    states: rendy_core::hal::buffer::Access::TRANSFER_WRITE
+                        ..rendy_core::hal::buffer::Access::HOST_READ,
+                    families: None,
+                    target: buffer.raw(),
+                    range: None..None,
+                });
+                log::trace!("Release {:?} : {:#?}", stages, barriers);
+                unsafe {
+                    encoder.pipeline_barrier(
+                        stages,
+                        rendy_core::hal::memory::Dependencies::empty(),
+                        barriers,
+                    );
+                }
+
+                let (submit, command_buffer) = recording.finish().submit();
+
+                Ok(ForFrame {
+                    buffer,
+                    submit,
+                    command_buffer,
+                    pending: None,
+                })
+            })
+            .collect()
+    }
+
+    /// Read captured data of complete frames and pass it to the callback
+    /// in order of frames.
+    ///
+    /// # Safety
+    ///
+    /// Frames for which `complete` returns `true` must be complete.
+    unsafe fn deliver(&mut self, factory: &Factory<B>, complete: impl Fn(Frame) -> bool) {
+        let size = self.size();
+        let mut ready: Vec<_> = self
+            .per_frame
+            .iter()
+            .enumerate()
+            .filter_map(|(index, for_frame)| {
+                for_frame
+                    .pending
+                    .filter(|&frame| complete(frame))
+                    .map(|frame| (frame, index))
+            })
+            .collect();
+        ready.sort();
+
+        for (frame, index) in ready {
+            let for_frame = &mut self.per_frame[index];
+            for_frame.pending = None;
+
+            let data = for_frame
+                .buffer
+                .map(factory.device(), 0..size)
+                .and_then(|mut mapped| {
+                    mapped
+                        .read::<u8>(factory.device(), 0..size)
+                        .map(|data| data.to_vec())
+                });
+
+            match data {
+                Ok(data) => (self.callback)(CapturedImage {
+                    frame: frame.index(),
+                    data,
+                    format: self.format,
+                    extent: self.extent,
+                }),
+                Err(err) => log::error!(
+                    "Failed to read image captured in frame {}: {:?}",
+                    frame.index(),
+                    err
+                ),
+            }
+        }
+    }
+
+    /// Deliver complete captures and get commands that capture image in next frame.
+    ///
+    /// # Safety
+    ///
+    /// Returned commands must be submitted within next frame of `frames`.
+    unsafe fn next_submit(
+        &mut self,
+        factory: &Factory<B>,
+        frames: &Frames<B>,
+    ) -> &Submit<B, SimultaneousUse> {
+        self.deliver(factory, |frame| frames.is_complete(frame));
+
+        let frame = frames.next();
+        let index = (frame.index() % self.per_frame.len() as u64) as usize;
+        let ref mut for_frame = self.per_frame[index];
+
+        // Graph waits for frame that used the same index to complete.
+        debug_assert!(for_frame.pending.is_none());
+        for_frame.pending = Some(frame);
+
+        &for_frame.submit
+    }
+}
+
+impl<B, T, F> DynNode<B, T> for CaptureNode<B, F>
+where
+    B: rendy_core::hal::Backend,
+    T: ?Sized,
+    F: FnMut(CapturedImage) + Send + 'static,
+{
+    unsafe fn run<'a>(
+        &mut self,
+        _ctx: &GraphContext<B>,
+        factory: &Factory<B>,
+        queue: &mut Queue<B>,
+        _aux: &T,
+        frames: &Frames<B>,
+        waits: &[(&'a B::Semaphore, rendy_core::hal::pso::PipelineStage)],
+        signals: &[&'a B::Semaphore],
+        fence: Option<&mut Fence<B>>,
+    ) {
+        queue.submit(
+            Some(
+                Submission::new()
+                    .submits(Some(self.next_submit(factory, frames)))
+                    .wait(waits.iter().cloned())
+                    .signal(signals.iter().cloned()),
+            ),
+            fence,
+        );
+    }
+
+    unsafe fn run_batched<'a>(
+        &mut self,
+        _ctx: &GraphContext<B>,
+        factory: &Factory<B>,
+        queue: QueueId,
+        _aux: &T,
+        frames: &Frames<B>,
+        waits: &[(&'a B::Semaphore, rendy_core::hal::pso::PipelineStage)],
+        signals: &[&'a B::Semaphore],
+        batcher: &mut SubmissionBatcher<'a, B>,
+    ) -> bool {
+        batcher.add(
+            queue,
+            Submission::new()
+                .submits(Some(self.next_submit(factory, frames)))
+                .wait(waits.iter().cloned())
+                .signal(signals.iter().cloned()),
+        );
+        true
+    }
+
+    unsafe fn resize(
+        &mut self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        _aux: &T,
+        _extent: rendy_core::hal::window::Extent2D,
+        _buffers: &[NodeBuffer],
+        images: &[NodeImage],
+        resized: bool,
+    ) -> Result<(), NodeBuildError> {
+        if !resized {
+            return Ok(());
+        }
+
+        // Device is idle.
+        self.deliver(factory, |_| true);
+        for for_frame in self.per_frame.drain(..) {
+            for_frame.dispose(&mut self.pool);
+        }
+
+        self.input_image = images
+            .iter()
+            .find(|image| image.id == self.input_image.id)
+            .expect("Capture node image must be provided")
+            .clone();
+        self.extent = ctx
+            .get_image(self.input_image.id)
+            .expect("Context must contain node's image")
+            .kind()
+            .extent();
+
+        self.per_frame = self.create_per_frame_data(ctx, factory)?;
+        Ok(())
+    }
+
+    unsafe fn dispose(mut self: Box<Self>, factory: &mut Factory<B>, _aux: &T) {
+        // Device is idle.
+        self.deliver(factory, |_| true);
+
+        let mut node = *self;
+        for for_frame in node.per_frame {
+            for_frame.dispose(&mut node.pool);
+        }
+        factory.destroy_command_pool(node.pool);
+    }
+}
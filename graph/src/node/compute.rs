@@ -0,0 +1,423 @@
+//! Simple compute node.
+//!
+//! Builds compute pipeline from shader set and layout,
+//! records pipeline barriers for the node resources
+//! and leaves only preparation and dispatching to the user.
+
+use crate::{
+    command::{
+        Compute, Encoder, Family, IndividualReset, MultiShot, NoSimultaneousUse, PrimaryLevel,
+        QueueId, Submit,
+    },
+    core::{
+        hal::{device::Device as _, Backend},
+        types::Layout,
+    },
+    factory::Factory,
+    frame::{
+        cirque::{CirqueRef, CommandCirque},
+        Frames,
+    },
+    graph::GraphContext,
+    node::{
+        gfx_acquire_barriers, gfx_release_barriers, render::PrepareResult, BufferAccess,
+        DescBuilder, ImageAccess, Node, NodeBuffer, NodeBuildError, NodeDesc, NodeImage,
+        NodeSubmittable,
+    },
+    resource::{DescriptorSetLayout, Handle},
+};
+
+/// Descriptor for simple compute pipeline implementation.
+pub trait SimpleComputePipelineDesc<B: Backend, T: ?Sized>: std::fmt::Debug {
+    /// Simple compute pipeline implementation
+    type Pipeline: SimpleComputePipeline<B, T>;
+
+    /// Make simple compute node builder.
+    fn builder(self) -> DescBuilder<B, T, SimpleComputeNodeDesc<Self>>
+    where
+        Self: Sized,
+    {
+        SimpleComputeNodeDesc { inner: self }.builder()
+    }
+
+    /// Get set or buffer resources the node uses.
+    fn buffers(&self) -> Vec<BufferAccess> {
+        Vec::new()
+    }
+
+    /// Get set or image resources the node uses.
+    fn images(&self) -> Vec<ImageAccess> {
+        Vec::new()
+    }
+
+    /// Layout for compute pipeline.
+    /// Must match descriptor sets and push constants of the compute shader.
+    ///
+    /// Shader set returned by `load_shader_set` can't be reflected,
+    /// so the layout is required here. Use `SpirvReflection::layout`
+    /// from `ShaderSetBuilder::reflect` (requires `spirv-reflection` feature
+    /// of `rendy-shader`) to reflect it from the shaders.
+    fn layout(&self) -> Layout;
+
+    /// Load shader set.
+    /// Shader set must contain compute shader.
+    ///
+    /// # Parameters
+    ///
+    /// `factory`   - factory to create shader modules.
+    ///
+    /// `aux`       - auxiliary data container. May be anything the implementation desires.
+    ///
+    fn load_shader_set(&self, factory: &mut Factory<B>, aux: &T) -> rendy_shader::ShaderSet<B>;
+
+    /// Build pipeline instance.
+    fn build<'a>(
+        self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        queue: QueueId,
+        aux: &T,
+        buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
+        set_layouts: &[Handle<DescriptorSetLayout<B>>],
+    ) -> Result<Self::Pipeline, rendy_core::hal::pso::CreationError>;
+}
+
+/// Simple compute pipeline.
+pub trait SimpleComputePipeline<B: Backend, T: ?Sized>:
+    std::fmt::Debug + Sized + Send + Sync + 'static
+{
+    /// This pipeline descriptor.
+    type Desc: SimpleComputePipelineDesc<B, T, Pipeline = Self>;
+
+    /// Make simple compute node builder.
+    fn builder() -> DescBuilder<B, T, SimpleComputeNodeDesc<Self::Desc>>
+    where
+        Self::Desc: Default,
+    {
+        Self::Desc::default().builder()
+    }
+
+    /// Prepare to record dispatch commands.
+    ///
+    /// Should return true if commands must be re-recorded.
+    fn prepare(
+        &mut self,
+        _factory: &Factory<B>,
+        _queue: QueueId,
+        _set_layouts: &[Handle<DescriptorSetLayout<B>>],
+        _index: usize,
+        _aux: &T,
+    ) -> PrepareResult {
+        PrepareResult::DrawRecord
+    }
+
+    /// Record dispatch commands to the command buffer provided.
+    /// Pipeline is already bound and barriers are inserted by the node.
+    fn dispatch(
+        &mut self,
+        layout: &B::PipelineLayout,
+        encoder: Encoder<'_, B, Compute, PrimaryLevel>,
+        index: usize,
+        aux: &T,
+    );
+
+    /// Update pipeline after transient images it uses were reallocated by `Graph::resize`.
+    ///
+    /// Returns `false` if pipeline can't be updated and must be rebuilt with the whole graph.
+    /// Default implementation always returns `false`.
+    fn resize(&mut self, _ctx: &GraphContext<B>, _factory: &Factory<B>, _aux: &T) -> bool {
+        false
+    }
+
+    /// Free all resources and destroy pipeline instance.
+    fn dispose(self, factory: &mut Factory<B>, aux: &T);
+}
+
+/// Descriptor for simple compute node.
+#[derive(Debug)]
+pub struct SimpleComputeNodeDesc<P: std::fmt::Debug> {
+    inner: P,
+}
+
+/// Node that consist of simple compute pipeline.
+#[derive(Debug)]
+pub struct SimpleComputeNode<B: Backend, P> {
+    set_layouts: Vec<Handle<DescriptorSetLayout<B>>>,
+    pipeline_layout: B::PipelineLayout,
+    compute_pipeline: B::ComputePipeline,
+    pipeline: P,
+    queue: QueueId,
+    buffers: Vec<NodeBuffer>,
+    images: Vec<NodeImage>,
+    command_pool: crate::command::CommandPool<B, Compute, IndividualReset>,
+    command_cirque: CommandCirque<B, Compute>,
+}
+
+impl<B, T, P> NodeDesc<B, T> for SimpleComputeNodeDesc<P>
+where
+    B: Backend,
+    T: ?Sized,
+    P: SimpleComputePipelineDesc<B, T> + 'static,
+{
+    type Node = SimpleComputeNode<B, P::Pipeline>;
+
+    fn buffers(&self) -> Vec<BufferAccess> {
+        self.inner.buffers()
+    }
+
+    fn images(&self) -> Vec<ImageAccess> {
+        self.inner.images()
+    }
+
+    fn build<'a>(
+        self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        family: &mut Family<B>,
+        queue: usize,
+        aux: &T,
+        buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
+    ) -> Result<Self::Node, NodeBuildError> {
+        log::trace!("Load shader sets for");
+
+        let mut shader_set = self.inner.load_shader_set(factory, aux);
+
+        let layout = self.inner.layout();
+
+        let set_layouts = layout
+            .sets
+            .into_iter()
+            .map(|set| {
+                factory
+                    .create_descriptor_set_layout(set.bindings)
+                    .map(Handle::from)
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| {
+                shader_set.dispose(factory);
+                NodeBuildError::OutOfMemory(e)
+            })?;
+
+        let pipeline_layout = unsafe {
+            factory
+                .device()
+                .create_pipeline_layout(set_layouts.iter().map(|l| l.raw()), layout.push_constants)
+        }
+        .map_err(|e| {
+            shader_set.dispose(factory);
+            NodeBuildError::OutOfMemory(e)
+        })?;
+
+        let shader = match shader_set.raw_compute() {
+            Err(e) => {
+                shader_set.dispose(factory);
+                log::warn!("Shader error {:?}", e);
+                return Err(NodeBuildError::Pipeline(
+                    rendy_core::hal::pso::CreationError::Other,
+                ));
+            }
+            Ok(s) => s,
+        };
+
+        let compute_pipeline = unsafe {
+            factory.device().create_compute_pipeline(
+                &rendy_core::hal::pso::ComputePipelineDesc {
+                    shader,
+                    layout: &pipeline_layout,
+                    flags: rendy_core::hal::pso::PipelineCreationFlags::empty(),
+                    parent: rendy_core::hal::pso::BasePipeline::None,
+                },
+                None,
+            )
+        }
+        .map_err(|e| {
+            shader_set.dispose(factory);
+            NodeBuildError::Pipeline(e)
+        })?;
+
+        let queue = QueueId {
+            family: family.id(),
+            index: queue,
+        };
+
+        let pipeline = self
+            .inner
+            .build(
+                ctx,
+                factory,
+                queue,
+                aux,
+                buffers.clone(),
+                images.clone(),
+                &set_layouts,
+            )
+            .map_err(|e| {
+                shader_set.dispose(factory);
+                NodeBuildError::Pipeline(e)
+            })?;
+
+        shader_set.dispose(factory);
+
+        let command_pool = factory
+            .create_command_pool(family)
+            .map_err(NodeBuildError::OutOfMemory)?
+            .with_capability()
+            .expect("Graph must specify family that supports `Compute`");
+
+        Ok(SimpleComputeNode {
+            set_layouts,
+            pipeline_layout,
+            compute_pipeline,
+            pipeline,
+            queue,
+            buffers,
+            images,
+            command_pool,
+            command_cirque: CommandCirque::new(),
+        })
+    }
+}
+
+impl<B, P> SimpleComputeNode<B, P>
+where
+    B: Backend,
+{
+    /// Free all recorded command buffers.
+    unsafe fn free_commands(&mut self) {
+        let pool = &mut self.command_pool;
+        std::mem::replace(&mut self.command_cirque, CommandCirque::new()).dispose(|buffer| {
+            buffer.either_with(
+                &mut *pool,
+                |pool, executable| pool.free_buffers(Some(executable)),
+                |pool, pending| {
+                    let executable = pending.mark_complete();
+                    pool.free_buffers(Some(executable))
+                },
+            );
+        });
+    }
+}
+
+impl<'a, B, P> NodeSubmittable<'a, B> for SimpleComputeNode<B, P>
+where
+    B: Backend,
+{
+    type Submittable = Submit<B, NoSimultaneousUse>;
+    type Submittables = Option<Submit<B, NoSimultaneousUse>>;
+}
+
+impl<B, T, P> Node<B, T> for SimpleComputeNode<B, P>
+where
+    B: Backend,
+    T: ?Sized,
+    P: SimpleComputePipeline<B, T>,
+{
+    type Capability = Compute;
+
+    fn run<'a>(
+        &'a mut self,
+        ctx: &GraphContext<B>,
+        factory: &Factory<B>,
+        aux: &T,
+        frames: &'a Frames<B>,
+    ) -> Option<Submit<B, NoSimultaneousUse>> {
+        let SimpleComputeNode {
+            set_layouts,
+            pipeline_layout,
+            compute_pipeline,
+            pipeline,
+            queue,
+            buffers,
+            images,
+            command_pool,
+            command_cirque,
+        } = self;
+
+        let submit = command_cirque.encode(frames, command_pool, |mut cbuf| {
+            let index = cbuf.index();
+
+            if pipeline
+                .prepare(factory, *queue, set_layouts, index, aux)
+                .force_record()
+            {
+                cbuf = CirqueRef::Initial(cbuf.or_reset(|cbuf| cbuf.reset()));
+            }
+
+            cbuf.or_init(|cbuf| {
+                let mut cbuf = cbuf.begin(MultiShot(NoSimultaneousUse), ());
+                let mut encoder = cbuf.encoder();
+
+                let (stages, barriers) = gfx_acquire_barriers(ctx, &*buffers, &*images);
+                if !barriers.is_empty() {
+                    unsafe {
+                        encoder.pipeline_barrier(
+                            stages,
+                            rendy_core::hal::memory::Dependencies::empty(),
+                            barriers,
+                        );
+                    }
+                }
+
+                encoder.bind_compute_pipeline(compute_pipeline);
+                pipeline.dispatch(pipeline_layout, encoder, index, aux);
+
+                let mut encoder = cbuf.encoder();
+                let (stages, barriers) = gfx_release_barriers(ctx, &*buffers, &*images);
+                if !barriers.is_empty() {
+                    unsafe {
+                        encoder.pipeline_barrier(
+                            stages,
+                            rendy_core::hal::memory::Dependencies::empty(),
+                            barriers,
+                        );
+                    }
+                }
+
+                cbuf.finish()
+            })
+        });
+
+        Some(submit)
+    }
+
+    unsafe fn resize(
+        &mut self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        aux: &T,
+        _extent: rendy_core::hal::window::Extent2D,
+        buffers: &[NodeBuffer],
+        images: &[NodeImage],
+        resized: bool,
+    ) -> Result<(), NodeBuildError> {
+        if !resized {
+            return Ok(());
+        }
+
+        // Recorded commands reference old images.
+        self.free_commands();
+        self.buffers = buffers.to_vec();
+        self.images = images.to_vec();
+
+        if self.pipeline.resize(ctx, factory, aux) {
+            Ok(())
+        } else {
+            Err(NodeBuildError::ResizeUnsupported)
+        }
+    }
+
+    unsafe fn dispose(mut self, factory: &mut Factory<B>, aux: &T) {
+        self.free_commands();
+        factory.destroy_command_pool(self.command_pool.with_queue_type());
+        self.pipeline.dispose(factory, aux);
+        factory
+            .device()
+            .destroy_compute_pipeline(self.compute_pipeline);
+        factory
+            .device()
+            .destroy_pipeline_layout(self.pipeline_layout);
+        drop(self.set_layouts);
+    }
+}
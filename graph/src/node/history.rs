@@ -0,0 +1,283 @@
+//! Defines node that copies history images.
+
+use crate::{
+    command::{
+        CommandBuffer, CommandPool, ExecutableState, Families, Family, FamilyId, Fence, MultiShot,
+        PendingState, Queue, QueueId, SimultaneousUse, Submission, SubmissionBatcher, Submit,
+        Transfer,
+    },
+    factory::Factory,
+    frame::Frames,
+    graph::GraphContext,
+    node::{
+        gfx_acquire_barriers, gfx_release_barriers, BufferAccess, DynNode, ImageAccess, NodeBuffer,
+        NodeBuildError, NodeBuilder, NodeImage,
+    },
+    BufferId, ImageId, NodeId,
+};
+
+/// Builder for the node that copies current image of the history into previous one.
+/// Graph adds it after all nodes that use the history images.
+///
+/// Copy costs one transfer per frame, but keeps single image behind each `ImageId`.
+/// Rotating images per frame in flight would require every node
+/// to rebind its descriptors whenever the images swap.
+#[derive(Debug)]
+pub(crate) struct HistoryCopyBuilder {
+    current: ImageId,
+    previous: ImageId,
+    dependencies: Vec<NodeId>,
+}
+
+impl HistoryCopyBuilder {
+    /// Create builder for history images.
+    pub(crate) fn new(current: ImageId, previous: ImageId, dependencies: Vec<NodeId>) -> Self {
+        HistoryCopyBuilder {
+            current,
+            previous,
+            dependencies,
+        }
+    }
+}
+
+impl<B, T> NodeBuilder<B, T> for HistoryCopyBuilder
+where
+    B: rendy_core::hal::Backend,
+    T: ?Sized,
+{
+    fn family(&self, _factory: &mut Factory<B>, families: &Families<B>) -> Option<FamilyId> {
+        families.with_capability::<Transfer>()
+    }
+
+    fn buffers(&self) -> Vec<(BufferId, BufferAccess)> {
+        Vec::new()
+    }
+
+    fn images(&self) -> Vec<(ImageId, ImageAccess)> {
+        vec![
+            (
+                self.current,
+                ImageAccess {
+                    access: rendy_core::hal::image::Access::TRANSFER_READ,
+                    layout: rendy_core::hal::image::Layout::TransferSrcOptimal,
+                    usage: rendy_core::hal::image::Usage::TRANSFER_SRC,
+                    stages: rendy_core::hal::pso::PipelineStage::TRANSFER,
+                },
+            ),
+            (
+                self.previous,
+                ImageAccess {
+                    access: rendy_core::hal::image::Access::TRANSFER_WRITE,
+                    layout: rendy_core::hal::image::Layout::TransferDstOptimal,
+                    usage: rendy_core::hal::image::Usage::TRANSFER_DST,
+                    stages: rendy_core::hal::pso::PipelineStage::TRANSFER,
+                },
+            ),
+        ]
+    }
+
+    fn dependencies(&self) -> Vec<NodeId> {
+        self.dependencies.clone()
+    }
+
+    fn build<'a>(
+        self: Box<Self>,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        family: &mut Family<B>,
+        _queue: usize,
+        _aux: &T,
+        buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
+    ) -> Result<Box<dyn DynNode<B, T>>, NodeBuildError> {
+        assert_eq!(buffers.len(), 0);
+        assert_eq!(images.len(), 2);
+
+        let mut pool = factory
+            .create_command_pool(family)
+            .map_err(NodeBuildError::OutOfMemory)?;
+
+        let find = |id: ImageId| images.iter().find(|image| image.id == id).unwrap().clone();
+        let current = find(self.current);
+        let previous = find(self.previous);
+
+        let (submit, buffer) = record_copy(ctx, &mut pool, &current, &previous);
+
+        Ok(Box::new(HistoryCopyNode {
+            pool,
+            submit,
+            buffer,
+            current,
+            previous,
+        }))
+    }
+}
+
+/// Node that copies current image of the history into previous one.
+#[derive(Debug)]
+pub(crate) struct HistoryCopyNode<B: rendy_core::hal::Backend> {
+    pool: CommandPool<B, rendy_core::hal::queue::QueueType>,
+    submit: Submit<B, SimultaneousUse>,
+    buffer: CommandBuffer<
+        B,
+        rendy_core::hal::queue::QueueType,
+        PendingState<ExecutableState<MultiShot<SimultaneousUse>>>,
+    >,
+    current: NodeImage,
+    previous: NodeImage,
+}
+
+fn record_copy<B: rendy_core::hal::Backend>(
+    ctx: &GraphContext<B>,
+    pool: &mut CommandPool<B, rendy_core::hal::queue::QueueType>,
+    current: &NodeImage,
+    previous: &NodeImage,
+) -> (
+    Submit<B, SimultaneousUse>,
+    CommandBuffer<
+        B,
+        rendy_core::hal::queue::QueueType,
+        PendingState<ExecutableState<MultiShot<SimultaneousUse>>>,
+    >,
+) {
+    let current_image = ctx.get_image(current.id).expect("Image does not exist");
+    let previous_image = ctx.get_image(previous.id).expect("Image does not exist");
+
+    let initial = pool.allocate_buffers(1).pop().unwrap();
+    let mut recording = initial.begin(MultiShot(SimultaneousUse), ());
+    let mut encoder = recording.encoder();
+
+    let images = [current.clone(), previous.clone()];
+
+    unsafe {
+        let (stages, barriers) = gfx_acquire_barriers(ctx, None, &images);
+        if !barriers.is_empty() {
+            log::trace!("Acquire {:?} : {:#?}", stages, barriers);
+            encoder.pipeline_barrier(
+                stages,
+                rendy_core::hal::memory::Dependencies::empty(),
+                barriers,
+            );
+        }
+
+        encoder.copy_image(
+            current_image.raw(),
+            current.layout,
+            previous_image.raw(),
+            previous.layout,
+            (0..current_image.levels()).map(|level| rendy_core::hal::command::ImageCopy {
+                src_subresource: rendy_core::hal::image::SubresourceLayers {
+                    aspects: current.range.aspects,
+                    level,
+                    layers: current.range.layers.clone(),
+                },
+                src_offset: rendy_core::hal::image::Offset::ZERO,
+                dst_subresource: rendy_core::hal::image::SubresourceLayers {
+                    aspects: previous.range.aspects,
+                    level,
+                    layers: previous.range.layers.clone(),
+                },
+                dst_offset: rendy_core::hal::image::Offset::ZERO,
+                extent: current_image.kind().level_extent(level),
+            }),
+        );
+
+        let (stages, barriers) = gfx_release_barriers(ctx, None, &images);
+        if !barriers.is_empty() {
+            log::trace!("Release {:?} : {:#?}", stages, barriers);
+            encoder.pipeline_barrier(
+                stages,
+                rendy_core::hal::memory::Dependencies::empty(),
+                barriers,
+            );
+        }
+    }
+
+    recording.finish().submit()
+}
+
+impl<B, T> DynNode<B, T> for HistoryCopyNode<B>
+where
+    B: rendy_core::hal::Backend,
+    T: ?Sized,
+{
+    unsafe fn run<'a>(
+        &mut self,
+        _ctx: &GraphContext<B>,
+        _factory: &Factory<B>,
+        queue: &mut Queue<B>,
+        _aux: &T,
+        _frames: &Frames<B>,
+        waits: &[(&'a B::Semaphore, rendy_core::hal::pso::PipelineStage)],
+        signals: &[&'a B::Semaphore],
+        fence: Option<&mut Fence<B>>,
+    ) {
+        queue.submit(
+            Some(
+                Submission::new()
+                    .submits(Some(&self.submit))
+                    .wait(waits.iter().cloned())
+                    .signal(signals.iter().cloned()),
+            ),
+            fence,
+        );
+    }
+
+    unsafe fn run_batched<'a>(
+        &mut self,
+        _ctx: &GraphContext<B>,
+        _factory: &Factory<B>,
+        queue: QueueId,
+        _aux: &T,
+        _frames: &Frames<B>,
+        waits: &[(&'a B::Semaphore, rendy_core::hal::pso::PipelineStage)],
+        signals: &[&'a B::Semaphore],
+        batcher: &mut SubmissionBatcher<'a, B>,
+    ) -> bool {
+        batcher.add(
+            queue,
+            Submission::new()
+                .submits(Some(&self.submit))
+                .wait(waits.iter().cloned())
+                .signal(signals.iter().cloned()),
+        );
+        true
+    }
+
+    unsafe fn resize(
+        &mut self,
+        ctx: &GraphContext<B>,
+        _factory: &mut Factory<B>,
+        _aux: &T,
+        _extent: rendy_core::hal::window::Extent2D,
+        _buffers: &[NodeBuffer],
+        images: &[NodeImage],
+        resized: bool,
+    ) -> Result<(), NodeBuildError> {
+        if !resized {
+            return Ok(());
+        }
+
+        let find = |id: ImageId| images.iter().find(|image| image.id == id).unwrap().clone();
+        self.current = find(self.current.id);
+        self.previous = find(self.previous.id);
+
+        let (submit, buffer) = record_copy(ctx, &mut self.pool, &self.current, &self.previous);
+        let old_buffer = std::mem::replace(&mut self.buffer, buffer);
+        drop(std::mem::replace(&mut self.submit, submit));
+        self.pool.free_buffers(Some(old_buffer.mark_complete()));
+        Ok(())
+    }
+
+    unsafe fn dispose(self: Box<Self>, factory: &mut Factory<B>, _aux: &T) {
+        let HistoryCopyNode {
+            mut pool,
+            submit,
+            buffer,
+            ..
+        } = *self;
+        drop(submit);
+        pool.free_buffers(Some(buffer.mark_complete()));
+        factory.destroy_command_pool(pool);
+    }
+}
@@ -1,16 +1,23 @@
 //! Defines node - building block for framegraph.
 //!
 
+pub mod capture;
+pub mod compute;
+pub(crate) mod history;
 pub mod present;
 pub mod render;
 
 use {
     crate::{
-        command::{Capability, Families, Family, FamilyId, Fence, Queue, Submission, Submittable},
+        command::{
+            Capability, Encoder, Families, Family, FamilyId, Fence, Queue, QueueId, Submission,
+            SubmissionBatcher, Submittable,
+        },
         factory::{Factory, UploadError},
         frame::Frames,
         graph::GraphContext,
-        wsi::SwapchainError,
+        resource::BufferCreationError,
+        wsi::{Surface, SwapchainError},
         BufferId, ImageId, NodeId,
     },
     rendy_core::hal::{queue::QueueFamilyId, Backend},
@@ -19,7 +26,7 @@ use {
 /// Buffer access node will perform.
 /// Node must not perform any access to the buffer not specified in `access`.
 /// All access must be between logically first and last `stages`.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 pub struct BufferAccess {
     /// Access flags.
     pub access: rendy_core::hal::buffer::Access,
@@ -30,6 +37,12 @@ pub struct BufferAccess {
 
     /// Pipeline stages at which buffer is accessd.
     pub stages: rendy_core::hal::pso::PipelineStage,
+
+    /// Region of the buffer node accesses.
+    /// `None` if node accesses the whole buffer.
+    /// Accesses to disjoint regions are synchronized independently.
+    /// Regions of the same buffer accessed by one node must not overlap.
+    pub range: Option<std::ops::Range<u64>>,
 }
 
 /// Buffer pipeline barrier.
@@ -43,9 +56,17 @@ pub struct BufferBarrier {
 
     /// Transfer between families.
     pub families: Option<std::ops::Range<QueueFamilyId>>,
+
+    /// Event of split barrier.
+    /// Release barrier with event is performed by setting the event
+    /// and acquire barrier with event by waiting for it.
+    /// Graph sets and waits for events around commands of the node,
+    /// node must not record these barriers.
+    pub event: Option<usize>,
 }
 
 /// Buffer shared between nodes.
+/// Node receives one `NodeBuffer` for each region of the buffer synchronized independently.
 ///
 /// If Node doesn't actually use the buffer it can merge acquire and release barriers into one.
 /// TODO: Make merge function.
@@ -54,7 +75,7 @@ pub struct NodeBuffer {
     /// Id of the buffer.
     pub id: BufferId,
 
-    /// Region of the buffer that is the transient resource.
+    /// Region of the buffer the barriers apply to.
     pub range: std::ops::Range<u64>,
 
     /// Acquire barrier.
@@ -101,6 +122,13 @@ pub struct ImageBarrier {
 
     /// Transfer between families.
     pub families: Option<std::ops::Range<QueueFamilyId>>,
+
+    /// Event of split barrier.
+    /// Release barrier with event is performed by setting the event
+    /// and acquire barrier with event by waiting for it.
+    /// Graph sets and waits for events around commands of the node,
+    /// node must not record these barriers.
+    pub event: Option<usize>,
 }
 
 /// Image shared between nodes.
@@ -118,6 +146,10 @@ pub struct NodeImage {
     /// Specify that node should clear image to this value.
     pub clear: Option<rendy_core::hal::command::ClearValue>,
 
+    /// Image content is used after the node.
+    /// If `false` node may leave content of the image undefined.
+    pub preserve: bool,
+
     /// Acquire barrier.
     /// Node implementation must insert it before first command that uses the image.
     /// Barrier must be inserted even if this node doesn't use the image.
@@ -164,6 +196,31 @@ pub trait Node<B: Backend, T: ?Sized>:
         frames: &'a Frames<B>,
     ) -> <Self as NodeSubmittable<'a, B>>::Submittables;
 
+    /// Rebuild size-dependent objects of the node after `Graph::resize`.
+    /// `resized` is `true` if any image of the node was reallocated with new size.
+    ///
+    /// Default implementation fails if any image of the node was resized.
+    ///
+    /// # Safety
+    ///
+    /// Must be called after waiting for device idle.
+    unsafe fn resize(
+        &mut self,
+        _ctx: &GraphContext<B>,
+        _factory: &mut Factory<B>,
+        _aux: &T,
+        _extent: rendy_core::hal::window::Extent2D,
+        _buffers: &[NodeBuffer],
+        _images: &[NodeImage],
+        resized: bool,
+    ) -> Result<(), NodeBuildError> {
+        if resized {
+            Err(NodeBuildError::ResizeUnsupported)
+        } else {
+            Ok(())
+        }
+    }
+
     /// Dispose of the node.
     ///
     /// # Safety
@@ -232,6 +289,50 @@ pub trait DynNode<B: Backend, T: ?Sized>: std::fmt::Debug + Sync + Send {
         fence: Option<&mut Fence<B>>,
     );
 
+    /// Record commands required by node and add them to the `batcher`
+    /// instead of submitting them immediately.
+    /// Returns `false` if node can't be batched and must be run with `run`.
+    ///
+    /// Default implementation doesn't batch.
+    unsafe fn run_batched<'a>(
+        &mut self,
+        _ctx: &GraphContext<B>,
+        _factory: &Factory<B>,
+        _queue: QueueId,
+        _aux: &T,
+        _frames: &Frames<B>,
+        _waits: &[(&'a B::Semaphore, rendy_core::hal::pso::PipelineStage)],
+        _signals: &[&'a B::Semaphore],
+        _batcher: &mut SubmissionBatcher<'a, B>,
+    ) -> bool {
+        false
+    }
+
+    /// Rebuild size-dependent objects of the node after `Graph::resize`.
+    /// `resized` is `true` if any image of the node was reallocated with new size.
+    ///
+    /// Default implementation fails if any image of the node was resized.
+    ///
+    /// # Safety
+    ///
+    /// Must be called after waiting for device idle.
+    unsafe fn resize(
+        &mut self,
+        _ctx: &GraphContext<B>,
+        _factory: &mut Factory<B>,
+        _aux: &T,
+        _extent: rendy_core::hal::window::Extent2D,
+        _buffers: &[NodeBuffer],
+        _images: &[NodeImage],
+        resized: bool,
+    ) -> Result<(), NodeBuildError> {
+        if resized {
+            Err(NodeBuildError::ResizeUnsupported)
+        } else {
+            Ok(())
+        }
+    }
+
     /// Dispose of the node.
     ///
     /// # Safety
@@ -269,6 +370,50 @@ where
         )
     }
 
+    unsafe fn run_batched<'a>(
+        &mut self,
+        ctx: &GraphContext<B>,
+        factory: &Factory<B>,
+        queue: QueueId,
+        aux: &T,
+        frames: &Frames<B>,
+        waits: &[(&'a B::Semaphore, rendy_core::hal::pso::PipelineStage)],
+        signals: &[&'a B::Semaphore],
+        batcher: &mut SubmissionBatcher<'a, B>,
+    ) -> bool {
+        let submittables = Node::run(&mut self.0, ctx, factory, aux, frames);
+        batcher.add(
+            queue,
+            Submission::new()
+                .submits(submittables)
+                .wait(waits.iter().cloned())
+                .signal(signals.iter().cloned()),
+        );
+        true
+    }
+
+    unsafe fn resize(
+        &mut self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        aux: &T,
+        extent: rendy_core::hal::window::Extent2D,
+        buffers: &[NodeBuffer],
+        images: &[NodeImage],
+        resized: bool,
+    ) -> Result<(), NodeBuildError> {
+        Node::resize(
+            &mut self.0,
+            ctx,
+            factory,
+            aux,
+            extent,
+            buffers,
+            images,
+            resized,
+        )
+    }
+
     unsafe fn dispose(self: Box<Self>, factory: &mut Factory<B>, aux: &T) {
         N::dispose(self.0, factory, aux);
     }
@@ -281,6 +426,8 @@ pub enum NodeBuildError {
     Upload(UploadError),
     /// Mismatched or unsupported queue family.
     QueueFamily(FamilyId),
+    /// Failed to create a buffer.
+    Buffer(BufferCreationError),
     /// Failed to create an imate view.
     View(rendy_core::hal::image::ViewError),
     /// Failed to create a pipeline.
@@ -289,6 +436,11 @@ pub enum NodeBuildError {
     Swapchain(SwapchainError),
     /// Ran out of memory when creating something.
     OutOfMemory(rendy_core::hal::device::OutOfMemory),
+    /// Node can't rebuild size-dependent objects and must be rebuilt with the whole graph.
+    ResizeUnsupported,
+    /// Attachment is cleared but has no clear value.
+    /// Contains `None` if the attachment is the surface.
+    MissingClearValue(Option<ImageId>),
 }
 
 impl std::fmt::Display for NodeBuildError {
@@ -304,6 +456,11 @@ impl std::fmt::Display for NodeBuildError {
                 "Failed to build node because of mismatched or unsupported queue family: {:?}",
                 family
             ),
+            NodeBuildError::Buffer(err) => write!(
+                fmt,
+                "Failed to build node because of failure to create a buffer: {:?}",
+                err
+            ),
             NodeBuildError::View(err) => write!(
                 fmt,
                 "Failed to build node because of failure to create an image view: {:?}",
@@ -324,6 +481,19 @@ impl std::fmt::Display for NodeBuildError {
                 "Failed to build node because device ran out of memory while attempting to build: {:?}",
                 err
             ),
+            NodeBuildError::ResizeUnsupported => write!(
+                fmt,
+                "Failed to resize node because it doesn't support resizing"
+            ),
+            NodeBuildError::MissingClearValue(Some(image)) => write!(
+                fmt,
+                "Failed to build node because attachment {:?} is cleared but has no clear value",
+                image
+            ),
+            NodeBuildError::MissingClearValue(None) => write!(
+                fmt,
+                "Failed to build node because surface is cleared but has no clear value"
+            ),
         }
     }
 }
@@ -333,10 +503,13 @@ impl std::error::Error for NodeBuildError {
         match self {
             NodeBuildError::Upload(err) => Some(err),
             NodeBuildError::QueueFamily(_) => None,
+            NodeBuildError::Buffer(err) => Some(err),
             NodeBuildError::View(err) => Some(err),
             NodeBuildError::Pipeline(err) => Some(err),
             NodeBuildError::Swapchain(err) => Some(err),
             NodeBuildError::OutOfMemory(err) => Some(err),
+            NodeBuildError::ResizeUnsupported => None,
+            NodeBuildError::MissingClearValue(_) => None,
         }
     }
 }
@@ -345,6 +518,13 @@ pub trait NodeBuilder<B: Backend, T: ?Sized>: std::fmt::Debug {
     /// Pick family for this node to be executed onto.
     fn family(&self, factory: &mut Factory<B>, families: &Families<B>) -> Option<FamilyId>;
 
+    /// Pick all families this node can be executed onto.
+    /// Graph schedules node onto one of them, preferring families listed first.
+    /// Returns family picked by `family` by default.
+    fn families(&self, factory: &mut Factory<B>, families: &Families<B>) -> Vec<FamilyId> {
+        self.family(factory, families).into_iter().collect()
+    }
+
     /// Get buffer accessed by the node.
     fn buffers(&self) -> Vec<(BufferId, BufferAccess)>;
 
@@ -354,6 +534,18 @@ pub trait NodeBuilder<B: Backend, T: ?Sized>: std::fmt::Debug {
     /// Indices of nodes this one dependes on.
     fn dependencies(&self) -> Vec<NodeId>;
 
+    /// Estimated cost of executing the node relative to other nodes.
+    /// Schedulers may use it to submit nodes on the longest path first.
+    fn cost(&self) -> usize {
+        1
+    }
+
+    /// Check if node can be disabled in built graph.
+    /// See `Graph::set_node_enabled`.
+    fn optional(&self) -> bool {
+        false
+    }
+
     /// Build node.
     fn build<'a>(
         self: Box<Self>,
@@ -373,6 +565,8 @@ pub struct DescBuilder<B: Backend, T: ?Sized, N> {
     buffers: Vec<BufferId>,
     images: Vec<ImageId>,
     dependencies: Vec<NodeId>,
+    cost: usize,
+    optional: bool,
     marker: std::marker::PhantomData<fn(B, &T)>,
 }
 
@@ -388,6 +582,8 @@ where
             .field("buffers", &self.buffers)
             .field("images", &self.images)
             .field("dependencies", &self.dependencies)
+            .field("cost", &self.cost)
+            .field("optional", &self.optional)
             .finish()
     }
 }
@@ -404,6 +600,8 @@ where
             buffers: Vec::new(),
             images: Vec::new(),
             dependencies: Vec::new(),
+            cost: 1,
+            optional: false,
             marker: std::marker::PhantomData,
         }
     }
@@ -448,6 +646,34 @@ where
         self.add_dependency(dependency);
         self
     }
+
+    /// Set estimated cost of executing the node relative to other nodes.
+    /// See `NodeBuilder::cost`.
+    pub fn set_cost(&mut self, cost: usize) -> &mut Self {
+        self.cost = cost;
+        self
+    }
+
+    /// Set estimated cost of executing the node relative to other nodes.
+    /// See `NodeBuilder::cost`.
+    pub fn with_cost(mut self, cost: usize) -> Self {
+        self.set_cost(cost);
+        self
+    }
+
+    /// Mark node as optional.
+    /// Optional node can be disabled in built graph.
+    pub fn set_optional(&mut self, optional: bool) -> &mut Self {
+        self.optional = optional;
+        self
+    }
+
+    /// Mark node as optional.
+    /// Optional node can be disabled in built graph.
+    pub fn with_optional(mut self, optional: bool) -> Self {
+        self.set_optional(optional);
+        self
+    }
 }
 
 impl<B, T, N> NodeBuilder<B, T> for DescBuilder<B, T, N>
@@ -460,6 +686,10 @@ where
         families.with_capability::<<N::Node as Node<B, T>>::Capability>()
     }
 
+    fn families(&self, _factory: &mut Factory<B>, families: &Families<B>) -> Vec<FamilyId> {
+        families.all_with_capability::<<N::Node as Node<B, T>>::Capability>()
+    }
+
     fn buffers(&self) -> Vec<(BufferId, BufferAccess)> {
         let desc_buffers = self.desc.buffers();
         assert_eq!(self.buffers.len(), desc_buffers.len());
@@ -478,6 +708,14 @@ where
         self.dependencies.clone()
     }
 
+    fn cost(&self) -> usize {
+        self.cost
+    }
+
+    fn optional(&self) -> bool {
+        self.optional
+    }
+
     fn build<'a>(
         self: Box<Self>,
         ctx: &GraphContext<B>,
@@ -495,6 +733,7 @@ where
 }
 
 /// Convert graph barriers into gfx barriers.
+/// Halves of split barriers are skipped as graph waits for their events.
 pub fn gfx_acquire_barriers<'a, 'b, B: Backend>(
     ctx: &'a GraphContext<B>,
     buffers: impl IntoIterator<Item = &'b NodeBuffer>,
@@ -503,53 +742,169 @@ pub fn gfx_acquire_barriers<'a, 'b, B: Backend>(
     std::ops::Range<rendy_core::hal::pso::PipelineStage>,
     Vec<rendy_core::hal::memory::Barrier<'a, B>>,
 ) {
-    let mut bstart = rendy_core::hal::pso::PipelineStage::empty();
-    let mut bend = rendy_core::hal::pso::PipelineStage::empty();
+    gfx_barriers(
+        ctx,
+        buffers
+            .into_iter()
+            .filter_map(|buffer| Some((buffer, buffer.acquire.as_ref()?)))
+            .filter(|(_, acquire)| acquire.event.is_none()),
+        images
+            .into_iter()
+            .filter_map(|image| Some((image, image.acquire.as_ref()?)))
+            .filter(|(_, acquire)| acquire.event.is_none()),
+    )
+}
 
-    let mut istart = rendy_core::hal::pso::PipelineStage::empty();
-    let mut iend = rendy_core::hal::pso::PipelineStage::empty();
+/// Convert graph barriers into gfx barriers.
+/// Halves of split barriers are skipped as graph sets their events.
+pub fn gfx_release_barriers<'a, B: Backend>(
+    ctx: &'a GraphContext<B>,
+    buffers: impl IntoIterator<Item = &'a NodeBuffer>,
+    images: impl IntoIterator<Item = &'a NodeImage>,
+) -> (
+    std::ops::Range<rendy_core::hal::pso::PipelineStage>,
+    Vec<rendy_core::hal::memory::Barrier<'a, B>>,
+) {
+    gfx_barriers(
+        ctx,
+        buffers
+            .into_iter()
+            .filter_map(|buffer| Some((buffer, buffer.release.as_ref()?)))
+            .filter(|(_, release)| release.event.is_none()),
+        images
+            .into_iter()
+            .filter_map(|image| Some((image, image.release.as_ref()?)))
+            .filter(|(_, release)| release.event.is_none()),
+    )
+}
 
-    let barriers: Vec<rendy_core::hal::memory::Barrier<'_, B>> = buffers
+/// Record acquire halves of split barriers.
+/// Waits for events of the `frame` set by earlier nodes,
+/// performs the transitions and resets the events.
+///
+/// # Safety
+///
+/// Must be recorded outside of render pass before first command that uses the resources.
+pub(crate) unsafe fn gfx_wait_events<'a, B: Backend, C, L>(
+    ctx: &GraphContext<B>,
+    frame: u64,
+    encoder: &mut Encoder<'_, B, C, L>,
+    buffers: impl IntoIterator<Item = &'a NodeBuffer>,
+    images: impl IntoIterator<Item = &'a NodeImage>,
+) {
+    let buffers: Vec<_> = buffers
         .into_iter()
-        .filter_map(|buffer| {
-            buffer.acquire.as_ref().map(|acquire| {
-                bstart |= acquire.stages.start;
-                bend |= acquire.stages.end;
-
-                rendy_core::hal::memory::Barrier::Buffer {
-                    states: acquire.states.clone(),
-                    families: acquire.families.clone(),
-                    target: ctx
-                        .get_buffer(buffer.id)
-                        .expect("Buffer does not exist")
-                        .raw(),
-                    range: Some(buffer.range.start)..Some(buffer.range.end),
-                }
-            })
-        })
-        .chain(images.into_iter().filter_map(|image| {
-            image.acquire.as_ref().map(|acquire| {
-                istart |= acquire.stages.start;
-                iend |= acquire.stages.end;
-
-                rendy_core::hal::memory::Barrier::Image {
-                    states: acquire.states.clone(),
-                    families: acquire.families.clone(),
-                    target: ctx.get_image(image.id).expect("Image does not exist").raw(),
-                    range: image.range.clone(),
-                }
-            })
-        }))
+        .filter_map(|buffer| Some((buffer, buffer.acquire.as_ref()?)))
+        .filter_map(|(buffer, acquire)| Some((acquire.event?, (buffer, acquire))))
+        .collect();
+    let images: Vec<_> = images
+        .into_iter()
+        .filter_map(|image| Some((image, image.acquire.as_ref()?)))
+        .filter_map(|(image, acquire)| Some((acquire.event?, (image, acquire))))
         .collect();
 
-    (bstart | istart..bend | iend, barriers)
+    let mut events: Vec<_> = buffers
+        .iter()
+        .map(|&(event, _)| event)
+        .chain(images.iter().map(|&(event, _)| event))
+        .collect();
+    if events.is_empty() {
+        return;
+    }
+    events.sort();
+    events.dedup();
+
+    let (stages, barriers) = gfx_barriers(
+        ctx,
+        buffers.into_iter().map(|(_, barrier)| barrier),
+        images.into_iter().map(|(_, barrier)| barrier),
+    );
+    log::trace!("Wait events {:?} {:?} : {:#?}", events, stages, barriers);
+
+    let event = |index: usize| {
+        ctx.get_event(index, frame)
+            .expect("Event does not exist")
+            .raw()
+    };
+    encoder.wait_events(
+        events.iter().map(|&index| event(index)),
+        stages.clone(),
+        barriers,
+    );
+    for &index in &events {
+        encoder.reset_event(event(index), stages.end);
+    }
 }
 
-/// Convert graph barriers into gfx barriers.
-pub fn gfx_release_barriers<'a, B: Backend>(
-    ctx: &'a GraphContext<B>,
+/// Record release halves of split barriers by setting their events of the `frame`.
+///
+/// # Safety
+///
+/// Must be recorded outside of render pass after last command that uses the resources.
+pub(crate) unsafe fn gfx_set_events<'a, B: Backend, C, L>(
+    ctx: &GraphContext<B>,
+    frame: u64,
+    encoder: &mut Encoder<'_, B, C, L>,
     buffers: impl IntoIterator<Item = &'a NodeBuffer>,
     images: impl IntoIterator<Item = &'a NodeImage>,
+) {
+    let mut events: Vec<_> = buffers
+        .into_iter()
+        .filter_map(|buffer| buffer.release.as_ref())
+        .filter_map(|release| Some((release.event?, release.stages.start)))
+        .chain(
+            images
+                .into_iter()
+                .filter_map(|image| image.release.as_ref())
+                .filter_map(|release| Some((release.event?, release.stages.start))),
+        )
+        .collect();
+    events.sort_by_key(|&(event, _)| event);
+
+    for (index, stages) in events {
+        log::trace!("Set event {} after {:?}", index, stages);
+        let event = ctx.get_event(index, frame).expect("Event does not exist");
+        encoder.set_event(event.raw(), stages);
+    }
+}
+
+/// Check if any acquire barrier of the resources waits for an event.
+pub(crate) fn waits_events(buffers: &[NodeBuffer], images: &[NodeImage]) -> bool {
+    buffers
+        .iter()
+        .filter_map(|buffer| buffer.acquire.as_ref())
+        .any(|acquire| acquire.event.is_some())
+        || images
+            .iter()
+            .filter_map(|image| image.acquire.as_ref())
+            .any(|acquire| acquire.event.is_some())
+}
+
+/// Check if any release barrier of the resources sets an event.
+pub(crate) fn sets_events(buffers: &[NodeBuffer], images: &[NodeImage]) -> bool {
+    buffers
+        .iter()
+        .filter_map(|buffer| buffer.release.as_ref())
+        .any(|release| release.event.is_some())
+        || images
+            .iter()
+            .filter_map(|image| image.release.as_ref())
+            .any(|release| release.event.is_some())
+}
+
+/// Check if swapchain can be recreated for the surface.
+/// Surface of minimized window has zero extent and recreation is pointless until it is restored.
+pub(crate) fn surface_usable<B: Backend>(factory: &Factory<B>, surface: &Surface<B>) -> bool {
+    factory
+        .get_surface_capabilities(surface)
+        .current_extent
+        .map_or(true, |extent| extent.width > 0 && extent.height > 0)
+}
+
+fn gfx_barriers<'a, 'b, B: Backend>(
+    ctx: &'a GraphContext<B>,
+    buffers: impl IntoIterator<Item = (&'b NodeBuffer, &'b BufferBarrier)>,
+    images: impl IntoIterator<Item = (&'b NodeImage, &'b ImageBarrier)>,
 ) -> (
     std::ops::Range<rendy_core::hal::pso::PipelineStage>,
     Vec<rendy_core::hal::memory::Barrier<'a, B>>,
@@ -562,34 +917,30 @@ pub fn gfx_release_barriers<'a, B: Backend>(
 
     let barriers: Vec<rendy_core::hal::memory::Barrier<'_, B>> = buffers
         .into_iter()
-        .filter_map(|buffer| {
-            buffer.release.as_ref().map(|release| {
-                bstart |= release.stages.start;
-                bend |= release.stages.end;
-
-                rendy_core::hal::memory::Barrier::Buffer {
-                    states: release.states.clone(),
-                    families: release.families.clone(),
-                    target: ctx
-                        .get_buffer(buffer.id)
-                        .expect("Buffer does not exist")
-                        .raw(),
-                    range: Some(buffer.range.start)..Some(buffer.range.end),
-                }
-            })
+        .map(|(buffer, barrier)| {
+            bstart |= barrier.stages.start;
+            bend |= barrier.stages.end;
+
+            rendy_core::hal::memory::Barrier::Buffer {
+                states: barrier.states.clone(),
+                families: barrier.families.clone(),
+                target: ctx
+                    .get_buffer(buffer.id)
+                    .expect("Buffer does not exist")
+                    .raw(),
+                range: Some(buffer.range.start)..Some(buffer.range.end),
+            }
         })
-        .chain(images.into_iter().filter_map(|image| {
-            image.release.as_ref().map(|release| {
-                istart |= release.stages.start;
-                iend |= release.stages.end;
-
-                rendy_core::hal::memory::Barrier::Image {
-                    states: release.states.clone(),
-                    families: release.families.clone(),
-                    target: ctx.get_image(image.id).expect("Image does not exist").raw(),
-                    range: image.range.clone(),
-                }
-            })
+        .chain(images.into_iter().map(|(image, barrier)| {
+            istart |= barrier.stages.start;
+            iend |= barrier.stages.end;
+
+            rendy_core::hal::memory::Barrier::Image {
+                states: barrier.states.clone(),
+                families: barrier.families.clone(),
+                target: ctx.get_image(image.id).expect("Image does not exist").raw(),
+                range: image.range.clone(),
+            }
         }))
         .collect();
 
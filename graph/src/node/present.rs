@@ -2,17 +2,18 @@
 
 use crate::{
     command::{
-        CommandBuffer, CommandPool, ExecutableState, Families, Family, FamilyId, Fence, MultiShot,
-        PendingState, Queue, SimultaneousUse, Submission, Submit,
+        Encoder, Families, Family, FamilyId, Fence, FrameCommandPools, FrameTimeline, OneShot,
+        PrimaryLevel, Queue, Submission,
     },
     factory::Factory,
     frame::Frames,
     graph::GraphContext,
     node::{
-        gfx_acquire_barriers, gfx_release_barriers, BufferAccess, DynNode, ImageAccess, NodeBuffer,
-        NodeBuildError, NodeBuilder, NodeImage,
+        gfx_acquire_barriers, gfx_release_barriers, surface_usable, BufferAccess, DynNode,
+        ImageAccess, NodeBuffer, NodeBuildError, NodeBuilder, NodeImage,
     },
-    wsi::{Surface, Target},
+    resource::Image,
+    wsi::{Surface, SwapchainError, Target},
     BufferId, ImageId, NodeId,
 };
 
@@ -20,34 +21,31 @@ use crate::{
 struct ForImage<B: rendy_core::hal::Backend> {
     acquire: B::Semaphore,
     release: B::Semaphore,
-    submit: Submit<B, SimultaneousUse>,
-    buffer: CommandBuffer<
-        B,
-        rendy_core::hal::queue::QueueType,
-        PendingState<ExecutableState<MultiShot<SimultaneousUse>>>,
-    >,
 }
 
 impl<B: rendy_core::hal::Backend> ForImage<B> {
-    unsafe fn dispose(
-        self,
-        factory: &Factory<B>,
-        pool: &mut CommandPool<B, rendy_core::hal::queue::QueueType>,
-    ) {
-        drop(self.submit);
+    unsafe fn dispose(self, factory: &Factory<B>) {
         factory.destroy_semaphore(self.acquire);
         factory.destroy_semaphore(self.release);
-        pool.free_buffers(Some(self.buffer.mark_complete()));
     }
 }
 
 /// Node that presents images to the surface.
+///
+/// Graph can contain multiple present nodes, each with its own surface.
+/// Each node acquires swapchain images independently.
+/// If surface is unavailable, e.g. window is minimized,
+/// node skips presentation and graph keeps running.
+///
+/// Commands are recorded each frame to the command buffer
+/// allocated from the node's frame command pools.
 #[derive(Debug)]
 pub struct PresentNode<B: rendy_core::hal::Backend> {
     per_image: Vec<ForImage<B>>,
     free_acquire: B::Semaphore,
     target: Target<B>,
-    pool: CommandPool<B, rendy_core::hal::queue::QueueType>,
+    outdated: bool,
+    pools: FrameCommandPools<B>,
     input_image: NodeImage,
     blit_filter: rendy_core::hal::image::Filter,
 }
@@ -96,163 +94,181 @@ where
 }
 
 fn create_per_image_data<B: rendy_core::hal::Backend>(
-    ctx: &GraphContext<B>,
-    input_image: &NodeImage,
-    pool: &mut CommandPool<B, rendy_core::hal::queue::QueueType>,
     factory: &Factory<B>,
     target: &Target<B>,
-    blit_filter: rendy_core::hal::image::Filter,
 ) -> Vec<ForImage<B>> {
+    target
+        .backbuffer()
+        .iter()
+        .map(|_| ForImage {
+            acquire: factory.create_semaphore().unwrap(),
+            release: factory.create_semaphore().unwrap(),
+        })
+        .collect()
+}
+
+/// Record commands that copy input image to the target image and prepare it for presentation.
+unsafe fn encode_present<B: rendy_core::hal::Backend>(
+    ctx: &GraphContext<B>,
+    input_image: &NodeImage,
+    target_image: &Image<B>,
+    blit_filter: rendy_core::hal::image::Filter,
+    encoder: &mut Encoder<'_, B, rendy_core::hal::queue::QueueType, PrimaryLevel>,
+) {
     let input_image_res = ctx.get_image(input_image.id).expect("Image does not exist");
 
-    let target_images = target.backbuffer();
-    let buffers = pool.allocate_buffers(target_images.len());
-    target_images
-        .iter()
-        .zip(buffers)
-        .map(|(target_image, buf_initial)| {
-            let mut buf_recording = buf_initial.begin(MultiShot(SimultaneousUse), ());
-            let mut encoder = buf_recording.encoder();
-            let (mut stages, mut barriers) =
-                gfx_acquire_barriers(ctx, None, Some(input_image));
-            stages.start |= rendy_core::hal::pso::PipelineStage::TRANSFER;
-            stages.end |= rendy_core::hal::pso::PipelineStage::TRANSFER;
-            barriers.push(rendy_core::hal::memory::Barrier::Image {
-                states: (
-                    rendy_core::hal::image::Access::empty(),
-                    rendy_core::hal::image::Layout::Undefined,
-                )
-                    ..(
-                        rendy_core::hal::image::Access::TRANSFER_WRITE,
-                        rendy_core::hal::image::Layout::TransferDstOptimal,
-                    ),
-                families: None,
-                target: target_image.raw(),
-                range: rendy_core::hal::image::SubresourceRange {
+    let (mut stages, mut barriers) = gfx_acquire_barriers(ctx, None, Some(input_image));
+    stages.start |= rendy_core::hal::pso::PipelineStage::TRANSFER;
+    stages.end |= rendy_core::hal::pso::PipelineStage::TRANSFER;
+    barriers.push(rendy_core::hal::memory::Barrier::Image {
+        states: (
+            rendy_core::hal::image::Access::empty(),
+            rendy_core::hal::image::Layout::Undefined,
+        )
+            ..(
+                rendy_core::hal::image::Access::TRANSFER_WRITE,
+                rendy_core::hal::image::Layout::TransferDstOptimal,
+            ),
+        families: None,
+        target: target_image.raw(),
+        range: rendy_core::hal::image::SubresourceRange {
+            aspects: rendy_core::hal::format::Aspects::COLOR,
+            levels: 0..1,
+            layers: 0..1,
+        },
+    });
+    log::trace!("Acquire {:?} : {:#?}", stages, barriers);
+    encoder.pipeline_barrier(
+        stages,
+        rendy_core::hal::memory::Dependencies::empty(),
+        barriers,
+    );
+
+    let extents_differ = target_image.kind().extent() != input_image_res.kind().extent();
+    let formats_differ = target_image.format() != input_image_res.format();
+
+    if extents_differ || formats_differ {
+        if formats_differ {
+            log::trace!(
+                "Present node is blitting because target format {:?} doesnt match image format {:?}",
+                target_image.format(),
+                input_image_res.format()
+            );
+        }
+        if extents_differ {
+            log::trace!(
+                "Present node is blitting because target extent {:?} doesnt match image extent {:?}",
+                target_image.kind().extent(),
+                input_image_res.kind().extent()
+            );
+        }
+        encoder.blit_image(
+            input_image_res.raw(),
+            input_image.layout,
+            target_image.raw(),
+            rendy_core::hal::image::Layout::TransferDstOptimal,
+            blit_filter,
+            Some(rendy_core::hal::command::ImageBlit {
+                src_subresource: rendy_core::hal::image::SubresourceLayers {
+                    aspects: input_image.range.aspects,
+                    level: 0,
+                    layers: input_image.range.layers.start..input_image.range.layers.start + 1,
+                },
+                src_bounds: rendy_core::hal::image::Offset::ZERO
+                    .into_bounds(&input_image_res.kind().extent()),
+                dst_subresource: rendy_core::hal::image::SubresourceLayers {
                     aspects: rendy_core::hal::format::Aspects::COLOR,
-                    levels: 0..1,
+                    level: 0,
                     layers: 0..1,
                 },
-            });
-            log::trace!("Acquire {:?} : {:#?}", stages, barriers);
-            unsafe {
-                encoder.pipeline_barrier(
-                    stages,
-                    rendy_core::hal::memory::Dependencies::empty(),
-                    barriers,
-                );
-            }
-
-            let extents_differ = target_image.kind().extent() != input_image_res.kind().extent();
-            let formats_differ = target_image.format() != input_image_res.format();
-
-            if extents_differ || formats_differ
-            {
-                if formats_differ {
-                    log::debug!("Present node is blitting because target format {:?} doesnt match image format {:?}", target_image.format(), input_image_res.format());
-                }
-                if extents_differ {
-                    log::debug!("Present node is blitting because target extent {:?} doesnt match image extent {:?}", target_image.kind().extent(), input_image_res.kind().extent());
-                }
-                unsafe {
-                    encoder.blit_image(
-                        input_image_res.raw(),
-                        input_image.layout,
-                        target_image.raw(),
-                        rendy_core::hal::image::Layout::TransferDstOptimal,
-                        blit_filter,
-                        Some(rendy_core::hal::command::ImageBlit {
-                            src_subresource: rendy_core::hal::image::SubresourceLayers {
-                                aspects: input_image.range.aspects,
-                                level: 0,
-                                layers: input_image.range.layers.start..input_image.range.layers.start + 1,
-                            },
-                            src_bounds: rendy_core::hal::image::Offset::ZERO
-                                .into_bounds(&input_image_res.kind().extent()),
-                            dst_subresource: rendy_core::hal::image::SubresourceLayers {
-                                aspects: rendy_core::hal::format::Aspects::COLOR,
-                                level: 0,
-                                layers: 0..1,
-                            },
-                            dst_bounds: rendy_core::hal::image::Offset::ZERO
-                                .into_bounds(&target_image.kind().extent()),
-                        }),
-                    );
-                }
-            } else {
-                log::debug!("Present node is copying");
-                unsafe {
-                    encoder.copy_image(
-                        input_image_res.raw(),
-                        input_image.layout,
-                        target_image.raw(),
-                        rendy_core::hal::image::Layout::TransferDstOptimal,
-                        Some(rendy_core::hal::command::ImageCopy {
-                            src_subresource: rendy_core::hal::image::SubresourceLayers {
-                                aspects: input_image.range.aspects,
-                                level: 0,
-                                layers: input_image.range.layers.start..input_image.range.layers.start + 1,
-                            },
-                            src_offset: rendy_core::hal::image::Offset::ZERO,
-                            dst_subresource: rendy_core::hal::image::SubresourceLayers {
-                                aspects: rendy_core::hal::format::Aspects::COLOR,
-                                level: 0,
-                                layers: 0..1,
-                            },
-                            dst_offset: rendy_core::hal::image::Offset::ZERO,
-                            extent: rendy_core::hal::image::Extent {
-                                width: target_image.kind().extent().width,
-                                height: target_image.kind().extent().height,
-                                depth: 1,
-                            },
-                        }),
-                    );
-                }
-            }
+                dst_bounds: rendy_core::hal::image::Offset::ZERO
+                    .into_bounds(&target_image.kind().extent()),
+            }),
+        );
+    } else {
+        log::trace!("Present node is copying");
+        encoder.copy_image(
+            input_image_res.raw(),
+            input_image.layout,
+            target_image.raw(),
+            rendy_core::hal::image::Layout::TransferDstOptimal,
+            Some(rendy_core::hal::command::ImageCopy {
+                src_subresource: rendy_core::hal::image::SubresourceLayers {
+                    aspects: input_image.range.aspects,
+                    level: 0,
+                    layers: input_image.range.layers.start..input_image.range.layers.start + 1,
+                },
+                src_offset: rendy_core::hal::image::Offset::ZERO,
+                dst_subresource: rendy_core::hal::image::SubresourceLayers {
+                    aspects: rendy_core::hal::format::Aspects::COLOR,
+                    level: 0,
+                    layers: 0..1,
+                },
+                dst_offset: rendy_core::hal::image::Offset::ZERO,
+                extent: rendy_core::hal::image::Extent {
+                    width: target_image.kind().extent().width,
+                    height: target_image.kind().extent().height,
+                    depth: 1,
+                },
+            }),
+        );
+    }
 
-            {
-                let (mut stages, mut barriers) =
-                    gfx_release_barriers(ctx, None, Some(input_image));
-                stages.start |= rendy_core::hal::pso::PipelineStage::TRANSFER;
-                stages.end |= rendy_core::hal::pso::PipelineStage::BOTTOM_OF_PIPE;
-                barriers.push(rendy_core::hal::memory::Barrier::Image {
-                    states: (
-                        rendy_core::hal::image::Access::TRANSFER_WRITE,
-                        rendy_core::hal::image::Layout::TransferDstOptimal,
-                    )
-                        ..(
-                            rendy_core::hal::image::Access::empty(),
-                            rendy_core::hal::image::Layout::Present,
-                        ),
-                    families: None,
-                    target: target_image.raw(),
-                    range: rendy_core::hal::image::SubresourceRange {
-                        aspects: rendy_core::hal::format::Aspects::COLOR,
-                        levels: 0..1,
-                        layers: 0..1,
-                    },
-                });
-
-                log::trace!("Release {:?} : {:#?}", stages, barriers);
-                unsafe {
-                    encoder.pipeline_barrier(
-                        stages,
-                        rendy_core::hal::memory::Dependencies::empty(),
-                        barriers,
-                    );
-                }
-            }
+    let (mut stages, mut barriers) = gfx_release_barriers(ctx, None, Some(input_image));
+    stages.start |= rendy_core::hal::pso::PipelineStage::TRANSFER;
+    stages.end |= rendy_core::hal::pso::PipelineStage::BOTTOM_OF_PIPE;
+    barriers.push(rendy_core::hal::memory::Barrier::Image {
+        states: (
+            rendy_core::hal::image::Access::TRANSFER_WRITE,
+            rendy_core::hal::image::Layout::TransferDstOptimal,
+        )
+            ..(
+                rendy_core::hal::image::Access::empty(),
+                rendy_core::hal::image::Layout::Present,
+            ),
+        families: None,
+        target: target_image.raw(),
+        range: rendy_core::hal::image::SubresourceRange {
+            aspects: rendy_core::hal::format::Aspects::COLOR,
+            levels: 0..1,
+            layers: 0..1,
+        },
+    });
+
+    log::trace!("Release {:?} : {:#?}", stages, barriers);
+    encoder.pipeline_barrier(
+        stages,
+        rendy_core::hal::memory::Dependencies::empty(),
+        barriers,
+    );
+}
 
-            let (submit, buffer) = buf_recording.finish().submit();
+/// Record commands submitted instead of presentation when surface is unavailable.
+/// Performs input image barriers to keep its state valid for other nodes.
+unsafe fn encode_skip<B: rendy_core::hal::Backend>(
+    ctx: &GraphContext<B>,
+    input_image: &NodeImage,
+    encoder: &mut Encoder<'_, B, rendy_core::hal::queue::QueueType, PrimaryLevel>,
+) {
+    let (stages, barriers) = gfx_acquire_barriers(ctx, None, Some(input_image));
+    if !barriers.is_empty() {
+        log::trace!("Acquire {:?} : {:#?}", stages, barriers);
+        encoder.pipeline_barrier(
+            stages,
+            rendy_core::hal::memory::Dependencies::empty(),
+            barriers,
+        );
+    }
 
-            ForImage {
-                submit,
-                buffer,
-                acquire: factory.create_semaphore().unwrap(),
-                release: factory.create_semaphore().unwrap(),
-            }
-        })
-        .collect()
+    let (stages, barriers) = gfx_release_barriers(ctx, None, Some(input_image));
+    if !barriers.is_empty() {
+        log::trace!("Release {:?} : {:#?}", stages, barriers);
+        encoder.pipeline_barrier(
+            stages,
+            rendy_core::hal::memory::Dependencies::empty(),
+            barriers,
+        );
+    }
 }
 
 /// Presentation node description.
@@ -430,23 +446,13 @@ where
             )
             .map_err(NodeBuildError::Swapchain)?;
 
-        let mut pool = factory
-            .create_command_pool(family)
-            .map_err(NodeBuildError::OutOfMemory)?;
-
-        let per_image = create_per_image_data(
-            ctx,
-            &input_image,
-            &mut pool,
-            factory,
-            &target,
-            self.blit_filter,
-        );
+        let per_image = create_per_image_data(factory, &target);
 
         Ok(Box::new(PresentNode {
             free_acquire: factory.create_semaphore().unwrap(),
-            pool,
+            pools: FrameCommandPools::new(family),
             target,
+            outdated: false,
             per_image,
             input_image,
             blit_filter: self.blit_filter,
@@ -454,6 +460,44 @@ where
     }
 }
 
+impl<B> PresentNode<B>
+where
+    B: rendy_core::hal::Backend,
+{
+    /// Recreate swapchain to match input image extent.
+    /// If swapchain can't be recreated node skips presentation
+    /// until next successful recreation.
+    ///
+    /// # Safety
+    ///
+    /// Swapchain and semaphores must not be in use.
+    unsafe fn recreate(
+        &mut self,
+        ctx: &GraphContext<B>,
+        factory: &Factory<B>,
+    ) -> Result<(), SwapchainError> {
+        let extent = ctx
+            .get_image(self.input_image.id)
+            .expect("Context must contain node's image")
+            .kind()
+            .extent()
+            .into();
+
+        for data in self.per_image.drain(..) {
+            data.dispose(factory);
+        }
+
+        self.outdated = true;
+        self.target
+            .recreate(factory.physical(), factory.device(), extent)?;
+        self.outdated = false;
+
+        self.per_image = create_per_image_data(factory, &self.target);
+
+        Ok(())
+    }
+}
+
 impl<B, T> DynNode<B, T> for PresentNode<B>
 where
     B: rendy_core::hal::Backend,
@@ -465,22 +509,56 @@ where
         factory: &Factory<B>,
         queue: &mut Queue<B>,
         _aux: &T,
-        _frames: &Frames<B>,
+        frames: &Frames<B>,
         waits: &[(&'a B::Semaphore, rendy_core::hal::pso::PipelineStage)],
         signals: &[&'a B::Semaphore],
         mut fence: Option<&mut Fence<B>>,
     ) {
+        // Swapchain is recreated at most once per frame.
+        let mut recreated = false;
+
         loop {
+            if self.outdated {
+                // Don't stall the device while surface is minimized.
+                if recreated || !surface_usable(factory, self.target.surface()) {
+                    break;
+                }
+                recreated = true;
+
+                // TODO: use retired swapchains once available in hal and remove that wait
+                factory.wait_idle().unwrap();
+
+                if let Err(err) = self.recreate(ctx, factory) {
+                    log::debug!("Failed to recreate swapchain: {:?}", err);
+                    break;
+                }
+            }
+
             match self.target.next_image(&self.free_acquire) {
                 Ok(next) => {
                     log::trace!("Present: {:#?}", next);
-                    let ref mut for_image = self.per_image[next[0] as usize];
+                    let index = next[0] as usize;
+                    let ref mut for_image = self.per_image[index];
                     core::mem::swap(&mut for_image.acquire, &mut self.free_acquire);
 
+                    let mut recording = self
+                        .pools
+                        .allocate(factory.device(), frames)
+                        .unwrap()
+                        .begin(OneShot, ());
+                    encode_present(
+                        ctx,
+                        &self.input_image,
+                        &self.target.backbuffer()[index],
+                        self.blit_filter,
+                        &mut recording.encoder(),
+                    );
+                    let (submit, buffer) = recording.finish().submit_once();
+
                     queue.submit(
                         Some(
                             Submission::new()
-                                .submits(Some(&for_image.submit))
+                                .submits(Some(submit))
                                 .wait(waits.iter().cloned().chain(Some((
                                     &for_image.acquire,
                                     rendy_core::hal::pso::PipelineStage::TRANSFER,
@@ -489,66 +567,85 @@ where
                         ),
                         fence.take(),
                     );
+                    self.pools.release(frames.next_frame(), buffer);
 
                     match next.present(queue.raw(), Some(&for_image.release)) {
-                        Ok(_) => break,
+                        Ok(None) => {}
+                        Ok(Some(_)) => {
+                            log::debug!("Swapchain is suboptimal");
+                            // recreate swapchain on next frame.
+                            self.outdated = true;
+                        }
                         Err(e) => {
                             log::debug!(
                                 "Swapchain present error after next_image is acquired: {:?}",
                                 e
                             );
                             // recreate swapchain on next frame.
-                            break;
+                            self.outdated = true;
                         }
                     }
+                    return;
                 }
                 Err(rendy_core::hal::window::AcquireError::OutOfDate) => {
                     // recreate swapchain and try again.
+                    self.outdated = true;
                 }
-                e => {
-                    e.unwrap();
+                Err(err) => {
+                    log::debug!("Swapchain acquisition error: {:?}", err);
                     break;
                 }
             }
-            // Recreate swapchain when OutOfDate
-            // The code has to execute after match due to mutable aliasing issues.
-
-            // TODO: use retired swapchains once available in hal and remove that wait
-            factory.wait_idle().unwrap();
-
-            let extent = ctx
-                .get_image(self.input_image.id)
-                .expect("Context must contain node's image")
-                .kind()
-                .extent()
-                .into();
-
-            self.target
-                .recreate(factory.physical(), factory.device(), extent)
-                .expect("Failed recreating swapchain");
+        }
 
-            for data in self.per_image.drain(..) {
-                data.dispose(factory, &mut self.pool);
-            }
+        // Presentation is skipped for this frame.
+        // Submit barriers for input image so that graph remains valid.
+        let mut recording = self
+            .pools
+            .allocate(factory.device(), frames)
+            .unwrap()
+            .begin(OneShot, ());
+        encode_skip(ctx, &self.input_image, &mut recording.encoder());
+        let (submit, buffer) = recording.finish().submit_once();
+
+        queue.submit(
+            Some(
+                Submission::new()
+                    .submits(Some(submit))
+                    .wait(waits.iter().cloned())
+                    .signal(signals.iter().cloned()),
+            ),
+            fence,
+        );
+        self.pools.release(frames.next_frame(), buffer);
+    }
 
-            self.per_image = create_per_image_data(
-                ctx,
-                &self.input_image,
-                &mut self.pool,
-                factory,
-                &self.target,
-                self.blit_filter,
-            );
+    unsafe fn resize(
+        &mut self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        _aux: &T,
+        _extent: rendy_core::hal::window::Extent2D,
+        _buffers: &[NodeBuffer],
+        _images: &[NodeImage],
+        _resized: bool,
+    ) -> Result<(), NodeBuildError> {
+        // Surface is resized along with the graph.
+        // It may be unavailable, e.g. when window is minimized.
+        // Presentation is skipped until swapchain is recreated.
+        if let Err(err) = self.recreate(ctx, factory) {
+            log::debug!("Failed to recreate swapchain: {:?}", err);
         }
+        Ok(())
     }
 
     unsafe fn dispose(mut self: Box<Self>, factory: &mut Factory<B>, _aux: &T) {
         for data in self.per_image {
-            data.dispose(factory, &mut self.pool);
+            data.dispose(factory);
         }
 
         factory.destroy_semaphore(self.free_acquire);
-        factory.destroy_command_pool(self.pool);
+        self.pools.dispose(factory.device());
         factory.destroy_target(self.target);
     }
 }
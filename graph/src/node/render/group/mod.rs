@@ -23,13 +23,7 @@ pub trait RenderGroupDesc<B: Backend, T: ?Sized>: std::fmt::Debug {
     where
         Self: Sized,
     {
-        DescBuilder {
-            desc: self,
-            buffers: Vec::new(),
-            images: Vec::new(),
-            dependencies: Vec::new(),
-            marker: std::marker::PhantomData,
-        }
+        DescBuilder::new(self)
     }
 
     /// Get buffers used by the group. Empty by default.
@@ -61,6 +55,7 @@ pub trait RenderGroupDesc<B: Backend, T: ?Sized>: std::fmt::Debug {
         aux: &T,
         framebuffer_width: u32,
         framebuffer_height: u32,
+        samples: rendy_core::hal::image::NumSamples,
         subpass: rendy_core::hal::pass::Subpass<'_, B>,
         buffers: Vec<NodeBuffer>,
         images: Vec<NodeImage>,
@@ -80,14 +75,33 @@ pub trait RenderGroup<B: Backend, T: ?Sized>: std::fmt::Debug + Send + Sync {
     ) -> PrepareResult;
 
     /// Record commands.
+    /// `region` is the render area of the render pass. Group must not draw outside of it.
     fn draw_inline(
         &mut self,
         encoder: RenderPassEncoder<'_, B>,
         index: usize,
         subpass: rendy_core::hal::pass::Subpass<'_, B>,
+        region: rendy_core::hal::pso::Rect,
         aux: &T,
     );
 
+    /// Update group for new framebuffer size after `Graph::resize`.
+    /// `resized` is `true` if any image of the render pass was reallocated with new size.
+    ///
+    /// Returns `false` if group can't be updated and must be rebuilt with the whole graph.
+    /// Default implementation always returns `false`.
+    fn resize(
+        &mut self,
+        _ctx: &GraphContext<B>,
+        _factory: &Factory<B>,
+        _aux: &T,
+        _framebuffer_width: u32,
+        _framebuffer_height: u32,
+        _resized: bool,
+    ) -> bool {
+        false
+    }
+
     /// Free all resources and destroy group instance.
     fn dispose(self: Box<Self>, factory: &mut Factory<B>, aux: &T);
 }
@@ -126,6 +140,7 @@ pub trait RenderGroupBuilder<B: Backend, T: ?Sized>: std::fmt::Debug {
         aux: &T,
         framebuffer_width: u32,
         framebuffer_height: u32,
+        samples: rendy_core::hal::image::NumSamples,
         subpass: rendy_core::hal::pass::Subpass<'_, B>,
         buffers: Vec<NodeBuffer>,
         images: Vec<NodeImage>,
@@ -174,6 +189,7 @@ where
         aux: &T,
         framebuffer_width: u32,
         framebuffer_height: u32,
+        samples: rendy_core::hal::image::NumSamples,
         subpass: rendy_core::hal::pass::Subpass<'_, B>,
         buffers: Vec<NodeBuffer>,
         images: Vec<NodeImage>,
@@ -185,6 +201,7 @@ where
             aux,
             framebuffer_width,
             framebuffer_height,
+            samples,
             subpass,
             buffers,
             images,
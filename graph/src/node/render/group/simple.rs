@@ -83,6 +83,32 @@ pub trait SimpleGraphicsPipelineDesc<B: Backend, T: ?Sized>: std::fmt::Debug {
         })
     }
 
+    /// Multisampling desc for the number of samples of the subpass attachments.
+    /// Default implementation enables multisampling for multisampled attachments.
+    fn multisampling(
+        &self,
+        samples: rendy_core::hal::image::NumSamples,
+    ) -> Option<rendy_core::hal::pso::Multisampling> {
+        if samples > 1 {
+            Some(rendy_core::hal::pso::Multisampling {
+                rasterization_samples: samples,
+                sample_shading: None,
+                sample_mask: !0,
+                alpha_coverage: false,
+                alpha_to_one: false,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Pipeline sets viewport and scissor itself in `SimpleGraphicsPipeline::draw_region`.
+    /// Otherwise render group sets both to cover the whole region before drawing.
+    /// `false` by default.
+    fn dynamic_viewport(&self) -> bool {
+        false
+    }
+
     /// Rasterizer desc.
     fn rasterizer(&self) -> rendy_core::hal::pso::Rasterizer {
         rendy_core::hal::pso::Rasterizer::FILL
@@ -193,6 +219,29 @@ pub trait SimpleGraphicsPipeline<B: Backend, T: ?Sized>:
         aux: &T,
     );
 
+    /// Record drawing commands into the region of the framebuffer.
+    /// Pipelines with dynamic viewport should set viewports and scissors within the `region` here.
+    /// Default implementation calls `draw`.
+    fn draw_region(
+        &mut self,
+        layout: &B::PipelineLayout,
+        encoder: RenderPassEncoder<'_, B>,
+        index: usize,
+        _region: rendy_core::hal::pso::Rect,
+        aux: &T,
+    ) {
+        self.draw(layout, encoder, index, aux)
+    }
+
+    /// Update pipeline after transient images it uses were reallocated by `Graph::resize`.
+    /// Called only for pipelines that use transient images.
+    ///
+    /// Returns `false` if pipeline can't be updated and must be rebuilt with the whole graph.
+    /// Default implementation always returns `false`.
+    fn resize(&mut self, _ctx: &GraphContext<B>, _factory: &Factory<B>, _aux: &T) -> bool {
+        false
+    }
+
     /// Free all resources and destroy pipeline instance.
     fn dispose(self, factory: &mut Factory<B>, aux: &T);
 }
@@ -204,6 +253,8 @@ pub struct SimpleRenderGroup<B: Backend, P> {
     pipeline_layout: B::PipelineLayout,
     graphics_pipeline: B::GraphicsPipeline,
     pipeline: P,
+    dynamic_viewport: bool,
+    uses_images: bool,
 }
 
 /// Descriptor for simple render group.
@@ -240,8 +291,9 @@ where
         factory: &mut Factory<B>,
         queue: QueueId,
         aux: &T,
-        framebuffer_width: u32,
-        framebuffer_height: u32,
+        _framebuffer_width: u32,
+        _framebuffer_height: u32,
+        samples: rendy_core::hal::image::NumSamples,
         subpass: rendy_core::hal::pass::Subpass<'_, B>,
         buffers: Vec<NodeBuffer>,
         images: Vec<NodeImage>,
@@ -287,13 +339,6 @@ where
             push_vertex_desc(elemets, stride, rate, &mut vertex_buffers, &mut attributes);
         }
 
-        let rect = rendy_core::hal::pso::Rect {
-            x: 0,
-            y: 0,
-            w: framebuffer_width as i16,
-            h: framebuffer_height as i16,
-        };
-
         let shaders = match shader_set.raw() {
             Err(e) => {
                 shader_set.dispose(factory);
@@ -316,13 +361,11 @@ where
                         targets: pipeline.colors.clone(),
                     },
                     depth_stencil: pipeline.depth_stencil,
-                    multisampling: None,
+                    multisampling: self.inner.multisampling(samples),
+                    // Viewport and scissor are dynamic to keep pipeline on resize.
                     baked_states: rendy_core::hal::pso::BakedStates {
-                        viewport: Some(rendy_core::hal::pso::Viewport {
-                            rect,
-                            depth: 0.0..1.0,
-                        }),
-                        scissor: Some(rect),
+                        viewport: None,
+                        scissor: None,
                         blend_color: None,
                         depth_bounds: None,
                     },
@@ -340,6 +383,9 @@ where
             e
         })?;
 
+        let dynamic_viewport = self.inner.dynamic_viewport();
+        let uses_images = !images.is_empty();
+
         let pipeline = self
             .inner
             .build(ctx, factory, queue, aux, buffers, images, &set_layouts)
@@ -355,6 +401,8 @@ where
             pipeline_layout,
             graphics_pipeline,
             pipeline,
+            dynamic_viewport,
+            uses_images,
         }))
     }
 }
@@ -382,11 +430,36 @@ where
         mut encoder: RenderPassEncoder<'_, B>,
         index: usize,
         _subpass: rendy_core::hal::pass::Subpass<'_, B>,
+        region: rendy_core::hal::pso::Rect,
         aux: &T,
     ) {
         encoder.bind_graphics_pipeline(&self.graphics_pipeline);
+        if !self.dynamic_viewport {
+            unsafe {
+                encoder.set_viewports(
+                    0,
+                    Some(&rendy_core::hal::pso::Viewport {
+                        rect: region,
+                        depth: 0.0..1.0,
+                    }),
+                );
+                encoder.set_scissors(0, Some(&region));
+            }
+        }
         self.pipeline
-            .draw(&self.pipeline_layout, encoder, index, aux);
+            .draw_region(&self.pipeline_layout, encoder, index, region, aux);
+    }
+
+    fn resize(
+        &mut self,
+        ctx: &GraphContext<B>,
+        factory: &Factory<B>,
+        aux: &T,
+        _framebuffer_width: u32,
+        _framebuffer_height: u32,
+        resized: bool,
+    ) -> bool {
+        !(resized && self.uses_images) || self.pipeline.resize(ctx, factory, aux)
     }
 
     fn dispose(self: Box<Self>, factory: &mut Factory<B>, aux: &T) {
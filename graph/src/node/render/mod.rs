@@ -16,11 +16,12 @@ pub enum PrepareResult {
     DrawRecord,
 
     /// Reuse draw commands.
+    /// Render pass nodes record draw commands each frame anyway.
     DrawReuse,
 }
 
 impl PrepareResult {
-    fn force_record(&self) -> bool {
+    pub(crate) fn force_record(&self) -> bool {
         match self {
             PrepareResult::DrawRecord => true,
             PrepareResult::DrawReuse => false,
@@ -1,24 +1,22 @@
 use {
     crate::{
         command::{
-            CommandBuffer, CommandPool, ExecutableState, Families, Family, FamilyId, Fence,
-            Graphics, IndividualReset, MultiShot, NoSimultaneousUse, PendingState, Queue, QueueId,
-            SecondaryLevel, SimultaneousUse, Submission, Submit,
+            Encoder, Families, Family, FamilyId, Fence, FrameCommandPools, FrameTimeline, Graphics,
+            NoSimultaneousUse, OneShot, PrimaryLevel, Queue, QueueId, Submission,
+            SubmissionBatcher, Submit,
         },
         core::{
             hal::{device::Device as _, image::Layout, Backend},
             uses_pipeline_barriers,
         },
         factory::Factory,
-        frame::{
-            cirque::{CirqueRef, CommandCirque},
-            Frames,
-        },
+        frame::Frames,
         graph::GraphContext,
         node::{
             gfx_acquire_barriers, gfx_release_barriers,
             render::group::{RenderGroup, RenderGroupBuilder},
-            BufferAccess, DynNode, ImageAccess, NodeBuffer, NodeBuildError, NodeBuilder, NodeImage,
+            surface_usable, BufferAccess, DynNode, ImageAccess, NodeBuffer, NodeBuildError,
+            NodeBuilder, NodeImage,
         },
         wsi::{Surface, Target},
         BufferId, ImageId, NodeId,
@@ -32,12 +30,47 @@ struct RenderPassSurface;
 
 type Attachment = Either<ImageId, RenderPassSurface>;
 
+/// Load and store operations of the render pass attachment.
+/// Operations left `None` are inferred by `RenderPassNode`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AttachmentOps {
+    /// Load operation for color or depth.
+    /// By default attachment is cleared if image has clear value and loaded otherwise.
+    pub load: Option<rendy_core::hal::pass::AttachmentLoadOp>,
+
+    /// Store operation for color or depth.
+    /// By default attachment is stored only if any node reads the image later.
+    pub store: Option<rendy_core::hal::pass::AttachmentStoreOp>,
+
+    /// Load operation for stencil.
+    /// Same as for depth by default if format has stencil.
+    pub stencil_load: Option<rendy_core::hal::pass::AttachmentLoadOp>,
+
+    /// Store operation for stencil.
+    /// Same as for depth by default if format has stencil.
+    pub stencil_store: Option<rendy_core::hal::pass::AttachmentStoreOp>,
+}
+
+impl AttachmentOps {
+    /// Take operations that are not specified from `other`.
+    fn or(self, other: Self) -> Self {
+        AttachmentOps {
+            load: self.load.or(other.load),
+            store: self.store.or(other.store),
+            stencil_load: self.stencil_load.or(other.stencil_load),
+            stencil_store: self.stencil_store.or(other.stencil_store),
+        }
+    }
+}
+
 /// Build for rendering sub-pass.
 pub struct SubpassBuilder<B: Backend, T: ?Sized> {
     groups: Vec<Box<dyn RenderGroupBuilder<B, T>>>,
     inputs: Vec<Attachment>,
     colors: Vec<Attachment>,
+    resolves: Vec<Option<Attachment>>,
     depth_stencil: Option<Attachment>,
+    attachment_ops: Vec<(Attachment, AttachmentOps)>,
     dependencies: Vec<NodeId>,
 }
 
@@ -51,7 +84,9 @@ where
             .field("groups", &self.groups)
             .field("inputs", &self.inputs)
             .field("colors", &self.colors)
+            .field("resolves", &self.resolves)
             .field("depth_stencil", &self.depth_stencil)
+            .field("attachment_ops", &self.attachment_ops)
             .field("dependencies", &self.dependencies)
             .finish()
     }
@@ -67,7 +102,9 @@ where
             groups: Vec::default(),
             inputs: Vec::default(),
             colors: Vec::default(),
+            resolves: Vec::default(),
             depth_stencil: None,
+            attachment_ops: Vec::default(),
             dependencies: Vec::default(),
         }
     }
@@ -128,6 +165,7 @@ where
     /// Add color attachment to the subpass.
     pub fn add_color(&mut self, color: ImageId) -> &mut Self {
         self.colors.push(Either::Left(color));
+        self.resolves.push(None);
         self
     }
 
@@ -140,6 +178,7 @@ where
     /// Add surface as color attachment to the subpass.
     pub fn add_color_surface(&mut self) -> &mut Self {
         self.colors.push(Either::Right(RenderPassSurface));
+        self.resolves.push(None);
         self
     }
 
@@ -149,6 +188,40 @@ where
         self
     }
 
+    /// Add multisampled color attachment to the subpass
+    /// that is resolved into single-sampled `resolve` image at the end of the subpass.
+    /// If any color attachment of the subpass is resolved then all of them must be.
+    pub fn add_color_resolve(&mut self, color: ImageId, resolve: ImageId) -> &mut Self {
+        self.colors.push(Either::Left(color));
+        self.resolves.push(Some(Either::Left(resolve)));
+        self
+    }
+
+    /// Add multisampled color attachment to the subpass
+    /// that is resolved into single-sampled `resolve` image at the end of the subpass.
+    /// If any color attachment of the subpass is resolved then all of them must be.
+    pub fn with_color_resolve(mut self, color: ImageId, resolve: ImageId) -> Self {
+        self.add_color_resolve(color, resolve);
+        self
+    }
+
+    /// Add multisampled color attachment to the subpass
+    /// that is resolved into the surface at the end of the subpass.
+    /// If any color attachment of the subpass is resolved then all of them must be.
+    pub fn add_color_resolve_surface(&mut self, color: ImageId) -> &mut Self {
+        self.colors.push(Either::Left(color));
+        self.resolves.push(Some(Either::Right(RenderPassSurface)));
+        self
+    }
+
+    /// Add multisampled color attachment to the subpass
+    /// that is resolved into the surface at the end of the subpass.
+    /// If any color attachment of the subpass is resolved then all of them must be.
+    pub fn with_color_resolve_surface(mut self, color: ImageId) -> Self {
+        self.add_color_resolve_surface(color);
+        self
+    }
+
     /// Set depth-stencil attachment to the subpass.
     pub fn set_depth_stencil(&mut self, depth_stencil: ImageId) -> &mut Self {
         self.depth_stencil = Some(Either::Left(depth_stencil));
@@ -173,6 +246,33 @@ where
         self
     }
 
+    /// Set load and store operations for the attachment image.
+    /// Overrides operations set with `GraphBuilder::set_attachment_ops`.
+    pub fn set_attachment_ops(&mut self, image: ImageId, ops: AttachmentOps) -> &mut Self {
+        self.attachment_ops.push((Either::Left(image), ops));
+        self
+    }
+
+    /// Set load and store operations for the attachment image.
+    /// Overrides operations set with `GraphBuilder::with_attachment_ops`.
+    pub fn with_attachment_ops(mut self, image: ImageId, ops: AttachmentOps) -> Self {
+        self.set_attachment_ops(image, ops);
+        self
+    }
+
+    /// Set load and store operations for the surface attachment.
+    pub fn set_surface_ops(&mut self, ops: AttachmentOps) -> &mut Self {
+        self.attachment_ops
+            .push((Either::Right(RenderPassSurface), ops));
+        self
+    }
+
+    /// Set load and store operations for the surface attachment.
+    pub fn with_surface_ops(mut self, ops: AttachmentOps) -> Self {
+        self.set_surface_ops(ops);
+        self
+    }
+
     /// Add dependency.
     /// `RenderPassNode` will be placed after its dependencies.
     pub fn add_dependency(&mut self, dependency: NodeId) -> &mut Self {
@@ -191,6 +291,25 @@ where
     pub fn into_pass(self) -> RenderPassNodeBuilder<B, T> {
         RenderPassNodeBuilder::new().with_subpass(self)
     }
+
+    /// Attachments used by the subpass.
+    fn attachments(&self) -> SubpassAttachments<'_> {
+        SubpassAttachments {
+            inputs: &self.inputs,
+            colors: &self.colors,
+            resolves: &self.resolves,
+            depth_stencil: self.depth_stencil,
+        }
+    }
+}
+
+/// Attachments used by a subpass.
+#[derive(Clone, Copy, Debug)]
+struct SubpassAttachments<'a> {
+    inputs: &'a [Attachment],
+    colors: &'a [Attachment],
+    resolves: &'a [Option<Attachment>],
+    depth_stencil: Option<Attachment>,
 }
 
 /// Builder for render-pass node.
@@ -201,6 +320,9 @@ pub struct RenderPassNodeBuilder<B: Backend, T: ?Sized> {
         rendy_core::hal::window::Extent2D,
         Option<rendy_core::hal::command::ClearValue>,
     )>,
+    render_area: Option<rendy_core::hal::pso::Rect>,
+    cost: usize,
+    optional: bool,
 }
 
 impl<B, T> std::fmt::Debug for RenderPassNodeBuilder<B, T>
@@ -212,6 +334,9 @@ where
         fmt.debug_struct("RenderPassNodeBuilder")
             .field("subpasses", &self.subpasses)
             .field("surface", &self.surface)
+            .field("render_area", &self.render_area)
+            .field("cost", &self.cost)
+            .field("optional", &self.optional)
             .finish()
     }
 }
@@ -225,6 +350,9 @@ where
         RenderPassNodeBuilder {
             subpasses: Vec::default(),
             surface: None,
+            render_area: None,
+            cost: 1,
+            optional: false,
         }
     }
 }
@@ -276,6 +404,54 @@ where
         self.add_surface(surface, suggested_extent, clear);
         self
     }
+
+    /// Limit rendering to the area of the framebuffer.
+    /// Area is clipped to framebuffer bounds, which follow graph resizes.
+    /// Render groups receive it as region to draw into.
+    /// Whole framebuffer is rendered by default.
+    pub fn set_render_area(&mut self, area: rendy_core::hal::pso::Rect) -> &mut Self {
+        self.render_area = Some(area);
+        self
+    }
+
+    /// Limit rendering to the area of the framebuffer.
+    /// Area is clipped to framebuffer bounds, which follow graph resizes.
+    /// Render groups receive it as region to draw into.
+    /// Whole framebuffer is rendered by default.
+    pub fn with_render_area(mut self, area: rendy_core::hal::pso::Rect) -> Self {
+        self.set_render_area(area);
+        self
+    }
+
+    /// Set estimated cost of executing the render pass relative to other nodes.
+    /// See `NodeBuilder::cost`.
+    pub fn set_cost(&mut self, cost: usize) -> &mut Self {
+        self.cost = cost;
+        self
+    }
+
+    /// Set estimated cost of executing the render pass relative to other nodes.
+    /// See `NodeBuilder::cost`.
+    pub fn with_cost(mut self, cost: usize) -> Self {
+        self.set_cost(cost);
+        self
+    }
+
+    /// Mark render pass as optional.
+    /// Optional render pass can be disabled in built graph.
+    /// Render pass with surface can't be optional.
+    pub fn set_optional(&mut self, optional: bool) -> &mut Self {
+        self.optional = optional;
+        self
+    }
+
+    /// Mark render pass as optional.
+    /// Optional render pass can be disabled in built graph.
+    /// Render pass with surface can't be optional.
+    pub fn with_optional(mut self, optional: bool) -> Self {
+        self.set_optional(optional);
+        self
+    }
 }
 
 impl<B, T> NodeBuilder<B, T> for RenderPassNodeBuilder<B, T>
@@ -288,20 +464,29 @@ where
     }
 
     fn buffers(&self) -> Vec<(BufferId, BufferAccess)> {
-        let empty = BufferAccess {
-            access: rendy_core::hal::buffer::Access::empty(),
-            usage: rendy_core::hal::buffer::Usage::empty(),
-            stages: rendy_core::hal::pso::PipelineStage::empty(),
-        };
-        let mut buffers = HashMap::new();
+        let mut buffers: HashMap<BufferId, BufferAccess> = HashMap::new();
 
         for subpass in &self.subpasses {
             for group in &subpass.groups {
                 for (index, access) in group.buffers() {
-                    let entry = buffers.entry(index).or_insert(empty);
-                    entry.access |= access.access;
-                    entry.usage |= access.usage;
-                    entry.stages |= access.stages;
+                    match buffers.get_mut(&index) {
+                        Some(entry) => {
+                            entry.access |= access.access;
+                            entry.usage |= access.usage;
+                            entry.stages |= access.stages;
+                            // Groups may access different regions of the buffer.
+                            // Pass uses region that covers all of them.
+                            entry.range = match (entry.range.take(), access.range) {
+                                (Some(left), Some(right)) => {
+                                    Some(left.start.min(right.start)..left.end.max(right.end))
+                                }
+                                _ => None,
+                            };
+                        }
+                        None => {
+                            buffers.insert(index, access);
+                        }
+                    }
                 }
             }
         }
@@ -341,6 +526,20 @@ where
                 entry.stages |= rendy_core::hal::pso::PipelineStage::COLOR_ATTACHMENT_OUTPUT;
             }
 
+            for &id in subpass
+                .resolves
+                .iter()
+                .filter_map(|e| e.as_ref().and_then(|e| e.as_ref().left()))
+            {
+                let entry = attachments.entry(id).or_insert(ImageAccess {
+                    layout: Layout::ColorAttachmentOptimal,
+                    ..empty
+                });
+                entry.access |= rendy_core::hal::image::Access::COLOR_ATTACHMENT_WRITE;
+                entry.usage |= rendy_core::hal::image::Usage::COLOR_ATTACHMENT;
+                entry.stages |= rendy_core::hal::pso::PipelineStage::COLOR_ATTACHMENT_OUTPUT;
+            }
+
             if let Some(id) = subpass.depth_stencil.and_then(Either::left) {
                 let entry = attachments.entry(id).or_insert(ImageAccess {
                     layout: Layout::DepthStencilAttachmentOptimal,
@@ -388,6 +587,18 @@ where
         dependencies
     }
 
+    fn cost(&self) -> usize {
+        self.cost
+    }
+
+    fn optional(&self) -> bool {
+        assert!(
+            !self.optional || self.surface.is_none(),
+            "Render pass with surface can't be optional"
+        );
+        self.optional
+    }
+
     fn build<'a>(
         self: Box<Self>,
         ctx: &GraphContext<B>,
@@ -421,6 +632,9 @@ where
                     .chain(subpass.colors.iter().inspect(|a| {
                         surface_color_usage = surface_color_usage || a.is_right();
                     }))
+                    .chain(subpass.resolves.iter().flatten().inspect(|a| {
+                        surface_color_usage = surface_color_usage || a.is_right();
+                    }))
                     .chain(subpass.depth_stencil.as_ref().into_iter().inspect(|a| {
                         surface_depth_usage = surface_depth_usage || a.is_right();
                     }))
@@ -429,6 +643,12 @@ where
             })
             .collect();
 
+        let subpass_attachments: Vec<_> = self
+            .subpasses
+            .iter()
+            .map(SubpassBuilder::attachments)
+            .collect();
+
         let mut surface_usage = rendy_core::hal::image::Usage::empty();
         if surface_color_usage {
             surface_usage |= rendy_core::hal::image::Usage::COLOR_ATTACHMENT;
@@ -453,117 +673,59 @@ where
                 .expect("Attachment image wasn't provided")
         };
 
-        let mut framebuffer_width = u32::max_value();
-        let mut framebuffer_height = u32::max_value();
-        let mut framebuffer_layers = u16::max_value();
-
-        let mut node_target = None;
-
-        log::trace!("Configure attachments");
-
-        let views: Vec<_> = attachments
-            .iter()
-            .map(|&attachment| -> Result<Vec<_>, NodeBuildError> {
-                match attachment {
-                    Either::Left(image_id) => {
-                        log::debug!("Image {:?} attachment", image_id);
-
-                        let node_image = find_attachment_node_image(image_id);
-                        let image = ctx.get_image(image_id).expect("Image does not exist");
-                        let extent = image.kind().extent();
-                        framebuffer_width = min(framebuffer_width, extent.width);
-                        framebuffer_height = min(framebuffer_height, extent.height);
-                        framebuffer_layers = min(
-                            framebuffer_layers,
-                            node_image.range.layers.end - node_image.range.layers.start,
-                        );
-                        Ok(vec![unsafe {
-                            factory
-                                .device()
-                                .create_image_view(
-                                    image.raw(),
-                                    rendy_core::hal::image::ViewKind::D2,
-                                    image.format(),
-                                    rendy_core::hal::format::Swizzle::NO,
-                                    rendy_core::hal::image::SubresourceRange {
-                                        // NOTE: Framebuffer must always be created with only one mip level. If image contains multiple levels,
-                                        // only the first one is bound as an attachment.
-                                        // TODO: Allow customizing this behaviour to choose which level to bind.
-                                        levels: 0 .. 1,
-                                        ..node_image.range.clone()
-                                    }
-                                )
-                                .map_err(NodeBuildError::View)?
-                            }])
-                    },
-                    Either::Right(RenderPassSurface) => {
-                        log::trace!("Surface attachment");
-
-                        let surface = surface.take().expect("Render pass should be configured with Surface instance if at least one subpass uses surface attachment");
-                        let surface_extent = unsafe {
-                            surface.extent(factory.physical()).unwrap_or(suggested_extent.expect("Must be set with surface"))
-                        };
+        let node_target = if attachments.contains(&Either::Right(RenderPassSurface)) {
+            log::trace!("Surface attachment");
 
-                        log::debug!("Surface extent {:#?}", surface_extent);
+            let surface = surface.take().expect("Render pass should be configured with Surface instance if at least one subpass uses surface attachment");
+            let surface_extent = unsafe {
+                surface
+                    .extent(factory.physical())
+                    .unwrap_or(suggested_extent.expect("Must be set with surface"))
+            };
 
-                        if !factory.surface_support(family.id(), &surface) {
-                            log::warn!("Surface {:?} presentation is unsupported by family {:?} bound to the node", surface, family);
-                            return Err(NodeBuildError::QueueFamily(family.id()));
-                        }
+            log::debug!("Surface extent {:#?}", surface_extent);
 
-                        let caps = factory.get_surface_capabilities(&surface);
+            if !factory.surface_support(family.id(), &surface) {
+                log::warn!(
+                    "Surface {:?} presentation is unsupported by family {:?} bound to the node",
+                    surface,
+                    family
+                );
+                return Err(NodeBuildError::QueueFamily(family.id()));
+            }
 
-                        let present_mode = match () {
-                            _ if caps.present_modes.contains(PresentMode::FIFO) => PresentMode::FIFO,
-                            _ if caps.present_modes.contains(PresentMode::MAILBOX) => PresentMode::MAILBOX,
-                            _ if caps.present_modes.contains(PresentMode::RELAXED) => PresentMode::RELAXED,
-                            _ if caps.present_modes.contains(PresentMode::IMMEDIATE) => PresentMode::IMMEDIATE,
-                            _ => panic!("No known present modes found"),
-                        };
+            let caps = factory.get_surface_capabilities(&surface);
 
-                        let img_count_caps = caps.image_count;
-                        let image_count = 3.min(*img_count_caps.end()).max(*img_count_caps.start());
+            let present_mode = match () {
+                _ if caps.present_modes.contains(PresentMode::FIFO) => PresentMode::FIFO,
+                _ if caps.present_modes.contains(PresentMode::MAILBOX) => PresentMode::MAILBOX,
+                _ if caps.present_modes.contains(PresentMode::RELAXED) => PresentMode::RELAXED,
+                _ if caps.present_modes.contains(PresentMode::IMMEDIATE) => PresentMode::IMMEDIATE,
+                _ => panic!("No known present modes found"),
+            };
 
-                        let target = factory
-                            .create_target(
-                                surface,
-                                surface_extent,
-                                image_count,
-                                present_mode,
-                                surface_usage,
-                            )
-                            .map_err(NodeBuildError::Swapchain)?;
+            let img_count_caps = caps.image_count;
+            let image_count = 3.min(*img_count_caps.end()).max(*img_count_caps.start());
 
-                        framebuffer_width = min(framebuffer_width, target.extent().width);
-                        framebuffer_height = min(framebuffer_height, target.extent().height);
-                        framebuffer_layers = min(
-                            framebuffer_layers,
-                            target.backbuffer()[0].layers(),
-                        );
+            Some(
+                factory
+                    .create_target(
+                        surface,
+                        surface_extent,
+                        image_count,
+                        present_mode,
+                        surface_usage,
+                    )
+                    .map_err(NodeBuildError::Swapchain)?,
+            )
+        } else {
+            None
+        };
 
-                        let views = target.backbuffer().iter().map(|image| unsafe {
-                            factory
-                                .device()
-                                .create_image_view(
-                                    image.raw(),
-                                    rendy_core::hal::image::ViewKind::D2,
-                                    image.format(),
-                                    rendy_core::hal::format::Swizzle::NO,
-                                    rendy_core::hal::image::SubresourceRange {
-                                        aspects: image.format().surface_desc().aspects,
-                                        levels: 0 .. 1,
-                                        layers: 0 .. 1,
-                                    },
-                                )
-                                .map_err(NodeBuildError::View)
-                        }).collect::<Result<Vec<_>, NodeBuildError>>()?;
+        log::trace!("Configure attachments");
 
-                        node_target = Some(target);
-                        Ok(views)
-                    }
-                }
-            }).collect::<Result<Vec<_>, _>>()?
-            .into_iter().flatten().collect();
+        let (views, framebuffer_width, framebuffer_height, framebuffer_layers) =
+            unsafe { create_views(ctx, factory, &attachments, &images, node_target.as_ref())? };
 
         log::trace!("Configure render pass instance");
 
@@ -571,7 +733,16 @@ where
             let pass_attachments: Vec<_> = attachments
                 .iter()
                 .map(|&attachment| {
-                    let (format, clear, layout, samples) = match attachment {
+                    use rendy_core::hal::pass::{AttachmentLoadOp, AttachmentStoreOp};
+
+                    let configured = self
+                        .subpasses
+                        .iter()
+                        .flat_map(|subpass| &subpass.attachment_ops)
+                        .filter(|&&(a, _)| a == attachment)
+                        .fold(AttachmentOps::default(), |acc, &(_, ops)| acc.or(ops));
+
+                    let (format, clear, layout, samples, ops, preserve) = match attachment {
                         Either::Left(image_id) => {
                             let node_image = find_attachment_node_image(image_id);
                             let image = ctx.get_image(image_id).expect("Image does not exist");
@@ -580,6 +751,8 @@ where
                                 node_image.clear,
                                 node_image.layout,
                                 image.kind().num_samples(),
+                                configured.or(ctx.get_attachment_ops(image_id)),
+                                node_image.preserve,
                             )
                         }
                         Either::Right(RenderPassSurface) => (
@@ -591,29 +764,64 @@ where
                             surface_clear,
                             rendy_core::hal::image::Layout::Present,
                             1,
+                            configured,
+                            // Surface image is presented after the render pass.
+                            true,
                         ),
                     };
 
-                    rendy_core::hal::pass::Attachment {
+                    let load = ops.load.unwrap_or_else(|| {
+                        if clear.is_some() {
+                            AttachmentLoadOp::Clear
+                        } else if resolve_only(&subpass_attachments, attachment) {
+                            // Resolve overwrites whole attachment.
+                            AttachmentLoadOp::DontCare
+                        } else {
+                            AttachmentLoadOp::Load
+                        }
+                    });
+                    let store = ops.store.unwrap_or(if preserve {
+                        AttachmentStoreOp::Store
+                    } else {
+                        AttachmentStoreOp::DontCare
+                    });
+                    let stencil_ops = if format
+                        .surface_desc()
+                        .aspects
+                        .contains(rendy_core::hal::format::Aspects::STENCIL)
+                    {
+                        rendy_core::hal::pass::AttachmentOps {
+                            load: ops.stencil_load.unwrap_or(load),
+                            store: ops.stencil_store.unwrap_or(store),
+                        }
+                    } else {
+                        rendy_core::hal::pass::AttachmentOps::DONT_CARE
+                    };
+
+                    if clear.is_none()
+                        && (load == AttachmentLoadOp::Clear
+                            || stencil_ops.load == AttachmentLoadOp::Clear)
+                    {
+                        return Err(NodeBuildError::MissingClearValue(attachment.left()));
+                    }
+
+                    // Previous content is not needed unless it is loaded.
+                    let discard = load != AttachmentLoadOp::Load
+                        && stencil_ops.load != AttachmentLoadOp::Load;
+
+                    Ok(rendy_core::hal::pass::Attachment {
                         format: Some(format),
-                        ops: rendy_core::hal::pass::AttachmentOps {
-                            load: if clear.is_some() {
-                                rendy_core::hal::pass::AttachmentLoadOp::Clear
-                            } else {
-                                rendy_core::hal::pass::AttachmentLoadOp::Load
-                            },
-                            store: rendy_core::hal::pass::AttachmentStoreOp::Store,
-                        },
-                        stencil_ops: rendy_core::hal::pass::AttachmentOps::DONT_CARE,
-                        layouts: if clear.is_some() {
+                        ops: rendy_core::hal::pass::AttachmentOps { load, store },
+                        stencil_ops,
+                        layouts: if discard {
                             rendy_core::hal::image::Layout::Undefined..layout
                         } else {
                             layout..layout
                         },
                         samples,
-                    }
+                    })
                 })
-                .collect();
+                .collect::<Result<_, _>>()?;
 
             log::debug!("Attachments {:#?}", pass_attachments);
 
@@ -622,59 +830,76 @@ where
                 inputs: Vec<(usize, Layout)>,
                 colors: Vec<(usize, Layout)>,
                 depth_stencil: Option<(usize, Layout)>,
+                resolves: Vec<(usize, Layout)>,
+                preserves: Vec<usize>,
             }
 
+            let position =
+                |attachment: Attachment| attachments.iter().position(|&a| a == attachment).unwrap();
+
             let subpasses: Vec<_> = self
                 .subpasses
                 .iter()
-                .map(|subpass| OwningSubpassDesc {
+                .enumerate()
+                .map(|(index, subpass)| OwningSubpassDesc {
                     inputs: subpass
                         .inputs
                         .iter()
                         .map(|&i| {
-                            (
-                                attachments.iter().position(|&a| a == i).unwrap(),
-                                match i {
-                                    Either::Left(image_id) => {
-                                        find_attachment_node_image(image_id).layout
-                                    }
-                                    Either::Right(RenderPassSurface) => {
-                                        rendy_core::hal::image::Layout::ShaderReadOnlyOptimal
-                                    }
-                                },
-                            )
+                            // Attachment that is also written by the subpass
+                            // must be in layout suitable for both.
+                            let layout = if subpass.colors.contains(&i)
+                                || subpass.depth_stencil == Some(i)
+                            {
+                                Layout::General
+                            } else {
+                                Layout::ShaderReadOnlyOptimal
+                            };
+                            (position(i), layout)
                         })
                         .collect(),
                     colors: subpass
                         .colors
                         .iter()
                         .map(|&c| {
-                            (
-                                attachments.iter().position(|&a| a == c).unwrap(),
-                                match c {
-                                    Either::Left(image_id) => {
-                                        find_attachment_node_image(image_id).layout
-                                    }
-                                    Either::Right(RenderPassSurface) => {
-                                        rendy_core::hal::image::Layout::ColorAttachmentOptimal
-                                    }
-                                },
-                            )
+                            let layout = if subpass.inputs.contains(&c) {
+                                Layout::General
+                            } else {
+                                Layout::ColorAttachmentOptimal
+                            };
+                            (position(c), layout)
                         })
                         .collect(),
                     depth_stencil: subpass.depth_stencil.map(|ds| {
-                        (
-                            attachments.iter().position(|&a| a == ds).unwrap(),
-                            match ds {
-                                Either::Left(image_id) => {
-                                    find_attachment_node_image(image_id).layout
-                                }
-                                Either::Right(RenderPassSurface) => {
-                                    rendy_core::hal::image::Layout::DepthStencilAttachmentOptimal
-                                }
-                            },
-                        )
+                        let layout = if subpass.inputs.contains(&ds) {
+                            Layout::General
+                        } else {
+                            Layout::DepthStencilAttachmentOptimal
+                        };
+                        (position(ds), layout)
                     }),
+                    resolves: if subpass.resolves.iter().any(Option::is_some) {
+                        assert!(
+                            subpass.resolves.iter().all(Option::is_some),
+                            "Either all or none of subpass color attachments must be resolved"
+                        );
+                        subpass
+                            .resolves
+                            .iter()
+                            .flatten()
+                            .map(|&r| (position(r), Layout::ColorAttachmentOptimal))
+                            .collect()
+                    } else {
+                        Vec::new()
+                    },
+                    preserves: attachments
+                        .iter()
+                        .enumerate()
+                        .filter(|&(_, &attachment)| {
+                            preserve_attachment(&subpass_attachments, index, attachment)
+                        })
+                        .map(|(attachment_index, _)| attachment_index)
+                        .collect(),
                 })
                 .collect();
 
@@ -686,22 +911,18 @@ where
                     inputs: &subpass.inputs[..],
                     colors: &subpass.colors[..],
                     depth_stencil: subpass.depth_stencil.as_ref(),
-                    resolves: &[],
-                    preserves: &[],
+                    resolves: &subpass.resolves[..],
+                    preserves: &subpass.preserves[..],
                 })
                 .collect();
 
+            let dependencies = subpass_dependencies(&subpass_attachments, &attachments);
+            log::debug!("Subpass dependencies {:#?}", dependencies);
+
             let result = unsafe {
                 factory
                     .device()
-                    .create_render_pass(pass_attachments, subpasses, {
-                        assert_eq!(
-                            self.subpasses.len(),
-                            1,
-                            "TODO: Implement subpass dependencies to allow more than one subpass"
-                        );
-                        std::iter::empty::<rendy_core::hal::pass::SubpassDependency>()
-                    })
+                    .create_render_pass(pass_attachments, subpasses, dependencies)
             }
             .unwrap();
 
@@ -709,106 +930,38 @@ where
             result
         };
 
-        log::trace!(
-            "Create {} framebuffers",
-            views.len() - attachments.len() + 1
-        );
-
-        // Swapchain image views, if any, are last ones.
-        let mut framebuffers = (attachments.len() - 1..views.len())
-            .map(|i| unsafe {
-                log::trace!(
-                    "Create framebuffer for views {}..{} and {}",
-                    0,
-                    attachments.len() - 1,
-                    i,
-                );
-                factory
-                    .device()
-                    .create_framebuffer(
-                        &render_pass,
-                        views[..attachments.len() - 1].iter().chain(Some(&views[i])),
-                        rendy_core::hal::image::Extent {
-                            width: framebuffer_width,
-                            height: framebuffer_height,
-                            depth: framebuffer_layers as u32, // This is gfx-hal BUG as this parameter actually means framebuffer layers number,
-                        },
-                    )
-                    .map_err(NodeBuildError::OutOfMemory)
-            })
-            .collect::<Result<Vec<_>, _>>()?;
+        let mut framebuffers = unsafe {
+            create_framebuffers(
+                factory,
+                &render_pass,
+                &views,
+                attachments.len(),
+                framebuffer_width,
+                framebuffer_height,
+                framebuffer_layers,
+            )?
+        };
 
         log::trace!("Collect clears for render pass");
 
+        // Clear values are indexed by attachment number.
+        // Values of attachments that aren't cleared are ignored.
         let clears: Vec<_> = attachments
             .iter()
-            .filter_map(|&a| match a {
-                Either::Left(image_id) => find_attachment_node_image(image_id).clear,
-                Either::Right(RenderPassSurface) => surface_clear,
+            .map(|&a| {
+                match a {
+                    Either::Left(image_id) => find_attachment_node_image(image_id).clear,
+                    Either::Right(RenderPassSurface) => surface_clear,
+                }
+                .unwrap_or(rendy_core::hal::command::ClearValue {
+                    color: rendy_core::hal::command::ClearColor { float32: [0.0; 4] },
+                })
             })
-            .map(Into::into)
             .collect();
 
-        let mut command_pool = factory
-            .create_command_pool(family)
-            .map_err(NodeBuildError::OutOfMemory)?
-            .with_capability()
+        let pools = FrameCommandPools::with_capability(family)
             .expect("Graph must specify family that supports `Graphics`");
 
-        let command_cirque = CommandCirque::new();
-
-        let acquire = if uses_pipeline_barriers::<B>(factory.device()) {
-            let (stages, barriers) = gfx_acquire_barriers(ctx, &buffers, &images);
-
-            if !barriers.is_empty() {
-                let initial = command_pool.allocate_buffers(1).pop().unwrap();
-                let mut recording = initial.begin(MultiShot(SimultaneousUse), ());
-                log::debug!("Acquire {:?} : {:#?}", stages, barriers);
-                unsafe {
-                    recording.encoder().pipeline_barrier(
-                        stages,
-                        rendy_core::hal::memory::Dependencies::empty(),
-                        barriers,
-                    );
-                }
-                let (acquire_submit, acquire_buffer) = recording.finish().submit();
-                Some(BarriersCommands {
-                    buffer: acquire_buffer,
-                    submit: acquire_submit,
-                })
-            } else {
-                None
-            }
-        } else {
-            None
-        };
-
-        let release = if uses_pipeline_barriers::<B>(factory.device()) {
-            let (stages, barriers) = gfx_release_barriers(ctx, &buffers, &images);
-
-            if !barriers.is_empty() {
-                let initial = command_pool.allocate_buffers(1).pop().unwrap();
-                let mut recording = initial.begin(MultiShot(SimultaneousUse), ());
-                log::debug!("Release {:?} : {:#?}", stages, barriers);
-                unsafe {
-                    recording.encoder().pipeline_barrier(
-                        stages,
-                        rendy_core::hal::memory::Dependencies::empty(),
-                        barriers,
-                    );
-                }
-                let (release_submit, release_buffer) = recording.finish().submit();
-                Some(BarriersCommands {
-                    buffer: release_buffer,
-                    submit: release_submit,
-                })
-            } else {
-                None
-            }
-        } else {
-            None
-        };
-
         let subpasses = self
             .subpasses
             .into_iter()
@@ -816,6 +969,7 @@ where
             .map(|(index, subpass)| {
                 let subpass_colors = subpass.colors.len();
                 let subpass_depth = subpass.depth_stencil.is_some();
+                let subpass_samples = subpass_samples(ctx, &subpass);
 
                 subpass
                     .groups
@@ -857,6 +1011,7 @@ where
                             aux,
                             framebuffer_width,
                             framebuffer_height,
+                            subpass_samples,
                             rendy_core::hal::pass::Subpass {
                                 index,
                                 main_pass: &render_pass,
@@ -881,16 +1036,16 @@ where
                         framebuffer_width,
                         framebuffer_height,
                         _framebuffer_layers: framebuffer_layers,
+                        render_area: self.render_area,
 
                         render_pass,
+                        attachments,
                         views,
                         clears,
 
-                        command_pool,
-                        command_cirque,
-
-                        acquire,
-                        release,
+                        pools,
+                        buffers,
+                        images,
 
                         relevant: relevant::Relevant,
                     },
@@ -901,11 +1056,12 @@ where
                             framebuffer: fb,
                             acquire: factory.create_semaphore().unwrap(),
                             release: factory.create_semaphore().unwrap(),
-                            index: 0,
                         })
                         .collect(),
                     free_acquire: factory.create_semaphore().unwrap(),
                     target,
+                    outdated: false,
+                    resized: false,
                 })
             }
             None => {
@@ -917,16 +1073,16 @@ where
                         framebuffer_width,
                         framebuffer_height,
                         _framebuffer_layers: framebuffer_layers,
+                        render_area: self.render_area,
 
                         render_pass,
+                        attachments,
                         views,
                         clears,
 
-                        command_pool,
-                        command_cirque,
-
-                        acquire,
-                        release,
+                        pools,
+                        buffers,
+                        images,
 
                         relevant: relevant::Relevant,
                     },
@@ -960,45 +1116,25 @@ where
     }
 }
 
-struct BarriersCommands<B: Backend> {
-    submit: Submit<B, SimultaneousUse, SecondaryLevel>,
-    buffer: CommandBuffer<
-        B,
-        Graphics,
-        PendingState<ExecutableState<MultiShot<SimultaneousUse>>>,
-        SecondaryLevel,
-        IndividualReset,
-    >,
-}
-
-impl<B> std::fmt::Debug for BarriersCommands<B>
-where
-    B: Backend,
-{
-    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        fmt.debug_struct("BarriersCommands")
-            .field("submit", &self.submit)
-            .field("buffer", &self.buffer)
-            .finish()
-    }
-}
-
 struct RenderPassNodeCommon<B: Backend, T: ?Sized> {
     subpasses: Vec<SubpassNode<B, T>>,
 
     framebuffer_width: u32,
     framebuffer_height: u32,
     _framebuffer_layers: u16,
+    render_area: Option<rendy_core::hal::pso::Rect>,
 
     render_pass: B::RenderPass,
+    attachments: Vec<Attachment>,
     views: Vec<B::ImageView>,
     clears: Vec<rendy_core::hal::command::ClearValue>,
 
-    command_pool: CommandPool<B, Graphics, IndividualReset>,
-    command_cirque: CommandCirque<B, Graphics>,
+    /// Commands are recorded each frame.
+    pools: FrameCommandPools<B, Graphics>,
 
-    acquire: Option<BarriersCommands<B>>,
-    release: Option<BarriersCommands<B>>,
+    /// Resources to record barriers for.
+    buffers: Vec<NodeBuffer>,
+    images: Vec<NodeImage>,
 
     relevant: relevant::Relevant,
 }
@@ -1014,13 +1150,14 @@ where
             .field("framebuffer_width", &self.framebuffer_width)
             .field("framebuffer_height", &self.framebuffer_height)
             .field("_framebuffer_layers", &self._framebuffer_layers)
+            .field("render_area", &self.render_area)
             .field("render_pass", &self.render_pass)
+            .field("attachments", &self.attachments)
             .field("views", &self.views)
             .field("clears", &self.clears)
-            .field("command_pool", &self.command_pool)
-            .field("command_cirque", &self.command_cirque)
-            .field("acquire", &self.acquire)
-            .field("release", &self.release)
+            .field("pools", &self.pools)
+            .field("buffers", &self.buffers)
+            .field("images", &self.images)
             .field("relevant", &self.relevant)
             .finish()
     }
@@ -1031,35 +1168,169 @@ where
     B: Backend,
     T: ?Sized,
 {
-    unsafe fn dispose(mut self, factory: &mut Factory<B>, aux: &T) {
+    /// Record commands for next frame.
+    /// Only barriers are recorded if `framebuffer` is `None`.
+    unsafe fn record(
+        &mut self,
+        ctx: &GraphContext<B>,
+        factory: &Factory<B>,
+        queue: QueueId,
+        aux: &T,
+        frames: &Frames<B>,
+        framebuffer: Option<&B::Framebuffer>,
+    ) -> Submit<B, NoSimultaneousUse> {
+        let RenderPassNodeCommon {
+            subpasses,
+
+            framebuffer_width,
+            framebuffer_height,
+            render_area,
+
+            render_pass,
+            clears,
+
+            pools,
+            buffers,
+            images,
+            ..
+        } = self;
+
+        // Groups' resources are reused once frame that used them is complete.
+        let index = (frames.next().index() % u64::from(ctx.frames_in_flight)) as usize;
+
+        let mut cbuf = pools
+            .allocate(factory.device(), frames)
+            .expect("Failed to allocate render pass commands")
+            .begin(OneShot, ());
+        let mut encoder = cbuf.encoder();
+
+        if uses_pipeline_barriers::<B>(factory.device()) {
+            let (stages, barriers) = gfx_acquire_barriers(ctx, &*buffers, &*images);
+            encode_barriers(&mut encoder, "Acquire", stages, barriers);
+        }
+
+        if let Some(framebuffer) = framebuffer {
+            for (subpass_index, subpass) in subpasses.iter_mut().enumerate() {
+                for group in subpass.groups.iter_mut() {
+                    // Commands are recorded each frame regardless of the result.
+                    let _ = group.prepare(
+                        factory,
+                        queue,
+                        index,
+                        rendy_core::hal::pass::Subpass {
+                            index: subpass_index,
+                            main_pass: &render_pass,
+                        },
+                        aux,
+                    );
+                }
+            }
+
+            let area = clip_render_area(*render_area, *framebuffer_width, *framebuffer_height);
+
+            let mut pass_encoder =
+                encoder.begin_render_pass_inline(&render_pass, framebuffer, area, &clears);
+
+            for (subpass_index, subpass) in subpasses.iter_mut().enumerate() {
+                if subpass_index > 0 {
+                    pass_encoder = pass_encoder.next_subpass_inline();
+                }
+                for group in subpass.groups.iter_mut() {
+                    group.draw_inline(
+                        pass_encoder.reborrow(),
+                        index,
+                        rendy_core::hal::pass::Subpass {
+                            index: subpass_index,
+                            main_pass: &render_pass,
+                        },
+                        area,
+                        aux,
+                    )
+                }
+            }
+
+            drop(pass_encoder);
+        }
+
+        if uses_pipeline_barriers::<B>(factory.device()) {
+            let (stages, barriers) = gfx_release_barriers(ctx, &*buffers, &*images);
+            encode_barriers(&mut encoder, "Release", stages, barriers);
+        }
+
+        let (submit, buffer) = cbuf.finish().submit_once();
+        pools.release(frames.next_frame(), buffer);
+        submit
+    }
+
+    /// Recreate attachment views, barriers and update groups for new attachments.
+    /// Framebuffers must be recreated afterwards.
+    unsafe fn resize(
+        &mut self,
+        ctx: &GraphContext<B>,
+        factory: &Factory<B>,
+        aux: &T,
+        buffers: &[NodeBuffer],
+        images: &[NodeImage],
+        target: Option<&Target<B>>,
+        resized: bool,
+    ) -> Result<(), NodeBuildError> {
+        self.buffers = buffers.to_vec();
+        self.images = images.to_vec();
+
+        for view in self.views.drain(..) {
+            factory.device().destroy_image_view(view);
+        }
+
+        let (views, framebuffer_width, framebuffer_height, framebuffer_layers) =
+            create_views(ctx, factory, &self.attachments, images, target)?;
+        self.views = views;
+        self.framebuffer_width = framebuffer_width;
+        self.framebuffer_height = framebuffer_height;
+        self._framebuffer_layers = framebuffer_layers;
+
+        for group in self
+            .subpasses
+            .iter_mut()
+            .flat_map(|subpass| subpass.groups.iter_mut())
+        {
+            if !group.resize(
+                ctx,
+                factory,
+                aux,
+                framebuffer_width,
+                framebuffer_height,
+                resized,
+            ) {
+                return Err(NodeBuildError::ResizeUnsupported);
+            }
+        }
+
+        Ok(())
+    }
+
+    unsafe fn create_framebuffers(
+        &self,
+        factory: &Factory<B>,
+    ) -> Result<Vec<B::Framebuffer>, NodeBuildError> {
+        create_framebuffers(
+            factory,
+            &self.render_pass,
+            &self.views,
+            self.attachments.len(),
+            self.framebuffer_width,
+            self.framebuffer_height,
+            self._framebuffer_layers,
+        )
+    }
+
+    unsafe fn dispose(self, factory: &mut Factory<B>, aux: &T) {
         self.relevant.dispose();
         for subpass in self.subpasses {
             for group in subpass.groups {
                 group.dispose(factory, aux)
             }
         }
-        let pool = &mut self.command_pool;
-        self.command_cirque.dispose(|buffer| {
-            buffer.either_with(
-                &mut *pool,
-                |pool, executable| pool.free_buffers(Some(executable)),
-                |pool, pending| {
-                    let executable = pending.mark_complete();
-                    pool.free_buffers(Some(executable))
-                },
-            );
-        });
-        if let Some(BarriersCommands { submit, buffer }) = self.acquire.take() {
-            drop(submit);
-            let executable = buffer.mark_complete();
-            pool.free_buffers(Some(executable));
-        }
-        if let Some(BarriersCommands { submit, buffer }) = self.release.take() {
-            drop(submit);
-            let executable = buffer.mark_complete();
-            pool.free_buffers(Some(executable));
-        }
-        factory.destroy_command_pool(self.command_pool.with_queue_type());
+        self.pools.dispose(factory.device());
 
         for view in self.views {
             factory.device().destroy_image_view(view);
@@ -1073,7 +1344,6 @@ struct PerImage<B: Backend> {
     framebuffer: B::Framebuffer,
     acquire: B::Semaphore,
     release: B::Semaphore,
-    index: usize,
 }
 
 struct RenderPassNodeWithSurface<B: Backend, T: ?Sized> {
@@ -1081,6 +1351,10 @@ struct RenderPassNodeWithSurface<B: Backend, T: ?Sized> {
     per_image: Vec<PerImage<B>>,
     free_acquire: B::Semaphore,
     target: Target<B>,
+    /// Swapchain must be recreated before next acquisition.
+    outdated: bool,
+    /// Transient images were resized while swapchain was outdated.
+    resized: bool,
 }
 
 impl<B, T> std::fmt::Debug for RenderPassNodeWithSurface<B, T>
@@ -1094,10 +1368,86 @@ where
             .field("per_image", &self.per_image)
             .field("free_acquire", &self.free_acquire)
             .field("target", &self.target)
+            .field("outdated", &self.outdated)
+            .field("resized", &self.resized)
             .finish()
     }
 }
 
+impl<B, T> RenderPassNodeWithSurface<B, T>
+where
+    B: Backend,
+    T: ?Sized,
+{
+    /// Recreate swapchain and everything that depends on it.
+    /// If surface is unavailable, e.g. window is minimized,
+    /// node is marked outdated and skips rendering until next successful recreation.
+    ///
+    /// # Safety
+    ///
+    /// Swapchain and framebuffers must not be in use.
+    unsafe fn recreate(
+        &mut self,
+        ctx: &GraphContext<B>,
+        factory: &Factory<B>,
+        aux: &T,
+        extent: rendy_core::hal::window::Extent2D,
+        resized: bool,
+    ) -> Result<(), NodeBuildError> {
+        self.resized |= resized;
+
+        for per_image in self.per_image.drain(..) {
+            factory.device().destroy_framebuffer(per_image.framebuffer);
+            factory.destroy_semaphore(per_image.acquire);
+            factory.destroy_semaphore(per_image.release);
+        }
+
+        // Each surface keeps its own extent when graph has many of them.
+        let extent = self
+            .target
+            .surface()
+            .extent(factory.physical())
+            .unwrap_or(extent);
+
+        if let Err(err) = self
+            .target
+            .recreate(factory.physical(), factory.device(), extent)
+        {
+            log::debug!("Failed to recreate swapchain: {:?}", err);
+            // Barriers are still submitted while rendering is skipped.
+            self.outdated = true;
+            return Ok(());
+        }
+        self.outdated = false;
+
+        let buffers = self.common.buffers.clone();
+        let images = self.common.images.clone();
+        self.common.resize(
+            ctx,
+            factory,
+            aux,
+            &buffers,
+            &images,
+            Some(&self.target),
+            self.resized,
+        )?;
+        self.resized = false;
+
+        self.per_image = self
+            .common
+            .create_framebuffers(factory)?
+            .into_iter()
+            .map(|framebuffer| PerImage {
+                framebuffer,
+                acquire: factory.create_semaphore().unwrap(),
+                release: factory.create_semaphore().unwrap(),
+            })
+            .collect();
+
+        Ok(())
+    }
+}
+
 impl<B, T> DynNode<B, T> for RenderPassNodeWithSurface<B, T>
 where
     B: Backend,
@@ -1105,7 +1455,7 @@ where
 {
     unsafe fn run<'a>(
         &mut self,
-        _ctx: &GraphContext<B>,
+        ctx: &GraphContext<B>,
         factory: &Factory<B>,
         queue: &mut Queue<B>,
         aux: &T,
@@ -1114,27 +1464,24 @@ where
         signals: &[&'a B::Semaphore],
         fence: Option<&mut Fence<B>>,
     ) {
-        let RenderPassNodeWithSurface {
-            common:
-                RenderPassNodeCommon {
-                    subpasses,
-
-                    framebuffer_width,
-                    framebuffer_height,
-
-                    render_pass,
-                    clears,
-
-                    command_cirque,
-                    command_pool,
+        // Don't stall the device while surface is minimized.
+        if self.outdated && surface_usable(factory, self.target.surface()) {
+            // TODO: use retired swapchains once available in hal and remove that wait
+            factory.wait_idle().unwrap();
+
+            let extent = self.target.extent();
+            if let Err(err) = self.recreate(ctx, factory, aux, extent, false) {
+                log::warn!("Failed to recreate render pass for surface: {:?}", err);
+            }
+        }
 
-                    acquire,
-                    release,
-                    ..
-                },
+        let RenderPassNodeWithSurface {
+            common,
             target,
             free_acquire,
             per_image,
+            outdated,
+            ..
         } = self;
 
         let next = match target.next_image(&free_acquire) {
@@ -1145,97 +1492,24 @@ where
             }
             Err(err) => {
                 log::debug!("Swapchain acquisition error: {:#?}", err);
+                if let rendy_core::hal::window::AcquireError::OutOfDate = err {
+                    *outdated = true;
+                }
                 None
             }
         };
 
-        let submit = command_cirque.encode(frames, command_pool, |mut cbuf| {
-            let index = cbuf.index();
-
-            if let Some(next) = &next {
-                let ref mut for_image = per_image[next[0] as usize];
-
-                let force_record = subpasses.iter_mut().enumerate().fold(
-                    false,
-                    |force_record, (subpass_index, subpass)| {
-                        subpass
-                            .groups
-                            .iter_mut()
-                            .fold(force_record, |force_record, group| {
-                                group
-                                    .prepare(
-                                        factory,
-                                        queue.id(),
-                                        index,
-                                        rendy_core::hal::pass::Subpass {
-                                            index: subpass_index,
-                                            main_pass: &render_pass,
-                                        },
-                                        aux,
-                                    )
-                                    .force_record()
-                                    || force_record
-                            })
-                    },
-                );
-
-                if force_record || for_image.index != index {
-                    for_image.index = index;
-                    cbuf = CirqueRef::Initial(cbuf.or_reset(|cbuf| cbuf.reset()));
-                }
-            }
-
-            cbuf.or_init(|cbuf| {
-                let mut cbuf = cbuf.begin(MultiShot(NoSimultaneousUse), ());
-                let mut encoder = cbuf.encoder();
-
-                if let Some(barriers) = &acquire {
-                    encoder.execute_commands(std::iter::once(&barriers.submit));
-                }
-
-                if let Some(next) = &next {
-                    let ref mut for_image = per_image[next[0] as usize];
-
-                    let area = rendy_core::hal::pso::Rect {
-                        x: 0,
-                        y: 0,
-                        w: *framebuffer_width as _,
-                        h: *framebuffer_height as _,
-                    };
-
-                    let mut pass_encoder = encoder.begin_render_pass_inline(
-                        &render_pass,
-                        &for_image.framebuffer,
-                        area,
-                        &clears,
-                    );
-
-                    subpasses
-                        .iter_mut()
-                        .enumerate()
-                        .for_each(|(subpass_index, subpass)| {
-                            subpass.groups.iter_mut().for_each(|group| {
-                                group.draw_inline(
-                                    pass_encoder.reborrow(),
-                                    index,
-                                    rendy_core::hal::pass::Subpass {
-                                        index: subpass_index,
-                                        main_pass: &render_pass,
-                                    },
-                                    aux,
-                                )
-                            })
-                        });
-
-                    drop(pass_encoder);
-                }
-
-                if let Some(barriers) = &release {
-                    encoder.execute_commands(std::iter::once(&barriers.submit));
-                }
-                cbuf.finish()
-            })
-        });
+        // Rendering is skipped and only barriers are recorded
+        // if image wasn't acquired.
+        let submit = common.record(
+            ctx,
+            factory,
+            queue.id(),
+            aux,
+            frames,
+            next.as_ref()
+                .map(|next| &per_image[next[0] as usize].framebuffer),
+        );
 
         log::trace!("Submit render pass");
 
@@ -1262,12 +1536,36 @@ where
         if let Some(next) = next {
             log::trace!("Present");
             let ref mut for_image = per_image[next[0] as usize];
-            if let Err(err) = next.present(queue.raw(), Some(&for_image.release)) {
-                log::debug!("Swapchain presentation error: {:#?}", err);
+            match next.present(queue.raw(), Some(&for_image.release)) {
+                Ok(None) => {}
+                Ok(Some(_)) => {
+                    log::debug!("Swapchain is suboptimal");
+                    *outdated = true;
+                }
+                Err(err) => {
+                    log::debug!("Swapchain presentation error: {:#?}", err);
+                    *outdated = true;
+                }
             }
         }
     }
 
+    unsafe fn resize(
+        &mut self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        aux: &T,
+        extent: rendy_core::hal::window::Extent2D,
+        buffers: &[NodeBuffer],
+        images: &[NodeImage],
+        resized: bool,
+    ) -> Result<(), NodeBuildError> {
+        // Surface is resized along with the graph.
+        self.common.buffers = buffers.to_vec();
+        self.common.images = images.to_vec();
+        self.recreate(ctx, factory, aux, extent, resized)
+    }
+
     unsafe fn dispose(self: Box<Self>, factory: &mut Factory<B>, aux: &T) {
         for per_image in self.per_image {
             factory.device().destroy_framebuffer(per_image.framebuffer);
@@ -1297,6 +1595,28 @@ where
     }
 }
 
+impl<B, T> RenderPassNodeWithoutSurface<B, T>
+where
+    B: Backend,
+    T: ?Sized,
+{
+    /// Record commands for next frame.
+    unsafe fn record(
+        &mut self,
+        ctx: &GraphContext<B>,
+        factory: &Factory<B>,
+        queue: QueueId,
+        aux: &T,
+        frames: &Frames<B>,
+    ) -> Submit<B, NoSimultaneousUse> {
+        let RenderPassNodeWithoutSurface {
+            common,
+            framebuffer,
+        } = self;
+        common.record(ctx, factory, queue, aux, frames, Some(&*framebuffer))
+    }
+}
+
 impl<B, T> DynNode<B, T> for RenderPassNodeWithoutSurface<B, T>
 where
     B: Backend,
@@ -1304,7 +1624,7 @@ where
 {
     unsafe fn run<'a>(
         &mut self,
-        _ctx: &GraphContext<B>,
+        ctx: &GraphContext<B>,
         factory: &Factory<B>,
         queue: &mut Queue<B>,
         aux: &T,
@@ -1313,120 +1633,406 @@ where
         signals: &[&'a B::Semaphore],
         fence: Option<&mut Fence<B>>,
     ) {
-        let RenderPassNodeWithoutSurface {
-            common:
-                RenderPassNodeCommon {
-                    subpasses,
+        let submit = self.record(ctx, factory, queue.id(), aux, frames);
+        queue.submit(
+            Some(
+                Submission::new()
+                    .submits(Some(submit))
+                    .wait(waits.iter().cloned())
+                    .signal(signals.iter().cloned()),
+            ),
+            fence,
+        );
+    }
 
-                    framebuffer_width,
-                    framebuffer_height,
+    unsafe fn run_batched<'a>(
+        &mut self,
+        ctx: &GraphContext<B>,
+        factory: &Factory<B>,
+        queue: QueueId,
+        aux: &T,
+        frames: &Frames<B>,
+        waits: &[(&'a B::Semaphore, rendy_core::hal::pso::PipelineStage)],
+        signals: &[&'a B::Semaphore],
+        batcher: &mut SubmissionBatcher<'a, B>,
+    ) -> bool {
+        let submit = self.record(ctx, factory, queue, aux, frames);
+        batcher.add(
+            queue,
+            Submission::new()
+                .submits(Some(submit))
+                .wait(waits.iter().cloned())
+                .signal(signals.iter().cloned()),
+        );
+        true
+    }
 
-                    render_pass,
-                    clears,
+    unsafe fn resize(
+        &mut self,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        aux: &T,
+        _extent: rendy_core::hal::window::Extent2D,
+        buffers: &[NodeBuffer],
+        images: &[NodeImage],
+        resized: bool,
+    ) -> Result<(), NodeBuildError> {
+        if !resized {
+            self.common.buffers = buffers.to_vec();
+            self.common.images = images.to_vec();
+            return Ok(());
+        }
 
-                    command_cirque,
-                    command_pool,
+        self.common
+            .resize(ctx, factory, aux, buffers, images, None, resized)?;
 
-                    acquire,
-                    release,
-                    ..
-                },
-            framebuffer,
-        } = self;
+        let framebuffer = self.common.create_framebuffers(factory)?.remove(0);
+        factory
+            .device()
+            .destroy_framebuffer(std::mem::replace(&mut self.framebuffer, framebuffer));
+
+        Ok(())
+    }
 
-        let submit = command_cirque.encode(frames, command_pool, |mut cbuf| {
-            let index = cbuf.index();
-
-            let force_record = subpasses.iter_mut().enumerate().fold(
-                false,
-                |force_record, (subpass_index, subpass)| {
-                    subpass
-                        .groups
-                        .iter_mut()
-                        .fold(force_record, |force_record, group| {
-                            group
-                                .prepare(
-                                    factory,
-                                    queue.id(),
-                                    index,
-                                    rendy_core::hal::pass::Subpass {
-                                        index: subpass_index,
-                                        main_pass: &render_pass,
+    unsafe fn dispose(self: Box<Self>, factory: &mut Factory<B>, aux: &T) {
+        self.common.dispose(factory, aux);
+        factory.device().destroy_framebuffer(self.framebuffer);
+    }
+}
+
+/// Create views for attachments.
+/// Swapchain image views, if any, are last ones.
+/// Returns views and framebuffer width, height and layers.
+unsafe fn create_views<B: Backend>(
+    ctx: &GraphContext<B>,
+    factory: &Factory<B>,
+    attachments: &[Attachment],
+    images: &[NodeImage],
+    target: Option<&Target<B>>,
+) -> Result<(Vec<B::ImageView>, u32, u32, u16), NodeBuildError> {
+    let mut framebuffer_width = u32::max_value();
+    let mut framebuffer_height = u32::max_value();
+    let mut framebuffer_layers = u16::max_value();
+
+    let views = attachments
+        .iter()
+        .map(|&attachment| -> Result<Vec<_>, NodeBuildError> {
+            match attachment {
+                Either::Left(image_id) => {
+                    log::debug!("Image {:?} attachment", image_id);
+
+                    let node_image = images
+                        .iter()
+                        .find(|a| a.id == image_id)
+                        .expect("Attachment image wasn't provided");
+                    let image = ctx.get_image(image_id).expect("Image does not exist");
+                    let extent = image.kind().extent();
+                    framebuffer_width = min(framebuffer_width, extent.width);
+                    framebuffer_height = min(framebuffer_height, extent.height);
+                    framebuffer_layers = min(
+                        framebuffer_layers,
+                        node_image.range.layers.end - node_image.range.layers.start,
+                    );
+                    Ok(vec![factory
+                        .device()
+                        .create_image_view(
+                            image.raw(),
+                            rendy_core::hal::image::ViewKind::D2,
+                            image.format(),
+                            rendy_core::hal::format::Swizzle::NO,
+                            rendy_core::hal::image::SubresourceRange {
+                                // NOTE: Framebuffer must always be created with only one mip level. If image contains multiple levels,
+                                // only the first one is bound as an attachment.
+                                // TODO: Allow customizing this behaviour to choose which level to bind.
+                                levels: 0..1,
+                                ..node_image.range.clone()
+                            },
+                        )
+                        .map_err(NodeBuildError::View)?])
+                }
+                Either::Right(RenderPassSurface) => {
+                    let target = target.expect("Expect target created");
+
+                    framebuffer_width = min(framebuffer_width, target.extent().width);
+                    framebuffer_height = min(framebuffer_height, target.extent().height);
+                    framebuffer_layers = min(framebuffer_layers, target.backbuffer()[0].layers());
+
+                    target
+                        .backbuffer()
+                        .iter()
+                        .map(|image| {
+                            factory
+                                .device()
+                                .create_image_view(
+                                    image.raw(),
+                                    rendy_core::hal::image::ViewKind::D2,
+                                    image.format(),
+                                    rendy_core::hal::format::Swizzle::NO,
+                                    rendy_core::hal::image::SubresourceRange {
+                                        aspects: image.format().surface_desc().aspects,
+                                        levels: 0..1,
+                                        layers: 0..1,
                                     },
-                                    aux,
                                 )
-                                .force_record()
-                                || force_record
+                                .map_err(NodeBuildError::View)
                         })
-                },
+                        .collect::<Result<Vec<_>, NodeBuildError>>()
+                }
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .flatten()
+        .collect();
+
+    Ok((
+        views,
+        framebuffer_width,
+        framebuffer_height,
+        framebuffer_layers,
+    ))
+}
+
+/// Create framebuffer for each swapchain image view or single framebuffer if there is no swapchain.
+unsafe fn create_framebuffers<B: Backend>(
+    factory: &Factory<B>,
+    render_pass: &B::RenderPass,
+    views: &[B::ImageView],
+    attachments: usize,
+    framebuffer_width: u32,
+    framebuffer_height: u32,
+    framebuffer_layers: u16,
+) -> Result<Vec<B::Framebuffer>, NodeBuildError> {
+    log::trace!("Create {} framebuffers", views.len() - attachments + 1);
+
+    // Swapchain image views, if any, are last ones.
+    (attachments - 1..views.len())
+        .map(|i| {
+            log::trace!(
+                "Create framebuffer for views {}..{} and {}",
+                0,
+                attachments - 1,
+                i,
             );
+            factory
+                .device()
+                .create_framebuffer(
+                    render_pass,
+                    views[..attachments - 1].iter().chain(Some(&views[i])),
+                    rendy_core::hal::image::Extent {
+                        width: framebuffer_width,
+                        height: framebuffer_height,
+                        depth: framebuffer_layers as u32, // This is gfx-hal BUG as this parameter actually means framebuffer layers number,
+                    },
+                )
+                .map_err(NodeBuildError::OutOfMemory)
+        })
+        .collect()
+}
 
-            if force_record {
-                cbuf = CirqueRef::Initial(cbuf.or_reset(|cbuf| cbuf.reset()));
-            }
+/// Record acquire and release barriers of the node into secondary command buffers.
+/// Record pipeline barriers if there are any.
+unsafe fn encode_barriers<B: Backend>(
+    encoder: &mut Encoder<'_, B, Graphics, PrimaryLevel>,
+    name: &str,
+    stages: std::ops::Range<rendy_core::hal::pso::PipelineStage>,
+    barriers: Vec<rendy_core::hal::memory::Barrier<'_, B>>,
+) {
+    if barriers.is_empty() {
+        return;
+    }
 
-            cbuf.or_init(|cbuf| {
-                let mut cbuf = cbuf.begin(MultiShot(NoSimultaneousUse), ());
-                let mut encoder = cbuf.encoder();
+    log::trace!("{} {:?} : {:#?}", name, stages, barriers);
+    encoder.pipeline_barrier(
+        stages,
+        rendy_core::hal::memory::Dependencies::empty(),
+        barriers,
+    );
+}
 
-                if let Some(barriers) = &acquire {
-                    encoder.execute_commands(std::iter::once(&barriers.submit));
-                }
+/// Pipeline stages and accesses of the attachment in the subpass.
+/// Returns `None` if subpass doesn't use the attachment.
+fn attachment_usage(
+    subpass: &SubpassAttachments<'_>,
+    attachment: Attachment,
+) -> Option<(
+    rendy_core::hal::pso::PipelineStage,
+    rendy_core::hal::image::Access,
+)> {
+    use rendy_core::hal::{image::Access, pso::PipelineStage};
+
+    let mut usage = None;
+    let mut add = |stages, access| {
+        let entry = usage.get_or_insert((PipelineStage::empty(), Access::empty()));
+        entry.0 |= stages;
+        entry.1 |= access;
+    };
+
+    if subpass.inputs.contains(&attachment) {
+        add(
+            PipelineStage::FRAGMENT_SHADER,
+            Access::INPUT_ATTACHMENT_READ,
+        );
+    }
+    if subpass.colors.contains(&attachment) {
+        add(
+            PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+            Access::COLOR_ATTACHMENT_READ | Access::COLOR_ATTACHMENT_WRITE,
+        );
+    }
+    if subpass.resolves.contains(&Some(attachment)) {
+        add(
+            PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+            Access::COLOR_ATTACHMENT_WRITE,
+        );
+    }
+    if subpass.depth_stencil == Some(attachment) {
+        add(
+            PipelineStage::EARLY_FRAGMENT_TESTS | PipelineStage::LATE_FRAGMENT_TESTS,
+            Access::DEPTH_STENCIL_ATTACHMENT_READ | Access::DEPTH_STENCIL_ATTACHMENT_WRITE,
+        );
+    }
+    usage
+}
 
-                let area = rendy_core::hal::pso::Rect {
-                    x: 0,
-                    y: 0,
-                    w: *framebuffer_width as _,
-                    h: *framebuffer_height as _,
-                };
-
-                let mut pass_encoder =
-                    encoder.begin_render_pass_inline(&render_pass, framebuffer, area, &clears);
-
-                subpasses
-                    .iter_mut()
-                    .enumerate()
-                    .for_each(|(subpass_index, subpass)| {
-                        subpass.groups.iter_mut().for_each(|group| {
-                            group.draw_inline(
-                                pass_encoder.reborrow(),
-                                index,
-                                rendy_core::hal::pass::Subpass {
-                                    index: subpass_index,
-                                    main_pass: &render_pass,
-                                },
-                                aux,
-                            )
-                        })
-                    });
+/// Clip render area to the framebuffer bounds.
+/// Whole framebuffer is used if area is not specified.
+fn clip_render_area(
+    area: Option<rendy_core::hal::pso::Rect>,
+    framebuffer_width: u32,
+    framebuffer_height: u32,
+) -> rendy_core::hal::pso::Rect {
+    let width = min(framebuffer_width, i16::max_value() as u32) as i16;
+    let height = min(framebuffer_height, i16::max_value() as u32) as i16;
+    match area {
+        None => rendy_core::hal::pso::Rect {
+            x: 0,
+            y: 0,
+            w: width,
+            h: height,
+        },
+        Some(area) => {
+            let clip = |start: i16, size: i16, bound: i16| {
+                let end = (start as i32 + size as i32).min(bound as i32);
+                let start = start.max(0).min(bound);
+                (start, (end - start as i32).max(0) as i16)
+            };
+            let (x, w) = clip(area.x, area.w, width);
+            let (y, h) = clip(area.y, area.h, height);
+            rendy_core::hal::pso::Rect { x, y, w, h }
+        }
+    }
+}
+
+/// Check if attachment is used only as resolve attachment.
+fn resolve_only(subpasses: &[SubpassAttachments<'_>], attachment: Attachment) -> bool {
+    subpasses.iter().all(|subpass| {
+        !subpass.inputs.contains(&attachment)
+            && !subpass.colors.contains(&attachment)
+            && subpass.depth_stencil != Some(attachment)
+    })
+}
 
-                drop(pass_encoder);
+/// Number of samples of the subpass color and depth-stencil attachments.
+/// Resolve attachments are not counted as they must have single sample.
+fn subpass_samples<B, T>(
+    ctx: &GraphContext<B>,
+    subpass: &SubpassBuilder<B, T>,
+) -> rendy_core::hal::image::NumSamples
+where
+    B: Backend,
+    T: ?Sized,
+{
+    let samples = |attachment: &Attachment| match *attachment {
+        Either::Left(image_id) => ctx
+            .get_image(image_id)
+            .expect("Image does not exist")
+            .kind()
+            .num_samples(),
+        Either::Right(RenderPassSurface) => 1,
+    };
+
+    let mut outputs = subpass.colors.iter().chain(&subpass.depth_stencil);
+    let result = outputs.next().map_or(1, samples);
+    assert!(
+        outputs.all(|attachment| samples(attachment) == result),
+        "All color and depth-stencil attachments of the subpass must have same number of samples"
+    );
+    assert!(
+        subpass
+            .resolves
+            .iter()
+            .flatten()
+            .all(|resolve| samples(resolve) == 1),
+        "Resolve attachments must have single sample"
+    );
+    result
+}
 
-                if let Some(barriers) = &release {
-                    encoder.execute_commands(std::iter::once(&barriers.submit));
+/// Derive dependencies between subpasses from attachments they use.
+/// Subpass depends on each earlier subpass that uses the same attachment
+/// unless both of them only read it.
+fn subpass_dependencies(
+    subpasses: &[SubpassAttachments<'_>],
+    attachments: &[Attachment],
+) -> Vec<rendy_core::hal::pass::SubpassDependency> {
+    use rendy_core::hal::{
+        image::Access,
+        pass::{SubpassDependency, SubpassRef},
+        pso::PipelineStage,
+    };
+
+    let writes = Access::COLOR_ATTACHMENT_WRITE | Access::DEPTH_STENCIL_ATTACHMENT_WRITE;
+    let mut dependencies = Vec::new();
+
+    for (dst, dst_subpass) in subpasses.iter().enumerate() {
+        for (src, src_subpass) in subpasses[..dst].iter().enumerate() {
+            let mut stages = PipelineStage::empty()..PipelineStage::empty();
+            let mut accesses = Access::empty()..Access::empty();
+
+            for &attachment in attachments {
+                let usage = (
+                    attachment_usage(src_subpass, attachment),
+                    attachment_usage(dst_subpass, attachment),
+                );
+                if let (Some(left), Some(right)) = usage {
+                    if left.1.intersects(writes) || right.1.intersects(writes) {
+                        stages.start |= left.0;
+                        stages.end |= right.0;
+                        accesses.start |= left.1;
+                        accesses.end |= right.1;
+                    }
                 }
-                cbuf.finish()
-            })
-        });
+            }
 
-        queue.submit(
-            Some(
-                Submission::new()
-                    .submits(Some(submit))
-                    .wait(waits.iter().cloned())
-                    .signal(signals.iter().cloned()),
-            ),
-            fence,
-        );
+            if !stages.start.is_empty() {
+                dependencies.push(SubpassDependency {
+                    passes: SubpassRef::Pass(src)..SubpassRef::Pass(dst),
+                    stages,
+                    accesses,
+                });
+            }
+        }
     }
 
-    unsafe fn dispose(self: Box<Self>, factory: &mut Factory<B>, aux: &T) {
-        self.common.dispose(factory, aux);
-        factory.device().destroy_framebuffer(self.framebuffer);
-    }
+    dependencies
+}
+
+/// Check if content of the attachment must be preserved through the subpass.
+/// That is when the subpass doesn't use attachment but subpasses before and after it do.
+fn preserve_attachment(
+    subpasses: &[SubpassAttachments<'_>],
+    index: usize,
+    attachment: Attachment,
+) -> bool {
+    let used = |subpass: &SubpassAttachments<'_>| attachment_usage(subpass, attachment).is_some();
+    !used(&subpasses[index])
+        && subpasses[..index].iter().any(used)
+        && subpasses[index + 1..].iter().any(used)
 }
 
-fn common_layout(acc: Layout, layout: Layout) -> Layout {
+/// Layout that supports operations of both layouts.
+pub(crate) fn common_layout(acc: Layout, layout: Layout) -> Layout {
     match (acc, layout) {
         (Layout::Undefined, layout) => layout,
         (left, right) if left == right => left,
@@ -1439,3 +2045,161 @@ fn common_layout(acc: Layout, layout: Layout) -> Layout {
         (_, _) => Layout::General,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rendy_core::hal::{
+        image::Access,
+        pass::{SubpassDependency, SubpassRef},
+        pso::PipelineStage,
+    };
+
+    fn image(id: usize) -> Attachment {
+        Either::Left(ImageId(id))
+    }
+
+    fn subpass<'a>(
+        inputs: &'a [Attachment],
+        colors: &'a [Attachment],
+        depth_stencil: Option<Attachment>,
+    ) -> SubpassAttachments<'a> {
+        SubpassAttachments {
+            inputs,
+            colors,
+            resolves: &[],
+            depth_stencil,
+        }
+    }
+
+    fn dependencies(
+        subpasses: &[SubpassAttachments<'_>],
+        attachments: &[Attachment],
+    ) -> Vec<(
+        std::ops::Range<SubpassRef>,
+        std::ops::Range<PipelineStage>,
+        std::ops::Range<Access>,
+    )> {
+        subpass_dependencies(subpasses, attachments)
+            .into_iter()
+            .map(|dependency: SubpassDependency| {
+                (dependency.passes, dependency.stages, dependency.accesses)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn gbuffer_then_lighting() {
+        let (albedo, normal, depth, output) = (image(0), image(1), image(2), image(3));
+        let gbuffer = [albedo, normal];
+        let lighting_inputs = [albedo, normal, depth];
+        let lighting_colors = [output];
+        let subpasses = [
+            subpass(&[], &gbuffer, Some(depth)),
+            subpass(&lighting_inputs, &lighting_colors, None),
+        ];
+
+        assert_eq!(
+            dependencies(&subpasses, &[albedo, normal, depth, output]),
+            vec![(
+                SubpassRef::Pass(0)..SubpassRef::Pass(1),
+                PipelineStage::COLOR_ATTACHMENT_OUTPUT
+                    | PipelineStage::EARLY_FRAGMENT_TESTS
+                    | PipelineStage::LATE_FRAGMENT_TESTS
+                    ..PipelineStage::FRAGMENT_SHADER,
+                Access::COLOR_ATTACHMENT_READ
+                    | Access::COLOR_ATTACHMENT_WRITE
+                    | Access::DEPTH_STENCIL_ATTACHMENT_READ
+                    | Access::DEPTH_STENCIL_ATTACHMENT_WRITE
+                    ..Access::INPUT_ATTACHMENT_READ,
+            )],
+        );
+        for &attachment in &[albedo, normal, depth, output] {
+            assert!(!preserve_attachment(&subpasses, 0, attachment));
+            assert!(!preserve_attachment(&subpasses, 1, attachment));
+        }
+    }
+
+    #[test]
+    fn depth_read_after_write() {
+        let (color, depth) = (image(0), image(1));
+        let colors = [color];
+        let subpasses = [
+            subpass(&[], &colors, Some(depth)),
+            subpass(&[], &colors, Some(depth)),
+        ];
+        let depth_stages = PipelineStage::EARLY_FRAGMENT_TESTS | PipelineStage::LATE_FRAGMENT_TESTS;
+        let depth_access =
+            Access::DEPTH_STENCIL_ATTACHMENT_READ | Access::DEPTH_STENCIL_ATTACHMENT_WRITE;
+        let color_access = Access::COLOR_ATTACHMENT_READ | Access::COLOR_ATTACHMENT_WRITE;
+
+        assert_eq!(
+            dependencies(&subpasses, &[depth]),
+            vec![(
+                SubpassRef::Pass(0)..SubpassRef::Pass(1),
+                depth_stages..depth_stages,
+                depth_access..depth_access,
+            )],
+        );
+        assert_eq!(
+            dependencies(&subpasses, &[color, depth]),
+            vec![(
+                SubpassRef::Pass(0)..SubpassRef::Pass(1),
+                PipelineStage::COLOR_ATTACHMENT_OUTPUT | depth_stages
+                    ..PipelineStage::COLOR_ATTACHMENT_OUTPUT | depth_stages,
+                color_access | depth_access..color_access | depth_access,
+            )],
+        );
+    }
+
+    #[test]
+    fn shared_inputs_need_no_dependency() {
+        let (input, first, second) = (image(0), image(1), image(2));
+        let inputs = [input];
+        let (first_colors, second_colors) = ([first], [second]);
+        let subpasses = [
+            subpass(&inputs, &first_colors, None),
+            subpass(&inputs, &second_colors, None),
+        ];
+
+        assert!(dependencies(&subpasses, &[input, first, second]).is_empty());
+    }
+
+    #[test]
+    fn preserve_through_unrelated_subpass() {
+        let (gbuffer, shadow, output) = (image(0), image(1), image(2));
+        let (gbuffer_colors, shadow_colors) = ([gbuffer], [shadow]);
+        let (lighting_inputs, lighting_colors) = ([gbuffer, shadow], [output]);
+        let subpasses = [
+            subpass(&[], &gbuffer_colors, None),
+            subpass(&[], &shadow_colors, None),
+            subpass(&lighting_inputs, &lighting_colors, None),
+        ];
+
+        assert!(!preserve_attachment(&subpasses, 0, gbuffer));
+        assert!(preserve_attachment(&subpasses, 1, gbuffer));
+        assert!(!preserve_attachment(&subpasses, 2, gbuffer));
+        for index in 0..3 {
+            assert!(!preserve_attachment(&subpasses, index, shadow));
+            assert!(!preserve_attachment(&subpasses, index, output));
+        }
+
+        let color_output = PipelineStage::COLOR_ATTACHMENT_OUTPUT;
+        let color_access = Access::COLOR_ATTACHMENT_READ | Access::COLOR_ATTACHMENT_WRITE;
+        assert_eq!(
+            dependencies(&subpasses, &[gbuffer, shadow, output]),
+            vec![
+                (
+                    SubpassRef::Pass(0)..SubpassRef::Pass(2),
+                    color_output..PipelineStage::FRAGMENT_SHADER,
+                    color_access..Access::INPUT_ATTACHMENT_READ,
+                ),
+                (
+                    SubpassRef::Pass(1)..SubpassRef::Pass(2),
+                    color_output..PipelineStage::FRAGMENT_SHADER,
+                    color_access..Access::INPUT_ATTACHMENT_READ,
+                ),
+            ],
+        );
+    }
+}
@@ -501,7 +501,7 @@ fn main() {
                     _ => {}
                 },
                 Event::EventsCleared => {
-                    factory.maintain(&mut families);
+                    factory.maintain(&mut families).unwrap();
                     if let Some(ref mut graph) = graph {
                         graph.run(&mut factory, &mut families, &scene);
                         frame += 1;
@@ -163,6 +163,7 @@ where
             access: hal::buffer::Access::SHADER_READ,
             stages: hal::pso::PipelineStage::VERTEX_SHADER,
             usage: hal::buffer::Usage::STORAGE,
+            range: None,
         }]
     }
 
@@ -396,6 +397,7 @@ where
             access: hal::buffer::Access::SHADER_READ | hal::buffer::Access::SHADER_WRITE,
             stages: hal::pso::PipelineStage::COMPUTE_SHADER,
             usage: hal::buffer::Usage::STORAGE | hal::buffer::Usage::TRANSFER_DST,
+            range: None,
         }]
     }
 
@@ -632,7 +634,7 @@ fn main() {
                         _ => {}
                     },
                     Event::EventsCleared => {
-                        factory.maintain(&mut families);
+                        factory.maintain(&mut families).unwrap();
                         if let Some(ref mut graph) = graph {
                             graph.run(&mut factory, &mut families, &());
                             frame += 1;
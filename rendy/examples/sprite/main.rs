@@ -330,7 +330,7 @@ fn run<B: hal::Backend>(
                 _ => {}
             },
             Event::EventsCleared => {
-                factory.maintain(&mut families);
+                factory.maintain(&mut families).unwrap();
                 if let Some(ref mut graph) = graph {
                     graph.run(&mut factory, &mut families, &());
                     frame += 1;
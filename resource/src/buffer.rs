@@ -31,7 +31,7 @@ pub struct BufferInfo {
 pub struct Buffer<B: Backend> {
     device: DeviceId,
     raw: B::Buffer,
-    block: MemoryBlock<B>,
+    block: Option<MemoryBlock<B>>,
     info: BufferInfo,
     relevant: Relevant,
 }
@@ -83,18 +83,70 @@ where
         Ok(Buffer {
             device: device.id(),
             raw: buf,
-            block,
+            block: Some(block),
             info,
             relevant: Relevant,
         })
     }
 
+    /// Create buffer without binding memory to it.
+    /// Memory must be bound with [`bind_memory`] before buffer is used.
+    ///
+    /// [`bind_memory`]: #method.bind_memory
+    pub unsafe fn create_unbound(
+        device: &Device<B>,
+        info: BufferInfo,
+    ) -> Result<Self, BufferCreationError> {
+        log::trace!("{:#?}", info);
+        assert_ne!(info.size, 0);
+
+        let buf = device
+            .create_buffer(info.size, info.usage)
+            .map_err(CreationError::Create)?;
+
+        Ok(Buffer {
+            device: device.id(),
+            raw: buf,
+            block: None,
+            info,
+            relevant: Relevant,
+        })
+    }
+
+    /// Get memory requirements of the buffer.
+    pub fn requirements(&self, device: &Device<B>) -> rendy_core::hal::memory::Requirements {
+        self.assert_device_owner(device);
+        unsafe { device.get_buffer_requirements(&self.raw) }
+    }
+
+    /// Bind memory not owned by the buffer.
+    /// Memory can be shared by multiple resources.
+    ///
+    /// # Safety
+    ///
+    /// Buffer must be created with [`create_unbound`] and not bound yet.
+    /// Memory range must satisfy buffer [`requirements`]
+    /// and outlive the buffer.
+    ///
+    /// [`create_unbound`]: #method.create_unbound
+    /// [`requirements`]: #method.requirements
+    pub unsafe fn bind_memory(
+        &mut self,
+        device: &Device<B>,
+        memory: &B::Memory,
+        offset: u64,
+    ) -> Result<(), rendy_core::hal::device::BindError> {
+        self.assert_device_owner(device);
+        assert!(self.block.is_none());
+        device.bind_buffer_memory(memory, offset, &mut self.raw)
+    }
+
     /// Dispose of buffer resource.
-    /// Deallocate memory block.
+    /// Deallocate memory block if buffer owns one.
     pub unsafe fn dispose(self, device: &Device<B>, heaps: &mut Heaps<B>) {
         self.assert_device_owner(device);
         device.destroy_buffer(self.raw);
-        heaps.free(device, self.block);
+        self.block.map(|block| heaps.free(device, block));
         self.relevant.dispose();
     }
 
@@ -109,13 +161,41 @@ where
     }
 
     /// Get reference to memory block occupied by buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if buffer doesn't own memory it is bound to.
+    /// Use [`try_block`] for buffers bound to shared memory.
+    ///
+    /// [`try_block`]: #method.try_block
     pub fn block(&self) -> &MemoryBlock<B> {
-        &self.block
+        self.try_block()
+            .expect("Buffer doesn't own memory it is bound to")
     }
 
     /// Get mutable reference to memory block occupied by buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if buffer doesn't own memory it is bound to.
+    /// Use [`try_block_mut`] for buffers bound to shared memory.
+    ///
+    /// [`try_block_mut`]: #method.try_block_mut
     pub unsafe fn block_mut(&mut self) -> &mut MemoryBlock<B> {
-        &mut self.block
+        self.try_block_mut()
+            .expect("Buffer doesn't own memory it is bound to")
+    }
+
+    /// Get reference to memory block occupied by buffer.
+    /// Returns `None` if buffer doesn't own memory it is bound to.
+    pub fn try_block(&self) -> Option<&MemoryBlock<B>> {
+        self.block.as_ref()
+    }
+
+    /// Get mutable reference to memory block occupied by buffer.
+    /// Returns `None` if buffer doesn't own memory it is bound to.
+    pub unsafe fn try_block_mut(&mut self) -> Option<&mut MemoryBlock<B>> {
+        self.block.as_mut()
     }
 
     /// Get buffer info.
@@ -129,9 +209,11 @@ where
     /// [`map`]: #method.map
     /// [`InvalidAccess`]: https://docs.rs/gfx-hal/0.1/rendy_core::hal/mapping/enum.Error.html#InvalidAccess
     pub fn visible(&self) -> bool {
-        self.block
-            .properties()
-            .contains(rendy_core::hal::memory::Properties::CPU_VISIBLE)
+        self.block.as_ref().map_or(false, |block| {
+            block
+                .properties()
+                .contains(rendy_core::hal::memory::Properties::CPU_VISIBLE)
+        })
     }
 
     /// Map range of the buffer to the CPU accessible memory.
@@ -140,7 +222,10 @@ where
         device: &Device<B>,
         range: std::ops::Range<u64>,
     ) -> Result<MappedRange<'a, B>, rendy_core::hal::device::MapError> {
-        self.block.map(device, range)
+        match &mut self.block {
+            Some(block) => block.map(device, range),
+            None => Err(rendy_core::hal::device::MapError::MappingFailed),
+        }
     }
 
     /// Get buffer info.
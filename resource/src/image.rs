@@ -116,6 +116,72 @@ where
         })
     }
 
+    /// Create image without binding memory to it.
+    /// Memory must be bound with [`bind_memory`] before image is used.
+    ///
+    /// [`bind_memory`]: #method.bind_memory
+    pub unsafe fn create_unbound(
+        device: &Device<B>,
+        info: ImageInfo,
+    ) -> Result<Self, ImageCreationError> {
+        assert!(
+            info.levels <= info.kind.num_levels(),
+            "Number of mip leves ({}) cannot be greater than {} for given kind {:?}",
+            info.levels,
+            info.kind.num_levels(),
+            info.kind,
+        );
+
+        log::trace!("{:#?}", info);
+
+        let img = device
+            .create_image(
+                info.kind,
+                info.levels,
+                info.format,
+                info.tiling,
+                info.usage,
+                info.view_caps,
+            )
+            .map_err(CreationError::Create)?;
+
+        Ok(Image {
+            device: device.id(),
+            raw: img,
+            block: None,
+            info,
+            relevant: Relevant,
+        })
+    }
+
+    /// Get memory requirements of the image.
+    pub fn requirements(&self, device: &Device<B>) -> rendy_core::hal::memory::Requirements {
+        self.assert_device_owner(device);
+        unsafe { device.get_image_requirements(&self.raw) }
+    }
+
+    /// Bind memory not owned by the image.
+    /// Memory can be shared by multiple resources.
+    ///
+    /// # Safety
+    ///
+    /// Image must be created with [`create_unbound`] and not bound yet.
+    /// Memory range must satisfy image [`requirements`]
+    /// and outlive the image.
+    ///
+    /// [`create_unbound`]: #method.create_unbound
+    /// [`requirements`]: #method.requirements
+    pub unsafe fn bind_memory(
+        &mut self,
+        device: &Device<B>,
+        memory: &B::Memory,
+        offset: u64,
+    ) -> Result<(), rendy_core::hal::device::BindError> {
+        self.assert_device_owner(device);
+        assert!(self.block.is_none());
+        device.bind_image_memory(memory, offset, &mut self.raw)
+    }
+
     /// Create image handler for swapchain image.
     pub unsafe fn create_from_swapchain(device: DeviceId, info: ImageInfo, raw: B::Image) -> Self {
         Image {
@@ -153,6 +219,7 @@ where
     }
 
     /// Get reference to memory block occupied by image.
+    /// Returns `None` if image doesn't own memory it is bound to.
     pub fn block(&self) -> Option<&MemoryBlock<B>> {
         self.block.as_ref()
     }
@@ -97,3 +97,33 @@ impl<T> ResourceTracker<T> {
             .for_each(dispose);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn epochs(value: u64) -> Epochs {
+        Epochs {
+            values: smallvec::smallvec![smallvec::smallvec![value]],
+        }
+    }
+
+    #[test]
+    fn dropped_resource_freed_once_epoch_completes() {
+        let mut tracker = ResourceTracker::new();
+        let mut freed = Vec::new();
+
+        drop(tracker.escape(42u32));
+
+        // Resource may be used by epochs up to 1.
+        tracker.cleanup(|r| freed.push(r), &epochs(2), &epochs(0));
+        assert!(freed.is_empty());
+
+        // Completed epochs don't advance, resource must stay alive.
+        tracker.cleanup(|r| freed.push(r), &epochs(3), &epochs(0));
+        assert!(freed.is_empty());
+
+        tracker.cleanup(|r| freed.push(r), &epochs(4), &epochs(3));
+        assert_eq!(freed, vec![42]);
+    }
+}
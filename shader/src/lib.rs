@@ -206,6 +206,18 @@ impl<B: Backend> ShaderSet<B> {
         })
     }
 
+    /// Returns the compute `EntryPoint` to provide all the runtime information needed to use the compute shader in this set in rendy_core::hal.
+    pub fn raw_compute<'a>(
+        &'a self,
+    ) -> Result<rendy_core::hal::pso::EntryPoint<'a, B>, ShaderError> {
+        Ok(self
+            .shaders
+            .get(&ShaderStageFlags::COMPUTE)
+            .expect("ShaderSet doesn't contain compute shader")
+            .get_entry_point()?
+            .unwrap())
+    }
+
     /// Must be called to perform a drop of the Backend ShaderModule object otherwise the shader will never be destroyed in memory.
     pub fn dispose(&mut self, factory: &rendy_factory::Factory<B>) {
         for (_, shader) in self.shaders.iter_mut() {